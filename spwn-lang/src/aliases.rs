@@ -0,0 +1,117 @@
+// keyword aliases, an experiment for teaching and translated tutorials: an [aliases]
+// section in spwn.toml gives other words for keywords and builtin names, like
+//
+// [aliases]
+// si = "if"
+// imprimir = "print"
+//
+// there are none unless spwn.toml has the section, and they only apply to the files in
+// the folder with the spwn.toml (not to libraries like std, which might use the words)
+use crate::parser::Token;
+use logos::Logos;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone)]
+pub struct Aliases {
+    root: PathBuf,
+    // word -> (the token it stands for, its text)
+    words: HashMap<String, (Token, String)>,
+}
+
+// the one token a word lexes to, if it is just one
+fn single_token(word: &str) -> Option<Token> {
+    let mut lexer = Token::lexer(word);
+    let token = lexer.next()?;
+    if lexer.span() != (0..word.len()) || lexer.next().is_some() {
+        None
+    } else {
+        Some(token)
+    }
+}
+
+impl Aliases {
+    // looks for a spwn.toml file with an [aliases] section, starting in the given folder
+    // and going up through its parents
+    pub fn find(start: &Path) -> Result<Option<Self>, String> {
+        for dir in start.ancestors() {
+            let manifest = dir.join("spwn.toml");
+            if manifest.is_file() {
+                let words = match std::fs::read_to_string(&manifest) {
+                    Ok(content) => Self::parse(&content)
+                        .map_err(|e| format!("Error in {}: {}", manifest.to_string_lossy(), e))?,
+                    Err(e) => {
+                        return Err(format!(
+                            "Could not read {}: {}",
+                            manifest.to_string_lossy(),
+                            e
+                        ))
+                    }
+                };
+                return Ok(if words.is_empty() {
+                    None
+                } else {
+                    let dir = if dir.as_os_str().is_empty() {
+                        Path::new(".")
+                    } else {
+                        dir
+                    };
+                    Some(Aliases {
+                        root: dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf()),
+                        words,
+                    })
+                });
+            }
+        }
+        Ok(None)
+    }
+
+    pub fn parse(content: &str) -> Result<HashMap<String, (Token, String)>, String> {
+        let mut words = HashMap::new();
+        for entry in crate::manifest::read(content, |s| s == "aliases")? {
+            let (word, meaning) = (entry.key.as_str(), entry.text());
+            // the alias has to be a name that isn't taken by the language already
+            if single_token(word) != Some(Token::Symbol) || word == "$" {
+                return Err(entry.error(format!(
+                    "`{}` can't be an alias (it has to be a name that isn't a keyword)",
+                    word
+                )));
+            }
+            let token = match single_token(&meaning) {
+                Some(t) => t,
+                None => return Err(entry.error(format!("`{}` isn't a keyword or a name", meaning))),
+            };
+            words.insert(word.to_string(), (token, meaning));
+        }
+        Ok(words)
+    }
+}
+
+// the aliases for a file, if it is in the project
+pub fn for_file(aliases: Option<&Aliases>, path: &Path) -> HashMap<String, (Token, String)> {
+    match aliases {
+        Some(aliases) => match path.canonicalize() {
+            Ok(path) if path.starts_with(&aliases.root) => aliases.words.clone(),
+            _ => HashMap::new(),
+        },
+        None => HashMap::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aliases_are_read_from_the_section() {
+        let words = Aliases::parse(
+            "[triggers]\nx = 10\n\n[aliases]\nsi = \"if\"\nimprimir = \"print\" # a builtin\n",
+        )
+        .unwrap();
+        assert_eq!(words.len(), 2);
+        assert_eq!(words["si"], (Token::If, "if".to_string()));
+        assert_eq!(words["imprimir"], (Token::Symbol, "print".to_string()));
+        assert!(Aliases::parse("[aliases]\nfor = \"if\"\n").is_err());
+        assert!(Aliases::parse("[aliases]\nsi = \"if x\"\n").is_err());
+    }
+}
@@ -0,0 +1,129 @@
+// hooks for tools that look at a build as it is compiled (style checkers, metrics and so on)
+// without changing the compiler: implement Analyzer and put it in globals.analyzers before
+// compiling (globals are sent between threads, so they have to be Send and Sync)
+use crate::ast;
+use crate::compiler_info::CompilerInfo;
+use crate::context::Context;
+use crate::globals::Globals;
+use crate::levelstring::GdObj;
+
+pub trait Analyzer: Send + Sync {
+    // after each statement, with the contexts it ended in
+    fn on_statement_compiled(
+        &mut self,
+        _statement: &ast::Statement,
+        _contexts: &[Context],
+        _info: &CompilerInfo,
+        _globals: &Globals,
+    ) {
+    }
+
+    // for each object and trigger the script adds (before ids are given out)
+    fn on_object_emitted(&mut self, _obj: &GdObj, _info: &CompilerInfo, _globals: &Globals) {}
+
+    // when a statement made more contexts than there were before it (like a comparison
+    // with a runtime value)
+    fn on_context_split(
+        &mut self,
+        _statement: &ast::Statement,
+        _before: usize,
+        _after: usize,
+        _info: &CompilerInfo,
+        _globals: &Globals,
+    ) {
+    }
+
+    // when the whole script is compiled
+    fn on_build_finished(&mut self, _globals: &Globals) {}
+}
+
+// the analyzers are taken out while they run, so they can look at the globals
+fn run(globals: &mut Globals, mut f: impl FnMut(&mut dyn Analyzer, &Globals)) {
+    if globals.analyzers.is_empty() {
+        return;
+    }
+    let mut analyzers = std::mem::take(&mut globals.analyzers);
+    for a in analyzers.iter_mut() {
+        f(a.as_mut(), globals);
+    }
+    globals.analyzers = analyzers;
+}
+
+pub fn statement_compiled(
+    statement: &ast::Statement,
+    before: usize,
+    contexts: &[Context],
+    info: &CompilerInfo,
+    globals: &mut Globals,
+) {
+    run(globals, |a, globals| {
+        if contexts.len() > before {
+            a.on_context_split(statement, before, contexts.len(), info, globals);
+        }
+        a.on_statement_compiled(statement, contexts, info, globals);
+    });
+}
+
+pub fn build_finished(globals: &mut Globals) {
+    run(globals, |a, globals| a.on_build_finished(globals));
+}
+
+pub fn object_emitted(obj: &GdObj, info: &CompilerInfo, globals: &mut Globals) {
+    run(globals, |a, globals| {
+        a.on_object_emitted(obj, info, globals)
+    });
+}
+
+// spwn build --metrics
+#[derive(Default)]
+pub struct Metrics {
+    statements: usize,
+    objects: usize,
+    triggers: usize,
+    splits: usize,
+    most_contexts: usize,
+}
+
+impl Analyzer for Metrics {
+    fn on_statement_compiled(
+        &mut self,
+        _: &ast::Statement,
+        contexts: &[Context],
+        _: &CompilerInfo,
+        _: &Globals,
+    ) {
+        self.statements += 1;
+        self.most_contexts = self.most_contexts.max(contexts.len());
+    }
+
+    fn on_object_emitted(&mut self, obj: &GdObj, _: &CompilerInfo, _: &Globals) {
+        match obj.mode {
+            ast::ObjectMode::Object => self.objects += 1,
+            ast::ObjectMode::Trigger => self.triggers += 1,
+        }
+    }
+
+    fn on_context_split(
+        &mut self,
+        _: &ast::Statement,
+        _: usize,
+        _: usize,
+        _: &CompilerInfo,
+        _: &Globals,
+    ) {
+        self.splits += 1;
+    }
+
+    fn on_build_finished(&mut self, _: &Globals) {
+        let count =
+            |n: usize, what: &str| format!("{} {}{}", n, what, if n == 1 { "" } else { "s" });
+        println!(
+            "{} compiled, {} and {} added, contexts split {} (at most {} at once)",
+            count(self.statements, "statement"),
+            count(self.objects, "object"),
+            count(self.triggers, "trigger"),
+            count(self.splits, "time"),
+            self.most_contexts
+        );
+    }
+}
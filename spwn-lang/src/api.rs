@@ -0,0 +1,325 @@
+// building scripts from other programs (bots, web playgrounds, editor plugins): the same
+// steps as spwn build -c, without printing anything or exiting the process. what the
+// script prints with $.print is given back in `output`, and $.get_input is an error
+use crate::aliases::Aliases;
+use crate::ast::Statement;
+use crate::compiler::compile_main;
+use crate::diagnostics::Diagnostic;
+use crate::globals::Globals;
+use crate::levelstring::{self, GdObj};
+use crate::parser::{parse_spwn_partial_with, ParseNotes, SyntaxError};
+use crate::trigger_area::TriggerArea;
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+// spwn build uses this too, for the steps it shares with it (with its own printing and
+// saving around them)
+#[derive(Clone)]
+pub struct Spwn {
+    include_paths: Vec<PathBuf>,
+    optimize: bool,
+    reuse_items: bool,
+    release: bool,
+    timeout: Option<Duration>,
+    trigger_area: Option<TriggerArea>,
+    aliases: Option<Aliases>,
+}
+
+// a built script
+#[derive(Debug)]
+pub struct CompiledLevel {
+    // with the ids they got in the level
+    pub objects: Vec<GdObj>,
+    // the objects as a level string, to add to a level (or paste into the editor)
+    pub level_string: String,
+    // how many groups, colors, block ids and item ids are used
+    pub used_ids: [usize; 4],
+    // what the script printed
+    pub output: Vec<String>,
+    // warnings from the compiler, like "file:line:column: message"
+    pub warnings: Vec<String>,
+    // unused variables and the other lints
+    pub lints: Vec<Diagnostic>,
+}
+
+// what the optimizations did to the objects (shown with --remarks)
+#[derive(Default)]
+pub struct Remarks {
+    pub optimized: Vec<(usize, String)>,
+    pub shared_groups: Vec<(usize, String)>,
+    pub hoisted: Vec<(usize, String)>,
+    // how many item ids were saved by sharing temporaries
+    pub items_saved: usize,
+}
+
+// why a script couldn't be built
+#[derive(Debug)]
+pub struct Diagnostics {
+    pub errors: Vec<Diagnostic>,
+    // what the script printed before the error
+    pub output: Vec<String>,
+}
+
+impl std::fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, err) in self.errors.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", err)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Diagnostics {}
+
+impl Diagnostics {
+    fn single(err: Diagnostic) -> Self {
+        Diagnostics {
+            errors: vec![err],
+            output: Vec::new(),
+        }
+    }
+}
+
+// an error that isn't in the script (like a file that can't be read)
+fn other_error(message: String, file: &Path) -> Diagnostics {
+    Diagnostics::single(Diagnostic {
+        severity: "error",
+        code: "io_error",
+        message,
+        file: file.to_path_buf(),
+        pos: ((0, 0), (0, 0)),
+        notes: Vec::new(),
+    })
+}
+
+// the folder a script is in, where spwn.toml is looked for
+fn script_dir(path: &Path) -> PathBuf {
+    path.canonicalize()
+        .ok()
+        .and_then(|p| p.parent().map(|p| p.to_path_buf()))
+        .unwrap_or_default()
+}
+
+impl Spwn {
+    // the same settings as spwn build: optimized, with libraries looked up in the current
+    // folder and next to the spwn executable
+    pub fn builder() -> Self {
+        let mut include_paths = Vec::new();
+        if let Ok(dir) = std::env::current_dir() {
+            include_paths.push(dir);
+        }
+        if let Some(dir) = std::env::current_exe()
+            .ok()
+            .and_then(|exe| exe.parent().map(|p| p.to_path_buf()))
+        {
+            include_paths.push(dir);
+        }
+        if cfg!(feature = "embedded-libraries") {
+            include_paths.push(PathBuf::from(crate::files::EMBEDDED));
+        }
+        Spwn {
+            include_paths,
+            optimize: true,
+            reuse_items: true,
+            release: false,
+            timeout: None,
+            trigger_area: None,
+            aliases: None,
+        }
+    }
+
+    // another folder to look for libraries in (like --include-path)
+    pub fn include_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.include_paths.push(path.into());
+        self
+    }
+
+    pub fn optimize(mut self, optimize: bool) -> Self {
+        self.optimize = optimize;
+        self
+    }
+
+    // false is like --no-item-reuse
+    pub fn reuse_items(mut self, reuse_items: bool) -> Self {
+        self.reuse_items = reuse_items;
+        self
+    }
+
+    // like --release
+    pub fn release(mut self, release: bool) -> Self {
+        self.release = release;
+        self
+    }
+
+    // stops scripts that take longer than this (like --timeout)
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    // where the triggers go (the [triggers] section of spwn.toml next to the file is used
+    // if this isn't set)
+    pub fn trigger_area(mut self, area: TriggerArea) -> Self {
+        self.trigger_area = Some(area);
+        self
+    }
+
+    // the keyword aliases the project's files are parsed with (the [aliases] section of
+    // spwn.toml next to the file is used if this isn't set)
+    pub fn aliases(mut self, aliases: Aliases) -> Self {
+        self.aliases = Some(aliases);
+        self
+    }
+
+    pub fn include_paths(&self) -> &[PathBuf] {
+        &self.include_paths
+    }
+
+    pub fn compile_file(&self, path: impl AsRef<Path>) -> Result<CompiledLevel, Diagnostics> {
+        let path = path.as_ref();
+        let source = std::fs::read_to_string(path)
+            .map_err(|e| other_error(format!("Could not read the file: {}", e), path))?;
+        let dir = script_dir(path);
+        let mut spwn = self.clone();
+        if spwn.aliases.is_none() {
+            spwn.aliases = Aliases::find(&dir).map_err(|e| other_error(e, path))?;
+        }
+        if spwn.trigger_area.is_none() {
+            spwn.trigger_area = Some(TriggerArea::find(&dir).map_err(|e| other_error(e, path))?);
+        }
+        spwn.compile(source, path.to_path_buf())
+    }
+
+    // a script that isn't in a file (imports are looked up from the current folder)
+    pub fn compile_source(&self, source: &str) -> Result<CompiledLevel, Diagnostics> {
+        self.clone()
+            .compile(source.to_string(), PathBuf::from("main.spwn"))
+    }
+
+    // the script's statements, with every syntax error in it
+    pub fn parse(
+        &self,
+        source: String,
+        path: PathBuf,
+    ) -> (Vec<Statement>, ParseNotes, Vec<SyntaxError>) {
+        parse_spwn_partial_with(source, path, self.aliases.as_ref())
+    }
+
+    // the globals a script is built with (anything else, like defines, can be set on them
+    // before building)
+    pub fn globals(&self, path: PathBuf) -> Globals {
+        let mut globals = Globals::new(path);
+        globals.release = self.release;
+        globals.aliases = self.aliases.clone();
+        globals.timeout = self.timeout.map(|t| (Instant::now(), t));
+        globals
+    }
+
+    // the objects of a built script (optimized, if that's on), before they get ids.
+    // the optimized trigger functions are kept in the globals
+    pub fn objects(&self, globals: &mut Globals, area: &TriggerArea) -> (Vec<GdObj>, Remarks) {
+        let mut remarks = Remarks::default();
+        if self.optimize && globals.func_ids.iter().any(|f| !f.obj_list.is_empty()) {
+            let (func_ids, optimized) = crate::optimize::optimize_with_remarks(
+                std::mem::take(&mut globals.func_ids),
+                globals.closed_groups,
+                globals.fps,
+                &globals.objects,
+            );
+            globals.func_ids = func_ids;
+            remarks.optimized = optimized;
+        }
+        let mut objects = levelstring::apply_fn_ids(&globals.func_ids, area);
+        objects.extend(std::mem::take(&mut globals.objects));
+        if self.optimize {
+            remarks.shared_groups = crate::group_reuse::merge_spawned_together(&mut objects);
+            remarks.hoisted = crate::loop_hoist::hoist_invariants(&mut objects);
+            if self.reuse_items {
+                remarks.items_saved = crate::item_reuse::share_temporaries(&mut objects);
+            }
+        }
+        (objects, remarks)
+    }
+
+    fn compile(self, source: String, path: PathBuf) -> Result<CompiledLevel, Diagnostics> {
+        // there are no threads in the browser (the stack size is set when linking there)
+        if cfg!(target_arch = "wasm32") {
+            return self.build(source, path);
+        }
+        // the compiler needs the same stack as the spwn binary
+        std::thread::Builder::new()
+            .stack_size(crate::STACK_SIZE)
+            .spawn(move || self.build(source, path))
+            .map_err(|e| other_error(e.to_string(), Path::new("")))?
+            .join()
+            .unwrap_or_else(|_| {
+                Err(other_error(
+                    "The compiler crashed".to_string(),
+                    Path::new(""),
+                ))
+            })
+    }
+
+    fn build(&self, source: String, path: PathBuf) -> Result<CompiledLevel, Diagnostics> {
+        let (statements, notes, errors) = self.parse(source, path.clone());
+        if !errors.is_empty() {
+            return Err(Diagnostics {
+                errors: errors.iter().map(Diagnostic::from).collect(),
+                output: Vec::new(),
+            });
+        }
+
+        let mut globals = self.globals(path.clone());
+        globals.interactive = false;
+        globals.output = Some(Vec::new());
+
+        let result = compile_main(statements, self.include_paths.clone(), notes, &mut globals);
+        let output = globals.output.take().unwrap_or_default();
+        if let Err(err) = result {
+            let mut errors = vec![Diagnostic::from(&err)];
+            errors.extend(globals.errors.iter().map(Diagnostic::from));
+            return Err(Diagnostics { errors, output });
+        }
+
+        let area = self.trigger_area.unwrap_or_default();
+        let (mut objects, _) = self.objects(&mut globals, &area);
+        let ids_error = |e: String| other_error(e, &path);
+        levelstring::resolve_ids(&mut objects, "").map_err(ids_error)?;
+        let (level_string, used_ids) =
+            levelstring::append_objects(objects.clone(), "").map_err(ids_error)?;
+
+        Ok(CompiledLevel {
+            objects,
+            level_string,
+            used_ids,
+            output,
+            warnings: std::mem::take(&mut globals.warnings),
+            lints: globals.lints.iter().map(Diagnostic::from).collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scripts_are_built_without_printing() {
+        let spwn = Spwn::builder().include_path(env!("CARGO_MANIFEST_DIR"));
+        let level = spwn
+            .compile_source("$.print(\"hi\")\n$.add(obj{1: 1, 2: 30, 3: 15})\n")
+            .unwrap();
+        assert_eq!(level.output, vec!["hi"]);
+        assert_eq!(level.objects.len(), 1);
+        assert!(level.level_string.starts_with("1,1,2,30,3,15"));
+
+        let err = spwn.compile_source("a = 1 +\n").unwrap_err();
+        assert_eq!(err.errors[0].code, "expected_token");
+        let err = spwn.compile_source("$.print(missing)\n").unwrap_err();
+        assert_eq!(err.errors[0].code, "undefined");
+    }
+}
@@ -10,14 +10,174 @@ pub enum DictDef {
     Extract(Expression),
 }
 
-//pub type Comment = (Option<String>, Option<String>);
+// comments and blank lines around a statement. the compiler doesn't care about these,
+// they are kept so that the formatter and doc generator can reproduce the source
+#[derive(Clone, PartialEq, Debug)]
+pub struct Trivia {
+    pub comments_before: Vec<String>,
+    // comment on the same line, after the statement
+    pub comment_after: Option<String>,
+    // comments after the last statement of a block (or file)
+    pub comments_end: Vec<String>,
+    pub blank_line_before: bool,
+}
+
+impl Default for Trivia {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Trivia {
+    pub fn new() -> Self {
+        Trivia {
+            comments_before: Vec::new(),
+            comment_after: None,
+            comments_end: Vec::new(),
+            blank_line_before: false,
+        }
+    }
+
+    // the comments before the statement, without the comment markers
+    pub fn doc_lines(&self) -> Vec<String> {
+        self.comments_before
+            .iter()
+            .map(|c| {
+                c.trim_start_matches("//")
+                    .trim_start_matches("/*")
+                    .trim_end_matches("*/")
+                    .trim()
+                    .to_string()
+            })
+            .collect()
+    }
+}
 
 #[derive(Clone, PartialEq, Debug)]
 pub struct Statement {
     pub body: StatementBody,
     pub arrow: bool, /*context changing */
     pub pos: FileRange,
-    //pub comment: Comment,
+    pub trivia: Trivia,
+    // #[...] before the statement
+    pub attributes: Attribute,
+}
+
+impl Statement {
+    // all the statement blocks directly inside this statement
+    // (blocks inside those blocks are not included)
+    pub fn blocks_mut(&mut self) -> Vec<&mut Vec<Statement>> {
+        let mut out = Vec::new();
+        match &mut self.body {
+            StatementBody::Call(c) => variable_blocks(&mut c.function, &mut out),
+            StatementBody::Expr(e) | StatementBody::Extract(e) => expression_blocks(e, &mut out),
+            StatementBody::Return(Some(e)) => expression_blocks(e, &mut out),
+            StatementBody::Impl(imp) => {
+                variable_blocks(&mut imp.symbol, &mut out);
+                dict_blocks(&mut imp.members, &mut out);
+            }
+            StatementBody::If(i) => {
+                expression_blocks(&mut i.condition, &mut out);
+                out.push(&mut i.if_body);
+                if let Some(body) = &mut i.else_body {
+                    out.push(body);
+                }
+            }
+            StatementBody::For(f) => {
+                expression_blocks(&mut f.array, &mut out);
+                out.push(&mut f.body);
+            }
+            StatementBody::Error(e) => expression_blocks(&mut e.message, &mut out),
+            StatementBody::Try(t) => {
+                out.push(&mut t.try_body);
+                out.push(&mut t.catch_body);
+            }
+            StatementBody::Defer(body) => out.push(body),
+            _ => (),
+        }
+        out
+    }
+}
+
+fn expression_blocks<'a>(expr: &'a mut Expression, out: &mut Vec<&'a mut Vec<Statement>>) {
+    for val in &mut expr.values {
+        variable_blocks(val, out);
+    }
+}
+
+fn dict_blocks<'a>(dict: &'a mut Vec<DictDef>, out: &mut Vec<&'a mut Vec<Statement>>) {
+    for def in dict {
+        match def {
+            DictDef::Def((_, e)) | DictDef::Extract(e) => expression_blocks(e, out),
+        }
+    }
+}
+
+fn variable_blocks<'a>(var: &'a mut Variable, out: &mut Vec<&'a mut Vec<Statement>>) {
+    match &mut var.value.body {
+        ValueBody::CmpStmt(c) => out.push(&mut c.statements),
+        ValueBody::Macro(m) => {
+            for (_, default, _, typ) in &mut m.args {
+                if let Some(e) = typ {
+                    expression_blocks(e, out);
+                }
+                if let Some(e) = default {
+                    expression_blocks(e, out);
+                }
+            }
+            out.push(&mut m.body.statements);
+        }
+        ValueBody::Dictionary(d) => dict_blocks(d, out),
+        ValueBody::Expression(e) => expression_blocks(e, out),
+        ValueBody::Switch(e, cases) => {
+            expression_blocks(e, out);
+            for case in cases {
+                match &mut case.typ {
+                    CaseType::Value(values) => {
+                        for e in values {
+                            expression_blocks(e, out);
+                        }
+                    }
+                    CaseType::Pattern(e) => expression_blocks(e, out),
+                    CaseType::Default => (),
+                }
+                expression_blocks(&mut case.body, out);
+            }
+        }
+        ValueBody::Array(arr) => {
+            for e in arr {
+                expression_blocks(e, out);
+            }
+        }
+        ValueBody::Obj(o) => {
+            for (k, v) in &mut o.props {
+                expression_blocks(k, out);
+                expression_blocks(v, out);
+            }
+        }
+        ValueBody::Ternary(t) => {
+            expression_blocks(&mut t.if_expr, out);
+            expression_blocks(&mut t.condition, out);
+            expression_blocks(&mut t.else_expr, out);
+        }
+        ValueBody::Try(t) => {
+            expression_blocks(&mut t.try_expr, out);
+            expression_blocks(&mut t.catch_expr, out);
+        }
+        _ => (),
+    }
+    for p in &mut var.path {
+        match p {
+            Path::Index(e) => expression_blocks(e, out),
+            Path::Call(args) => {
+                for arg in args {
+                    expression_blocks(&mut arg.value, out);
+                }
+            }
+            Path::Constructor(d) => dict_blocks(d, out),
+            _ => (),
+        }
+    }
 }
 
 #[derive(Clone, PartialEq, Debug)]
@@ -33,10 +193,17 @@ pub enum StatementBody {
     If(If),
     For(For),
     Error(Error),
+    Try(Try),
+    // runs when the scope it's in ends
+    Defer(Vec<Statement>),
     Extract(Expression),
 
-    Break,
-    Continue,
+    // with the label of the loop to break out of, if there is one
+    Break(Option<String>),
+    Continue(Option<String>),
+
+    // source code of a statement that couldn't be parsed (see parse_spwn_partial)
+    ErrorStatement(String),
     //EOI,
 }
 #[derive(Clone, PartialEq, Debug)]
@@ -73,7 +240,11 @@ pub enum ValueBody {
     TypeIndicator(String),
     SelfVal,
     Ternary(Ternary),
+    Try(TryExpr),
     Null,
+
+    // source code of an expression that couldn't be parsed
+    ErrorExpr(String),
 }
 
 impl ValueBody {
@@ -159,6 +330,12 @@ pub struct Attribute {
     pub tags: Vec<(String, Vec<Argument>)>,
 }
 
+impl Default for Attribute {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Attribute {
     pub fn new() -> Self {
         Attribute { tags: Vec::new() }
@@ -203,6 +380,18 @@ impl Attribute {
             None
         }
     }
+
+    // example("...", run = false) is only checked for syntax errors by spwn test
+    // (for examples that need things the tests don't have, like files to read)
+    pub fn example_runs(&self) -> bool {
+        match self.get("example") {
+            Some(args) => !args.iter().any(|arg| {
+                arg.symbol.as_deref() == Some("run")
+                    && matches!(arg.value.values[0].value.body, ValueBody::Bool(false))
+            }),
+            None => true,
+        }
+    }
 }
 
 #[derive(Clone, PartialEq, Debug)]
@@ -276,13 +465,16 @@ pub struct Macro {
 
 #[derive(Clone, PartialEq, Debug)]
 pub struct For {
+    // `outer: for ...`, so `break outer` can be used in loops inside it
+    pub label: Option<String>,
     pub symbol: String,
     pub array: Expression,
     pub body: Vec<Statement>,
 }
 #[derive(Clone, PartialEq, Debug)]
 pub enum CaseType {
-    Value(Expression),
+    // `case 1, 3, 5:` (ranges like `case 1..10:` match the numbers in them)
+    Value(Vec<Expression>),
     Pattern(Expression),
     Default,
 }
@@ -304,6 +496,14 @@ pub struct Error {
     pub message: Expression,
 }
 
+#[derive(Clone, PartialEq, Debug)]
+pub struct Try {
+    pub try_body: Vec<Statement>,
+    // the variable that gets the thrown message
+    pub catch_symbol: Option<String>,
+    pub catch_body: Vec<Statement>,
+}
+
 #[derive(Clone, PartialEq, Debug)]
 pub struct Variable {
     pub operator: Option<UnaryOperator>,
@@ -341,6 +541,14 @@ pub struct Ternary {
     pub else_expr: Expression,
 }
 
+// x = try { value } catch err { other value }
+#[derive(Clone, PartialEq, Debug)]
+pub struct TryExpr {
+    pub try_expr: Expression,
+    pub catch_symbol: Option<String>,
+    pub catch_expr: Expression,
+}
+
 impl Expression {
     pub fn to_variable(&self) -> Variable {
         Variable {
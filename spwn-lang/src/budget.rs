@@ -0,0 +1,199 @@
+// #[budget(objects = 5000)] before a statement: the objects and triggers it adds (with the
+// macros it calls and the trigger functions in it) can't go over the number, so a part of
+// a big level can't quietly eat the space the others need. going over is an error that
+// lists where in the statement the objects came from, or a warning with warn = true
+use crate::ast::{Statement, ValueBody};
+use crate::compiler::RuntimeError;
+use crate::compiler_info::CompilerInfo;
+use crate::globals::Globals;
+use crate::parser::FileRange;
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+// how many of the places the objects came from are listed
+const SHOWN_SOURCES: usize = 5;
+
+pub struct Budget {
+    objects: usize,
+    warn: bool,
+    // the objects made before the statement have unique ids up to this
+    first_id: usize,
+}
+
+fn error(message: String, info: &CompilerInfo) -> Box<RuntimeError> {
+    Box::new(RuntimeError::RuntimeError {
+        message,
+        info: info.clone(),
+    })
+}
+
+// the budget of a statement, if it has one. also keeps track of which statement in a
+// budget is being compiled (scope_budget is the one the scope started with)
+pub fn start(
+    statement: &Statement,
+    scope_budget: &Option<(PathBuf, FileRange, FileRange)>,
+    info: &mut CompilerInfo,
+    globals: &Globals,
+) -> Result<Option<Budget>, Box<RuntimeError>> {
+    info.budget = match scope_budget {
+        Some((file, range, _)) if *file == info.current_file && contains(*range, statement.pos) => {
+            Some((file.clone(), *range, statement.pos))
+        }
+        other => other.clone(),
+    };
+    let args = match statement.attributes.get("budget") {
+        Some(args) => args,
+        None => return Ok(None),
+    };
+    let mut objects = None;
+    let mut warn = false;
+    for arg in &args {
+        let value = match arg.value.values.as_slice() {
+            [v] if v.path.is_empty() && v.operator.is_none() => &v.value.body,
+            _ => &ValueBody::Null,
+        };
+        match (arg.symbol.as_deref(), value) {
+            (Some("objects"), ValueBody::Number(n)) if *n >= 0.0 && n.fract() == 0.0 => {
+                objects = Some(*n as usize)
+            }
+            (Some("warn"), ValueBody::Bool(b)) => warn = *b,
+            (Some("objects"), _) => {
+                return Err(error(
+                    "Expected a whole number for the object budget".to_string(),
+                    info,
+                ))
+            }
+            (Some("warn"), _) => {
+                return Err(error(
+                    "Expected true or false for warn in #[budget]".to_string(),
+                    info,
+                ))
+            }
+            _ => {
+                return Err(error(
+                    "Expected objects = (number) or warn = (true or false) in #[budget]"
+                        .to_string(),
+                    info,
+                ))
+            }
+        }
+    }
+    match objects {
+        Some(objects) => {
+            info.budget = Some((info.current_file.clone(), statement.pos, statement.pos));
+            Ok(Some(Budget {
+                objects,
+                warn,
+                first_id: globals.uid_counter,
+            }))
+        }
+        None => Err(error(
+            "#[budget] needs the number of objects, like #[budget(objects = 5000)]".to_string(),
+            info,
+        )),
+    }
+}
+
+fn contains(range: FileRange, pos: FileRange) -> bool {
+    range.0 <= pos.0 && pos.1 <= range.1
+}
+
+// after the statement: errors (or warns) if it added too many objects
+pub fn end(
+    budget: &Option<Budget>,
+    info: &CompilerInfo,
+    globals: &mut Globals,
+) -> Result<(), Box<RuntimeError>> {
+    let budget = match budget {
+        Some(b) => b,
+        None => return Ok(()),
+    };
+    let added: Vec<usize> = globals
+        .func_ids
+        .iter()
+        .flat_map(|f| f.obj_list.iter().map(|(obj, _)| obj))
+        .chain(globals.objects.iter())
+        .map(|obj| obj.unique_id)
+        .filter(|id| *id > budget.first_id)
+        .collect();
+    if added.len() <= budget.objects {
+        return Ok(());
+    }
+
+    // where in the statement they came from, most first
+    let mut sources = HashMap::<(String, (usize, usize)), usize>::new();
+    for id in &added {
+        let source = match globals
+            .budget_sites
+            .get(id)
+            .or_else(|| globals.object_origins.get(id))
+        {
+            Some((file, pos)) => (crate::compiler::display_path(file), pos.0),
+            None => ("<compiler>".to_string(), (0, 0)),
+        };
+        *sources.entry(source).or_insert(0) += 1;
+    }
+    let mut sources: Vec<_> = sources.into_iter().collect();
+    sources.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+    let mut message = format!(
+        "adds {} objects, which is {} over its budget of {}",
+        added.len(),
+        added.len() - budget.objects,
+        budget.objects
+    );
+    for ((file, (line, col)), count) in sources.iter().take(SHOWN_SOURCES) {
+        message += &format!("\n    {}:{}:{}: {} objects", file, line, col + 1, count);
+    }
+    if sources.len() > SHOWN_SOURCES {
+        message += &format!("\n    and {} other places", sources.len() - SHOWN_SOURCES);
+    }
+
+    if budget.warn {
+        globals.warnings.push(format!(
+            "{}:{}:{}: this statement {}",
+            crate::compiler::display_path(&info.current_file),
+            info.pos.0 .0,
+            info.pos.0 .1 + 1,
+            message
+        ));
+        Ok(())
+    } else {
+        Err(error(format!("This statement {}", message), info))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Spwn;
+
+    #[test]
+    fn budgets_count_the_objects_in_them() {
+        let spwn = Spwn::builder().include_path(env!("CARGO_MANIFEST_DIR"));
+        let script = |budget: &str| {
+            format!(
+                "add = (n) {{ for i in 0..n {{ $.add(obj{{1: 1, 2: i * 30, 3: 15}}) }} }}\n{}\nfeature = () {{\n    add(3)\n    add(2)\n}}()\n$.print(feature)\n",
+                budget
+            )
+        };
+        spwn.compile_source(&script("#[budget(objects = 5)]"))
+            .unwrap();
+
+        let err = spwn
+            .compile_source(&script("#[budget(objects = 4)]"))
+            .unwrap_err();
+        let message = &err.errors[0].message;
+        assert!(
+            message.starts_with("This statement adds 5 objects, which is 1 over its budget of 4")
+        );
+        // counted for the calls in it, not the $.add in the macro
+        assert!(message.contains("main.spwn:4:5: 3 objects"), "{}", message);
+        assert!(message.contains("main.spwn:5:5: 2 objects"), "{}", message);
+
+        let level = spwn
+            .compile_source(&script("#[budget(objects = 4, warn = true)]"))
+            .unwrap();
+        assert_eq!(level.warnings.len(), 1);
+    }
+}
@@ -14,6 +14,10 @@ use crate::value_storage::*;
 use std::io::stdout;
 use std::io::Write;
 //use text_io;
+use crate::colors::{self, Blend, Rgb};
+use crate::noise;
+use crate::shapes;
+use crate::typeset;
 use crate::compiler_info::CompilerInfo;
 
 macro_rules! arg_length {
@@ -131,6 +135,189 @@ impl Item {
     }
 }
 
+// player physics, as measured by the community. velocities are in units per tick,
+// and there are 60 ticks a second
+const PLAYER_SPEEDS: [(f64, f64); 5] = [
+    (0.5, 251.16),
+    (1.0, 311.58),
+    (2.0, 387.42),
+    (3.0, 468.0),
+    (4.0, 576.0),
+];
+const PLAYER_GRAVITY: f64 = 0.958_199;
+const CUBE_JUMP: f64 = 11.180_032;
+const CUBE_MAX_FALL: f64 = 15.0;
+// the ship speeds up by a part of the gravity
+const SHIP_HOLD: f64 = 0.4;
+const SHIP_RELEASE: f64 = 0.32;
+const SHIP_MAX_RISE: f64 = 8.0;
+const SHIP_MAX_FALL: f64 = 6.4;
+const PHYSICS_STEPS: usize = 4;
+
+// where the player is every tick (starting at 0, 0 on the ground), while the
+// jump button is held between the times in holds
+fn simulate_player(
+    ship: bool,
+    speed: f64,
+    duration: f64,
+    holds: &[(f64, f64)],
+) -> Vec<[f64; 2]> {
+    let ticks = (duration * 60.0).round() as usize;
+    let dt = 1.0 / PHYSICS_STEPS as f64;
+    let (mut y, mut vel) = (0.0, 0.0);
+    let mut points = vec![[0.0, 0.0]];
+    for tick in 0..ticks {
+        for step in 0..PHYSICS_STEPS {
+            let time = (tick as f64 + step as f64 * dt) / 60.0;
+            let holding = holds.iter().any(|(a, b)| time >= *a && time < *b);
+            if ship {
+                vel = if holding {
+                    (vel + PLAYER_GRAVITY * SHIP_HOLD * dt).min(SHIP_MAX_RISE)
+                } else {
+                    (vel - PLAYER_GRAVITY * SHIP_RELEASE * dt).max(-SHIP_MAX_FALL)
+                };
+            } else {
+                if y <= 0.0 && holding {
+                    vel = CUBE_JUMP;
+                }
+                vel = (vel - PLAYER_GRAVITY * dt).max(-CUBE_MAX_FALL);
+            }
+            y += vel * dt;
+            if y <= 0.0 {
+                y = 0.0;
+                vel = vel.max(0.0);
+            }
+        }
+        points.push([(tick + 1) as f64 / 60.0 * speed, y]);
+    }
+    points
+}
+
+// arguments of the builtins for generating things (colors, shapes and so on)
+fn number_arg(globals: &Globals, arg: StoredValue) -> Option<f64> {
+    match &globals.stored_values[arg] {
+        Value::Number(n) => Some(*n),
+        _ => None,
+    }
+}
+
+fn rgb_arg(globals: &Globals, arg: StoredValue) -> Option<Rgb> {
+    match &globals.stored_values[arg] {
+        Value::Array(a) if a.len() == 3 => Some([
+            number_arg(globals, a[0])?,
+            number_arg(globals, a[1])?,
+            number_arg(globals, a[2])?,
+        ]),
+        _ => None,
+    }
+}
+
+fn gradient_args(
+    globals: &Globals,
+    arguments: &[StoredValue],
+    info: &CompilerInfo,
+) -> Result<(Vec<Rgb>, Blend), RuntimeError> {
+    let colors = match &globals.stored_values[arguments[0]] {
+        Value::Array(a) if !a.is_empty() => a
+            .iter()
+            .map(|c| rgb_arg(globals, *c))
+            .collect::<Option<Vec<_>>>(),
+        _ => None,
+    };
+    let colors = match colors {
+        Some(c) => c,
+        None => {
+            return Err(RuntimeError::BuiltinError {
+                message: "Expected a list of colors as [r, g, b]".to_string(),
+                info: info.clone(),
+            })
+        }
+    };
+    let blend = match arguments.get(2).map(|a| &globals.stored_values[*a]) {
+        None => Blend::Rgb,
+        Some(Value::Str(s)) if s == "rgb" => Blend::Rgb,
+        Some(Value::Str(s)) if s == "hsv" => Blend::Hsv,
+        Some(_) => {
+            return Err(RuntimeError::BuiltinError {
+                message: "Blending needs to be \"rgb\" or \"hsv\"".to_string(),
+                info: info.clone(),
+            })
+        }
+    };
+    Ok((colors, blend))
+}
+
+// rgb values are rounded, since the game only takes whole numbers
+fn store_rgb(rgb: Rgb, globals: &mut Globals, context: &Context) -> Value {
+    Value::Array(
+        rgb.iter()
+            .map(|n| store_const_value(Value::Number(n.round()), 1, globals, context))
+            .collect(),
+    )
+}
+
+fn points_arg(globals: &Globals, arg: StoredValue) -> Option<Vec<shapes::Point>> {
+    match &globals.stored_values[arg] {
+        Value::Array(a) => a
+            .iter()
+            .map(|p| match &globals.stored_values[*p] {
+                Value::Array(xy) if xy.len() == 2 => {
+                    Some([number_arg(globals, xy[0])?, number_arg(globals, xy[1])?])
+                }
+                _ => None,
+            })
+            .collect(),
+        _ => None,
+    }
+}
+
+// copies of the object at every position, turned to follow the shape if turn is true
+fn place_objects(
+    positions: Vec<(shapes::Point, f64)>,
+    object: StoredValue,
+    turn: bool,
+    globals: &mut Globals,
+    context: &Context,
+    info: &CompilerInfo,
+) -> Result<Value, RuntimeError> {
+    let (params, mode) = match &globals.stored_values[object] {
+        Value::Obj(params, mode) => (params.clone(), *mode),
+        a => {
+            return Err(RuntimeError::BuiltinError {
+                message: format!(
+                    "Expected an object to place as the last argument, found {}",
+                    a.to_short_str(globals)
+                ),
+                info: info.clone(),
+            })
+        }
+    };
+    let rotation = params
+        .iter()
+        .find(|(k, _)| *k == 6)
+        .map_or(0.0, |(_, p)| match p {
+            ObjParam::Number(n) => *n,
+            _ => 0.0,
+        });
+
+    let mut objects = Vec::new();
+    for ([x, y], dir) in positions {
+        let mut obj: Vec<(u16, ObjParam)> = params
+            .iter()
+            .filter(|(k, _)| *k != 2 && *k != 3 && !(turn && *k == 6))
+            .cloned()
+            .collect();
+        obj.push((2, ObjParam::Number(x)));
+        obj.push((3, ObjParam::Number(y)));
+        if turn {
+            // the game turns objects clockwise
+            obj.push((6, ObjParam::Number(rotation - dir)));
+        }
+        objects.push(store_const_value(Value::Obj(obj, mode), 1, globals, context));
+    }
+    Ok(Value::Array(objects))
+}
+
 pub fn context_trigger(context: &Context, uid_counter: &mut usize) -> GdObj {
     let mut params = HashMap::new();
     params.insert(57, ObjParam::Group(context.start_group));
@@ -277,15 +464,84 @@ impl Value {
     }
 }
 
+// a hash of the compiler version, the script and the files it imported (by their path in
+// the project folder and what's in them), so it only changes when the sources do. it's
+// made the first time it's used, so it's from the files that were imported before that
+fn build_id(globals: &mut Globals) -> String {
+    if let Some(id) = &globals.build_id {
+        return id.clone();
+    }
+    // the script's folder is empty when it's in the current folder
+    let root = match globals.project_root.as_os_str().is_empty() {
+        true => std::path::Path::new("."),
+        false => globals.project_root.as_path(),
+    };
+    let root = fs::canonicalize(root).unwrap_or_default();
+    let script = fs::canonicalize(&globals.path).unwrap_or_else(|_| globals.path.clone());
+    let mut files: Vec<(String, String)> = std::iter::once(&script)
+        .chain(globals.imported_files.iter())
+        .map(|f| {
+            // libraries outside the project (like std) only count by what's in them, since
+            // where they are depends on the computer
+            let name = match f.strip_prefix(&root) {
+                Ok(relative) if !root.as_os_str().is_empty() => relative
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy().to_string())
+                    .collect::<Vec<_>>()
+                    .join("/"),
+                _ => String::new(),
+            };
+            // the same on every system, whatever line endings git checked it out with
+            let content = fs::read_to_string(f)
+                .unwrap_or_default()
+                .replace("\r\n", "\n");
+            (name, content)
+        })
+        .collect();
+    files.sort();
+    files.dedup();
+
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(env!("CARGO_PKG_VERSION").as_bytes());
+    for (name, content) in files {
+        hasher.update(&[0]);
+        hasher.update(name.as_bytes());
+        hasher.update(&[0]);
+        hasher.update(content.as_bytes());
+    }
+    let id = format!("{:08x}", hasher.finalize());
+    globals.build_id = Some(id.clone());
+    id
+}
+
 pub const BUILTIN_LIST: &[&str] = &[
     "assert",
     "print",
     "time",
     "get_input",
     "matches",
+    "hash",
+    "deep_copy",
+    "freeze",
     "b64encode",
     "b64decode",
     "spwn_version",
+    "build_id",
+    "release",
+    "defines",
+    "fps",
+    "hsv_to_rgb",
+    "rgb_to_hsv",
+    "gradient",
+    "palette",
+    "line",
+    "circle",
+    "arc",
+    "bezier",
+    "polygon_fill",
+    "typeset",
+    "noise",
+    "trajectory",
     "sin",
     "cos",
     "tan",
@@ -363,23 +619,34 @@ pub fn built_in_function(
 
                 a => {
                     return Err(RuntimeError::BuiltinError {
-                        message: format!("Expected boolean, found {}", a.to_str(globals)),
+                        message: format!("Expected boolean, found {}", a.to_short_str(globals)),
                         info,
                     })
                 }
             }
         }
         "print" => {
+            // a library that prints can't be loaded from .spwn-cache/
+            crate::import_cache::impure(globals);
             let mut out = String::new();
             for val in arguments {
-                out += &globals.stored_values[val].to_str(globals);
+                out += &globals.stored_values[val].to_pretty_str(globals, globals.print_limits);
             }
             //out.pop();
-            println!("{}", out);
+            match &mut globals.output {
+                Some(output) => output.push(out),
+                None => println!("{}", out),
+            }
+            globals.printed = true;
             Value::Null
         }
         "time" => {
             arg_length!(info, 0, arguments, "Expected no arguments".to_string());
+            // a cached call would keep giving the same time
+            if let Some(cache) = &mut globals.macro_cache {
+                cache.impure = true;
+            }
+            crate::import_cache::impure(globals);
             use std::time::SystemTime;
             let now = match SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
                 Ok(time) => time,
@@ -394,6 +661,37 @@ pub fn built_in_function(
             Value::Number(now as f64)
         }
 
+        "release" => {
+            arg_length!(info, 0, arguments, "Expected no arguments".to_string());
+            Value::Bool(globals.release)
+        }
+
+        // spwn build main.spwn -- difficulty=hard debug gives {difficulty: "hard", debug: true}
+        "defines" => {
+            arg_length!(info, 0, arguments, "Expected no arguments".to_string());
+            let mut out = HashMap::new();
+            for (name, value) in globals.defines.clone() {
+                let value = match value {
+                    Some(value) => Value::Str(value),
+                    None => Value::Bool(true),
+                };
+                out.insert(name, store_const_value(value, 1, globals, context));
+            }
+            Value::Dict(out)
+        }
+
+        "fps" => {
+            arg_length!(info, 0, arguments, "Expected no arguments".to_string());
+            Value::Number(globals.fps)
+        }
+
+        "build_id" => {
+            arg_length!(info, 0, arguments, "Expected no arguments".to_string());
+            // it's made from every file in the build
+            crate::import_cache::impure(globals);
+            Value::Str(build_id(globals))
+        }
+
         "spwn_version" => {
             arg_length!(info, 0, arguments, "Expected no arguments".to_string());
 
@@ -407,6 +705,17 @@ pub fn built_in_function(
                 arguments,
                 "Expected one arguments, the prompt".to_string()
             );
+            if !globals.interactive {
+                return Err(RuntimeError::BuiltinError {
+                    message: "get_input can't ask for input when building with --non-interactive"
+                        .to_string(),
+                    info,
+                });
+            }
+            if let Some(cache) = &mut globals.macro_cache {
+                cache.impure = true;
+            }
+            crate::import_cache::impure(globals);
             let mut out = String::new();
             for val in arguments {
                 out += &globals.stored_values[val].to_str(globals);
@@ -431,6 +740,35 @@ pub fn built_in_function(
             Value::Bool(val.matches_pat(&pattern, &info, globals, context)?)
         }
 
+        "hash" => {
+            arg_length!(info, 1, arguments, "Expected one argument".to_string());
+            // values that are equal (==) always have the same hash
+            // (only 53 bits, so it fits in a number)
+            Value::Number((value_hash(arguments[0], globals) >> 11) as f64)
+        }
+
+        "deep_copy" => {
+            arg_length!(info, 1, arguments, "Expected one argument".to_string());
+            // everything inside is copied too, so nothing is shared with the original
+            let copy = clone_and_get_value(arguments[0], 1, globals, context.start_group, false);
+            let inner: Vec<StoredValue> = match &copy {
+                Value::Array(a) => a.clone(),
+                Value::Dict(d) => d.values().copied().collect(),
+                _ => Vec::new(),
+            };
+            for v in inner {
+                globals.set_frozen(v, None);
+            }
+            copy
+        }
+
+        "freeze" => {
+            arg_length!(info, 1, arguments, "Expected one argument".to_string());
+            // the value given back is frozen too (see where builtins are called)
+            globals.set_frozen(arguments[0], Some((info.current_file.clone(), info.pos)));
+            globals.stored_values[arguments[0]].clone()
+        }
+
         "b64encode" => {
             arg_length!(
                 info,
@@ -514,6 +852,438 @@ pub fn built_in_function(
         //     }
         //     out
         // }
+        "hsv_to_rgb" | "rgb_to_hsv" => {
+            arg_length!(
+                info,
+                3,
+                arguments,
+                "Expected three numbers as arguments".to_string()
+            );
+            let values = match (
+                number_arg(globals, arguments[0]),
+                number_arg(globals, arguments[1]),
+                number_arg(globals, arguments[2]),
+            ) {
+                (Some(a), Some(b), Some(c)) => [a, b, c],
+                _ => {
+                    return Err(RuntimeError::BuiltinError {
+                        message: "Expected three numbers as arguments".to_string(),
+                        info,
+                    })
+                }
+            };
+            if name == "hsv_to_rgb" {
+                store_rgb(
+                    colors::hsv_to_rgb(values[0], values[1], values[2]),
+                    globals,
+                    context,
+                )
+            } else {
+                Value::Array(
+                    colors::rgb_to_hsv(values)
+                        .iter()
+                        .map(|n| store_const_value(Value::Number(*n), 1, globals, context))
+                        .collect(),
+                )
+            }
+        }
+
+        "gradient" | "palette" => {
+            if arguments.len() < 2 || arguments.len() > 3 {
+                return Err(RuntimeError::BuiltinError {
+                    message: format!(
+                        "Expected 2 or 3 arguments: the colors, {}, and the blending (\"rgb\" or \"hsv\", default: \"rgb\")",
+                        if name == "gradient" {
+                            "the position along the gradient (from 0 to 1)"
+                        } else {
+                            "how many colors to make"
+                        }
+                    ),
+                    info,
+                });
+            }
+            let (stops, blend) = gradient_args(globals, &arguments, &info)?;
+            let n = match number_arg(globals, arguments[1]) {
+                Some(n) => n,
+                None => {
+                    return Err(RuntimeError::BuiltinError {
+                        message: "Expected a number as the second argument".to_string(),
+                        info,
+                    })
+                }
+            };
+            if name == "gradient" {
+                store_rgb(colors::gradient(&stops, n, blend), globals, context)
+            } else {
+                if n < 1.0 || n.fract() != 0.0 {
+                    return Err(RuntimeError::BuiltinError {
+                        message: format!("Expected a whole number of colors, found {}", n),
+                        info,
+                    });
+                }
+                Value::Array(
+                    colors::palette(&stops, n as usize, blend)
+                        .into_iter()
+                        .map(|c| {
+                            let rgb = store_rgb(c, globals, context);
+                            store_const_value(rgb, 1, globals, context)
+                        })
+                        .collect(),
+                )
+            }
+        }
+
+        "line" | "circle" | "arc" | "bezier" | "polygon_fill" => {
+            let usage = match name {
+                "line" => "x1, y1, x2, y2, spacing, object",
+                "circle" => "x, y, radius, spacing, object",
+                "arc" => "x, y, radius, start_angle, end_angle, spacing, object",
+                _ => "points, spacing, object",
+            };
+            let arg_count = usage.split(", ").count();
+            if arguments.len() != arg_count {
+                return Err(RuntimeError::BuiltinError {
+                    message: format!("Expected {} arguments: {}", arg_count, usage),
+                    info,
+                });
+            }
+            let object = arguments[arg_count - 1];
+            let spacing = match number_arg(globals, arguments[arg_count - 2]) {
+                Some(s) if s > 0.0 => s,
+                _ => {
+                    return Err(RuntimeError::BuiltinError {
+                        message: "Expected a spacing above 0".to_string(),
+                        info,
+                    })
+                }
+            };
+
+            let positions = if name == "bezier" || name == "polygon_fill" {
+                let min = if name == "bezier" { 2 } else { 3 };
+                match points_arg(globals, arguments[0]) {
+                    Some(points) if points.len() >= min => {
+                        if name == "bezier" {
+                            shapes::bezier(&points, spacing)
+                        } else {
+                            shapes::polygon_fill(&points, spacing)
+                        }
+                    }
+                    _ => {
+                        return Err(RuntimeError::BuiltinError {
+                            message: format!(
+                                "Expected a list of at least {} points as [x, y]",
+                                min
+                            ),
+                            info,
+                        })
+                    }
+                }
+            } else {
+                let mut numbers = Vec::new();
+                for arg in &arguments[..arg_count - 2] {
+                    match number_arg(globals, *arg) {
+                        Some(n) => numbers.push(n),
+                        None => {
+                            return Err(RuntimeError::BuiltinError {
+                                message: format!("Expected numbers for {}", usage),
+                                info,
+                            })
+                        }
+                    }
+                }
+                match name {
+                    "line" => shapes::line(
+                        [numbers[0], numbers[1]],
+                        [numbers[2], numbers[3]],
+                        spacing,
+                    ),
+                    "circle" => {
+                        shapes::arc([numbers[0], numbers[1]], numbers[2], 0.0, 360.0, spacing)
+                    }
+                    _ => shapes::arc(
+                        [numbers[0], numbers[1]],
+                        numbers[2],
+                        numbers[3],
+                        numbers[4],
+                        spacing,
+                    ),
+                }
+            };
+            place_objects(
+                positions,
+                object,
+                name != "polygon_fill",
+                globals,
+                context,
+                &info,
+            )?
+        }
+
+        "typeset" => {
+            if arguments.len() < 3 || arguments.len() > 4 {
+                return Err(RuntimeError::BuiltinError {
+                    message: "Expected 3 or 4 arguments: text, x, y and the settings (a dictionary with scale, align, max_width, line_height, kerning and object)".to_string(),
+                    info,
+                });
+            }
+            let text = match &globals.stored_values[arguments[0]] {
+                Value::Str(s) => s.clone(),
+                a => {
+                    return Err(RuntimeError::BuiltinError {
+                        message: format!("Expected a string to typeset, found {}", a.to_short_str(globals)),
+                        info,
+                    })
+                }
+            };
+            let (x, y) = match (
+                number_arg(globals, arguments[1]),
+                number_arg(globals, arguments[2]),
+            ) {
+                (Some(x), Some(y)) => (x, y),
+                _ => {
+                    return Err(RuntimeError::BuiltinError {
+                        message: "Expected numbers for x and y".to_string(),
+                        info,
+                    })
+                }
+            };
+
+            let mut settings = typeset::Settings {
+                scale: 1.0,
+                align: typeset::Align::Left,
+                max_width: 0.0,
+                line_height: 30.0,
+                kerning: 0.0,
+            };
+            let mut line_height = None;
+            let mut template = (Vec::new(), ObjectMode::Object);
+            if let Some(arg) = arguments.get(3) {
+                let dict = match &globals.stored_values[*arg] {
+                    Value::Dict(d) => d.clone(),
+                    a => {
+                        return Err(RuntimeError::BuiltinError {
+                            message: format!(
+                                "Expected a dictionary of settings, found {}",
+                                a.to_short_str(globals)
+                            ),
+                            info,
+                        })
+                    }
+                };
+                for (key, val) in dict {
+                    let number = number_arg(globals, val);
+                    match (key.as_str(), &globals.stored_values[val]) {
+                        (TYPE_MEMBER_NAME, _) => (),
+                        ("scale", _) if matches!(number, Some(n) if n > 0.0) => {
+                            settings.scale = number.unwrap()
+                        }
+                        ("max_width", _) if number.is_some() => settings.max_width = number.unwrap(),
+                        ("line_height", _) if number.is_some() => line_height = number,
+                        ("kerning", _) if number.is_some() => settings.kerning = number.unwrap(),
+                        ("align", Value::Str(a)) if a == "left" => {
+                            settings.align = typeset::Align::Left
+                        }
+                        ("align", Value::Str(a)) if a == "center" => {
+                            settings.align = typeset::Align::Center
+                        }
+                        ("align", Value::Str(a)) if a == "right" => {
+                            settings.align = typeset::Align::Right
+                        }
+                        ("object", Value::Obj(params, mode)) => {
+                            template = (params.clone(), *mode)
+                        }
+                        ("scale", _) | ("max_width", _) | ("line_height", _) | ("kerning", _) => {
+                            return Err(RuntimeError::BuiltinError {
+                                message: format!("Expected a number above 0 for {}", key),
+                                info,
+                            })
+                        }
+                        ("align", _) => {
+                            return Err(RuntimeError::BuiltinError {
+                                message: "Expected \"left\", \"center\" or \"right\" for align"
+                                    .to_string(),
+                                info,
+                            })
+                        }
+                        ("object", _) => {
+                            return Err(RuntimeError::BuiltinError {
+                                message: "Expected an object for the letters to be copies of"
+                                    .to_string(),
+                                info,
+                            })
+                        }
+                        _ => {
+                            return Err(RuntimeError::BuiltinError {
+                                message: format!("Unknown setting: {} (expected scale, align, max_width, line_height, kerning or object)", key),
+                                info,
+                            })
+                        }
+                    }
+                }
+            }
+            settings.line_height = line_height.unwrap_or(30.0 * settings.scale);
+
+            let layout = typeset::layout(&text, x, y, &settings);
+            let mut objects = Vec::new();
+            for (c, x, y) in layout.letters {
+                let mut obj: Vec<(u16, ObjParam)> = template
+                    .0
+                    .iter()
+                    .filter(|(k, _)| ![1, 2, 3, 31, 32].contains(k))
+                    .cloned()
+                    .collect();
+                obj.push((1, ObjParam::Number(914.0)));
+                obj.push((2, ObjParam::Number(x)));
+                obj.push((3, ObjParam::Number(y)));
+                obj.push((31, ObjParam::Text(base64::encode(c.to_string().as_bytes()))));
+                if settings.scale != 1.0 {
+                    obj.push((32, ObjParam::Number(settings.scale)));
+                }
+                objects.push(store_const_value(
+                    Value::Obj(obj, template.1),
+                    1,
+                    globals,
+                    context,
+                ));
+            }
+
+            let mut out = HashMap::new();
+            out.insert(
+                "objects".to_string(),
+                store_const_value(Value::Array(objects), 1, globals, context),
+            );
+            for (key, n) in &[
+                ("left", layout.left),
+                ("top", layout.top),
+                ("width", layout.width),
+                ("height", layout.height),
+            ] {
+                out.insert(
+                    key.to_string(),
+                    store_const_value(Value::Number(*n), 1, globals, context),
+                );
+            }
+            Value::Dict(out)
+        }
+
+        "noise" => {
+            const USAGE: &str = "Expected the kind of noise and then numbers: (\"perlin\", \"simplex\" or \"voronoi\", x, y, seed = 0) or (\"hash\", value, seed = 0)";
+            let kind = match arguments.first().map(|a| &globals.stored_values[*a]) {
+                Some(Value::Str(s)) => s.clone(),
+                _ => {
+                    return Err(RuntimeError::BuiltinError {
+                        message: USAGE.to_string(),
+                        info,
+                    })
+                }
+            };
+            let mut numbers = Vec::new();
+            for arg in &arguments[1..] {
+                match number_arg(globals, *arg) {
+                    Some(n) => numbers.push(n),
+                    None => {
+                        return Err(RuntimeError::BuiltinError {
+                            message: USAGE.to_string(),
+                            info,
+                        })
+                    }
+                }
+            }
+            let (min, max) = if kind == "hash" { (1, 2) } else { (2, 3) };
+            if numbers.len() < min || numbers.len() > max {
+                return Err(RuntimeError::BuiltinError {
+                    message: USAGE.to_string(),
+                    info,
+                });
+            }
+            let seed = if numbers.len() == max {
+                numbers[max - 1] as i64
+            } else {
+                0
+            };
+            Value::Number(match kind.as_str() {
+                "hash" => noise::hash_number(numbers[0], seed),
+                "perlin" => noise::perlin(numbers[0], numbers[1], seed),
+                "simplex" => noise::simplex(numbers[0], numbers[1], seed),
+                "voronoi" => noise::voronoi(numbers[0], numbers[1], seed),
+                _ => {
+                    return Err(RuntimeError::BuiltinError {
+                        message: format!("Unknown kind of noise: {} (expected perlin, simplex, voronoi or hash)", kind),
+                        info,
+                    })
+                }
+            })
+        }
+
+        "trajectory" => {
+            const USAGE: &str = "Expected 3 or 4 arguments: the gamemode (\"cube\" or \"ship\"), the speed (0.5, 1, 2, 3 or 4), how many seconds to simulate, and when the jump button is held (a list of [start, end] times)";
+            if arguments.len() < 3 || arguments.len() > 4 {
+                return Err(RuntimeError::BuiltinError {
+                    message: USAGE.to_string(),
+                    info,
+                });
+            }
+            let ship = match &globals.stored_values[arguments[0]] {
+                Value::Str(s) if s == "cube" => false,
+                Value::Str(s) if s == "ship" => true,
+                _ => {
+                    return Err(RuntimeError::BuiltinError {
+                        message: USAGE.to_string(),
+                        info,
+                    })
+                }
+            };
+            let speed = match number_arg(globals, arguments[1])
+                .and_then(|n| PLAYER_SPEEDS.iter().find(|(s, _)| *s == n))
+            {
+                Some((_, units)) => *units,
+                None => {
+                    return Err(RuntimeError::BuiltinError {
+                        message: "Expected a speed of 0.5, 1, 2, 3 or 4 (like the speed portals)"
+                            .to_string(),
+                        info,
+                    })
+                }
+            };
+            let duration = match number_arg(globals, arguments[2]) {
+                Some(d) if d >= 0.0 => d,
+                _ => {
+                    return Err(RuntimeError::BuiltinError {
+                        message: USAGE.to_string(),
+                        info,
+                    })
+                }
+            };
+            let holds = match arguments.get(3) {
+                None => Some(Vec::new()),
+                Some(arg) => points_arg(globals, *arg)
+                    .map(|list| list.iter().map(|p| (p[0], p[1])).collect()),
+            };
+            let holds = match holds {
+                Some(h) => h,
+                None => {
+                    return Err(RuntimeError::BuiltinError {
+                        message: USAGE.to_string(),
+                        info,
+                    })
+                }
+            };
+
+            let points = simulate_player(ship, speed, duration, &holds);
+            Value::Array(
+                points
+                    .into_iter()
+                    .map(|[x, y]| {
+                        let point = Value::Array(vec![
+                            store_const_value(Value::Number(x), 1, globals, context),
+                            store_const_value(Value::Number(y), 1, globals, context),
+                        ]);
+                        store_const_value(point, 1, globals, context)
+                    })
+                    .collect(),
+            )
+        }
+
         "sin" | "cos" | "tan" | "asin" | "acos" | "atan" | "floor" | "ceil" => {
             arg_length!(info, 1, arguments, "Expected one argument".to_string());
 
@@ -533,7 +1303,7 @@ pub fn built_in_function(
 
                 a => {
                     return Err(RuntimeError::BuiltinError {
-                        message: format!("Expected number, found {}", a.to_str(globals)),
+                        message: format!("Expected number, found {}", a.to_short_str(globals)),
                         info,
                     })
                 }
@@ -564,6 +1334,7 @@ pub fn built_in_function(
                                 });
                             }
                             (*globals).uid_counter += 1;
+                            globals.record_origin(globals.uid_counter, &info);
                             let obj = GdObj {
                                 params: obj_map,
                                 func_id: context.func_id,
@@ -572,6 +1343,7 @@ pub fn built_in_function(
                                 sync_group: context.sync_group,
                                 sync_part: context.sync_part,
                             };
+                            crate::analyzer::object_emitted(&obj, &info, globals);
                             (*globals).objects.push(obj)
                         }
                         ObjectMode::Trigger => {
@@ -581,6 +1353,8 @@ pub fn built_in_function(
                                 ..c_t
                             }
                             .context_parameters(context);
+                            globals.record_origin(obj.unique_id, &info);
+                            crate::analyzer::object_emitted(&obj, &info, globals);
                             (*globals).trigger_order += 1;
                             (*globals).func_ids[context.func_id]
                                 .obj_list
@@ -591,7 +1365,7 @@ pub fn built_in_function(
 
                 a => {
                     return Err(RuntimeError::BuiltinError {
-                        message: format!("Expected object, found {}", a.to_str(globals)),
+                        message: format!("Expected object, found {}", a.to_short_str(globals)),
                         info,
                     })
                 }
@@ -610,7 +1384,7 @@ pub fn built_in_function(
 
             if !globals.can_mutate(arguments[0]) {
                 return Err(RuntimeError::BuiltinError {
-                    message: String::from("This array is not mutable"),
+                    message: format!("This array is not mutable{}", globals.frozen_note(arguments[0])),
                     info,
                 });
             }
@@ -683,7 +1457,7 @@ pub fn built_in_function(
             arg_length!(info, 3, arguments, "Expected three arguments".to_string());
             if !globals.can_mutate(arguments[0]) {
                 return Err(RuntimeError::BuiltinError {
-                    message: "Cannot modify an immutable value".to_string(),
+                    message: format!("Cannot modify an immutable value{}", globals.frozen_note(arguments[0])),
                     info,
                 });
             }
@@ -782,7 +1556,7 @@ pub fn built_in_function(
                             return Err(RuntimeError::RuntimeError {
                                 message: format!(
                                 "expected either @number or @object_key as object key, found: {}",
-                                a.to_str(globals)
+                                a.to_short_str(globals)
                             ),
                                 info,
                             })
@@ -795,14 +1569,14 @@ pub fn built_in_function(
                                 message: format!(
                                     "key required value to match {}, found {}",
                                     pat.to_str(globals),
-                                    value.to_str(globals)
+                                    value.to_short_str(globals)
                                 ),
                                 info,
                             });
                         }
                     }
                     let err = Err(RuntimeError::RuntimeError {
-                        message: format!("{} is not a valid object value", value.to_str(globals)),
+                        message: format!("{} is not a valid object value", value.to_short_str(globals)),
                         info: info.clone(),
                     });
 
@@ -909,7 +1683,7 @@ pub fn built_in_function(
                     return Err(RuntimeError::BuiltinError {
                         message: format!(
                             "Expected group or trigger function, found {}",
-                            a.to_str(globals)
+                            a.to_short_str(globals)
                         ),
                         info,
                     })
@@ -919,7 +1693,7 @@ pub fn built_in_function(
                 Value::Macro(m) => *m,
                 a => {
                     return Err(RuntimeError::BuiltinError {
-                        message: format!("Expected macro, found {}", a.to_str(globals)),
+                        message: format!("Expected macro, found {}", a.to_short_str(globals)),
                         info,
                     })
                 }
@@ -934,6 +1708,8 @@ pub fn built_in_function(
         }
 
         "readfile" => {
+            // the file isn't one of the imports the cache checks
+            crate::import_cache::impure(globals);
             if arguments.is_empty() || arguments.len() > 2 {
                 return Err(RuntimeError::BuiltinError {
                     message: String::from("Expected 1 or 2 arguments, the path to the file and the data format (default: utf-8)"),
@@ -1033,7 +1809,7 @@ pub fn built_in_function(
 
             if !globals.can_mutate(arguments[0]) {
                 return Err(RuntimeError::BuiltinError {
-                    message: String::from("This value is not mutable"),
+                    message: format!("This value is not mutable{}", globals.frozen_note(arguments[0])),
                     info,
                 });
             }
@@ -1133,7 +1909,7 @@ pub fn built_in_function(
 
             if !globals.can_mutate(arguments[0]) {
                 return Err(RuntimeError::BuiltinError {
-                    message: String::from("This value is not mutable"),
+                    message: format!("This value is not mutable{}", globals.frozen_note(arguments[0])),
                     info,
                 });
             }
@@ -1182,60 +1958,95 @@ pub fn built_in_function(
                 info,
                 4,
                 arguments,
-                "Expected four arguments, a string for regex, a string to process, type of regex operation to perform, string for \"replace\" mode (?)"
+                "Expected four arguments, a string for regex, a string to process, type of regex operation to perform, string for \"replace\" mode (null for the others)"
                     .to_string()
             );
 
             if let Value::Str(s) = &globals.stored_values[arguments[0]] {
-                if let Ok(r) = Regex::new(s) {
-                    if let Value::Str(s) = &globals.stored_values[arguments[1]] {
-                        if let Value::Str(mode) = &globals.stored_values[arguments[2]] {
-                            match &**mode {
-                                "match" => return Ok(Value::Bool(r.is_match(s))),
-                                "replace" => {
-                                    match &globals.stored_values[arguments[3]] {
-                                        Value::Str(replacer) => {
-                                            return Ok(Value::Str(r.replace_all(s, replacer).to_string()))
-                                        }
-                                        _ => {
-                                            return Err(
-                                                RuntimeError::BuiltinError {
-                                                    message: format!("Invalid or missing replacer. Expected @string, found @{}", &globals.get_type_str(arguments[3])),
-                                                    info
-                                                }
-                                            )
+                match Regex::new(s) {
+                    Ok(r) => {
+                        if let Value::Str(s) = &globals.stored_values[arguments[1]] {
+                            if let Value::Str(mode) = &globals.stored_values[arguments[2]] {
+                                match &**mode {
+                                    "match" => return Ok(Value::Bool(r.is_match(s))),
+                                    // the whole match and then each group (null for the ones that
+                                    // didn't match), or null if nothing matched
+                                    "captures" => {
+                                        let groups: Option<Vec<Option<String>>> = r.captures(s).map(|c| {
+                                            c.iter().map(|m| m.map(|m| m.as_str().to_string())).collect()
+                                        });
+                                        return Ok(match groups {
+                                            Some(groups) => Value::Array(
+                                                groups
+                                                    .into_iter()
+                                                    .map(|g| {
+                                                        let val = match g {
+                                                            Some(g) => Value::Str(g),
+                                                            None => Value::Null,
+                                                        };
+                                                        store_const_value(val, 1, globals, context)
+                                                    })
+                                                    .collect(),
+                                            ),
+                                            None => Value::Null,
+                                        });
+                                    }
+                                    "find_all" => {
+                                        let found: Vec<String> =
+                                            r.find_iter(s).map(|m| m.as_str().to_string()).collect();
+                                        return Ok(Value::Array(
+                                            found
+                                                .into_iter()
+                                                .map(|m| store_const_value(Value::Str(m), 1, globals, context))
+                                                .collect(),
+                                        ));
+                                    }
+                                    "replace" => {
+                                        match &globals.stored_values[arguments[3]] {
+                                            Value::Str(replacer) => {
+                                                return Ok(Value::Str(r.replace_all(s, replacer).to_string()))
+                                            }
+                                            _ => {
+                                                return Err(
+                                                    RuntimeError::BuiltinError {
+                                                        message: format!("Invalid or missing replacer. Expected @string, found @{}", &globals.get_type_str(arguments[3])),
+                                                        info
+                                                    }
+                                                )
+                                            }
                                         }
                                     }
+                                    _ => {
+                                        return Err(RuntimeError::BuiltinError {
+                                            message: format!(
+                                                "Invalid regex mode \"{}\" in regex {}. Expected \"match\", \"captures\", \"find_all\" or \"replace\"",
+                                                mode, r
+                                            ),
+                                            info,
+                                        })
+                                    }
                                 }
-                                _ => {
-                                    return Err(RuntimeError::BuiltinError {
-                                        message: format!(
-                                            "Invalid regex mode \"{}\" in regex {}. Expected \"match\" or \"replace\"",
-                                            mode, r
-                                        ),
-                                        info,
-                                    })
-                                }
+                            } else {
+                                return Err(RuntimeError::TypeError {
+                                    expected: "String".into(),
+                                    found: globals.get_type_str(arguments[2]),
+                                    info,
+                                });
                             }
                         } else {
                             return Err(RuntimeError::TypeError {
                                 expected: "String".into(),
-                                found: globals.get_type_str(arguments[2]),
+                                found: globals.get_type_str(arguments[1]),
                                 info,
                             });
                         }
-                    } else {
-                        return Err(RuntimeError::TypeError {
-                            expected: "String".into(),
-                            found: globals.get_type_str(arguments[1]),
+                    }
+                    Err(e) => {
+                        return Err(RuntimeError::BuiltinError {
+                            message: format!("Failed to build regex (invalid syntax):\n{}", e),
                             info,
                         });
                     }
-                } else {
-                    return Err(RuntimeError::BuiltinError {
-                        message: "Failed to build regex (invalid syntax)".to_string(),
-                        info,
-                    });
                 }
             } else {
                 return Err(RuntimeError::TypeError {
@@ -1269,11 +2080,22 @@ pub fn built_in_function(
 
             let mutable = globals.can_mutate(acum_val);
             let val_mutable = globals.can_mutate(val);
+            let frozen_note = globals.frozen_note(if mutable { val } else { acum_val });
 
             let val_b = globals.stored_values[val].clone();
             let val_a = &mut globals.stored_values[acum_val];
 
-            fn mutable_err(info: CompilerInfo, attempted_op_macro: &str) -> RuntimeError {
+            fn mutable_err(
+                info: CompilerInfo,
+                attempted_op_macro: &str,
+                frozen_note: &str,
+            ) -> RuntimeError {
+                if !frozen_note.is_empty() {
+                    return RuntimeError::RuntimeError {
+                        message: format!("This value can't be changed{}", frozen_note),
+                        info,
+                    };
+                }
                 RuntimeError::RuntimeError {
                     message: format!(
                         "
@@ -1487,7 +2309,7 @@ Consider defining it with 'let', or implementing a '{}' macro on its type.",
                 "_assign_" => {
                     //println!("hi1");
                     if !mutable {
-                        return Err(mutable_err(info, "_assign_"));
+                        return Err(mutable_err(info, "_assign_", &frozen_note));
                     }
                     if acum_val_fn_context != c2.start_group {
                         return Err(RuntimeError::RuntimeError {
@@ -1497,7 +2319,7 @@ Consider defining it with 'let', or implementing a '{}' macro on its type.",
                     }
 
                     if globals.stored_values[acum_val] == Value::Null
-                        && globals.stored_values.map.get(&acum_val).unwrap().mutable
+                        && globals.stored_values.get(acum_val).unwrap().mutable
                     {
                         //println!("hi");
                         globals.stored_values[acum_val] = clone_and_get_value(
@@ -1508,6 +2330,7 @@ Consider defining it with 'let', or implementing a '{}' macro on its type.",
                             false,
                         );
                         globals.stored_values.set_mutability(acum_val, true);
+                        globals.copy_frozen(val, acum_val);
                         globals.stored_values[acum_val].clone()
                     } else {
                         //println!("{:?}", globals.stored_values[acum_val]);
@@ -1518,6 +2341,7 @@ Consider defining it with 'let', or implementing a '{}' macro on its type.",
                             c2.start_group,
                             false,
                         );
+                        globals.copy_frozen(val, acum_val);
                         globals.stored_values[acum_val].clone()
                     }
                 }
@@ -1525,7 +2349,7 @@ Consider defining it with 'let', or implementing a '{}' macro on its type.",
                 "_swap_" => {
                     //println!("hi1");
                     if !mutable || !val_mutable {
-                        return Err(mutable_err(info, "_swap_"));
+                        return Err(mutable_err(info, "_swap_", &frozen_note));
                     }
                     if acum_val_fn_context != c2.start_group || val_fn_context != c2.start_group {
                         return Err(RuntimeError::RuntimeError {
@@ -1683,7 +2507,7 @@ Consider defining it with 'let', or implementing a '{}' macro on its type.",
                 )),
                 "_add_" => {
                     if !mutable {
-                        return Err(mutable_err(info, "_add_"));
+                        return Err(mutable_err(info, "_add_", &frozen_note));
                     }
                     if acum_val_fn_context != c2.start_group {
                         return Err(RuntimeError::RuntimeError {
@@ -1710,7 +2534,7 @@ Consider defining it with 'let', or implementing a '{}' macro on its type.",
                 }
                 "_subtract_" => {
                     if !mutable {
-                        return Err(mutable_err(info, "_subtract_"));
+                        return Err(mutable_err(info, "_subtract_", &frozen_note));
                     }
                     if acum_val_fn_context != c2.start_group {
                         return Err(RuntimeError::RuntimeError {
@@ -1734,7 +2558,7 @@ Consider defining it with 'let', or implementing a '{}' macro on its type.",
                 }
                 "_multiply_" => {
                     if !mutable {
-                        return Err(mutable_err(info, "_multiply_"));
+                        return Err(mutable_err(info, "_multiply_", &frozen_note));
                     }
                     if acum_val_fn_context != c2.start_group {
                         return Err(RuntimeError::RuntimeError {
@@ -1758,7 +2582,7 @@ Consider defining it with 'let', or implementing a '{}' macro on its type.",
                 }
                 "_exponate_" => {
                     if !mutable {
-                        return Err(mutable_err(info, "_exponate_"));
+                        return Err(mutable_err(info, "_exponate_", &frozen_note));
                     }
                     if acum_val_fn_context != c2.start_group {
                         return Err(RuntimeError::RuntimeError {
@@ -1782,7 +2606,7 @@ Consider defining it with 'let', or implementing a '{}' macro on its type.",
                 }
                 "_modulate_" => {
                     if !mutable {
-                        return Err(mutable_err(info, "_modulate_"));
+                        return Err(mutable_err(info, "_modulate_", &frozen_note));
                     }
                     if acum_val_fn_context != c2.start_group {
                         return Err(RuntimeError::RuntimeError {
@@ -1806,7 +2630,7 @@ Consider defining it with 'let', or implementing a '{}' macro on its type.",
                 }
                 "_divide_" => {
                     if !mutable {
-                        return Err(mutable_err(info, "_divide_"));
+                        return Err(mutable_err(info, "_divide_", &frozen_note));
                     }
                     if acum_val_fn_context != c2.start_group {
                         return Err(RuntimeError::RuntimeError {
@@ -1830,7 +2654,7 @@ Consider defining it with 'let', or implementing a '{}' macro on its type.",
                 }
                 "_intdivide_" => {
                     if !mutable {
-                        return Err(mutable_err(info, "_intdivide_"));
+                        return Err(mutable_err(info, "_intdivide_", &frozen_note));
                     }
                     if acum_val_fn_context != c2.start_group {
                         return Err(RuntimeError::RuntimeError {
@@ -0,0 +1,802 @@
+// how the files in .spwn-cache/ are written: parsed files, and the values libraries
+// export (with everything they point to in globals.stored_values). numbers are little
+// endian and lengths are u32s. there's nothing in here to tell one version of this
+// format from another, so the compiler version is part of every cache key instead
+use crate::builtin::{Block, Color, Group, Id, Item};
+use crate::compiler::{BUILTIN_STORAGE, NULL_STORAGE};
+use crate::compiler_types::{Implementations, ImportType};
+use crate::context::Context;
+use crate::globals::Globals;
+use crate::levelstring::ObjParam;
+use crate::value::{Macro, Pattern, TriggerFunction, Value};
+use crate::value_storage::{StoredValData, StoredValue};
+
+use std::collections::HashMap;
+use std::convert::{TryFrom, TryInto};
+use std::hash::Hash;
+use std::path::PathBuf;
+
+#[derive(Default)]
+pub struct Writer {
+    pub bytes: Vec<u8>,
+    // set when something can't be written, so the whole thing is left out
+    pub failed: bool,
+}
+
+impl Writer {
+    pub fn byte(&mut self, b: u8) {
+        self.bytes.push(b);
+    }
+}
+
+pub struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Reader { bytes, pos: 0 }
+    }
+
+    pub fn byte(&mut self) -> Option<u8> {
+        let b = *self.bytes.get(self.pos)?;
+        self.pos += 1;
+        Some(b)
+    }
+
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        let out = self.bytes.get(self.pos..self.pos.checked_add(n)?)?;
+        self.pos += n;
+        Some(out)
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.pos == self.bytes.len()
+    }
+}
+
+pub trait Cached: Sized {
+    fn write(&self, w: &mut Writer);
+    fn read(r: &mut Reader) -> Option<Self>;
+}
+
+macro_rules! numbers {
+    ($($t:ty),*) => {$(
+        impl Cached for $t {
+            fn write(&self, w: &mut Writer) {
+                w.bytes.extend_from_slice(&self.to_le_bytes());
+            }
+            fn read(r: &mut Reader) -> Option<Self> {
+                let bytes = r.take(std::mem::size_of::<$t>())?;
+                Some(<$t>::from_le_bytes(bytes.try_into().ok()?))
+            }
+        }
+    )*};
+}
+numbers!(u8, u16, u32, u64, i32, i64, f64);
+
+impl Cached for usize {
+    fn write(&self, w: &mut Writer) {
+        (*self as u64).write(w)
+    }
+    fn read(r: &mut Reader) -> Option<Self> {
+        usize::try_from(u64::read(r)?).ok()
+    }
+}
+
+impl Cached for bool {
+    fn write(&self, w: &mut Writer) {
+        w.byte(*self as u8)
+    }
+    fn read(r: &mut Reader) -> Option<Self> {
+        match r.byte()? {
+            0 => Some(false),
+            1 => Some(true),
+            _ => None,
+        }
+    }
+}
+
+fn write_len(len: usize, w: &mut Writer) {
+    match u32::try_from(len) {
+        Ok(len) => len.write(w),
+        Err(_) => w.failed = true,
+    }
+}
+
+impl Cached for String {
+    fn write(&self, w: &mut Writer) {
+        write_len(self.len(), w);
+        w.bytes.extend_from_slice(self.as_bytes());
+    }
+    fn read(r: &mut Reader) -> Option<Self> {
+        let len = u32::read(r)? as usize;
+        String::from_utf8(r.take(len)?.to_vec()).ok()
+    }
+}
+
+impl Cached for PathBuf {
+    fn write(&self, w: &mut Writer) {
+        match self.to_str() {
+            Some(s) => s.to_string().write(w),
+            None => w.failed = true,
+        }
+    }
+    fn read(r: &mut Reader) -> Option<Self> {
+        String::read(r).map(PathBuf::from)
+    }
+}
+
+impl<T: Cached> Cached for Vec<T> {
+    fn write(&self, w: &mut Writer) {
+        write_len(self.len(), w);
+        for x in self {
+            x.write(w);
+        }
+    }
+    fn read(r: &mut Reader) -> Option<Self> {
+        let len = u32::read(r)? as usize;
+        // not trusting the length with the memory before there's that much to read
+        let mut out = Vec::with_capacity(len.min(1024));
+        for _ in 0..len {
+            out.push(T::read(r)?);
+        }
+        Some(out)
+    }
+}
+
+impl<T: Cached> Cached for Option<T> {
+    fn write(&self, w: &mut Writer) {
+        match self {
+            Some(x) => {
+                w.byte(1);
+                x.write(w);
+            }
+            None => w.byte(0),
+        }
+    }
+    fn read(r: &mut Reader) -> Option<Self> {
+        match r.byte()? {
+            0 => Some(None),
+            1 => Some(Some(T::read(r)?)),
+            _ => None,
+        }
+    }
+}
+
+impl<T: Cached> Cached for Box<T> {
+    fn write(&self, w: &mut Writer) {
+        (**self).write(w)
+    }
+    fn read(r: &mut Reader) -> Option<Self> {
+        T::read(r).map(Box::new)
+    }
+}
+
+impl<K: Cached + Eq + Hash, V: Cached> Cached for HashMap<K, V> {
+    fn write(&self, w: &mut Writer) {
+        write_len(self.len(), w);
+        for (k, v) in self {
+            k.write(w);
+            v.write(w);
+        }
+    }
+    fn read(r: &mut Reader) -> Option<Self> {
+        let len = u32::read(r)? as usize;
+        let mut out = HashMap::new();
+        for _ in 0..len {
+            let k = K::read(r)?;
+            out.insert(k, V::read(r)?);
+        }
+        Some(out)
+    }
+}
+
+macro_rules! tuples {
+    ($(($($x:ident),+))*) => {$(
+        #[allow(non_snake_case)]
+        impl<$($x: Cached),+> Cached for ($($x,)+) {
+            fn write(&self, w: &mut Writer) {
+                let ($($x,)+) = self;
+                $($x.write(w);)+
+            }
+            fn read(r: &mut Reader) -> Option<Self> {
+                Some(($($x::read(r)?,)+))
+            }
+        }
+    )*};
+}
+tuples!((A, B)(A, B, C)(A, B, C, D));
+
+// structs, written field by field
+macro_rules! fields {
+    ($($t:ident { $($f:ident),* })*) => {$(
+        impl Cached for $t {
+            fn write(&self, w: &mut Writer) {
+                $(self.$f.write(w);)*
+            }
+            fn read(r: &mut Reader) -> Option<Self> {
+                Some($t { $($f: Cached::read(r)?),* })
+            }
+        }
+    )*};
+}
+
+// enums, written as the number of the variant and then what's in it
+macro_rules! variants {
+    ($($t:ident { $($tag:literal $v:ident $(($($x:ident),+))?),* $(,)? })*) => {$(
+        impl Cached for $t {
+            fn write(&self, w: &mut Writer) {
+                match self {
+                    $($t::$v $(($($x),+))? => {
+                        w.byte($tag);
+                        $($($x.write(w);)+)?
+                    })*
+                }
+            }
+            fn read(r: &mut Reader) -> Option<Self> {
+                Some(match r.byte()? {
+                    $($tag => $t::$v $(($({
+                        let $x = Cached::read(r)?;
+                        $x
+                    }),+))?,)*
+                    _ => return None,
+                })
+            }
+        }
+    )*};
+}
+
+variants! {
+    Id { 0 Specific(x), 1 Arbitrary(x) }
+    ImportType { 0 Script(x), 1 Lib(x), 2 Project(x) }
+    ObjParam {
+        0 Group(x), 1 Color(x), 2 Block(x), 3 Item(x), 4 Number(x), 5 Bool(x), 6 Text(x),
+        7 GroupList(x), 8 Epsilon,
+    }
+    Pattern { 0 Type(x), 1 Array(x), 2 Either(a, b) }
+}
+
+fields! {
+    Group { id }
+    Color { id }
+    Block { id }
+    Item { id }
+}
+
+// the syntax tree, which has its own Id and Macro
+mod syntax {
+    use super::{Cached, Reader, Writer};
+    use crate::ast::*;
+
+    fields! {
+        Trivia { comments_before, comment_after, comments_end, blank_line_before }
+        Statement { body, arrow, pos, trivia, attributes }
+        ValueLiteral { body }
+        ObjectLiteral { props, mode }
+        Attribute { tags }
+        Argument { symbol, value }
+        Call { function }
+        Macro { args, body, properties }
+        For { label, symbol, array, body }
+        Case { typ, body }
+        Error { message }
+        Try { try_body, catch_symbol, catch_body }
+        Variable { operator, value, path, pos, tag }
+        Expression { values, operators }
+        Ternary { condition, if_expr, else_expr }
+        TryExpr { try_expr, catch_symbol, catch_expr }
+        CompoundStatement { statements }
+        Implementation { symbol, members }
+        If { condition, if_body, else_body }
+        Id { number, unspecified, class_name }
+    }
+
+    variants! {
+        DictDef { 0 Def(x), 1 Extract(x) }
+        StatementBody {
+            0 Call(x), 1 Expr(x), 2 TypeDef(x), 3 Return(x), 4 Impl(x), 5 If(x), 6 For(x),
+            7 Error(x), 8 Try(x), 9 Defer(x), 10 Extract(x), 11 Break(x), 12 Continue(x),
+            13 ErrorStatement(x),
+        }
+        ObjectMode { 0 Object, 1 Trigger }
+        Operator {
+            0 Or, 1 And, 2 Equal, 3 NotEqual, 4 Range, 5 MoreOrEqual, 6 LessOrEqual, 7 More,
+            8 Less, 9 Slash, 10 IntDividedBy, 11 Star, 12 Power, 13 Plus, 14 Minus, 15 Modulo,
+            16 Either, 17 Assign, 18 Add, 19 Subtract, 20 Multiply, 21 Divide, 22 IntDivide,
+            23 As, 24 Has, 25 Exponate, 26 Modulate, 27 Swap,
+        }
+        UnaryOperator { 0 Not, 1 Minus, 2 Range, 3 Let, 4 Increment, 5 Decrement }
+        IdClass { 0 Group, 1 Color, 2 Item, 3 Block }
+        Path {
+            0 Member(x), 1 Associated(x), 2 Index(x), 3 Call(x), 4 Constructor(x), 5 Increment,
+            6 Decrement,
+        }
+        CaseType { 0 Value(x), 1 Pattern(x), 2 Default }
+    }
+
+    impl Cached for ValueBody {
+        fn write(&self, w: &mut Writer) {
+            match self {
+                ValueBody::Id(x) => {
+                    w.byte(0);
+                    x.write(w)
+                }
+                ValueBody::Number(x) => {
+                    w.byte(1);
+                    x.write(w)
+                }
+                ValueBody::CmpStmt(x) => {
+                    w.byte(2);
+                    x.write(w)
+                }
+                ValueBody::Dictionary(x) => {
+                    w.byte(3);
+                    x.write(w)
+                }
+                ValueBody::Symbol(x) => {
+                    w.byte(4);
+                    x.write(w)
+                }
+                ValueBody::Bool(x) => {
+                    w.byte(5);
+                    x.write(w)
+                }
+                ValueBody::Expression(x) => {
+                    w.byte(6);
+                    x.write(w)
+                }
+                ValueBody::Str(x) => {
+                    w.byte(7);
+                    x.write(w)
+                }
+                ValueBody::Import(x, forced) => {
+                    w.byte(8);
+                    x.write(w);
+                    forced.write(w)
+                }
+                ValueBody::Switch(x, cases) => {
+                    w.byte(9);
+                    x.write(w);
+                    cases.write(w)
+                }
+                ValueBody::Array(x) => {
+                    w.byte(10);
+                    x.write(w)
+                }
+                ValueBody::Obj(x) => {
+                    w.byte(11);
+                    x.write(w)
+                }
+                ValueBody::Macro(x) => {
+                    w.byte(12);
+                    x.write(w)
+                }
+                // a value the compiler put in, which is only there while it's compiling
+                ValueBody::Resolved(_) => w.failed = true,
+                ValueBody::TypeIndicator(x) => {
+                    w.byte(13);
+                    x.write(w)
+                }
+                ValueBody::SelfVal => w.byte(14),
+                ValueBody::Ternary(x) => {
+                    w.byte(15);
+                    x.write(w)
+                }
+                ValueBody::Try(x) => {
+                    w.byte(16);
+                    x.write(w)
+                }
+                ValueBody::Null => w.byte(17),
+                ValueBody::ErrorExpr(x) => {
+                    w.byte(18);
+                    x.write(w)
+                }
+            }
+        }
+
+        fn read(r: &mut Reader) -> Option<Self> {
+            Some(match r.byte()? {
+                0 => ValueBody::Id(Cached::read(r)?),
+                1 => ValueBody::Number(Cached::read(r)?),
+                2 => ValueBody::CmpStmt(Cached::read(r)?),
+                3 => ValueBody::Dictionary(Cached::read(r)?),
+                4 => ValueBody::Symbol(Cached::read(r)?),
+                5 => ValueBody::Bool(Cached::read(r)?),
+                6 => ValueBody::Expression(Cached::read(r)?),
+                7 => ValueBody::Str(Cached::read(r)?),
+                8 => ValueBody::Import(Cached::read(r)?, Cached::read(r)?),
+                9 => ValueBody::Switch(Cached::read(r)?, Cached::read(r)?),
+                10 => ValueBody::Array(Cached::read(r)?),
+                11 => ValueBody::Obj(Cached::read(r)?),
+                12 => ValueBody::Macro(Cached::read(r)?),
+                13 => ValueBody::TypeIndicator(Cached::read(r)?),
+                14 => ValueBody::SelfVal,
+                15 => ValueBody::Ternary(Cached::read(r)?),
+                16 => ValueBody::Try(Cached::read(r)?),
+                17 => ValueBody::Null,
+                18 => ValueBody::ErrorExpr(Cached::read(r)?),
+                _ => return None,
+            })
+        }
+    }
+}
+
+pub fn to_bytes<T: Cached>(x: &T) -> Option<Vec<u8>> {
+    let mut w = Writer::default();
+    x.write(&mut w);
+    if w.failed {
+        None
+    } else {
+        Some(w.bytes)
+    }
+}
+
+// all of it, or nothing if there's anything wrong with it
+pub fn from_bytes<T: Cached>(bytes: &[u8]) -> Option<T> {
+    let mut r = Reader::new(bytes);
+    let x = T::read(&mut r)?;
+    if r.is_done() {
+        Some(x)
+    } else {
+        None
+    }
+}
+
+// stored values are written as where they are in the list of values that's written
+// with them (after the two that are in every build, the builtins and null)
+#[derive(Default)]
+struct Refs {
+    index: HashMap<StoredValue, u32>,
+    order: Vec<StoredValue>,
+}
+
+impl Refs {
+    fn write(&mut self, val: StoredValue, w: &mut Writer) {
+        let n = match val {
+            BUILTIN_STORAGE => 0,
+            NULL_STORAGE => 1,
+            _ => {
+                let order = &mut self.order;
+                *self.index.entry(val).or_insert_with(|| {
+                    order.push(val);
+                    order.len() as u32 + 1
+                })
+            }
+        };
+        n.write(w)
+    }
+}
+
+fn read_ref(r: &mut Reader, base: StoredValue, count: usize) -> Option<StoredValue> {
+    match u32::read(r)? as usize {
+        0 => Some(BUILTIN_STORAGE),
+        1 => Some(NULL_STORAGE),
+        n if n - 2 < count => Some(base + n - 2),
+        _ => None,
+    }
+}
+
+fn write_context(c: &Context, refs: &mut Refs, w: &mut Writer) {
+    // a context that's been broken out of is only there while it's compiling
+    if c.broken.is_some() {
+        w.failed = true;
+    }
+    c.start_group.write(w);
+    write_len(c.variables.len(), w);
+    for (name, val) in &c.variables {
+        name.write(w);
+        refs.write(*val, w);
+    }
+    c.func_id.write(w);
+    c.sync_group.write(w);
+    c.sync_part.write(w);
+}
+
+fn read_context(r: &mut Reader, base: StoredValue, count: usize) -> Option<Context> {
+    let mut c = Context::new();
+    c.start_group = Cached::read(r)?;
+    for _ in 0..u32::read(r)? {
+        let name = String::read(r)?;
+        c.variables.insert(name, read_ref(r, base, count)?);
+    }
+    c.func_id = Cached::read(r)?;
+    c.sync_group = Cached::read(r)?;
+    c.sync_part = Cached::read(r)?;
+    Some(c)
+}
+
+fn write_value(val: &Value, refs: &mut Refs, w: &mut Writer) {
+    match val {
+        Value::Group(x) => {
+            w.byte(0);
+            x.write(w)
+        }
+        Value::Color(x) => {
+            w.byte(1);
+            x.write(w)
+        }
+        Value::Block(x) => {
+            w.byte(2);
+            x.write(w)
+        }
+        Value::Item(x) => {
+            w.byte(3);
+            x.write(w)
+        }
+        Value::Number(x) => {
+            w.byte(4);
+            x.write(w)
+        }
+        Value::Bool(x) => {
+            w.byte(5);
+            x.write(w)
+        }
+        Value::TriggerFunc(f) => {
+            w.byte(6);
+            f.start_group.write(w)
+        }
+        Value::Dict(d) => {
+            w.byte(7);
+            write_len(d.len(), w);
+            for (k, v) in d {
+                k.write(w);
+                refs.write(*v, w);
+            }
+        }
+        Value::Macro(m) => {
+            w.byte(8);
+            write_len(m.args.len(), w);
+            for (name, default, tag, pattern) in &m.args {
+                name.write(w);
+                for val in [default, pattern].iter() {
+                    match val {
+                        Some(v) => {
+                            w.byte(1);
+                            refs.write(*v, w)
+                        }
+                        None => w.byte(0),
+                    }
+                }
+                tag.write(w);
+            }
+            write_context(&m.def_context, refs, w);
+            m.def_file.write(w);
+            m.def_pos.write(w);
+            m.body.write(w);
+            m.tag.write(w);
+        }
+        Value::Str(x) => {
+            w.byte(9);
+            x.write(w)
+        }
+        Value::Array(a) => {
+            w.byte(10);
+            write_len(a.len(), w);
+            for v in a {
+                refs.write(*v, w);
+            }
+        }
+        Value::Obj(params, mode) => {
+            w.byte(11);
+            params.write(w);
+            mode.write(w)
+        }
+        Value::Builtins => w.byte(12),
+        Value::BuiltinFunction(x) => {
+            w.byte(13);
+            x.write(w)
+        }
+        Value::TypeIndicator(x) => {
+            w.byte(14);
+            x.write(w)
+        }
+        Value::Range(start, end, step) => {
+            w.byte(15);
+            start.write(w);
+            end.write(w);
+            step.write(w)
+        }
+        Value::Pattern(x) => {
+            w.byte(16);
+            x.write(w)
+        }
+        Value::Null => w.byte(17),
+    }
+}
+
+fn read_value(r: &mut Reader, base: StoredValue, count: usize) -> Option<Value> {
+    let r_ref = |r: &mut Reader| read_ref(r, base, count);
+    Some(match r.byte()? {
+        0 => Value::Group(Cached::read(r)?),
+        1 => Value::Color(Cached::read(r)?),
+        2 => Value::Block(Cached::read(r)?),
+        3 => Value::Item(Cached::read(r)?),
+        4 => Value::Number(Cached::read(r)?),
+        5 => Value::Bool(Cached::read(r)?),
+        6 => Value::TriggerFunc(TriggerFunction {
+            start_group: Cached::read(r)?,
+        }),
+        7 => {
+            let mut d = HashMap::new();
+            for _ in 0..u32::read(r)? {
+                let k = String::read(r)?;
+                d.insert(k, r_ref(r)?);
+            }
+            Value::Dict(d)
+        }
+        8 => {
+            let mut args = Vec::new();
+            for _ in 0..u32::read(r)? {
+                let name = String::read(r)?;
+                let mut refs = [None, None];
+                for v in refs.iter_mut() {
+                    *v = match r.byte()? {
+                        0 => None,
+                        1 => Some(r_ref(r)?),
+                        _ => return None,
+                    };
+                }
+                args.push((name, refs[0], Cached::read(r)?, refs[1]));
+            }
+            Value::Macro(Box::new(Macro {
+                args,
+                def_context: read_context(r, base, count)?,
+                def_file: Cached::read(r)?,
+                def_pos: Cached::read(r)?,
+                body: Cached::read(r)?,
+                tag: Cached::read(r)?,
+            }))
+        }
+        9 => Value::Str(Cached::read(r)?),
+        10 => {
+            let mut a = Vec::new();
+            for _ in 0..u32::read(r)? {
+                a.push(r_ref(r)?);
+            }
+            Value::Array(a)
+        }
+        11 => Value::Obj(Cached::read(r)?, Cached::read(r)?),
+        12 => Value::Builtins,
+        13 => Value::BuiltinFunction(Cached::read(r)?),
+        14 => Value::TypeIndicator(Cached::read(r)?),
+        15 => Value::Range(Cached::read(r)?, Cached::read(r)?, Cached::read(r)?),
+        16 => Value::Pattern(Cached::read(r)?),
+        17 => Value::Null,
+        _ => return None,
+    })
+}
+
+// values with everything they point to, each with the implementations that came with it
+pub fn values_to_bytes(roots: &[(&Value, &Implementations)], globals: &Globals) -> Option<Vec<u8>> {
+    let mut refs = Refs::default();
+    let mut main = Writer::default();
+    write_len(roots.len(), &mut main);
+    for (val, implementations) in roots {
+        write_value(val, &mut refs, &mut main);
+        write_len(implementations.len(), &mut main);
+        for (typ, members) in implementations.iter() {
+            typ.write(&mut main);
+            write_len(members.len(), &mut main);
+            for (name, (val, in_scope)) in members {
+                name.write(&mut main);
+                refs.write(*val, &mut main);
+                in_scope.write(&mut main);
+            }
+        }
+    }
+
+    // the values are found while they're written, so they're written after
+    let mut stored = Writer::default();
+    let mut i = 0;
+    while i < refs.order.len() {
+        let data = globals.stored_values.get(refs.order[i])?;
+        data.fn_context.write(&mut stored);
+        data.mutable.write(&mut stored);
+        data.lifetime.write(&mut stored);
+        write_value(&data.val, &mut refs, &mut stored);
+        i += 1;
+    }
+
+    let mut out = Writer::default();
+    write_len(refs.order.len(), &mut out);
+    out.bytes.extend(stored.bytes);
+    out.bytes.extend(main.bytes);
+    if main.failed || stored.failed || out.failed {
+        None
+    } else {
+        Some(out.bytes)
+    }
+}
+
+// puts the values in globals.stored_values (nothing, if they can't be read)
+pub fn values_from_bytes(
+    bytes: &[u8],
+    globals: &mut Globals,
+) -> Option<Vec<(Value, Implementations)>> {
+    let mut r = Reader::new(bytes);
+    let count = u32::read(&mut r)? as usize;
+    let base = globals.val_id;
+
+    let read = |r: &mut Reader, globals: &mut Globals| -> Option<Vec<(Value, Implementations)>> {
+        for i in 0..count {
+            let fn_context = Cached::read(r)?;
+            let mutable = Cached::read(r)?;
+            let lifetime = Cached::read(r)?;
+            let val = read_value(r, base, count)?;
+            globals.stored_values.insert(
+                base + i,
+                StoredValData {
+                    val,
+                    fn_context,
+                    mutable,
+                    lifetime,
+                },
+            );
+        }
+        let mut roots = Vec::new();
+        for _ in 0..u32::read(r)? {
+            let val = read_value(r, base, count)?;
+            let mut implementations = Implementations::new();
+            for _ in 0..u32::read(r)? {
+                let typ = Cached::read(r)?;
+                let mut members = HashMap::new();
+                for _ in 0..u32::read(r)? {
+                    let name = String::read(r)?;
+                    let val = read_ref(r, base, count)?;
+                    members.insert(name, (val, bool::read(r)?));
+                }
+                implementations.insert(typ, members);
+            }
+            roots.push((val, implementations));
+        }
+        if r.is_done() {
+            Some(roots)
+        } else {
+            None
+        }
+    };
+
+    globals.val_id += count;
+    match read(&mut r, globals) {
+        Some(out) => Some(out),
+        None => {
+            for i in 0..count {
+                globals.stored_values.remove(base + i);
+            }
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parsed_files_read_back_the_same() {
+        let std = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("libraries/std");
+        let mut files = 0;
+        for entry in std::fs::read_dir(std).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().map(|e| e == "spwn") != Some(true) {
+                continue;
+            }
+            let code = std::fs::read_to_string(&path).unwrap();
+            let (parsed, notes, errors) = crate::parser::parse_spwn_partial(code, path.clone());
+            assert!(errors.is_empty(), "{}", path.display());
+            let bytes = to_bytes(&(parsed.clone(), notes.tag.clone())).unwrap();
+            assert_eq!(from_bytes(&bytes), Some((parsed, notes.tag)));
+            // cut short, it's not read at all
+            assert_eq!(
+                from_bytes::<(Vec<crate::ast::Statement>, crate::ast::Attribute)>(
+                    &bytes[..bytes.len() - 1]
+                ),
+                None
+            );
+            files += 1;
+        }
+        assert!(files > 5);
+    }
+}
@@ -0,0 +1,104 @@
+// rotate triggers turn a group around the one object in their center group, and follow
+// triggers move a group along with the one object in the group they follow. with no
+// object in that group (or more than one) the trigger doesn't do what was meant, and
+// nothing in the game says why
+use crate::builtin::{Group, Id};
+use crate::levelstring::{GdObj, ObjParam};
+
+use std::collections::HashMap;
+
+const ROTATE: f64 = 1346.0;
+const FOLLOW: f64 = 1347.0;
+
+fn groups(obj: &GdObj) -> Vec<Group> {
+    match obj.params.get(&57) {
+        Some(ObjParam::Group(g)) => vec![*g],
+        Some(ObjParam::GroupList(l)) => l.clone(),
+        _ => Vec::new(),
+    }
+}
+
+// warnings for the rotate and follow triggers: (unique id, message)
+// `level` is the level the objects are added to, if it was read (without it, groups
+// with a specific id could have objects that were placed by hand)
+pub fn check_center_groups(objects: &[GdObj], level: Option<&str>) -> Vec<(usize, String)> {
+    let mut count = HashMap::<Group, usize>::new();
+    for obj in objects {
+        for g in groups(obj) {
+            *count.entry(g).or_insert(0) += 1;
+        }
+    }
+    if let Some(level) = level {
+        for (id, n) in crate::ownership::hand_placed_groups(level) {
+            *count.entry(Group::new(id)).or_insert(0) += n;
+        }
+    }
+
+    let mut out = Vec::new();
+    for obj in objects {
+        let what = match obj.params.get(&1) {
+            Some(ObjParam::Number(id)) if *id == ROTATE => "center group of this rotate trigger",
+            Some(ObjParam::Number(id)) if *id == FOLLOW => "group this follow trigger follows",
+            _ => continue,
+        };
+        let center = match obj.params.get(&71) {
+            Some(ObjParam::Group(g)) if g.id != Id::Specific(0) => *g,
+            _ => continue,
+        };
+        let n = count.get(&center).copied().unwrap_or(0);
+        let unknown = level.is_none() && matches!(center.id, Id::Specific(_));
+        let problem = match n {
+            1 => continue,
+            0 if unknown => continue,
+            0 => "has no objects in it".to_string(),
+            n => format!("has {} objects in it", n),
+        };
+        out.push((
+            obj.unique_id,
+            format!(
+                "the {} ({:?}) {}, but it should have exactly one",
+                what, center, problem
+            ),
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::ObjectMode;
+
+    fn rotate(unique_id: usize, center: Group) -> GdObj {
+        GdObj::for_test(
+            unique_id,
+            ObjectMode::Trigger,
+            &[(1, ObjParam::Number(ROTATE)), (71, ObjParam::Group(center))],
+        )
+    }
+
+    #[test]
+    fn center_groups_need_one_object() {
+        let block = |unique_id, g| {
+            GdObj::for_test(unique_id, ObjectMode::Object, &[(57, ObjParam::Group(g))])
+        };
+        let objects = vec![
+            rotate(0, Group::new(1)),
+            rotate(1, Group::new(2)),
+            rotate(2, Group::new(3)),
+            block(3, Group::new(1)),
+            block(4, Group::new(2)),
+            block(5, Group::new(2)),
+        ];
+        let warned = |level| -> Vec<usize> {
+            check_center_groups(&objects, level)
+                .iter()
+                .map(|(id, _)| *id)
+                .collect()
+        };
+        // 3g could have an object placed by hand
+        assert_eq!(warned(None), vec![1]);
+        assert_eq!(warned(Some("kS38,1;")), vec![1, 2]);
+        assert_eq!(warned(Some("kS38,1;1,1,2,15,57,3;")), vec![1]);
+    }
+}
@@ -0,0 +1,77 @@
+// the clipboard, through the programs each system has for it (for level strings copied
+// to and from the game with a mod, with --level-from-clipboard and --copy-to-clipboard)
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+type Program = (&'static str, &'static [&'static str]);
+
+#[cfg(windows)]
+const PASTE: &[Program] = &[(
+    "powershell",
+    &["-NoProfile", "-Command", "Get-Clipboard -Raw"],
+)];
+#[cfg(windows)]
+const COPY: &[Program] = &[("clip", &[])];
+
+#[cfg(target_os = "macos")]
+const PASTE: &[Program] = &[("pbpaste", &[])];
+#[cfg(target_os = "macos")]
+const COPY: &[Program] = &[("pbcopy", &[])];
+
+// wayland first, then the x11 ones
+#[cfg(not(any(windows, target_os = "macos")))]
+const PASTE: &[Program] = &[
+    ("wl-paste", &["--no-newline"]),
+    ("xclip", &["-selection", "clipboard", "-o"]),
+    ("xsel", &["--clipboard", "--output"]),
+];
+#[cfg(not(any(windows, target_os = "macos")))]
+const COPY: &[Program] = &[
+    ("wl-copy", &[]),
+    ("xclip", &["-selection", "clipboard"]),
+    ("xsel", &["--clipboard", "--input"]),
+];
+
+fn missing(programs: &[Program]) -> String {
+    let names: Vec<&str> = programs.iter().map(|(name, _)| *name).collect();
+    format!(
+        "Could not use the clipboard (this needs {} to be installed)",
+        names.join(" or ")
+    )
+}
+
+pub fn read() -> Result<String, String> {
+    for (program, args) in PASTE {
+        if let Ok(out) = Command::new(program).args(*args).output() {
+            if out.status.success() {
+                return Ok(String::from_utf8_lossy(&out.stdout).trim().to_string());
+            }
+        }
+    }
+    Err(missing(PASTE))
+}
+
+pub fn write(text: &str) -> Result<(), String> {
+    for (program, args) in COPY {
+        let mut child = match Command::new(program)
+            .args(*args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+        {
+            Ok(child) => child,
+            Err(_) => continue,
+        };
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin
+                .write_all(text.as_bytes())
+                .map_err(|e| format!("Could not copy to the clipboard: {}", e))?;
+        }
+        match child.wait() {
+            Ok(status) if status.success() => return Ok(()),
+            _ => continue,
+        }
+    }
+    Err(missing(COPY))
+}
@@ -0,0 +1,96 @@
+// color math for the color builtins ($.hsv_to_rgb, $.gradient and so on), done
+// natively since decorations can need a color for thousands of objects
+
+// red, green and blue from 0 to 255
+pub type Rgb = [f64; 3];
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Blend {
+    Rgb,
+    // goes around the color wheel the short way
+    Hsv,
+}
+
+// hue in degrees, saturation and value from 0 to 1
+pub fn hsv_to_rgb(h: f64, s: f64, v: f64) -> Rgb {
+    let h = h.rem_euclid(360.0) / 60.0;
+    let s = s.clamp(0.0, 1.0);
+    let v = v.clamp(0.0, 1.0);
+
+    let c = v * s;
+    let x = c * (1.0 - (h % 2.0 - 1.0).abs());
+    let (r, g, b) = match h as u8 {
+        0 => (c, x, 0.0),
+        1 => (x, c, 0.0),
+        2 => (0.0, c, x),
+        3 => (0.0, x, c),
+        4 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    };
+    let m = v - c;
+    [(r + m) * 255.0, (g + m) * 255.0, (b + m) * 255.0]
+}
+
+pub fn rgb_to_hsv(rgb: Rgb) -> [f64; 3] {
+    let [r, g, b] = [rgb[0] / 255.0, rgb[1] / 255.0, rgb[2] / 255.0];
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let delta = max - min;
+
+    let h = if delta == 0.0 {
+        0.0
+    } else if max == r {
+        60.0 * ((g - b) / delta).rem_euclid(6.0)
+    } else if max == g {
+        60.0 * ((b - r) / delta + 2.0)
+    } else {
+        60.0 * ((r - g) / delta + 4.0)
+    };
+    let s = if max == 0.0 { 0.0 } else { delta / max };
+    [h, s, max]
+}
+
+fn mix(a: Rgb, b: Rgb, t: f64, blend: Blend) -> Rgb {
+    match blend {
+        Blend::Rgb => [
+            a[0] + (b[0] - a[0]) * t,
+            a[1] + (b[1] - a[1]) * t,
+            a[2] + (b[2] - a[2]) * t,
+        ],
+        Blend::Hsv => {
+            let a = rgb_to_hsv(a);
+            let b = rgb_to_hsv(b);
+            let mut turn = b[0] - a[0];
+            if turn > 180.0 {
+                turn -= 360.0;
+            } else if turn < -180.0 {
+                turn += 360.0;
+            }
+            hsv_to_rgb(
+                a[0] + turn * t,
+                a[1] + (b[1] - a[1]) * t,
+                a[2] + (b[2] - a[2]) * t,
+            )
+        }
+    }
+}
+
+// the color at t (from 0 to 1) along a gradient through the colors, which are spaced evenly
+pub fn gradient(colors: &[Rgb], t: f64, blend: Blend) -> Rgb {
+    if colors.len() == 1 {
+        return colors[0];
+    }
+    let pos = t.clamp(0.0, 1.0) * (colors.len() - 1) as f64;
+    let i = (pos.floor() as usize).min(colors.len() - 2);
+    mix(colors[i], colors[i + 1], pos - i as f64, blend)
+}
+
+// count colors spread evenly along the gradient, from the first color to the last
+pub fn palette(colors: &[Rgb], count: usize, blend: Blend) -> Vec<Rgb> {
+    if count == 1 {
+        return vec![gradient(colors, 0.0, blend)];
+    }
+    (0..count)
+        .map(|i| gradient(colors, i as f64 / (count - 1) as f64, blend))
+        .collect()
+}
@@ -1,12 +1,15 @@
 //! Tools for compiling SPWN into GD object strings
+use crate::analyzer;
 use crate::ast;
 use crate::builtin::*;
 use crate::compiler_info::CompilerInfo;
 use crate::context::*;
+use crate::files;
 use crate::globals::Globals;
 use crate::levelstring::*;
 use crate::value::*;
 use crate::value_storage::*;
+use crate::trace;
 use crate::STD_PATH;
 use std::collections::{HashMap, HashSet};
 
@@ -48,12 +51,55 @@ pub enum RuntimeError {
         message: String,
         info: CompilerInfo,
     },
+
+    // from a throw statement, can be caught with try/catch
+    ThrownError {
+        message: String,
+        info: CompilerInfo,
+    },
 }
+// how a file is shown in messages: relative to the folder spwn runs in if it's
+// inside it, and without the \\?\ windows puts in front of canonical paths
+pub fn display_path(file: &Path) -> String {
+    let canonical = fs::canonicalize(file).unwrap_or_else(|_| file.to_path_buf());
+    if let Ok(dir) = std::env::current_dir().and_then(fs::canonicalize) {
+        if let Ok(relative) = canonical.strip_prefix(&dir) {
+            return relative.to_string_lossy().to_string();
+        }
+    }
+    let shown = file.to_string_lossy();
+    if let Some(unc) = shown.strip_prefix(r"\\?\UNC\") {
+        format!(r"\\{}", unc)
+    } else {
+        shown.trim_start_matches(r"\\?\").to_string()
+    }
+}
+
 pub fn print_error_intro(pos: crate::parser::FileRange, file: &Path) {
+    print_intro("Error", TColor::Red, pos, file)
+}
+
+// a lint warning, with the code it's about
+fn print_lint(warning: &crate::lints::Warning) {
+    if crate::diagnostics::json() {
+        eprintln!(
+            "{}",
+            crate::diagnostics::Diagnostic::from(warning).to_json()
+        );
+        return;
+    }
+    print_intro("Warning", TColor::Yellow, warning.pos, &warning.file);
+    eprintln!(
+        "{} (turn it off with #[allow({})])",
+        warning.message, warning.lint
+    );
+}
+
+fn print_intro(title: &str, color: TColor, pos: crate::parser::FileRange, file: &Path) {
     use std::io::Write;
-    use termcolor::{Color, ColorChoice, ColorSpec, StandardStream, WriteColor};
+    use termcolor::{Color, ColorSpec, StandardStream, WriteColor};
 
-    let mut stdout = StandardStream::stderr(ColorChoice::Always);
+    let mut stdout = StandardStream::stderr(crate::color_choice(&std::io::stderr()));
 
     let mut write_with_color = |text: &str, color: Color| {
         stdout
@@ -64,12 +110,12 @@ pub fn print_error_intro(pos: crate::parser::FileRange, file: &Path) {
 
     let path_str = format!(
         "{}:{}:{}",
-        file.to_string_lossy().to_string(),
+        display_path(file),
         pos.0 .0,
         pos.0 .1 + 1
     );
 
-    write_with_color("Error", TColor::Red);
+    write_with_color(title, color);
     write_with_color(&format!(" at {}\n", path_str), TColor::White);
 
     if pos.0 .0 == pos.1 .0 {
@@ -100,7 +146,7 @@ pub fn print_error_intro(pos: crate::parser::FileRange, file: &Path) {
                     out += squiggly_line;
                 }
                 out += "\n";
-                write_with_color(&out, TColor::Red);
+                write_with_color(&out, color);
                 stdout
                     .set_color(ColorSpec::new().set_fg(Some(TColor::White)))
                     .unwrap();
@@ -109,51 +155,48 @@ pub fn print_error_intro(pos: crate::parser::FileRange, file: &Path) {
     };
 }
 
-impl std::fmt::Display for RuntimeError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let info = match self {
-            RuntimeError::UndefinedErr {
-                undefined: _,
-                desc: _,
-                info,
-            } => info,
-            RuntimeError::PackageSyntaxError { err: _, info } => info,
-
-            RuntimeError::TypeError {
-                expected: _,
-                found: _,
-                info,
-            } => info,
-
-            RuntimeError::RuntimeError { message: _, info } => info,
-
-            RuntimeError::BuiltinError { message: _, info } => info,
-        };
-
-        print_error_intro(info.pos, &info.current_file);
+impl RuntimeError {
+    pub fn info(&self) -> &CompilerInfo {
+        match self {
+            RuntimeError::UndefinedErr { info, .. }
+            | RuntimeError::PackageSyntaxError { info, .. }
+            | RuntimeError::TypeError { info, .. }
+            | RuntimeError::RuntimeError { info, .. }
+            | RuntimeError::BuiltinError { info, .. }
+            | RuntimeError::ThrownError { info, .. } => info,
+        }
+    }
 
+    // the message without where the error is
+    pub fn message(&self) -> String {
         match self {
             RuntimeError::UndefinedErr {
-                undefined,
-                desc,
-                info: _,
-            } => write!(f, "{} '{}' is not defined", desc, undefined,),
-            RuntimeError::PackageSyntaxError { err, info: _ } => {
-                write!(f, "Error when parsing library: {}", err)
+                undefined, desc, ..
+            } => format!("{} '{}' is not defined", desc, undefined),
+            RuntimeError::PackageSyntaxError { err, .. } => {
+                format!("Error when parsing library: {}", err.message())
             }
-
             RuntimeError::TypeError {
-                expected,
-                found,
-                info: _,
-            } => write!(f, "Type mismatch: expected {}, found {}", expected, found,),
-
-            RuntimeError::RuntimeError { message, info: _ } => write!(f, "{}", message,),
-
-            RuntimeError::BuiltinError { message, info: _ } => {
-                write!(f, "Error when calling built-in-function: {}", message,)
+                expected, found, ..
+            } => format!("Type mismatch: expected {}, found {}", expected, found),
+            RuntimeError::RuntimeError { message, .. } => message.clone(),
+            RuntimeError::BuiltinError { message, .. } => {
+                format!("Error when calling built-in-function: {}", message)
             }
+            RuntimeError::ThrownError { message, .. } => message.clone(),
+        }
+    }
+}
+
+impl std::fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let info = self.info();
+        print_error_intro(info.pos, &info.current_file);
+        if let RuntimeError::PackageSyntaxError { err, .. } = self {
+            // shows where in the library it is
+            return write!(f, "Error when parsing library: {}", err);
         }
+        write!(f, "{}", self.message())
     }
 }
 
@@ -163,6 +206,13 @@ impl std::error::Error for RuntimeError {
     }
 }
 
+// for the functions that box their errors to keep their results small
+impl From<Box<RuntimeError>> for RuntimeError {
+    fn from(err: Box<RuntimeError>) -> Self {
+        *err
+    }
+}
+
 pub const NULL_STORAGE: usize = 1;
 pub const BUILTIN_STORAGE: usize = 0;
 
@@ -172,8 +222,89 @@ pub fn compile_spwn(
     included_paths: Vec<PathBuf>,
     notes: ParseNotes,
 ) -> Result<Globals, RuntimeError> {
-    //variables that get changed throughout the compiling
-    let mut globals = Globals::new(path.clone());
+    compile_spwn_with(Globals::new(path), statements, included_paths, notes).map_err(|(e, _)| e)
+}
+
+// same as compile_spwn, but with globals that are already set up (like with a history to record)
+// when the build fails, the globals are given back with the error (with what was built before it)
+pub fn compile_spwn_with(
+    mut globals: Globals,
+    statements: Vec<ast::Statement>,
+    included_paths: Vec<PathBuf>,
+    notes: ParseNotes,
+) -> Result<Globals, (RuntimeError, Box<Globals>)> {
+    use std::time::Instant;
+
+    //println!("Importing standard library...");
+    print_with_color("Building script ...", TColor::Cyan);
+    print_with_color("———————————————————————————\n", TColor::White);
+    let start_time = Instant::now();
+
+    let result = compile_main(statements, included_paths, notes, &mut globals);
+
+    print_with_color("———————————————————————————\n", TColor::White);
+
+    // also shown when the build fails, since they might explain why
+    for warning in &globals.warnings {
+        print_with_color(&format!("Warning: {}", warning), TColor::Yellow);
+    }
+    for warning in &globals.lints {
+        print_lint(warning);
+    }
+    if let Err(e) = result {
+        return Err((e, Box::new(globals)));
+    }
+
+    /*  Build Timing ----------------------------------------------------- **
+        New build timing changes the unit form milliseconds, to seconds,
+        to minutes depending on the time building took.
+    */
+
+    // Define the different units
+    let build_time_secs = start_time.elapsed().as_secs();
+    let build_time_millis = start_time.elapsed().as_millis();
+    let build_time_mins = build_time_secs / 60;
+
+    // Check which unit to unit to use
+    if build_time_secs < 1 {
+        print_with_color(
+            &format!("Built in {} milliseconds!", build_time_millis),
+            TColor::Green,
+        );
+    } else if build_time_secs > 1 && build_time_secs < 60 {
+        print_with_color(
+            &format!("Built in {} seconds!", build_time_secs),
+            TColor::Green,
+        );
+    } else {
+        print_with_color(
+            &format!("Built in {} minutes!", build_time_mins),
+            TColor::Green,
+        );
+    }
+
+    //----------------------------------------------------------------------- **
+
+    if let Some(cache) = &globals.macro_cache {
+        if cache.reused > 0 {
+            print_with_color(
+                &format!("Copied {} macro calls from the cache", cache.reused),
+                TColor::Green,
+            );
+        }
+    }
+
+    Ok(globals)
+}
+
+// compiles the script (and the standard library), giving back the contexts at the end of it
+pub fn compile_main(
+    statements: Vec<ast::Statement>,
+    included_paths: Vec<PathBuf>,
+    notes: ParseNotes,
+    globals: &mut Globals,
+) -> Result<SmallVec<[Context; CONTEXT_MAX]>, RuntimeError> {
+    let path = globals.path.clone();
     if statements.is_empty() {
         return Err(RuntimeError::RuntimeError {
             message: "this script is empty".to_string(),
@@ -181,9 +312,11 @@ pub fn compile_spwn(
                 depth: 0,
                 path: vec!["main scope".to_string()],
                 pos: ((0, 0), (0, 0)),
-                current_file: path,
+                current_file: path.clone(),
                 current_module: String::new(),
                 includes: vec![],
+                origin: (path, ((0, 0), (0, 0))),
+                budget: None,
             },
         });
     }
@@ -196,22 +329,40 @@ pub fn compile_spwn(
         depth: 0,
         path: vec!["main scope".to_string()],
         pos: statements[0].pos,
-        current_file: path,
+        current_file: path.clone(),
         current_module: String::new(),
         includes: included_paths,
+        origin: (path, statements[0].pos),
+        budget: None,
     };
-    use std::time::Instant;
 
-    //println!("Importing standard library...");
-    print_with_color("Building script ...", TColor::Cyan);
-    print_with_color("———————————————————————————\n", TColor::White);
-    let start_time = Instant::now();
+    crate::nullcheck::check_file(&statements, &start_info.current_file, &mut globals.warnings);
+    crate::lints::check_file(&statements, &notes.tag, &start_info.current_file, &mut globals.lints);
+
+    if let Some(args) = notes.tag.get("fps") {
+        globals.fps = match args.first().map(|arg| &arg.value.values[0]) {
+            Some(ast::Variable {
+                operator: None,
+                value: ast::ValueLiteral {
+                    body: ast::ValueBody::Number(n),
+                },
+                ..
+            }) if *n > 0.0 => *n,
+            _ => {
+                return Err(RuntimeError::RuntimeError {
+                    message: "#[fps(...)] expects a positive number, like #[fps(240)]"
+                        .to_string(),
+                    info: start_info,
+                })
+            }
+        };
+    }
 
     if !notes.tag.tags.iter().any(|x| x.0 == "no_std") {
         let standard_lib = import_module(
             &ImportType::Lib(STD_PATH.to_string()),
             &start_context,
-            &mut globals,
+            globals,
             start_info.clone(),
             false,
         )?;
@@ -234,59 +385,90 @@ pub fn compile_spwn(
             });
         }
     }
+    // std is the same for the rest of the build, so copies of the storage share it
+    globals.stored_values.share();
+
+    globals.collect_errors = true;
+    let result = compile_scope(&statements, smallvec![start_context], globals, start_info);
+    if !globals.errors.is_empty() {
+        // the build stops with the first error, and the rest are left in globals.errors
+        let mut errors = std::mem::take(&mut globals.errors);
+        if let Err(e) = result {
+            errors.push(e);
+        }
+        let first = errors.remove(0);
+        globals.errors = errors;
+        return Err(first);
+    }
+    let (contexts, _) = result?;
 
-    let (contexts, _) = compile_scope(
-        &statements,
-        smallvec![start_context],
-        &mut globals,
-        start_info,
-    )?;
-
-    for c in contexts {
-        if let Some((i, _)) = c.broken {
-            return Err(RuntimeError::RuntimeError {
-                message: "break statement is never used".to_string(),
-                info: i,
-            });
+    for c in &contexts {
+        match &c.broken {
+            // usually a library being built instead of the script importing it
+            Some((i, BreakType::Macro)) => {
+                return Err(RuntimeError::RuntimeError {
+                    message: "return only exports values from a script that is imported, but this is the script being built (build the script that imports this one instead)".to_string(),
+                    info: i.clone(),
+                })
+            }
+            Some((i, _)) => {
+                return Err(RuntimeError::RuntimeError {
+                    message: "break statement is never used".to_string(),
+                    info: i.clone(),
+                })
+            }
+            None => (),
         }
     }
 
-    print_with_color("———————————————————————————\n", TColor::White);
+    Ok(contexts)
+}
 
-    /*  Build Timing ----------------------------------------------------- **
-        New build timing changes the unit form milliseconds, to seconds,
-        to minutes depending on the time building took.
-    */
+use smallvec::{smallvec, SmallVec};
 
-    // Define the different units
-    let build_time_secs = start_time.elapsed().as_secs();
-    let build_time_millis = start_time.elapsed().as_millis();
-    let build_time_mins = build_time_secs / 60;
+// how many errors the top level of a script can skip before the build stops anyway
+const MAX_COLLECTED_ERRORS: usize = 20;
 
-    // Check which unit to unit to use
-    if build_time_secs < 1 {
-        print_with_color(
-            &format!("Built in {} milliseconds!", build_time_millis),
-            TColor::Green,
-        );
-    } else if build_time_secs > 1 && build_time_secs < 60 {
-        print_with_color(
-            &format!("Built in {} seconds!", build_time_secs),
-            TColor::Green,
-        );
-    } else {
-        print_with_color(
-            &format!("Built in {} minutes!", build_time_mins),
-            TColor::Green,
-        );
+// runs a try block (or expression), giving back the thrown message if it throws. the
+// objects and triggers it added before throwing are taken out again, but values it changed
+// stay changed, like in a macro that was called
+pub fn catch_thrown<T>(
+    globals: &mut Globals,
+    body: impl FnOnce(&mut Globals) -> Result<T, RuntimeError>,
+) -> Result<Result<T, String>, RuntimeError> {
+    let objects = globals.objects.len();
+    let triggers: Vec<usize> = globals.func_ids.iter().map(|f| f.obj_list.len()).collect();
+    globals.try_depth += 1;
+    let result = body(globals);
+    globals.try_depth -= 1;
+    match result {
+        Ok(out) => Ok(Ok(out)),
+        // only thrown errors can be caught, the rest are bugs in the script
+        Err(RuntimeError::ThrownError { message, .. }) => {
+            globals.objects.truncate(objects);
+            for (i, f) in globals.func_ids.iter_mut().enumerate() {
+                f.obj_list.truncate(triggers.get(i).copied().unwrap_or(0));
+            }
+            Ok(Err(message))
+        }
+        Err(e) => Err(e),
     }
-
-    //----------------------------------------------------------------------- **
-
-    Ok(globals)
 }
 
-use smallvec::{smallvec, SmallVec};
+// the context the catch block runs in, with the message in its variable
+pub fn catch_context(
+    context: &Context,
+    symbol: &Option<String>,
+    message: String,
+    globals: &mut Globals,
+) -> Context {
+    let mut catch_context = context.clone();
+    if let Some(symbol) = symbol {
+        let stored = store_const_value(Value::Str(message), 1, globals, &catch_context);
+        catch_context.variables.insert(symbol.clone(), stored);
+    }
+    catch_context
+}
 
 pub fn compile_scope(
     statements: &[ast::Statement],
@@ -317,6 +499,15 @@ pub fn compile_scope(
 
     globals.stored_values.increment_lifetimes();
 
+    // defer blocks, with how many contexts were already broken when they were reached
+    let mut deferred = Vec::<(&[ast::Statement], usize)>::new();
+
+    // in the script's top level, a statement that fails is skipped (keeping the error) so the
+    // mistakes after it are found in the same build
+    let collect_errors = std::mem::take(&mut globals.collect_errors);
+    let mut skipped_names = HashSet::new();
+    let scope_budget = info.budget.clone();
+
     for statement in statements.iter() {
         //find out what kind of statement this is
         //let start_time = Instant::now();
@@ -329,12 +520,21 @@ pub fn compile_scope(
         //     contexts.len()
         // );
         info.pos = statement.pos;
+        trace::enter_call(statement, &mut info, globals);
         if contexts.is_empty() {
             return Err(RuntimeError::RuntimeError {
                 message: "No context! This is probably a bug, please contact sputnix".to_string(),
                 info,
             });
         }
+        if let Some(reason) = crate::interrupt::stop_reason(globals) {
+            return Err(RuntimeError::RuntimeError {
+                message: reason,
+                info,
+            });
+        }
+        check_statement_attributes(&statement.attributes, &info)?;
+        let budget = crate::budget::start(statement, &scope_budget, &mut info, globals)?;
         use ast::StatementBody::*;
 
         let stored_context = if statement.arrow {
@@ -345,124 +545,28 @@ pub fn compile_scope(
 
         //println!("{}:{}:{}", info.current_file.to_string_lossy(), info.pos.0.0, info.pos.0.1);
         //use crate::fmt::SpwnFmt;
+        trace::begin_statement(statement, &info, globals);
+        let contexts_before = contexts.len();
         match &statement.body {
-            Expr(expr) => {
-                let mut new_contexts: SmallVec<[Context; CONTEXT_MAX]> = SmallVec::new();
-                for context in &contexts {
-                    let is_assign = !expr.operators.is_empty()
-                        && expr.operators[0] == ast::Operator::Assign
-                        && !expr.values[0].is_undefinable(&context, globals);
-
-                    //println!("{:?}, {}", expr, is_assign);
-
-                    if is_assign {
-                        let mut new_expr = expr.clone();
-                        let symbol = new_expr.values.remove(0);
-                        //use crate::fmt::SpwnFmt;
-                        new_expr.operators.remove(0); //assign operator
-                        let mutable = symbol.operator == Some(ast::UnaryOperator::Let);
-
-                        //let mut new_context = context.clone();
-
-                        match (
-                            new_expr.values.len() == 1
-                                && new_expr.values[0].path.is_empty()
-                                && new_expr.values[0].operator.is_none(),
-                            &new_expr.values[0].value.body,
-                        ) {
-                            (true, ast::ValueBody::CmpStmt(f)) => {
-                                //to account for recursion
-
-                                //create the function context
-                                let mut new_context = context.clone();
-                                let storage = symbol.define(&mut new_context, globals, &info)?;
-
-                                //pick a start group
-                                let start_group = Group::next_free(&mut globals.closed_groups);
-                                //store value
-                                globals.stored_values[storage] =
-                                    Value::TriggerFunc(TriggerFunction { start_group });
-
-                                new_context.start_group = start_group;
-
-                                let new_info = info.clone();
-                                let (_, inner_returns) = compile_scope(
-                                    &f.statements,
-                                    smallvec![new_context],
-                                    globals,
-                                    new_info,
-                                )?;
-                                returns.extend(inner_returns);
-
-                                let mut after_context = context.clone();
-
-                                let var_storage =
-                                    symbol.define(&mut after_context, globals, &info)?;
-
-                                globals.stored_values[var_storage] =
-                                    Value::TriggerFunc(TriggerFunction { start_group });
-
-                                new_contexts.push(after_context);
-                            }
-                            // (true, ast::ValueBody::Macro(m)) => {
-                            //     let (evaled, inner_returns) =
-                            //         new_expr.eval(context, globals, info.clone(), !mutable)?;
-
-                            //     returns.extend(inner_returns);
-                            //     for (e, c2) in evaled {
-                            //         let mut new_context = c2.clone();
-                            //         let storage =
-                            //             symbol.define(&mut new_context, globals, &info, None)?;
-
-                            //         if let Value::Macro(m) = &mut globals.stored_values[e] {
-                            //             m.def_context
-                            //         } else {
-                            //             unreachable!()
-                            //         }
-
-                            //         globals.stored_values[storage] =
-                            //             globals.stored_values[e].clone();
-                            //         new_contexts.push(new_context);
-                            //     }
-                            // }
-                            _ => {
-                                let (evaled, inner_returns) =
-                                    new_expr.eval(context, globals, info.clone(), !mutable)?;
-
-                                returns.extend(inner_returns);
-                                for (e, c2) in evaled {
-                                    let mut new_context = c2.clone();
-                                    let storage =
-                                        symbol.define(&mut new_context, globals, &info)?;
-                                    //clone the value so as to not share the reference
-
-                                    let cloned = clone_value(
-                                        e,
-                                        globals.get_lifetime(storage),
-                                        globals,
-                                        new_context.start_group,
-                                        !mutable,
-                                    );
-
-                                    globals.stored_values[storage] =
-                                        globals.stored_values[cloned].clone();
-                                    new_contexts.push(new_context);
-                                }
-                            }
-                        }
-                    } else {
-                        //we dont care about the return value in this case
-                        let (evaled, inner_returns) =
-                            expr.eval(context, globals, info.clone(), false)?;
-                        returns.extend(inner_returns);
-                        new_contexts.extend(evaled.iter().map(|x| {
-                            //globals.stored_values.map.remove(&x.0);
-                            x.1.clone()
-                        }));
+            Expr(expr) => match compile_expr_statement(expr, &contexts, globals, &info) {
+                Ok((new_contexts, inner_returns)) => {
+                    returns.extend(inner_returns);
+                    contexts = new_contexts;
+                }
+                Err(e) if collect_errors && globals.errors.len() < MAX_COLLECTED_ERRORS => {
+                    // whatever it would have defined is undefined after it, which isn't
+                    // another mistake
+                    if let Some(name) = assigned_name(expr) {
+                        skipped_names.insert(name);
+                    }
+                    match &e {
+                        RuntimeError::UndefinedErr { undefined, .. }
+                            if skipped_names.contains(undefined) => {}
+                        _ => globals.errors.push(e),
                     }
                 }
-                contexts = new_contexts;
-            }
+                Err(e) => return Err(e),
+            },
 
             Extract(val) => {
                 let mut all_values: Returns = SmallVec::new();
@@ -503,7 +607,7 @@ pub fn compile_scope(
                             return Err(RuntimeError::RuntimeError {
                                 message: format!(
                                     "This type ({}) can not be extracted!",
-                                    a.to_str(globals)
+                                    a.to_short_str(globals)
                                 ),
                                 info,
                             })
@@ -588,7 +692,7 @@ pub fn compile_scope(
                             return Err(RuntimeError::RuntimeError {
                                 message: format!(
                                     "Expected boolean condition in if statement, found {}",
-                                    a.to_str(globals)
+                                    a.to_short_str(globals)
                                 ),
                                 info,
                             })
@@ -674,7 +778,7 @@ pub fn compile_scope(
                         return Err(RuntimeError::RuntimeError {
                             message: format!(
                                 "Expected type-indicator, found {}",
-                                a.to_str(globals)
+                                a.to_short_str(globals)
                             ),
                             info,
                         })
@@ -709,7 +813,7 @@ pub fn compile_scope(
                                 return Err(RuntimeError::RuntimeError {
                                     message: format!(
                                         "Expected trigger function or group, found: {}",
-                                        a.to_str(globals)
+                                        a.to_short_str(globals)
                                     ),
                                     info,
                                 })
@@ -719,19 +823,22 @@ pub fn compile_scope(
                     params.insert(1, ObjParam::Number(1268.0));
                     (*globals).trigger_order += 1;
 
-                    (*globals).func_ids[context.func_id].obj_list.push((
-                        GdObj {
-                            params,
+                    let obj = GdObj {
+                        params,
 
-                            ..context_trigger(&context, &mut globals.uid_counter)
-                        }
-                        .context_parameters(&context),
-                        globals.trigger_order,
-                    ))
+                        ..context_trigger(&context, &mut globals.uid_counter)
+                    }
+                    .context_parameters(&context);
+                    globals.record_origin(obj.unique_id, &info);
+                    (*globals).func_ids[context.func_id]
+                        .obj_list
+                        .push((obj, globals.trigger_order))
                 }
             }
 
             For(f) => {
+                // `break` and `continue` without a label are for the innermost loop
+                let this_loop = |label: &Option<String>| label.is_none() || *label == f.label;
                 let mut all_arrays: Returns = SmallVec::new();
                 for context in &contexts {
                     let (evaled, inner_returns) =
@@ -768,7 +875,15 @@ pub fn compile_scope(
                                 for c in &mut new_contexts {
                                     // reset all variables per context
                                     (*c).variables = context.variables.clone();
-                                    (*c).variables.insert(f.symbol.clone(), element);
+                                    // a copy, so changing it doesn't change the array
+                                    let copied = clone_value(
+                                        element,
+                                        1,
+                                        globals,
+                                        c.start_group,
+                                        !globals.is_mutable(element),
+                                    );
+                                    (*c).variables.insert(f.symbol.clone(), copied);
                                 }
 
                                 let new_info = info.clone(); // file position info
@@ -781,17 +896,18 @@ pub fn compile_scope(
                                 new_contexts = SmallVec::new();
                                 for mut c in end_contexts {
                                     // add contexts made in the loop to the new_contexts, if they dont have a break
-                                    match c.broken {
-                                        Some((_, BreakType::Loop)) => {
+                                    match &c.broken {
+                                        Some((_, BreakType::Loop(label))) if this_loop(label) => {
                                             c.broken = None;
                                             out_contexts.push(c)
                                         }
-                                        Some((_, BreakType::Macro)) => out_contexts.push(c),
-                                        Some((_, BreakType::ContinueLoop)) => {
+                                        Some((_, BreakType::ContinueLoop(label))) if this_loop(label) => {
                                             c.broken = None;
                                             new_contexts.push(c)
                                         }
-                                        _ => new_contexts.push(c),
+                                        // returns, and breaks or continues of an outer loop
+                                        Some(_) => out_contexts.push(c),
+                                        None => new_contexts.push(c),
                                     }
                                 }
 
@@ -832,9 +948,16 @@ pub fn compile_scope(
                                         globals,
                                         c,
                                     );
+                                    let copied = clone_value(
+                                        v,
+                                        1,
+                                        globals,
+                                        c.start_group,
+                                        !globals.is_mutable(v),
+                                    );
                                     let stored = store_const_value(
                                         // store the val key
-                                        Value::Array(vec![key_stored, v]),
+                                        Value::Array(vec![key_stored, copied]),
                                         1,
                                         globals,
                                         c,
@@ -851,17 +974,18 @@ pub fn compile_scope(
                                 new_contexts = SmallVec::new();
                                 for mut c in end_contexts {
                                     // add contexts made in the loop to the new_contexts, if they dont have a break
-                                    match c.broken {
-                                        Some((_, BreakType::Loop)) => {
+                                    match &c.broken {
+                                        Some((_, BreakType::Loop(label))) if this_loop(label) => {
                                             c.broken = None;
                                             out_contexts.push(c)
                                         }
-                                        Some((_, BreakType::Macro)) => out_contexts.push(c),
-                                        Some((_, BreakType::ContinueLoop)) => {
+                                        Some((_, BreakType::ContinueLoop(label))) if this_loop(label) => {
                                             c.broken = None;
                                             new_contexts.push(c)
                                         }
-                                        _ => new_contexts.push(c),
+                                        // returns, and breaks or continues of an outer loop
+                                        Some(_) => out_contexts.push(c),
+                                        None => new_contexts.push(c),
                                     }
                                 }
 
@@ -907,17 +1031,18 @@ pub fn compile_scope(
                                 new_contexts = SmallVec::new();
                                 for mut c in end_contexts {
                                     // add contexts made in the loop to the new_contexts, if they dont have a break
-                                    match c.broken {
-                                        Some((_, BreakType::Loop)) => {
+                                    match &c.broken {
+                                        Some((_, BreakType::Loop(label))) if this_loop(label) => {
                                             c.broken = None;
                                             out_contexts.push(c)
                                         }
-                                        Some((_, BreakType::Macro)) => out_contexts.push(c),
-                                        Some((_, BreakType::ContinueLoop)) => {
+                                        Some((_, BreakType::ContinueLoop(label))) if this_loop(label) => {
                                             c.broken = None;
                                             new_contexts.push(c)
                                         }
-                                        _ => new_contexts.push(c),
+                                        // returns, and breaks or continues of an outer loop
+                                        Some(_) => out_contexts.push(c),
+                                        None => new_contexts.push(c),
                                     }
                                 }
 
@@ -964,17 +1089,18 @@ pub fn compile_scope(
                                 new_contexts = SmallVec::new();
                                 for mut c in end_contexts {
                                     // add contexts made in the loop to the new_contexts, if they dont have a break
-                                    match c.broken {
-                                        Some((_, BreakType::Loop)) => {
+                                    match &c.broken {
+                                        Some((_, BreakType::Loop(label))) if this_loop(label) => {
                                             c.broken = None;
                                             out_contexts.push(c)
                                         }
-                                        Some((_, BreakType::Macro)) => out_contexts.push(c),
-                                        Some((_, BreakType::ContinueLoop)) => {
+                                        Some((_, BreakType::ContinueLoop(label))) if this_loop(label) => {
                                             c.broken = None;
                                             new_contexts.push(c)
                                         }
-                                        _ => new_contexts.push(c),
+                                        // returns, and breaks or continues of an outer loop
+                                        Some(_) => out_contexts.push(c),
+                                        None => new_contexts.push(c),
                                     }
                                 }
 
@@ -993,26 +1119,32 @@ pub fn compile_scope(
 
                         a => {
                             return Err(RuntimeError::RuntimeError {
-                                message: format!("{} is not iteratable!", a.to_str(globals)),
+                                message: format!("{} is not iteratable!", a.to_short_str(globals)),
                                 info,
                             })
                         }
                     }
                 }
             }
-            Break => {
+            Break(label) => {
                 //set all contexts to broken
                 for c in &mut contexts {
-                    (*c).broken = Some((info.clone(), BreakType::Loop));
+                    (*c).broken = Some((info.clone(), BreakType::Loop(label.clone())));
                 }
+                trace::end_statement(statement, &contexts, &info, globals);
+                crate::budget::end(&budget, &info, globals)?;
+                analyzer::statement_compiled(statement, contexts_before, &contexts, &info, globals);
                 break;
             }
 
-            Continue => {
+            Continue(label) => {
                 //set all contexts to broken
                 for c in &mut contexts {
-                    (*c).broken = Some((info.clone(), BreakType::ContinueLoop));
+                    (*c).broken = Some((info.clone(), BreakType::ContinueLoop(label.clone())));
                 }
+                trace::end_statement(statement, &contexts, &info, globals);
+                crate::budget::end(&budget, &info, globals)?;
+                analyzer::statement_compiled(statement, contexts_before, &contexts, &info, globals);
                 break;
             }
 
@@ -1046,29 +1178,89 @@ pub fn compile_scope(
                     for c in &mut contexts {
                         (*c).broken = Some((info.clone(), BreakType::Macro));
                     }
+                    trace::end_statement(statement, &contexts, &info, globals);
+                    crate::budget::end(&budget, &info, globals)?;
+                analyzer::statement_compiled(statement, contexts_before, &contexts, &info, globals);
                     break;
                 }
             }
 
+            ErrorStatement(_) => {
+                // parse_spwn never gives back error nodes
+                return Err(RuntimeError::RuntimeError {
+                    message: "Cannot compile a statement with syntax errors".to_string(),
+                    info,
+                });
+            }
+
             Error(e) => {
+                let mut messages = Vec::new();
                 for context in &contexts {
                     let (evaled, _) = e.message.eval(context, globals, info.clone(), true)?;
                     for (msg, _) in evaled {
-                        eprintln!(
-                            "{}",
-                            match &globals.stored_values[msg] {
-                                Value::Str(s) => s,
-                                _ => "no message",
-                            },
-                        );
+                        messages.push(match &globals.stored_values[msg] {
+                            Value::Str(s) => s.clone(),
+                            _ => "no message".to_string(),
+                        });
+                    }
+                }
+                // in a try block the message goes to the catch block instead
+                if globals.try_depth > 0 {
+                    let mut unique = Vec::new();
+                    for message in messages {
+                        if !unique.contains(&message) {
+                            unique.push(message);
+                        }
                     }
+                    return Err(RuntimeError::ThrownError {
+                        message: unique.join("\n"),
+                        info,
+                    });
+                }
+                for message in messages {
+                    eprintln!("{}", message);
                 }
                 return Err(RuntimeError::RuntimeError {
                     message: "Error statement, see message(s) above.".to_string(),
                     info,
                 });
             }
+
+            Defer(body) => deferred.push((body, broken_contexts.len())),
+
+            Try(t) => {
+                let mut new_contexts: SmallVec<[Context; CONTEXT_MAX]> = SmallVec::new();
+                for context in contexts {
+                    let tried = catch_thrown(globals, |globals| {
+                        compile_scope(
+                            &t.try_body,
+                            smallvec![context.clone()],
+                            globals,
+                            info.clone(),
+                        )
+                    })?;
+                    let (end_contexts, inner_returns) = match tried {
+                        Ok(compiled) => compiled,
+                        Err(message) => compile_scope(
+                            &t.catch_body,
+                            smallvec![catch_context(&context, &t.catch_symbol, message, globals)],
+                            globals,
+                            info.clone(),
+                        )?,
+                    };
+                    returns.extend(inner_returns);
+                    new_contexts.extend(end_contexts.iter().map(|c| Context {
+                        variables: context.variables.clone(),
+
+                        ..c.clone()
+                    }));
+                }
+                contexts = new_contexts;
+            }
         }
+        trace::end_statement(statement, &contexts, &info, globals);
+        crate::budget::end(&budget, &info, globals)?;
+                analyzer::statement_compiled(statement, contexts_before, &contexts, &info, globals);
 
         let mut to_be_removed = Vec::new();
 
@@ -1085,7 +1277,9 @@ pub fn compile_scope(
 
         // does this make sense?? why wasn't this here earlier??
         if contexts.is_empty() {
-            return Ok((broken_contexts, returns));
+            run_deferred(&deferred, &mut contexts, &mut broken_contexts, globals, &info)?;
+            contexts.extend(broken_contexts);
+            return Ok((contexts, returns));
         }
 
         if let Some(c) = stored_context {
@@ -1127,6 +1321,8 @@ pub fn compile_scope(
             .increment_single_lifetime(*val, 1, &mut HashSet::new());
     }
 
+    run_deferred(&deferred, &mut contexts, &mut broken_contexts, globals, &info)?;
+
     globals.stored_values.decrement_lifetimes();
     //collect garbage
     globals.stored_values.clean_up();
@@ -1138,6 +1334,190 @@ pub fn compile_scope(
     Ok((contexts, returns))
 }
 
+// an expression statement, like `$.print(a)` or `let b = a + 1`
+fn compile_expr_statement(
+    expr: &ast::Expression,
+    contexts: &SmallVec<[Context; CONTEXT_MAX]>,
+    globals: &mut Globals,
+    info: &CompilerInfo,
+) -> Result<(SmallVec<[Context; CONTEXT_MAX]>, Returns), RuntimeError> {
+    let mut returns: Returns = SmallVec::new();
+    let mut new_contexts: SmallVec<[Context; CONTEXT_MAX]> = SmallVec::new();
+    for context in contexts {
+        let is_assign = !expr.operators.is_empty()
+            && expr.operators[0] == ast::Operator::Assign
+            && !expr.values[0].is_undefinable(context, globals);
+
+        //println!("{:?}, {}", expr, is_assign);
+
+        if is_assign {
+            let mut new_expr = expr.clone();
+            let symbol = new_expr.values.remove(0);
+            //use crate::fmt::SpwnFmt;
+            new_expr.operators.remove(0); //assign operator
+            let mutable = symbol.operator == Some(ast::UnaryOperator::Let);
+
+            //let mut new_context = context.clone();
+
+            match (
+                new_expr.values.len() == 1
+                    && new_expr.values[0].path.is_empty()
+                    && new_expr.values[0].operator.is_none(),
+                &new_expr.values[0].value.body,
+            ) {
+                (true, ast::ValueBody::CmpStmt(f)) => {
+                    //to account for recursion
+
+                    //create the function context
+                    let mut new_context = context.clone();
+                    let storage = symbol.define(&mut new_context, globals, info)?;
+
+                    //pick a start group
+                    let start_group = Group::next_free(&mut globals.closed_groups);
+                    //store value
+                    globals.stored_values[storage] =
+                        Value::TriggerFunc(TriggerFunction { start_group });
+
+                    new_context.start_group = start_group;
+
+                    let new_info = info.clone();
+                    let (_, inner_returns) = compile_scope(
+                        &f.statements,
+                        smallvec![new_context],
+                        globals,
+                        new_info,
+                    )?;
+                    returns.extend(inner_returns);
+
+                    let mut after_context = context.clone();
+
+                    let var_storage =
+                        symbol.define(&mut after_context, globals, info)?;
+
+                    globals.stored_values[var_storage] =
+                        Value::TriggerFunc(TriggerFunction { start_group });
+
+                    new_contexts.push(after_context);
+                }
+                // (true, ast::ValueBody::Macro(m)) => {
+                //     let (evaled, inner_returns) =
+                //         new_expr.eval(context, globals, info.clone(), !mutable)?;
+
+                //     returns.extend(inner_returns);
+                //     for (e, c2) in evaled {
+                //         let mut new_context = c2.clone();
+                //         let storage =
+                //             symbol.define(&mut new_context, globals, &info, None)?;
+
+                //         if let Value::Macro(m) = &mut globals.stored_values[e] {
+                //             m.def_context
+                //         } else {
+                //             unreachable!()
+                //         }
+
+                //         globals.stored_values[storage] =
+                //             globals.stored_values[e].clone();
+                //         new_contexts.push(new_context);
+                //     }
+                // }
+                _ => {
+                    let (evaled, inner_returns) =
+                        new_expr.eval(context, globals, info.clone(), !mutable)?;
+
+                    returns.extend(inner_returns);
+                    for (e, c2) in evaled {
+                        let mut new_context = c2.clone();
+                        let storage =
+                            symbol.define(&mut new_context, globals, info)?;
+                        //clone the value so as to not share the reference
+
+                        let cloned = clone_value(
+                            e,
+                            globals.get_lifetime(storage),
+                            globals,
+                            new_context.start_group,
+                            !mutable,
+                        );
+
+                        globals.stored_values[storage] =
+                            globals.stored_values[cloned].clone();
+                        globals.copy_frozen(cloned, storage);
+                        new_contexts.push(new_context);
+                    }
+                }
+            }
+        } else {
+            //we dont care about the return value in this case
+            let (evaled, inner_returns) =
+                expr.eval(context, globals, info.clone(), false)?;
+            returns.extend(inner_returns);
+            new_contexts.extend(evaled.iter().map(|x| {
+                //globals.stored_values.map.remove(&x.0);
+                x.1.clone()
+            }));
+        }
+    }
+    Ok((new_contexts, returns))
+}
+
+// the variable a statement like `a = 1` or `let a = 1` defines
+fn assigned_name(expr: &ast::Expression) -> Option<String> {
+    match (expr.operators.first(), expr.values.first()) {
+        (Some(ast::Operator::Assign), Some(var)) if var.path.is_empty() => match &var.value.body {
+            ast::ValueBody::Symbol(name) => Some(name.clone()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// runs the defer blocks of a scope that ended (the last one first), in every
+// context that got to them, including the ones that returned or broke out after
+fn run_deferred(
+    deferred: &[(&[ast::Statement], usize)],
+    contexts: &mut SmallVec<[Context; CONTEXT_MAX]>,
+    broken_contexts: &mut SmallVec<[Context; CONTEXT_MAX]>,
+    globals: &mut Globals,
+    info: &CompilerInfo,
+) -> Result<(), RuntimeError> {
+    for (body, broken_before) in deferred.iter().rev() {
+        let mut ending: SmallVec<[Context; CONTEXT_MAX]> = contexts.drain(..).collect();
+        ending.extend(broken_contexts.drain(*broken_before..));
+
+        for context in ending {
+            let (end_contexts, _) = compile_scope(
+                body,
+                smallvec![Context {
+                    broken: None,
+                    ..context.clone()
+                }],
+                globals,
+                info.clone(),
+            )?;
+            for c in end_contexts {
+                if let Some((info, _)) = c.broken {
+                    return Err(RuntimeError::RuntimeError {
+                        message: "return, break and continue can't be used in a defer block"
+                            .to_string(),
+                        info,
+                    });
+                }
+                let c = Context {
+                    variables: context.variables.clone(),
+                    broken: context.broken.clone(),
+                    ..c
+                };
+                if c.broken.is_some() {
+                    broken_contexts.push(c);
+                } else {
+                    contexts.push(c);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
 fn merge_impl(target: &mut Implementations, source: &Implementations) {
     for (key, imp) in source.iter() {
         match target.get_mut(key) {
@@ -1149,6 +1529,220 @@ fn merge_impl(target: &mut Implementations, source: &Implementations) {
     }
 }
 
+// the implementations after importing a file that added `own` (the ones outside of a
+// library win, since it has its own)
+fn imported_impl(
+    path: &ImportType,
+    own: &Implementations,
+    outer: &Implementations,
+) -> Implementations {
+    let (mut target, source) = match path {
+        ImportType::Lib(_) => (own.clone(), outer),
+        _ => (outer.clone(), own),
+    };
+    merge_impl(&mut target, source);
+    target
+}
+
+// the attributes a statement can have (the analyzers can look at all of them,
+// but unknown ones are most likely typos)
+const STATEMENT_ATTRIBUTES: &[&str] = &["desc", "allow", "budget"];
+
+fn check_statement_attributes(
+    attributes: &ast::Attribute,
+    info: &CompilerInfo,
+) -> Result<(), RuntimeError> {
+    for (name, _) in &attributes.tags {
+        if !STATEMENT_ATTRIBUTES.contains(&name.as_str()) {
+            return Err(RuntimeError::RuntimeError {
+                message: format!(
+                    "Unknown statement attribute: {} (expected one of: {})",
+                    name,
+                    STATEMENT_ATTRIBUTES.join(", ")
+                ),
+                info: info.clone(),
+            });
+        }
+    }
+    Ok(())
+}
+
+// checks #[spwn(version = ">=0.8")] in the file tag of a library against this version
+fn check_version(
+    tag: &ast::Attribute,
+    path: &ImportType,
+    info: &CompilerInfo,
+) -> Result<(), RuntimeError> {
+    let requirement = match tag.get("spwn") {
+        Some(args) => args
+            .iter()
+            .find(|arg| arg.symbol.as_deref() == Some("version"))
+            .and_then(|arg| match &arg.value.values[0].value.body {
+                ast::ValueBody::Str(s) => Some(s.clone()),
+                _ => None,
+            }),
+        None => None,
+    };
+    let requirement = match requirement {
+        Some(r) => r,
+        None => return Ok(()),
+    };
+
+    let name = import_name(path);
+    let current = env!("CARGO_PKG_VERSION");
+
+    match version_matches(&requirement, current) {
+        Ok(None) => Ok(()),
+        Ok(Some(failed)) => Err(RuntimeError::RuntimeError {
+            message: if failed.starts_with('<') {
+                format!(
+                    "Library \"{}\" is not compatible with this version of SPWN (requires {}, this is v{})",
+                    name, requirement, current
+                )
+            } else {
+                format!(
+                    "Library \"{}\" requires a newer version of SPWN ({}), but this is v{}",
+                    name, requirement, current
+                )
+            },
+            info: info.clone(),
+        }),
+        Err(e) => Err(RuntimeError::RuntimeError {
+            message: format!(
+                "Library \"{}\" has an invalid version requirement: {}",
+                name, e
+            ),
+            info: info.clone(),
+        }),
+    }
+}
+
+// requirements are comma separated comparisons like ">=0.8, <1.0"
+// (without an operator, the version must be at least the one given)
+// gives back the first comparison that doesn't hold, if any
+fn version_matches(requirement: &str, version: &str) -> Result<Option<String>, String> {
+    fn parse(version: &str) -> Result<Vec<u32>, String> {
+        let mut parts = version
+            .trim()
+            .trim_start_matches('v')
+            .split('.')
+            .map(|p| p.parse::<u32>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| format!("\"{}\" is not a version number", version.trim()))?;
+        parts.resize(3, 0);
+        Ok(parts)
+    }
+
+    let version = parse(version)?;
+    for comparison in requirement.split(',') {
+        let comparison = comparison.trim();
+        let (op, num) = match comparison.find(|c: char| c.is_ascii_digit() || c == 'v') {
+            Some(i) => comparison.split_at(i),
+            None => return Err(format!("\"{}\" has no version number", comparison)),
+        };
+        let required = parse(num)?;
+        let ok = match op.trim() {
+            ">=" | "" => version >= required,
+            ">" => version > required,
+            "<=" => version <= required,
+            "<" => version < required,
+            "=" | "==" => version == required,
+            a => return Err(format!("unknown comparison \"{}\"", a)),
+        };
+        if !ok {
+            return Ok(Some(comparison.to_string()));
+        }
+    }
+    Ok(None)
+}
+
+// the places an import is looked for, in order:
+// 0. `import name` is the library folder for name in [dependencies], if it's there
+// 1. next to the file that imports it
+// 2. in the project root (the main script's folder)
+// 3. in the libraries folder of each include path
+// 4. in each include path itself
+// `import project::a::b` is only looked for in the project root
+fn import_candidates(path: &ImportType, info: &CompilerInfo, globals: &Globals) -> Vec<PathBuf> {
+    let relative = match path {
+        ImportType::Script(p) => p.clone(),
+        ImportType::Lib(name) => PathBuf::from(name),
+        ImportType::Project(parts) => {
+            return vec![parts.iter().fold(globals.project_root.clone(), |p, part| p.join(part))]
+        }
+    };
+    let importing_folder = info
+        .current_file
+        .parent()
+        .map(|p| p.to_path_buf())
+        .unwrap_or_default();
+
+    let mut candidates = Vec::new();
+    if let ImportType::Lib(name) = path {
+        let mut parts = Path::new(name).iter();
+        let dependency = parts
+            .next()
+            .and_then(|first| globals.dependencies.get(&*first.to_string_lossy()));
+        if let Some(folder) = dependency {
+            candidates.push(parts.fold(folder.clone(), |p, part| p.join(part)));
+        }
+    }
+    candidates.extend([
+        importing_folder.join(&relative),
+        globals.project_root.join(&relative),
+    ]);
+    for include in &info.includes {
+        candidates.push(include.join("libraries").join(&relative));
+    }
+    for include in &info.includes {
+        candidates.push(include.join(&relative));
+    }
+    candidates.dedup();
+    candidates
+}
+
+// finds the file to import (folders are imported through their lib.spwn)
+fn resolve_import(
+    path: &ImportType,
+    info: &CompilerInfo,
+    globals: &Globals,
+) -> Result<PathBuf, RuntimeError> {
+    let candidates = import_candidates(path, info, globals);
+    for candidate in &candidates {
+        if files::is_dir(candidate) {
+            if files::is_file(&candidate.join("lib.spwn")) {
+                return Ok(candidate.join("lib.spwn"));
+            }
+        } else if files::is_file(candidate) {
+            return Ok(candidate.clone());
+        } else if candidate.extension().is_none()
+            && files::is_file(&candidate.with_extension("spwn"))
+        {
+            return Ok(candidate.with_extension("spwn"));
+        }
+    }
+    Err(RuntimeError::RuntimeError {
+        message: format!(
+            "Couldn't find {} to import, looked in:\n{}",
+            import_name(path),
+            candidates
+                .iter()
+                .map(|c| format!("  {}", display_path(c)))
+                .collect::<Vec<_>>()
+                .join("\n")
+        ),
+        info: info.clone(),
+    })
+}
+
+pub fn import_name(path: &ImportType) -> String {
+    match path {
+        ImportType::Lib(name) => name.clone(),
+        ImportType::Script(p) => p.to_string_lossy().to_string(),
+        ImportType::Project(parts) => format!("project::{}", parts.join("::")),
+    }
+}
+
 pub fn import_module(
     path: &ImportType,
     context: &Context,
@@ -1156,8 +1750,40 @@ pub fn import_module(
     info: CompilerInfo,
     forced: bool,
 ) -> Result<Returns, RuntimeError> {
+    let name = match path {
+        ImportType::Script(_) if !info.current_module.is_empty() => None,
+        _ => Some(import_name(path)),
+    };
+    crate::import_costs::start(globals);
+    let depth = crate::import_cache::depth(globals);
+    let result = compile_import(path, context, globals, info, forced);
+    crate::import_cache::close(globals, depth, result.is_err());
+    crate::import_costs::end(name, globals);
+    result
+}
+
+fn compile_import(
+    path: &ImportType,
+    context: &Context,
+    globals: &mut Globals,
+    info: CompilerInfo,
+    forced: bool,
+) -> Result<Returns, RuntimeError> {
+    let module_path = resolve_import(path, &info, globals)?;
+
+    let canonical = |p: &Path| fs::canonicalize(p).unwrap_or_else(|_| p.to_path_buf());
+    let importing = canonical(&module_path);
+    let cache_key = (path.clone(), importing.clone());
+
+    crate::import_cache::imported(
+        globals,
+        path,
+        &info.current_file,
+        &importing,
+        !forced && globals.prev_imports.contains_key(&cache_key),
+    );
     if !forced {
-        if let Some(ret) = globals.prev_imports.get(path) {
+        if let Some(ret) = globals.prev_imports.get(&cache_key) {
             merge_impl(&mut globals.implementations, &ret.1);
             return Ok(smallvec![(
                 store_value(ret.0.clone(), 1, globals, context),
@@ -1165,81 +1791,134 @@ pub fn import_module(
             )]);
         }
     }
+    // the file that started the imports, and the ones that are being imported
+    let root = match globals.import_stack.first() {
+        Some((_, first)) => &first.current_file,
+        None => &info.current_file,
+    };
+    let in_progress: Vec<PathBuf> = std::iter::once(canonical(root))
+        .chain(globals.import_stack.iter().map(|(p, _)| p.clone()))
+        .collect();
+    if let Some(start) = in_progress.iter().position(|p| *p == importing) {
+        let folder = in_progress[0].parent().unwrap_or(&in_progress[0]);
+        let name = |p: &PathBuf| p.strip_prefix(folder).unwrap_or(p).to_string_lossy().to_string();
+        let mut chain: Vec<String> = in_progress[start..].iter().map(name).collect();
+        chain.push(name(&importing));
+
+        let mut message = format!("Circular import: {}", chain.join(" → "));
+        // the import statements that make up the cycle
+        let sites = globals.import_stack[start..]
+            .iter()
+            .map(|(_, site)| site)
+            .chain(std::iter::once(&info));
+        for (site, imported) in sites.zip(&chain[1..]) {
+            message += &format!(
+                "\n  {}:{}:{}: imports {}",
+                name(&canonical(&site.current_file)),
+                site.pos.0 .0,
+                site.pos.0 .1 + 1,
+                imported
+            );
+        }
+        return Err(RuntimeError::RuntimeError { message, info });
+    }
 
-    let mut module_path = match path {
-        ImportType::Script(p) => globals
-            .path
-            .clone()
-            .parent()
-            .expect("Your file must be in a folder to import modules!")
-            .join(&p),
-
-        ImportType::Lib(name) => {
-            let mut outpath = info.includes[0].clone();
-            let mut found = false;
-            for path in &info.includes {
-                if path.join("libraries").join(name).exists() {
-                    outpath = path.to_path_buf();
-                    found = true;
-                    break;
+    // the library the file belongs to
+    let module = match path {
+        ImportType::Lib(l) => l.clone(),
+        _ => info.current_module.clone(),
+    };
+    if !forced {
+        let resolve = |path: &ImportType, from: &Path, globals: &Globals| {
+            let info = CompilerInfo {
+                current_file: from.to_path_buf(),
+                ..info.clone()
+            };
+            resolve_import(path, &info, globals)
+                .ok()
+                .map(|p| canonical(&p))
+        };
+        if let Some(loaded) =
+            crate::import_cache::load(globals, &cache_key, &module, context, resolve)
+        {
+            // the same as after compiling them, and then the file and the ones it
+            // imported can be imported again without compiling them
+            let val = loaded[0].1.clone();
+            for (i, (key, val, own_impl)) in loaded.into_iter().enumerate() {
+                let implementations = imported_impl(&key.0, &own_impl, &globals.implementations);
+                if i == 0 {
+                    globals.implementations = implementations.clone();
                 }
+                globals.prev_imports.insert(key, (val, implementations));
             }
-            if found {
-                outpath
-            } else {
-                return Err(RuntimeError::RuntimeError {
-                    message: "Unable to find library folder in given search paths".to_string(),
-                    info,
-                });
-            }
+            return Ok(smallvec![(
+                store_value(val, 1, globals, context),
+                context.clone()
+            )]);
         }
-        // .parent()
-        // .unwrap()
-        .join("libraries")
-        .join(name),
-    };
-
-    if module_path.is_dir() {
-        module_path = module_path.join("lib.spwn");
-    } else if module_path.is_file() && module_path.extension().is_none() {
-        module_path.set_extension("spwn");
-    } else if !module_path.is_file() {
-        return Err(RuntimeError::RuntimeError {
-            message: format!(
-                "Couldn't find library file ({})",
-                module_path.to_string_lossy()
-            ),
-            info,
-        });
     }
+    crate::import_cache::start(globals, &importing);
 
-    let unparsed = match fs::read_to_string(&module_path) {
+    globals.imported_files.insert(importing.clone());
+    let unparsed = match files::read_to_string(&module_path) {
         Ok(content) => content,
         Err(e) => {
             return Err(RuntimeError::RuntimeError {
                 message: format!(
                     "Something went wrong when opening library file ({}): {}",
-                    module_path.to_string_lossy(),
+                    display_path(&module_path),
                     e
                 ),
                 info,
             })
         }
     };
-    let (parsed, notes) = match crate::parse_spwn(unparsed, module_path.clone()) {
-        Ok(p) => p,
-        Err(err) => return Err(RuntimeError::PackageSyntaxError { err, info }),
+    let (parsed, tag) = match crate::import_cache::parsed(globals, &module_path, &unparsed) {
+        Some((parsed, tag)) => {
+            check_version(&tag, path, &info)?;
+            (parsed, tag)
+        }
+        None => {
+            // the file tag is checked before any syntax errors are reported,
+            // since libraries for newer versions might use syntax this version doesn't know
+            let (parsed, notes, mut errors) =
+                crate::parser::parse_spwn_partial_with(
+                    unparsed.clone(),
+                    module_path.clone(),
+                    globals.aliases.as_ref(),
+                );
+            check_version(&notes.tag, path, &info)?;
+            if !errors.is_empty() {
+                return Err(RuntimeError::PackageSyntaxError {
+                    err: errors.remove(0),
+                    info,
+                });
+            }
+            crate::import_cache::keep_parsed(globals, &module_path, &unparsed, &parsed, &notes.tag);
+            (parsed, notes.tag)
+        }
     };
 
+    // libraries are left alone, their users can't do anything about the warnings
+    if !matches!(path, ImportType::Lib(_)) && info.current_module.is_empty() {
+        crate::nullcheck::check_file(&parsed, &module_path, &mut globals.warnings);
+        crate::lints::check_file(&parsed, &tag, &module_path, &mut globals.lints);
+    }
+
     let mut start_context = Context::new();
 
     let mut stored_impl = None;
+    // the implementations it adds (or before it, to find them), for .spwn-cache/
+    let mut own_impl = None;
+    let mut impl_before = None;
     if let ImportType::Lib(_) = path {
         stored_impl = Some(globals.implementations.clone());
         globals.implementations = HashMap::new();
+    } else if crate::import_cache::active(globals) {
+        impl_before = Some(globals.implementations.clone());
     }
 
-    if !notes.tag.tags.iter().any(|x| x.0 == "no_std") {
+    if !tag.tags.iter().any(|x| x.0 == "no_std") {
         let standard_lib = import_module(
             &ImportType::Lib(STD_PATH.to_string()),
             &start_context,
@@ -1270,24 +1949,44 @@ pub fn import_module(
     let stored_path = globals.path.clone();
     (*globals).path = module_path.clone();
 
+    let info_for_stack = info.clone();
     let mut new_info = info;
+    if !globals.lib_files.contains(&new_info.current_file) {
+        new_info.origin = (new_info.current_file.clone(), new_info.pos);
+    }
 
-    new_info.current_file = module_path;
+    new_info.current_file = module_path.clone();
     new_info.pos = ((0, 0), (0, 0));
 
     if let ImportType::Lib(l) = path {
         new_info.current_module = l.clone();
     }
+    if !new_info.current_module.is_empty() {
+        crate::import_costs::library_file(module_path.clone(), &new_info.current_module, globals);
+        globals.lib_files.insert(module_path);
+    }
 
+    globals.import_stack.push((importing.clone(), info_for_stack));
     let (contexts, mut returns) =
         compile_scope(&parsed, smallvec![start_context], globals, new_info)?;
+    globals.import_stack.pop();
 
     for c in &contexts {
-        if let Some((i, BreakType::Loop)) = &c.broken {
-            return Err(RuntimeError::RuntimeError {
-                message: "break statement is never used".to_string(),
-                info: i.clone(),
-            });
+        match &c.broken {
+            Some((i, BreakType::Loop(None))) => {
+                return Err(RuntimeError::RuntimeError {
+                    message: "break statement is never used".to_string(),
+                    info: i.clone(),
+                });
+            }
+            Some((i, BreakType::Loop(Some(label))))
+            | Some((i, BreakType::ContinueLoop(Some(label)))) => {
+                return Err(RuntimeError::RuntimeError {
+                    message: format!("there is no loop called {} around this statement", label),
+                    info: i.clone(),
+                });
+            }
+            _ => (),
         }
     }
     (*globals).path = stored_path;
@@ -1308,6 +2007,9 @@ pub fn import_module(
             (*globals).implementations.get_mut(&k1).unwrap().remove(&k2);
         }
 
+        if crate::import_cache::active(globals) {
+            own_impl = Some(globals.implementations.clone());
+        }
         //merge impls
         merge_impl(&mut globals.implementations, &stored_impl);
     }
@@ -1329,12 +2031,24 @@ pub fn import_module(
         returns
     };
 
+    let mut exports = None;
     if out.len() == 1 && &out[0].1 == context {
         let cloned = clone_and_get_value(out[0].0, 9999, globals, context.start_group, true);
         let s_impl = globals.implementations.clone();
 
-        globals.prev_imports.insert(path.clone(), (cloned, s_impl));
+        globals
+            .prev_imports
+            .insert(cache_key.clone(), (cloned.clone(), s_impl));
+        exports = Some(cloned);
+    }
+    if let Some(before) = impl_before {
+        own_impl = Some(crate::import_cache::added_impl(
+            &before,
+            &globals.implementations,
+        ));
     }
+    let exports = exports.as_ref().zip(own_impl.as_ref());
+    crate::import_cache::finish(globals, &cache_key, &module, context, exports);
 
     Ok(out)
 }
@@ -1356,3 +2070,115 @@ pub fn import_module(
 //     Err(RuntimeError::IDError { id_class, info })
 //     //panic!("All ids of this type are used up!");
 // }
+
+#[cfg(test)]
+mod tests {
+    use super::{compile_spwn, version_matches};
+    use std::path::PathBuf;
+
+    // the number of objects and triggers the code makes
+    fn build(code: &'static str) -> Result<(usize, usize), String> {
+        // the compiler needs a bigger stack than tests get
+        std::thread::Builder::new()
+            .stack_size(crate::STACK_SIZE)
+            .spawn(move || {
+                let path = PathBuf::from("test.spwn");
+                let (statements, notes) =
+                    crate::parser::parse_spwn(code.to_string(), path.clone()).unwrap();
+                let included = vec![PathBuf::from(env!("CARGO_MANIFEST_DIR"))];
+                compile_spwn(statements, path, included, notes)
+                    .map(|globals| {
+                        (
+                            globals.objects.len(),
+                            globals.func_ids.iter().map(|f| f.obj_list.len()).sum(),
+                        )
+                    })
+                    .map_err(|e| e.to_string())
+            })
+            .unwrap()
+            .join()
+            .unwrap()
+    }
+
+    #[test]
+    fn version_requirements() {
+        assert_eq!(version_matches(">=0.8", "0.8.0"), Ok(None));
+        assert_eq!(
+            version_matches(">=0.8", "0.0.4"),
+            Ok(Some(">=0.8".to_string()))
+        );
+        assert_eq!(version_matches("0.0.4", "0.0.4"), Ok(None));
+        assert_eq!(version_matches(">0.7, <1.0", "0.9.1"), Ok(None));
+        assert_eq!(
+            version_matches(">0.7, <1.0", "1.0"),
+            Ok(Some("<1.0".to_string()))
+        );
+        assert_eq!(
+            version_matches("=0.0.3", "0.0.4"),
+            Ok(Some("=0.0.3".to_string()))
+        );
+        assert!(version_matches("~>0.8", "0.8").is_err());
+        assert!(version_matches(">=new", "0.8").is_err());
+    }
+
+    #[test]
+    fn values_are_copied() {
+        let code = "
+let arr = [{a: 1}, {a: 2}]
+for x in arr { x.a = 10 }
+$.assert(arr[0].a == 1)
+
+let b = arr
+b[0].a = 3
+$.assert(arr[0].a == 1)
+
+let d = {a: 1}
+let in_arr = [d]
+d.a = 5
+$.assert(in_arr[0].a == 1)
+
+len = (v) { return v.length }
+$.assert(len(arr) == 2)
+
+let copy = $.deep_copy(arr)
+copy[1].a = 4
+$.assert(arr[1].a == 2 && copy[1].a == 4)
+";
+        if let Err(e) = build(code) {
+            panic!("{}", e);
+        }
+    }
+
+    #[test]
+    fn thrown_errors_are_caught() {
+        let code = "
+risky = (n) {
+    if n > 2 { throw \"too big\" }
+    return n * 2
+}
+$.assert(try { risky(1) } catch { -1 } == 2)
+$.assert(try { risky(5) } catch e { e } == \"too big\")
+
+let caught = \"nothing\"
+try {
+    $.add(obj { 1: 1, 2: 30, 3: 30 })
+    10g.move(10, 0)
+    risky(10)
+} catch err {
+    caught = err
+}
+$.assert(caught == \"too big\")
+";
+        // the object and trigger from before the throw are taken out
+        assert_eq!(build(code), Ok((0, 0)));
+    }
+
+    #[test]
+    fn uncaught_errors_stop_the_build() {
+        let error = build("throw \"stop\"").unwrap_err();
+        assert!(error.contains("Error statement, see message(s) above."));
+        // only thrown errors can be caught
+        assert!(build("try { $.assert(false) } catch { }").is_err());
+        assert!(build("try { throw \"a\" } catch { throw \"b\" }").is_err());
+    }
+}
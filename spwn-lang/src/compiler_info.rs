@@ -9,6 +9,18 @@ pub struct CompilerInfo {
     pub current_module: String, // empty string means script
     pub pos: FileRange,
     pub includes: Vec<PathBuf>,
+    // where in the script the code came from, if this is library code
+    // (objects added by libraries are counted for the script using them)
+    pub origin: (PathBuf, FileRange),
+    // inside a statement with #[budget]: its file and position, and the statement in it
+    // that's being compiled (what its objects are counted for)
+    pub budget: Option<(PathBuf, FileRange, FileRange)>,
+}
+
+impl Default for CompilerInfo {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl CompilerInfo {
@@ -20,6 +32,8 @@ impl CompilerInfo {
             current_module: String::new(),
             pos: ((0, 0), (0, 0)),
             includes: vec![],
+            origin: (PathBuf::new(), ((0, 0), (0, 0))),
+            budget: None,
         }
     }
 }
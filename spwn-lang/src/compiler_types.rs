@@ -29,13 +29,16 @@ pub type Returns = SmallVec<[(StoredValue, Context); CONTEXT_MAX]>;
 pub enum ImportType {
     Script(PathBuf),
     Lib(String),
+    // `import project::utils::shapes`, relative to the project root
+    Project(Vec<String>),
 }
 
 #[derive(PartialEq, Eq, Debug, Clone)]
 pub enum BreakType {
     Macro,
-    Loop,
-    ContinueLoop,
+    // with the label of the loop, if there is one
+    Loop(Option<String>),
+    ContinueLoop(Option<String>),
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -60,6 +63,33 @@ pub fn handle_operator(
     globals: &mut Globals,
     info: &CompilerInfo,
 ) -> Result<Returns, RuntimeError> {
+    // a type that only implements _equal_ gets != from it too
+    if macro_name == "_not_equal_" {
+        let mut implements = |name: &str| {
+            globals.stored_values[value1]
+                .clone()
+                .member(name.to_string(), context, globals)
+                .is_some()
+        };
+        if !implements("_not_equal_") && implements("_equal_") {
+            let mut out = Returns::new();
+            for (val, c) in handle_operator(value1, value2, "_equal_", context, globals, info)? {
+                let negated = match globals.stored_values[val] {
+                    Value::Bool(b) => Value::Bool(!b),
+                    _ => {
+                        return Err(RuntimeError::RuntimeError {
+                            message: "_equal_ should return a boolean for != to use it"
+                                .to_string(),
+                            info: info.clone(),
+                        })
+                    }
+                };
+                out.push((store_value(negated, 1, globals, &c), c));
+            }
+            return Ok(out);
+        }
+    }
+
     Ok(
         if let Some(val) =
             globals.stored_values[value1]
@@ -342,7 +372,7 @@ pub fn execute_macro(
                                 if !val.matches_pat(&pat, &info, globals, context)? {
                                     return Err(RuntimeError::TypeError {
                                         expected: pat.to_str(globals),
-                                        found: val.to_str(globals),
+                                        found: val.to_short_str(globals),
                                         info,
                                     });
                                 }
@@ -373,7 +403,7 @@ pub fn execute_macro(
                             if !val.matches_pat(&pat, &info, globals, context)? {
                                 return Err(RuntimeError::TypeError {
                                     expected: pat.to_str(globals),
-                                    found: val.to_str(globals),
+                                    found: val.to_short_str(globals),
                                     info,
                                 });
                             }
@@ -447,17 +477,53 @@ Should be used like this: value.macro(arguments)".to_string(), info
 
         new_contexts.push(new_context);
     }
+    // with --cache-macros, a call that was already made with the same values is copied
+    let cache_key = if new_contexts.len() == 1 && !matches!(m.args.first(), Some(a) if a.0 == "self") {
+        crate::macro_cache::key(&m, &new_contexts[0], globals)
+    } else {
+        None
+    };
+    if let Some(key) = cache_key {
+        if let Some(val) = crate::macro_cache::replay(key, &new_contexts[0], globals) {
+            return Ok((
+                smallvec![(
+                    val,
+                    Context {
+                        variables: context.variables.clone(),
+                        ..new_contexts[0].clone()
+                    },
+                )],
+                inner_inner_returns,
+            ));
+        }
+    }
+    let recording =
+        cache_key.map(|key| crate::macro_cache::start(key, new_contexts[0].clone(), globals));
+
     let mut new_info = info;
-    new_info.current_file = m.def_file;
+    if !globals.lib_files.contains(&new_info.current_file) {
+        new_info.origin = (new_info.current_file.clone(), new_info.pos);
+    }
+    new_info.current_file = m.def_file.clone();
     let mut compiled = compile_scope(&m.body, new_contexts, globals, new_info)?;
 
     // stop break chain
     for c in &mut compiled.0 {
-        if let Some((i, BreakType::Loop)) = &(*c).broken {
-            return Err(RuntimeError::RuntimeError {
-                message: "break statement is never used".to_string(),
-                info: i.clone(),
-            });
+        match &(*c).broken {
+            Some((i, BreakType::Loop(None))) => {
+                return Err(RuntimeError::RuntimeError {
+                    message: "break statement is never used".to_string(),
+                    info: i.clone(),
+                });
+            }
+            Some((i, BreakType::Loop(Some(label))))
+            | Some((i, BreakType::ContinueLoop(Some(label)))) => {
+                return Err(RuntimeError::RuntimeError {
+                    message: format!("there is no loop called {} around this statement", label),
+                    info: i.clone(),
+                });
+            }
+            _ => (),
         }
         (*c).broken = None;
     }
@@ -467,13 +533,16 @@ Should be used like this: value.macro(arguments)".to_string(), info
     } else {
         compiled.1
     };
+    if let Some(recording) = recording {
+        crate::macro_cache::finish(recording, &m, &returns, globals);
+    }
 
     Ok((
         returns
             .iter()
             .map(|x| {
                 //set mutable to false
-                (*globals.stored_values.map.get_mut(&x.0).unwrap()).mutable = false;
+                globals.stored_values.get_mut(x.0).unwrap().mutable = false;
                 (
                     x.0,
                     Context {
@@ -573,7 +642,7 @@ pub fn eval_dict(
                                 return Err(RuntimeError::RuntimeError {
                                     message: format!(
                                         "Cannot extract from this value: {}",
-                                        a.to_str(globals)
+                                        a.to_short_str(globals)
                                     ),
                                     info,
                                 })
@@ -618,7 +687,7 @@ impl ast::CompoundStatement {
         for c in contexts {
             if let Some((i, t)) = c.broken {
                 match t {
-                    BreakType::Loop => {
+                    BreakType::Loop(_) => {
                         return Err(RuntimeError::RuntimeError {
                             message: "break statement is never used because it's inside a trigger function"
                                 .to_string(),
@@ -626,7 +695,7 @@ impl ast::CompoundStatement {
                         });
                     }
 
-                    BreakType::ContinueLoop => {
+                    BreakType::ContinueLoop(_) => {
                         return Err(RuntimeError::RuntimeError {
                             message: "continue statement is never used because it's inside a trigger function"
                                 .to_string(),
@@ -29,6 +29,12 @@ pub struct Context {
     pub sync_part: SyncPartId,
 }
 
+impl Default for Context {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Context {
     pub fn new() -> Context {
         Context {
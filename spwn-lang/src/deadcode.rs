@@ -0,0 +1,161 @@
+// finds the macros a library exports (and the type members it adds)
+// that no script in the project uses (spwn deadcode)
+use crate::builtin::TYPE_MEMBER_NAME;
+use crate::compiler::RuntimeError;
+use crate::documentation::import_lib;
+use crate::globals::Globals;
+use crate::parser::{FileRange, Token};
+use crate::value::*;
+
+use logos::Logos;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+pub struct Unused {
+    // like "wait" or "@group.move" (all of them if it's exported under several names)
+    pub names: Vec<String>,
+    pub file: PathBuf,
+    pub pos: FileRange,
+}
+
+// the macros that could be dead: (full name, name used in scripts, macro)
+fn exported_macros(
+    name: &str,
+    val: &Value,
+    globals: &Globals,
+    out: &mut Vec<(String, String, Macro)>,
+) {
+    match val {
+        Value::Macro(m) => {
+            let symbol = name.rsplit('.').next().unwrap_or(name).to_string();
+            out.push((name.to_string(), symbol, (**m).clone()))
+        }
+        Value::Dict(d) if !d.contains_key(TYPE_MEMBER_NAME) => {
+            let mut members: Vec<_> = d.iter().collect();
+            members.sort();
+            for (key, member) in members {
+                let full_name = if name.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", name, key)
+                };
+                exported_macros(&full_name, &globals.stored_values[*member], globals, out);
+            }
+        }
+        _ => (),
+    }
+}
+
+fn spwn_files(folder: &Path, out: &mut Vec<PathBuf>) {
+    let entries = match std::fs::read_dir(folder) {
+        Ok(e) => e,
+        Err(_) => return,
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let hidden = matches!(path.file_name(), Some(n) if n.to_string_lossy().starts_with('.'));
+        if hidden {
+            continue;
+        }
+        if path.is_dir() {
+            spwn_files(&path, out);
+        } else if matches!(path.extension(), Some(e) if e == "spwn") {
+            out.push(path);
+        }
+    }
+}
+
+// every name that is used (not just defined) somewhere in the file.
+// `name = ...`, `name: ...` and the `name` in `name: name` are definitions,
+// anything else (`name(...)`, `.name`, `extract`ed names) is a use
+fn used_symbols(source: &str, used: &mut HashSet<String>) {
+    let mut lexer = Token::lexer(source);
+    let mut tokens = Vec::new();
+    while let Some(token) = lexer.next() {
+        tokens.push((token, lexer.slice()));
+    }
+    for (i, (token, text)) in tokens.iter().enumerate() {
+        if *token != Token::Symbol {
+            continue;
+        }
+        let next = tokens.get(i + 1).map(|t| t.0);
+        if next == Some(Token::Assign) || next == Some(Token::Colon) {
+            continue;
+        }
+        let reexported = i >= 2 && tokens[i - 1].0 == Token::Colon && tokens[i - 2].1 == *text;
+        if reexported {
+            continue;
+        }
+        used.insert(text.to_string());
+    }
+}
+
+// (the unused macros, how many were checked)
+pub fn find_unused(
+    lib_path: &str,
+    project: &Path,
+) -> Result<(Vec<Unused>, usize), Box<RuntimeError>> {
+    let (globals, exports) = import_lib(lib_path)?;
+
+    let mut candidates = Vec::new();
+    exported_macros("", &exports, &globals, &mut candidates);
+
+    // only the members the library adds itself, not the ones from its dependencies
+    let lib_folders: Vec<PathBuf> = candidates
+        .iter()
+        .filter_map(|(_, _, m)| m.def_file.parent().map(|p| p.to_path_buf()))
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    let in_lib = |file: &Path| lib_folders.iter().any(|f| file.starts_with(f));
+    for (typ, members) in &globals.implementations {
+        let type_name =
+            find_key_for_value(&globals.type_ids, *typ).expect("Implemented type was not found!");
+        for (name, (val, _)) in members {
+            // operator overloads like _plus_ are used through the operator
+            if name.starts_with('_') && name.ends_with('_') {
+                continue;
+            }
+            if let Value::Macro(m) = &globals.stored_values[*val] {
+                if in_lib(&m.def_file) {
+                    candidates.push((
+                        format!("@{}.{}", type_name, name),
+                        name.clone(),
+                        (**m).clone(),
+                    ));
+                }
+            }
+        }
+    }
+
+    let mut files = Vec::new();
+    spwn_files(project, &mut files);
+    let mut used = HashSet::new();
+    for file in files {
+        if let Ok(source) = std::fs::read_to_string(&file) {
+            used_symbols(&source, &mut used);
+        }
+    }
+
+    // a macro exported under several names is only dead if none of them are used
+    let mut by_definition = HashMap::<(PathBuf, FileRange), Vec<&(String, String, Macro)>>::new();
+    for candidate in &candidates {
+        by_definition
+            .entry((candidate.2.def_file.clone(), candidate.2.def_pos))
+            .or_default()
+            .push(candidate);
+    }
+    let checked = by_definition.len();
+
+    let mut unused: Vec<Unused> = by_definition
+        .into_iter()
+        .filter(|(_, names)| names.iter().all(|(_, symbol, _)| !used.contains(symbol)))
+        .map(|((file, pos), names)| {
+            let mut names: Vec<String> = names.iter().map(|(name, _, _)| name.clone()).collect();
+            names.sort();
+            Unused { names, file, pos }
+        })
+        .collect();
+    unused.sort_by(|a, b| (&a.file, a.pos).cmp(&(&b.file, b.pos)));
+    Ok((unused, checked))
+}
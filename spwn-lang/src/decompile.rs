@@ -0,0 +1,208 @@
+// spwn decompile: writes the objects in a level as a .spwn script of $.add(obj{...})
+// calls, for moving trigger systems that were made by hand into spwn. properties get
+// their names from std's obj_props (with the value types it expects), and the objects are
+// put together by the first group they're in. the objects spwn added are left out, since
+// they come from a script already
+use crate::levelstring::prop_name;
+use crate::ownership::{is_spwn_object, properties, signature};
+use std::collections::BTreeMap;
+
+const PULSE: &str = "1006";
+const COLLISION: &str = "1815";
+const COLLISION_BLOCK: &str = "1816";
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Kind {
+    Number,
+    Bool,
+    Text,
+    Group,
+    Groups,
+    Color,
+    Item,
+    Block,
+}
+
+// the type std's obj_props has for the key (in the object with these properties)
+fn kind(key: &str, props: &[(&str, &str)]) -> Kind {
+    let get = |k: &str| props.iter().find(|(key, _)| *key == k).map(|(_, v)| *v);
+    match key {
+        "4" | "5" | "11" | "13" | "15" | "16" | "17" | "34" | "36" | "41" | "42" | "48" | "56"
+        | "58" | "59" | "60" | "62" | "64" | "65" | "66" | "67" | "70" | "81" | "86" | "87"
+        | "89" | "93" | "94" | "96" | "98" | "100" | "102" | "103" | "104" | "106" => Kind::Bool,
+        "31" | "43" | "44" | "49" => Kind::Text,
+        "21" | "22" | "23" | "50" => Kind::Color,
+        // a pulse trigger targets a color channel unless its target type is group
+        "51" if get("1") == Some(PULSE) && get("52") != Some("1") => Kind::Color,
+        "51" | "71" => Kind::Group,
+        "57" => Kind::Groups,
+        "80" if matches!(get("1"), Some(COLLISION) | Some(COLLISION_BLOCK)) => Kind::Block,
+        "80" => Kind::Item,
+        "95" => Kind::Block,
+        _ => Kind::Number,
+    }
+}
+
+fn str_literal(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+// the value in spwn, if it's one the type allows (None if it should be left out)
+fn typed_value(kind: Kind, value: &str) -> Result<Option<String>, ()> {
+    let id = |suffix: &str| match value.parse::<u16>() {
+        // 0 is no id
+        Ok(0) => Ok(None),
+        Ok(n) => Ok(Some(format!("{}{}", n, suffix))),
+        Err(_) => Err(()),
+    };
+    match kind {
+        Kind::Number => match value.parse::<f64>() {
+            Ok(_) => Ok(Some(value.to_string())),
+            Err(_) => Err(()),
+        },
+        Kind::Bool => match value {
+            "1" => Ok(Some("true".to_string())),
+            "0" => Ok(Some("false".to_string())),
+            _ => Err(()),
+        },
+        Kind::Text => {
+            // text objects store their text in base64, which is easier to edit decoded
+            let padding = "=".repeat((4 - value.len() % 4) % 4);
+            let decoded = base64::decode(&(value.replace("-", "+").replace("_", "/") + &padding))
+                .ok()
+                .and_then(|d| String::from_utf8(d).ok());
+            Ok(Some(match decoded {
+                Some(text) if base64::encode(text.as_bytes()) == value => {
+                    format!("$.b64encode({})", str_literal(&text))
+                }
+                _ => str_literal(value),
+            }))
+        }
+        Kind::Group => id("g"),
+        Kind::Color => id("c"),
+        Kind::Item => id("i"),
+        Kind::Block => id("b"),
+        Kind::Groups => {
+            let mut groups = Vec::new();
+            for g in value
+                .split('.')
+                .filter(|g| !g.is_empty() && *g != signature())
+            {
+                match g.parse::<u16>() {
+                    Ok(n) => groups.push(format!("{}g", n)),
+                    Err(_) => return Err(()),
+                }
+            }
+            Ok(match groups.len() {
+                0 => None,
+                1 => groups.pop(),
+                _ => Some(format!("[{}]", groups.join(", "))),
+            })
+        }
+    }
+}
+
+fn object(obj: &str) -> String {
+    let props = properties(obj);
+    let mut out = Vec::new();
+    for (key, value) in &props {
+        let kind = kind(key, &props);
+        let name = match (*key, kind) {
+            ("80", Kind::Block) => Some("BLOCK_A"),
+            _ => key.parse().ok().and_then(prop_name),
+        };
+        match (name, typed_value(kind, value)) {
+            (_, Ok(None)) => (),
+            (Some(name), Ok(Some(v))) => out.push(format!("{}: {}", name, v)),
+            // anything else is written with the number of the key, like the game has it
+            _ => out.push(format!(
+                "{}: {}",
+                key,
+                if value.parse::<f64>().is_ok() {
+                    value.to_string()
+                } else {
+                    str_literal(value)
+                }
+            )),
+        }
+    }
+    format!("$.add(obj{{{}}})", out.join(", "))
+}
+
+// the first group of the object (0 if it has none)
+fn first_group(obj: &str) -> u16 {
+    properties(obj)
+        .iter()
+        .find(|(key, _)| *key == "57")
+        .and_then(|(_, groups)| {
+            groups
+                .split('.')
+                .filter(|g| *g != signature())
+                .find_map(|g| g.parse().ok())
+        })
+        .unwrap_or(0)
+}
+
+// the script, and how many objects are in it
+pub fn to_spwn(ls: &str, source: &str) -> (String, usize) {
+    // by group, in the order they're in the level
+    let mut groups = BTreeMap::<u16, Vec<String>>::new();
+    let mut count = 0;
+    for obj in ls.split(';').skip(1) {
+        if obj.is_empty() || is_spwn_object(obj) {
+            continue;
+        }
+        groups
+            .entry(first_group(obj))
+            .or_default()
+            .push(object(obj));
+        count += 1;
+    }
+
+    let mut out = format!(
+        "// the objects in {}, from spwn decompile\nextract obj_props\n",
+        source.replace('\n', " ")
+    );
+    // the ones without a group last
+    let ungrouped = groups.remove(&0);
+    for (group, objects) in groups
+        .into_iter()
+        .chain(ungrouped.map(|objects| (0, objects)))
+    {
+        out += &if group == 0 {
+            "\n// no group\n".to_string()
+        } else {
+            format!("\n// {}g\n", group)
+        };
+        for obj in objects {
+            out += &obj;
+            out += "\n";
+        }
+    }
+    (out, count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn objects_get_named_properties_by_group() {
+        let level = "kS38,1;1,1,2,15,3,15;1,901,2,45,3,15,51,3,28,10,62,1,57,2.5;1,1006,51,4,52,0;1,1815,80,2,95,3,51,6;1,914,31,aGk=,57,2;1,1,57,1001;1,1,900,7;";
+        let (spwn, count) = to_spwn(level, "a level");
+        assert_eq!(count, 6);
+        assert_eq!(
+            spwn,
+            "// the objects in a level, from spwn decompile\n\
+             extract obj_props\n\
+             \n// 2g\n\
+             $.add(obj{OBJ_ID: 901, X: 45, Y: 15, TARGET: 3g, MOVE_X: 10, SPAWN_TRIGGERED: true, GROUPS: [2g, 5g]})\n\
+             $.add(obj{OBJ_ID: 914, TEXT: $.b64encode(\"hi\"), GROUPS: 2g})\n\
+             \n// no group\n\
+             $.add(obj{OBJ_ID: 1, X: 15, Y: 15})\n\
+             $.add(obj{OBJ_ID: 1006, TARGET: 4c, TARGET_TYPE: 0})\n\
+             $.add(obj{OBJ_ID: 1815, BLOCK_A: 2b, BLOCK_B: 3b, TARGET: 6g})\n\
+             $.add(obj{OBJ_ID: 1, 900: 7})\n"
+        );
+    }
+}
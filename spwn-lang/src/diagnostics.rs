@@ -0,0 +1,138 @@
+// errors and warnings as json (--error-format=json), one record per line on stderr,
+// for editors and other tools. a record looks like
+// {"severity": "error", "code": "type_mismatch", "message": "...", "file": "main.spwn",
+//  "span": {"start": {"line": 3, "column": 5}, "end": {"line": 3, "column": 9}}, "notes": []}
+// lines and columns start at 1, and the end column is the one after the span
+use crate::compiler::{display_path, RuntimeError};
+use crate::documentation::json_str;
+use crate::parser::{FileRange, SyntaxError};
+
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static JSON: AtomicBool = AtomicBool::new(false);
+
+pub fn set_json(on: bool) {
+    JSON.store(on, Ordering::SeqCst)
+}
+
+pub fn json() -> bool {
+    JSON.load(Ordering::SeqCst)
+}
+
+// --error-format json or --error-format human (the default)
+pub fn parse_format(format: &str) -> Result<bool, String> {
+    match format {
+        "json" => Ok(true),
+        "human" => Ok(false),
+        a => Err(format!(
+            "Unknown error format: {} (expected json or human)",
+            a
+        )),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: &'static str,
+    pub code: &'static str,
+    pub message: String,
+    pub file: PathBuf,
+    pub pos: FileRange,
+    pub notes: Vec<String>,
+}
+
+fn location(file: &std::path::Path, pos: FileRange) -> String {
+    format!("{}:{}:{}", display_path(file), pos.0 .0, pos.0 .1 + 1)
+}
+
+impl Diagnostic {
+    pub fn to_json(&self) -> String {
+        let ((start_line, start_col), (end_line, end_col)) = self.pos;
+        let notes: Vec<String> = self.notes.iter().map(|n| json_str(n)).collect();
+        format!(
+            "{{\"severity\": {}, \"code\": {}, \"message\": {}, \"file\": {}, \"span\": {{\"start\": {{\"line\": {}, \"column\": {}}}, \"end\": {{\"line\": {}, \"column\": {}}}}}, \"notes\": [{}]}}",
+            json_str(self.severity),
+            json_str(self.code),
+            json_str(&self.message),
+            json_str(&display_path(&self.file)),
+            start_line,
+            start_col + 1,
+            end_line,
+            end_col + 1,
+            notes.join(", ")
+        )
+    }
+}
+
+// file:line:column: message
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", location(&self.file, self.pos), self.message)?;
+        for note in &self.notes {
+            write!(f, "\n  {}", note)?;
+        }
+        Ok(())
+    }
+}
+
+impl From<&crate::lints::Warning> for Diagnostic {
+    fn from(warning: &crate::lints::Warning) -> Self {
+        Diagnostic {
+            severity: "warning",
+            code: warning.lint,
+            message: warning.message.clone(),
+            file: warning.file.clone(),
+            pos: warning.pos,
+            notes: Vec::new(),
+        }
+    }
+}
+
+impl From<&SyntaxError> for Diagnostic {
+    fn from(err: &SyntaxError) -> Self {
+        let (file, pos) = err.location();
+        Diagnostic {
+            severity: "error",
+            code: match err {
+                SyntaxError::ExpectedErr { .. } => "expected_token",
+                SyntaxError::UnexpectedErr { .. } => "unexpected_token",
+                SyntaxError::SyntaxError { .. } => "syntax_error",
+            },
+            message: err.message(),
+            file: file.clone(),
+            pos,
+            notes: Vec::new(),
+        }
+    }
+}
+
+impl From<&RuntimeError> for Diagnostic {
+    fn from(err: &RuntimeError) -> Self {
+        let info = err.info();
+        let mut notes = Vec::new();
+        if let RuntimeError::PackageSyntaxError { err, .. } = err {
+            let (file, pos) = err.location();
+            notes.push(format!("the syntax error is at {}", location(file, pos)));
+        }
+        let (origin_file, origin_pos) = &info.origin;
+        if !origin_file.as_os_str().is_empty() && *origin_file != info.current_file {
+            notes.push(format!("used from {}", location(origin_file, *origin_pos)));
+        }
+        Diagnostic {
+            severity: "error",
+            code: match err {
+                RuntimeError::UndefinedErr { .. } => "undefined",
+                RuntimeError::PackageSyntaxError { .. } => "library_syntax_error",
+                RuntimeError::TypeError { .. } => "type_mismatch",
+                RuntimeError::RuntimeError { .. } => "runtime_error",
+                RuntimeError::BuiltinError { .. } => "builtin_error",
+                RuntimeError::ThrownError { .. } => "thrown_error",
+            },
+            message: err.message(),
+            file: info.current_file.clone(),
+            pos: info.pos,
+            notes,
+        }
+    }
+}
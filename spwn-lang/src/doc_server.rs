@@ -0,0 +1,572 @@
+// local web server for previewing library documentation (spwn doc --serve)
+use crate::documentation::{generate_docs, json_str, LibraryStats};
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+struct Site {
+    // increases every time the docs are rebuilt, pages reload when it changes
+    version: usize,
+    // (name, markdown)
+    pages: Vec<(String, String)>,
+    index: String,
+    error: Option<String>,
+    // from the last build that worked
+    stats: Option<LibraryStats>,
+    // when the last build finished, and how long it took
+    built: Option<(SystemTime, Duration)>,
+}
+
+pub fn serve(lib_path: &str, port: u16) -> Result<(), Box<dyn std::error::Error>> {
+    let listener = TcpListener::bind(("127.0.0.1", port))?;
+
+    let site = Arc::new(Mutex::new(Site {
+        version: 0,
+        pages: Vec::new(),
+        index: String::new(),
+        error: None,
+        stats: None,
+        built: None,
+    }));
+    rebuild(lib_path, &site);
+
+    {
+        let site = site.clone();
+        let lib_path = lib_path.to_string();
+        // rebuilding compiles the library, so this needs the same stack as the main thread
+        std::thread::Builder::new()
+            .stack_size(crate::STACK_SIZE)
+            .spawn(move || {
+                let mut last = source_state(&lib_path);
+                loop {
+                    std::thread::sleep(POLL_INTERVAL);
+                    let current = source_state(&lib_path);
+                    if current != last {
+                        last = current;
+                        rebuild(&lib_path, &site);
+                    }
+                }
+            })?;
+    }
+
+    println!(
+        "Serving documentation for {} at http://localhost:{}/ (Ctrl+C to stop)",
+        lib_path, port
+    );
+
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                if let Err(e) = handle_request(stream, lib_path, &site) {
+                    eprintln!("Error handling request: {}", e);
+                }
+            }
+            Err(e) => eprintln!("Connection failed: {}", e),
+        }
+    }
+    Ok(())
+}
+
+fn rebuild(lib_path: &str, site: &Mutex<Site>) {
+    let start = Instant::now();
+    let result = generate_docs(lib_path);
+    let mut site = site.lock().unwrap();
+    site.version += 1;
+    site.built = Some((SystemTime::now(), start.elapsed()));
+    match result {
+        Ok((pages, index, stats)) => {
+            site.pages = pages;
+            site.index = index;
+            site.stats = Some(stats);
+            site.error = None;
+            println!("Documentation rebuilt");
+        }
+        Err(e) => {
+            // keep the old pages around, and show the error on top
+            eprintln!("{}", e);
+            site.error = Some(e.to_string());
+        }
+    }
+}
+
+// folder the library is imported from (same search paths as `spwn doc`)
+fn library_folder(lib_path: &str) -> Option<PathBuf> {
+    let includes = vec![
+        std::env::current_dir().ok()?,
+        std::env::current_exe().ok()?.parent()?.to_path_buf(),
+    ];
+    includes
+        .into_iter()
+        .map(|p| p.join("libraries").join(lib_path))
+        .find(|p| p.exists())
+}
+
+// modification times of all the files in the library
+fn source_state(lib_path: &str) -> Vec<(PathBuf, Option<SystemTime>)> {
+    fn visit(path: &Path, out: &mut Vec<(PathBuf, Option<SystemTime>)>) {
+        if path.is_dir() {
+            if let Ok(entries) = std::fs::read_dir(path) {
+                for entry in entries.flatten() {
+                    visit(&entry.path(), out);
+                }
+            }
+        } else {
+            let modified = std::fs::metadata(path).and_then(|m| m.modified()).ok();
+            out.push((path.to_path_buf(), modified));
+        }
+    }
+
+    let mut out = Vec::new();
+    if let Some(folder) = library_folder(lib_path) {
+        visit(&folder, &mut out);
+    }
+    out.sort();
+    out
+}
+
+fn handle_request(
+    mut stream: TcpStream,
+    lib_path: &str,
+    site: &Mutex<Site>,
+) -> std::io::Result<()> {
+    let mut request_line = String::new();
+    BufReader::new(&stream).read_line(&mut request_line)?;
+
+    // GET /path?query HTTP/1.1
+    let target = request_line.split_whitespace().nth(1).unwrap_or("/");
+    let (path, query) = match target.find('?') {
+        Some(i) => (&target[..i], &target[(i + 1)..]),
+        None => (target, ""),
+    };
+
+    let site = site.lock().unwrap();
+
+    let (status, content_type, body) = if path == "/version" {
+        ("200 OK", "text/plain", site.version.to_string())
+    } else if path == "/index.json" {
+        ("200 OK", "application/json", site.index.clone())
+    } else if path == "/stats.json" {
+        ("200 OK", "application/json", stats_json(&site))
+    } else if path == "/search" {
+        let search = query
+            .split('&')
+            .find_map(|param| param.strip_prefix("q="))
+            .map(url_decode)
+            .unwrap_or_default();
+        (
+            "200 OK",
+            "text/html",
+            page(
+                lib_path,
+                &site,
+                &format!("Search: {}", search),
+                &search_results(&site, &search),
+            ),
+        )
+    } else {
+        // links in the docs look like `[library]-docs/[page].md`
+        let name = path
+            .trim_end_matches('/')
+            .rsplit('/')
+            .next()
+            .unwrap_or("")
+            .trim_end_matches(".md");
+
+        let found = if name.is_empty() {
+            site.pages.first()
+        } else {
+            site.pages.iter().find(|(page_name, _)| page_name == name)
+        };
+        match found {
+            Some((page_name, markdown)) => (
+                "200 OK",
+                "text/html",
+                page(lib_path, &site, page_name, &render_markdown(markdown)),
+            ),
+            None => (
+                "404 Not Found",
+                "text/html",
+                page(
+                    lib_path,
+                    &site,
+                    "Not found",
+                    &format!("<p>No page called <code>{}</code></p>", escape(name)),
+                ),
+            ),
+        }
+    };
+
+    // dashboards on other pages can read the stats
+    let cors = if path == "/stats.json" {
+        "Access-Control-Allow-Origin: *\r\n"
+    } else {
+        ""
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}; charset=utf-8\r\nContent-Length: {}\r\n{}Connection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        cors,
+        body
+    )?;
+    stream.flush()
+}
+
+// for dashboards and editor status bars: how the last build went
+// {"build", "finished" (unix time in seconds), "duration_ms", "ok", "error", "objects",
+//  "used_ids": {"groups", "colors", "blocks", "items"}, "warnings"}
+// the counts and warnings are from the last build that worked
+fn stats_json(site: &Site) -> String {
+    let (finished, duration) = match site.built {
+        Some((time, duration)) => (
+            time.duration_since(SystemTime::UNIX_EPOCH)
+                .map_or(0, |t| t.as_secs())
+                .to_string(),
+            duration.as_millis().to_string(),
+        ),
+        None => ("null".to_string(), "null".to_string()),
+    };
+    let (objects, used_ids, warnings) = match &site.stats {
+        Some(stats) => (
+            stats.objects.to_string(),
+            format!(
+                "{{\"groups\": {}, \"colors\": {}, \"blocks\": {}, \"items\": {}}}",
+                stats.used_ids[0], stats.used_ids[1], stats.used_ids[2], stats.used_ids[3]
+            ),
+            format!(
+                "[{}]",
+                stats
+                    .warnings
+                    .iter()
+                    .map(|w| json_str(w))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        ),
+        None => ("null".to_string(), "null".to_string(), "[]".to_string()),
+    };
+    format!(
+        "{{\"build\": {}, \"finished\": {}, \"duration_ms\": {}, \"ok\": {}, \"error\": {}, \"objects\": {}, \"used_ids\": {}, \"warnings\": {}}}\n",
+        site.version,
+        finished,
+        duration,
+        site.error.is_none(),
+        site.error.as_deref().map_or("null".to_string(), json_str),
+        objects,
+        used_ids,
+        warnings
+    )
+}
+
+fn search_results(site: &Site, search: &str) -> String {
+    let search = search.trim().to_lowercase();
+    if search.is_empty() {
+        return "<p>Type something to search for</p>".to_string();
+    }
+
+    let mut out = String::from("<ul>");
+    let mut count = 0;
+    for (name, markdown) in &site.pages {
+        let mut heading = String::new();
+        for line in markdown.lines() {
+            let line = line.trim_start_matches('>').trim();
+            // link to the member the match is in (member headings are bold)
+            if line.starts_with('#') && line.contains("**") {
+                heading = line.trim_start_matches('#').trim().to_string();
+            }
+            if line.to_lowercase().contains(&search) {
+                count += 1;
+                out += &format!(
+                    "<li><a href=\"/{}.md#{}\">{}</a>: {}</li>",
+                    name,
+                    slug(&heading),
+                    escape(name),
+                    render_inline(line.trim_start_matches('#').trim())
+                );
+            }
+        }
+    }
+    out += "</ul>";
+
+    if count == 0 {
+        "<p>No results</p>".to_string()
+    } else {
+        out
+    }
+}
+
+fn page(lib_path: &str, site: &Site, title: &str, content: &str) -> String {
+    let nav: String = site
+        .pages
+        .iter()
+        .map(|(name, _)| format!("<li><a href=\"/{0}.md\">{0}</a></li>", escape(name)))
+        .collect();
+    let error = match &site.error {
+        Some(e) => format!("<pre class=\"error\">{}</pre>", escape(e)),
+        None => String::new(),
+    };
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>{title} - {lib}</title>
+<style>
+body {{ font-family: sans-serif; display: flex; margin: 0; }}
+nav {{ min-width: 200px; padding: 1em; background: #f0f0f0; min-height: 100vh; }}
+main {{ padding: 1em 2em; max-width: 900px; }}
+pre {{ background: #f6f6f6; padding: 0.5em; overflow-x: auto; }}
+pre.error {{ background: #fdd; }}
+blockquote {{ border-left: 3px solid #ccc; margin-left: 0; padding-left: 1em; }}
+table {{ border-collapse: collapse; }}
+td, th {{ border: 1px solid #ccc; padding: 0.2em 0.5em; }}
+</style>
+</head>
+<body>
+<nav>
+<form action="/search"><input name="q" placeholder="Search"></form>
+<ul>{nav}</ul>
+</nav>
+<main>
+{error}
+{content}
+</main>
+<script>
+setInterval(() => fetch("/version").then(r => r.text()).then(v => {{
+    if (v != "{version}") location.reload();
+}}).catch(() => {{}}), 1000);
+</script>
+</body>
+</html>
+"#,
+        title = escape(title),
+        lib = escape(lib_path),
+        nav = nav,
+        error = error,
+        content = content,
+        version = site.version,
+    )
+}
+
+// renders the subset of markdown that `generate_docs` produces
+fn render_markdown(markdown: &str) -> String {
+    let lines: Vec<&str> = markdown.lines().collect();
+    let mut out = String::new();
+    let mut i = 0;
+
+    while i < lines.len() {
+        let line = lines[i].trim();
+
+        if line.starts_with("```") {
+            let mut code = String::new();
+            i += 1;
+            while i < lines.len() && !lines[i].trim().starts_with("```") {
+                code += lines[i];
+                code += "\n";
+                i += 1;
+            }
+            i += 1;
+            out += &format!("<pre><code>{}</code></pre>\n", escape(&code));
+        } else if line.starts_with('>') {
+            let mut inner = String::new();
+            while i < lines.len() && lines[i].trim().starts_with('>') {
+                inner += &lines[i].trim()[1..];
+                inner += "\n";
+                i += 1;
+            }
+            out += &format!("<blockquote>\n{}</blockquote>\n", render_markdown(&inner));
+        } else if line.starts_with('#') {
+            let level = line.chars().take_while(|c| *c == '#').count().min(6);
+            let text = line[level..].trim();
+            out += &format!(
+                "<h{0} id=\"{1}\">{2}</h{0}>\n",
+                level,
+                slug(text),
+                render_inline(text)
+            );
+            i += 1;
+        } else if line.starts_with('|') {
+            out += "<table>\n";
+            let mut header = true;
+            while i < lines.len() && lines[i].trim().starts_with('|') {
+                let row = lines[i].trim().trim_matches('|');
+                i += 1;
+                if row.chars().all(|c| c == '-' || c == '|' || c == ' ') {
+                    continue;
+                }
+                let tag = if header { "th" } else { "td" };
+                header = false;
+                out += "<tr>";
+                for cell in row.split('|') {
+                    out += &format!("<{0}>{1}</{0}>", tag, render_inline(cell.trim()));
+                }
+                out += "</tr>\n";
+            }
+            out += "</table>\n";
+        } else if line.starts_with("- ") {
+            out += "<ul>\n";
+            while i < lines.len() && lines[i].trim().starts_with("- ") {
+                out += &format!("<li>{}</li>\n", render_inline(&lines[i].trim()[2..]));
+                i += 1;
+            }
+            out += "</ul>\n";
+        } else if line.is_empty() {
+            i += 1;
+        } else {
+            // the first line is always in it, so that every line is used up
+            let mut text = vec![line];
+            i += 1;
+            while i < lines.len() {
+                let l = lines[i].trim();
+                if l.is_empty()
+                    || l.starts_with(|c| "#>|".contains(c))
+                    || l.starts_with("```")
+                    || l.starts_with("- ")
+                {
+                    break;
+                }
+                text.push(l);
+                i += 1;
+            }
+            out += &format!("<p>{}</p>\n", render_inline(&text.join(" ")));
+        }
+    }
+    out
+}
+
+fn render_inline(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut out = String::new();
+    let mut bold = false;
+    let mut italic = false;
+    let mut i = 0;
+
+    let is_word = |c: Option<&char>| c.map(|c| c.is_alphanumeric()).unwrap_or(false);
+
+    while i < chars.len() {
+        match chars[i] {
+            '\\' if i + 1 < chars.len() => {
+                out += &escape(&chars[i + 1].to_string());
+                i += 2;
+                continue;
+            }
+            '`' => {
+                if let Some(len) = chars[(i + 1)..].iter().position(|c| *c == '`') {
+                    let code: String = chars[(i + 1)..(i + 1 + len)].iter().collect();
+                    out += &format!("<code>{}</code>", escape(&code));
+                    i += len + 2;
+                    continue;
+                }
+                out += "`";
+            }
+            '*' if chars.get(i + 1) == Some(&'*') => {
+                out += if bold { "</strong>" } else { "<strong>" };
+                bold = !bold;
+                i += 2;
+                continue;
+            }
+            '_' if (!italic && !is_word(i.checked_sub(1).and_then(|j| chars.get(j))))
+                || (italic && !is_word(chars.get(i + 1))) =>
+            {
+                out += if italic { "</em>" } else { "<em>" };
+                italic = !italic;
+            }
+            '[' => {
+                let rest: String = chars[i..].iter().collect();
+                if let (Some(mid), Some(end)) = (rest.find("]("), rest.find(')')) {
+                    if mid < end {
+                        out += &format!(
+                            "<a href=\"{}\">{}</a>",
+                            escape(&rest[(mid + 2)..end]),
+                            render_inline(&rest[1..mid])
+                        );
+                        i += rest[..=end].chars().count();
+                        continue;
+                    }
+                }
+                out += "[";
+            }
+            c => out += &escape(&c.to_string()),
+        }
+        i += 1;
+    }
+    if italic {
+        out += "</em>";
+    }
+    if bold {
+        out += "</strong>";
+    }
+    out
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn slug(text: &str) -> String {
+    text.chars()
+        .filter_map(|c| {
+            if c.is_alphanumeric() {
+                Some(c.to_ascii_lowercase())
+            } else if c == ' ' || c == '_' || c == '-' {
+                Some('-')
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+fn url_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => out.push(b' '),
+            b'%' if i + 2 < bytes.len() => {
+                match std::str::from_utf8(&bytes[(i + 1)..(i + 3)])
+                    .ok()
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                {
+                    Some(b) => {
+                        out.push(b);
+                        i += 2;
+                    }
+                    None => out.push(b'%'),
+                }
+            }
+            b => out.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines_starting_with_inline_code_are_paragraphs() {
+        let html = render_markdown("Adds a group.\n`group` is moved\n\n`a` and `b`");
+        assert_eq!(
+            html,
+            "<p>Adds a group. <code>group</code> is moved</p>\n<p><code>a</code> and <code>b</code></p>\n"
+        );
+        assert_eq!(
+            render_markdown("text\n```\ncode\n```"),
+            "<p>text</p>\n<pre><code>code\n</code></pre>\n"
+        );
+    }
+}
@@ -1,16 +1,20 @@
 // tools for generating documentation for SPWN libraries
 //use crate::ast::*;
+use crate::ast;
 use crate::builtin::TYPE_MEMBER_NAME;
-use crate::compiler::{import_module, RuntimeError};
+use crate::compiler::{compile_main, import_module, RuntimeError};
 use crate::compiler_info::CompilerInfo;
 use crate::compiler_types::ImportType;
 use crate::context::Context;
 use crate::globals::Globals;
+use crate::parser::{parse_spwn, FileRange};
+use crate::print_with_color;
 use crate::value::*;
 use std::fs::File;
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::{collections::HashMap, env::current_dir};
+use termcolor::Color as TColor;
 fn create_doc_file(mut dir: PathBuf, name: String, content: &str) {
     use std::io::Write;
     dir.push(format!("{}.md", name));
@@ -19,48 +23,112 @@ fn create_doc_file(mut dir: PathBuf, name: String, content: &str) {
     println!("written to {:?}", dir);
 }
 pub fn document_lib(path: &str) -> Result<(), RuntimeError> {
-    let mut globals = Globals::new(PathBuf::new());
-
-    let start_context = Context::new();
-
-    // store_value(Value::Builtins, 1, &mut globals, &start_context);
-    // store_value(Value::Null, 1, &mut globals, &start_context);
+    let (pages, index, _) = generate_docs(path)?;
 
     let mut output_path = current_dir().unwrap();
     output_path.push(PathBuf::from(format!("{}-docs", path)));
     if !output_path.exists() {
         std::fs::create_dir(output_path.clone()).unwrap();
     }
-    let mut info = CompilerInfo::new();
-    info.includes
-        .push(std::env::current_dir().expect("Cannot access current directory"));
-    info.includes.push(
-        std::env::current_exe()
-            .expect("Cannot access directory of executable")
-            .parent()
-            .expect("Executable must be in some directory")
-            .to_path_buf(),
-    );
 
-    let module = import_module(
+    for (name, content) in pages {
+        create_doc_file(output_path.clone(), name, &content);
+    }
+
+    let index_path = output_path.join("index.json");
+    std::fs::write(&index_path, index).unwrap();
+    println!("written to {:?}", index_path);
+    Ok(())
+}
+
+// what building a library added (spwn doc --serve shows it at /stats.json)
+pub struct LibraryStats {
+    pub objects: usize,
+    // groups, colors, block ids and item ids
+    pub used_ids: [u16; 4],
+    pub warnings: Vec<String>,
+}
+
+// (name, content)
+type Pages = Vec<(String, String)>;
+
+// generates the markdown pages for a library, as (name, content),
+// and the json index of everything it (and its dependencies) export
+// the main page comes first, and is called "[library]-docs"
+pub fn generate_docs(
+    path: &str,
+) -> Result<(Pages, String, LibraryStats), RuntimeError> {
+    let (pages, index, stats, _) = generate(
         &ImportType::Lib(path.to_string()),
-        &start_context,
-        &mut globals,
-        info,
-        false,
+        path,
+        Globals::new(PathBuf::new()),
+        default_includes(),
     )?;
+    Ok((pages, index, stats))
+}
 
-    if module.len() > 1 {
-        return Err(RuntimeError::RuntimeError {
-            message: "Documentation of context-splitting libraries is not yet supported!"
-                .to_string(),
-            info: CompilerInfo::new(),
-        });
+// spwn build --docs: documents the lib.spwn of a project into its docs folder, but only
+// when what it exports has changed since the last time (the exported names and
+// signatures are kept in docs/signatures.txt). returns if the docs were written.
+// a missing lib.spwn or a failed write isn't in any script, so those come back
+// as plain errors instead of a RuntimeError with a position
+pub fn document_project(
+    root: &Path,
+    globals: Globals,
+    includes: Vec<PathBuf>,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let lib = root.join("lib.spwn");
+    let name = root
+        .canonicalize()
+        .ok()
+        .and_then(|r| r.file_name().map(|n| n.to_string_lossy().to_string()))
+        .unwrap_or_else(|| "lib".to_string());
+    if !lib.is_file() {
+        return Err(format!(
+            "There is no {} to document",
+            crate::compiler::display_path(&lib)
+        )
+        .into());
+    }
+    let (pages, index, _, signatures) =
+        generate(&ImportType::Script(lib), &name, globals, includes)?;
+
+    let output_path = root.join("docs");
+    let signatures_path = output_path.join("signatures.txt");
+    let signatures = signatures.join("\n") + "\n";
+    if std::fs::read_to_string(&signatures_path).ok().as_deref() == Some(&signatures) {
+        return Ok(false);
     }
 
+    let write = |path: PathBuf, content: &str| {
+        std::fs::write(&path, content)
+            .map_err(|e| format!("Could not write {}: {}", path.to_string_lossy(), e))
+    };
+    std::fs::create_dir_all(&output_path)
+        .map_err(|e| format!("Could not make {}: {}", output_path.to_string_lossy(), e))?;
+    for (name, content) in pages {
+        write(output_path.join(format!("{}.md", name)), &content)?;
+    }
+    write(output_path.join("index.json"), &index)?;
+    write(signatures_path, &signatures)?;
+    Ok(true)
+}
+
+// the pages, index and stats, and the exported names with their signatures (one per line)
+type Generated = (Pages, String, LibraryStats, Vec<String>);
+
+fn generate(
+    import: &ImportType,
+    path: &str,
+    globals: Globals,
+    includes: Vec<PathBuf>,
+) -> Result<Generated, RuntimeError> {
+    let (mut globals, exports) = import_with(import, globals, includes)?;
+
+    let mut pages = vec![(format!("{}-docs", path), String::new())];
+
     let mut doc = format!("# Documentation for `{}` \n", path);
 
-    let exports = globals.stored_values[module[0].0].clone();
     let implementations = globals.implementations.clone();
 
     doc += "_Generated using `spwn doc [file name]`_\n";
@@ -113,20 +181,393 @@ pub fn document_lib(path: &str) -> Result<(), RuntimeError> {
 
             doc += &format!("- [**@{1}**]({}-docs/{1}.md)\n", path, type_name);
 
-            let content = &format!(
+            let content = format!(
                 "  \n# **@{}**: \n {}",
                 type_name,
                 document_dict(dict, &mut globals)
             );
 
-            create_doc_file(output_path.clone(), type_name, content);
+            pages.push((type_name, content));
         }
     }
 
     doc += &format!("# Exports:\n{}", document_val(&exports, &mut globals));
 
-    create_doc_file(output_path, format!("{}-docs", path), &doc);
-    Ok(())
+    pages[0].1 = doc;
+
+    let (index, signatures) = generate_index(path, &exports, &mut globals);
+    let mut warnings = std::mem::take(&mut globals.warnings);
+    warnings.extend(
+        globals
+            .lints
+            .iter()
+            .map(|w| crate::diagnostics::Diagnostic::from(w).to_string()),
+    );
+    let stats = LibraryStats {
+        objects: total_objects,
+        used_ids: [used_groups, used_colors, used_blocks, used_items],
+        warnings,
+    };
+    Ok((pages, index, stats, signatures))
+}
+
+// machine readable index for editors and doc sites:
+// {"library": ..., "entries": [{name, kind, signature, summary, span}, ...]}
+// (also gives back "name: kind signature" for each entry)
+fn generate_index(path: &str, exports: &Value, globals: &mut Globals) -> (String, Vec<String>) {
+    let mut entries = Vec::new();
+
+    // the library itself and all the libraries it depends on
+    let mut libraries: Vec<(String, Value)> = globals
+        .prev_imports
+        .iter()
+        .filter_map(|((import, _), (val, _))| match import {
+            ImportType::Lib(name) => Some((name.clone(), val.clone())),
+            ImportType::Script(_) | ImportType::Project(_) => None,
+        })
+        .collect();
+    // a project's lib.spwn is imported as a script
+    if !libraries.iter().any(|(name, _)| name == path) {
+        libraries.push((path.to_string(), exports.clone()));
+    }
+    libraries.sort_by(|a, b| a.0.cmp(&b.0));
+
+    for (name, val) in libraries {
+        index_val(name, &val, globals, &mut entries);
+    }
+
+    let mut implementations: Vec<(u16, Vec<(String, usize)>)> = globals
+        .implementations
+        .iter()
+        .map(|(typ, members)| {
+            let mut members: Vec<_> = members
+                .iter()
+                .map(|(name, (val, _))| (name.clone(), *val))
+                .collect();
+            members.sort();
+            (*typ, members)
+        })
+        .collect();
+    implementations.sort_by_key(|a| a.0);
+
+    for (typ, members) in implementations {
+        let type_name = find_key_for_value(&globals.type_ids, typ)
+            .expect("Implemented type was not found!")
+            .clone();
+        for (name, val) in members {
+            let val = globals.stored_values[val].clone();
+            index_val(
+                format!("@{}.{}", type_name, name),
+                &val,
+                globals,
+                &mut entries,
+            );
+        }
+    }
+
+    let (entries, signatures): (Vec<String>, Vec<String>) = entries.into_iter().unzip();
+    (
+        format!(
+            "{{\n  \"library\": {},\n  \"entries\": [\n{}\n  ]\n}}\n",
+            json_str(path),
+            entries.join(",\n")
+        ),
+        signatures,
+    )
+}
+
+fn index_val(
+    name: String,
+    val: &Value,
+    globals: &mut Globals,
+    entries: &mut Vec<(String, String)>,
+) {
+    let typ_index = val
+        .member(TYPE_MEMBER_NAME.to_string(), &Context::new(), globals)
+        .unwrap();
+    let kind = globals.stored_values[typ_index].to_str(globals);
+
+    let (signature, summary, span) = match val {
+        Value::Macro(m) => {
+            let mut signature = String::from("(");
+            for (i, arg) in m.args.iter().enumerate() {
+                if i > 0 {
+                    signature += ", ";
+                }
+                signature += &arg.0;
+                if let Some(pat) = arg.3 {
+                    signature += &format!(": {}", globals.stored_values[pat].to_str(globals));
+                }
+                if let Some(def_val) = arg.1 {
+                    signature += &format!(" = {}", globals.stored_values[def_val].to_str(globals));
+                }
+            }
+            signature += ")";
+
+            let summary = m.tag.get_desc().or_else(|| comment_desc(m)).map(|desc| {
+                // first sentence
+                let line = desc.trim().lines().next().unwrap_or("").to_string();
+                match line.find(". ") {
+                    Some(i) => line[..=i].to_string(),
+                    None => line,
+                }
+            });
+
+            let span = format!(
+                "{{\"file\": {}, \"start\": [{}, {}], \"end\": [{}, {}]}}",
+                json_str(&m.def_file.to_string_lossy()),
+                m.def_pos.0 .0,
+                m.def_pos.0 .1,
+                m.def_pos.1 .0,
+                m.def_pos.1 .1
+            );
+            (Some(signature), summary, Some(span))
+        }
+        _ => (None, None, None),
+    };
+
+    let opt = |s: Option<String>| s.unwrap_or_else(|| "null".to_string());
+    let line = format!("{}: {}{}", name, kind, signature.as_deref().unwrap_or(""));
+    entries.push((
+        format!(
+            "    {{\"name\": {}, \"kind\": {}, \"signature\": {}, \"summary\": {}, \"span\": {}}}",
+            json_str(&name),
+            json_str(&kind),
+            opt(signature.map(|s| json_str(&s))),
+            opt(summary.map(|s| json_str(&s))),
+            opt(span)
+        ),
+        line,
+    ));
+
+    // members of custom types are left out, they are part of the type's value
+    if let (Value::Dict(d), "@dictionary") = (val, kind.as_str()) {
+        let mut members: Vec<(String, usize)> = d
+            .iter()
+            .filter(|(k, _)| *k != TYPE_MEMBER_NAME)
+            .map(|(k, v)| (k.clone(), *v))
+            .collect();
+        members.sort();
+        for (key, member) in members {
+            let member = globals.stored_values[member].clone();
+            index_val(format!("{}.{}", name, key), &member, globals, entries);
+        }
+    }
+}
+
+pub fn json_str(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out += "\\\"",
+            '\\' => out += "\\\\",
+            '\n' => out += "\\n",
+            '\r' => out += "\\r",
+            '\t' => out += "\\t",
+            c if (c as u32) < 0x20 => out += &format!("\\u{:04x}", c as u32),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+// imports a library, and gives back its exported value
+pub fn import_lib(path: &str) -> Result<(Globals, Value), RuntimeError> {
+    import_with(
+        &ImportType::Lib(path.to_string()),
+        Globals::new(PathBuf::new()),
+        default_includes(),
+    )
+}
+
+// the current folder and the folder of the executable
+fn default_includes() -> Vec<PathBuf> {
+    vec![
+        std::env::current_dir().expect("Cannot access current directory"),
+        std::env::current_exe()
+            .expect("Cannot access directory of executable")
+            .parent()
+            .expect("Executable must be in some directory")
+            .to_path_buf(),
+    ]
+}
+
+fn import_with(
+    import: &ImportType,
+    mut globals: Globals,
+    includes: Vec<PathBuf>,
+) -> Result<(Globals, Value), RuntimeError> {
+    let start_context = Context::new();
+
+    // store_value(Value::Builtins, 1, &mut globals, &start_context);
+    // store_value(Value::Null, 1, &mut globals, &start_context);
+
+    let mut info = CompilerInfo::new();
+    info.includes = includes;
+
+    let module = import_module(import, &start_context, &mut globals, info, false)?;
+
+    if module.len() > 1 {
+        return Err(RuntimeError::RuntimeError {
+            message: "Documentation of context-splitting libraries is not yet supported!"
+                .to_string(),
+            info: CompilerInfo::new(),
+        });
+    }
+
+    let exports = globals.stored_values[module[0].0].clone();
+    Ok((globals, exports))
+}
+
+// (member, code, if it should be run and not just parsed)
+type Examples = Vec<(String, String, bool)>;
+
+// finds all the examples in the doc comments of a library
+// examples are the `example` attribute, and fenced (```) blocks in the description
+pub fn collect_examples(path: &str) -> Result<Examples, RuntimeError> {
+    let (globals, exports) = import_lib(path)?;
+
+    let mut examples = Vec::new();
+    collect_val_examples(path.to_string(), &exports, &globals, &mut examples);
+
+    let mut implementations: Vec<_> = globals.implementations.iter().collect();
+    implementations.sort_by(|a, b| a.0.cmp(b.0));
+    for (typ, members) in implementations {
+        let type_name =
+            find_key_for_value(&globals.type_ids, *typ).expect("Implemented type was not found!");
+        let mut members: Vec<_> = members.iter().collect();
+        members.sort_by(|a, b| a.0.cmp(b.0));
+        for (name, (val, _)) in members {
+            collect_val_examples(
+                format!("@{}.{}", type_name, name),
+                &globals.stored_values[*val],
+                &globals,
+                &mut examples,
+            );
+        }
+    }
+    Ok(examples)
+}
+
+// compiles every example of a library (without making a level),
+// and gives back how many of them failed
+pub fn test_examples(path: &str) -> Result<usize, RuntimeError> {
+    let examples = collect_examples(path)?;
+
+    let included_paths = vec![
+        std::env::current_dir().expect("Cannot access current directory"),
+        std::env::current_exe()
+            .expect("Cannot access directory of executable")
+            .parent()
+            .expect("Executable must be in some directory")
+            .to_path_buf(),
+    ];
+
+    let mut failed = 0;
+    for (name, code, runs) in examples.iter() {
+        let script_path = PathBuf::from(format!("{} (example)", name));
+
+        let result = match parse_spwn(code.clone(), script_path.clone()) {
+            Ok(_) if !runs => Ok(()),
+            Ok((statements, notes)) => {
+                // compiler bugs shouldn't stop the other examples from being tested
+                std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                    let mut globals = Globals::new(script_path.clone());
+                    compile_main(statements, included_paths.clone(), notes, &mut globals)
+                        .map(|_| ())
+                        .map_err(|e| e.to_string())
+                }))
+                .unwrap_or_else(|_| Err("The compiler panicked".to_string()))
+            }
+            Err(e) => Err(e.to_string()),
+        };
+
+        match result {
+            Ok(_) if !runs => print_with_color(
+                &format!("example for {} ... ok (only parsed)", name),
+                TColor::Green,
+            ),
+            Ok(_) => print_with_color(&format!("example for {} ... ok", name), TColor::Green),
+            Err(e) => {
+                failed += 1;
+                print_with_color(&format!("example for {} ... FAILED", name), TColor::Red);
+                eprintln!("{}\n\nin example:\n{}\n", e, code);
+            }
+        }
+    }
+
+    print_with_color(
+        &format!(
+            "\n{} examples tested, {} passed, {} failed",
+            examples.len(),
+            examples.len() - failed,
+            failed
+        ),
+        if failed == 0 {
+            TColor::Green
+        } else {
+            TColor::Red
+        },
+    );
+    Ok(failed)
+}
+
+fn collect_val_examples(
+    name: String,
+    val: &Value,
+    globals: &Globals,
+    examples: &mut Examples,
+) {
+    match val {
+        Value::Macro(m) => {
+            if let Some(example) = m.tag.get_example() {
+                examples.push((name.clone(), example, m.tag.example_runs()));
+            }
+            if let Some(desc) = m.tag.get_desc() {
+                for block in fenced_blocks(&desc) {
+                    examples.push((name.clone(), block, true));
+                }
+            }
+        }
+        Value::Dict(d) => {
+            let mut members: Vec<_> = d.iter().filter(|(k, _)| *k != TYPE_MEMBER_NAME).collect();
+            members.sort_by(|a, b| a.0.cmp(b.0));
+            for (key, val) in members {
+                collect_val_examples(
+                    format!("{}.{}", name, key),
+                    &globals.stored_values[*val],
+                    globals,
+                    examples,
+                );
+            }
+        }
+        _ => (),
+    }
+}
+
+// code blocks in a description (only untagged or spwn ones)
+fn fenced_blocks(text: &str) -> Vec<String> {
+    let mut blocks = Vec::new();
+    let mut current: Option<(bool, String)> = None;
+    for line in text.lines() {
+        let trimmed = line.trim();
+        if let Some(lang) = trimmed.strip_prefix("```") {
+            current = match current {
+                Some((is_spwn, code)) => {
+                    if is_spwn {
+                        blocks.push(code.trim().to_string());
+                    }
+                    None
+                }
+                None => Some((lang.is_empty() || lang == "spwn", String::new())),
+            };
+        } else if let Some((_, code)) = &mut current {
+            *code += line;
+            *code += "\n";
+        }
+    }
+    blocks
 }
 
 fn document_dict(dict: &HashMap<String, usize>, globals: &mut Globals) -> String {
@@ -190,7 +631,7 @@ fn document_dict(dict: &HashMap<String, usize>, globals: &mut Globals) -> String
 fn document_macro(mac: &Macro, globals: &mut Globals) -> String {
     //description
     let mut doc = String::new();
-    if let Some(s) = mac.tag.get_desc() {
+    if let Some(s) = mac.tag.get_desc().or_else(|| comment_desc(mac)) {
         doc += &format!("## Description: \n _{}_\n", s)
     };
 
@@ -252,6 +693,44 @@ fn document_macro(mac: &Macro, globals: &mut Globals) -> String {
     doc
 }
 
+// for a macro without #[desc], the comments right before the statement that
+// defines it, like `// adds two numbers` above `add = (a, b) { ... }`
+fn comment_desc(mac: &Macro) -> Option<String> {
+    let unparsed = std::fs::read_to_string(&mac.def_file).ok()?;
+    let (statements, _) = parse_spwn(unparsed, mac.def_file.clone()).ok()?;
+    let lines = definition_comments(statements, mac.def_pos)?;
+    if lines.is_empty() {
+        None
+    } else {
+        Some(lines.join("\n"))
+    }
+}
+
+// the comments before the statement that assigns the value at `pos`
+// (looking inside blocks too)
+fn definition_comments(statements: Vec<ast::Statement>, pos: FileRange) -> Option<Vec<String>> {
+    for mut statement in statements {
+        if statement.pos.0 > pos.0 || statement.pos.1 < pos.1 {
+            continue;
+        }
+        if let ast::StatementBody::Expr(e) = &statement.body {
+            if e.values.len() == 2
+                && e.operators == [ast::Operator::Assign]
+                && e.values[1].pos == pos
+            {
+                return Some(statement.trivia.doc_lines());
+            }
+        }
+        for block in statement.blocks_mut() {
+            let found = definition_comments(std::mem::take(block), pos);
+            if found.is_some() {
+                return found;
+            }
+        }
+    }
+    None
+}
+
 fn document_val(val: &Value, globals: &mut Globals) -> String {
     let mut doc = String::new();
     let typ_index = val
@@ -0,0 +1,75 @@
+// reading the scripts and libraries that are imported. with the embedded-libraries
+// feature (for the browser, where there are no files) the libraries that come with spwn
+// are built into the compiler, and found as if they were in a folder called EMBEDDED
+use std::path::Path;
+
+pub const EMBEDDED: &str = "/embedded";
+
+#[cfg(feature = "embedded-libraries")]
+macro_rules! embed {
+    ($path:literal) => {
+        ($path, include_str!(concat!("../", $path)))
+    };
+}
+
+// (path from the EMBEDDED folder, contents)
+#[cfg(feature = "embedded-libraries")]
+const LIBRARIES: &[(&str, &str)] = &[
+    embed!("libraries/gamescene/lib.spwn"),
+    embed!("libraries/std/array.spwn"),
+    embed!("libraries/std/block.spwn"),
+    embed!("libraries/std/collision_matrix.spwn"),
+    embed!("libraries/std/color.spwn"),
+    embed!("libraries/std/constants.spwn"),
+    embed!("libraries/std/control_flow.spwn"),
+    embed!("libraries/std/counter.spwn"),
+    embed!("libraries/std/dictionary.spwn"),
+    embed!("libraries/std/events.spwn"),
+    embed!("libraries/std/fileio.spwn"),
+    embed!("libraries/std/general_triggers.spwn"),
+    embed!("libraries/std/group.spwn"),
+    embed!("libraries/std/item.spwn"),
+    embed!("libraries/std/item_array.spwn"),
+    embed!("libraries/std/lib.spwn"),
+    embed!("libraries/std/obj_set.spwn"),
+    embed!("libraries/std/object.spwn"),
+    embed!("libraries/std/option.spwn"),
+    embed!("libraries/std/regex.spwn"),
+    embed!("libraries/std/runtime_macro.spwn"),
+    embed!("libraries/std/runtime_text.spwn"),
+    embed!("libraries/std/state_machine.spwn"),
+    embed!("libraries/std/string.spwn"),
+    embed!("libraries/std/util.spwn"),
+];
+
+#[cfg(not(feature = "embedded-libraries"))]
+const LIBRARIES: &[(&str, &str)] = &[];
+
+fn embedded(path: &Path) -> Option<&'static str> {
+    let inside = path.strip_prefix(EMBEDDED).ok()?;
+    LIBRARIES
+        .iter()
+        .find(|(p, _)| Path::new(p) == inside)
+        .map(|(_, content)| *content)
+}
+
+pub fn is_file(path: &Path) -> bool {
+    embedded(path).is_some() || path.is_file()
+}
+
+pub fn is_dir(path: &Path) -> bool {
+    let embedded_dir = match path.strip_prefix(EMBEDDED) {
+        Ok(inside) => LIBRARIES
+            .iter()
+            .any(|(p, _)| Path::new(p).starts_with(inside) && Path::new(p) != inside),
+        Err(_) => false,
+    };
+    embedded_dir || path.is_dir()
+}
+
+pub fn read_to_string(path: &Path) -> std::io::Result<String> {
+    match embedded(path) {
+        Some(content) => Ok(content.to_string()),
+        None => std::fs::read_to_string(path),
+    }
+}
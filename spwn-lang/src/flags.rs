@@ -0,0 +1,190 @@
+// the flags spwn build knows, so a mistyped one is an error that suggests the closest
+// one instead of being left out (and quietly building something else). everything after
+// `--` isn't a flag, but a name=value define the script can read with $.defines()
+
+pub const BUILD_FLAGS: &[&str] = &[
+    "--console-output",
+    "-c",
+    "--no-level",
+    "-l",
+    "--no-optimize",
+    "-o",
+    "--level-name",
+    "-n",
+    "--live-editor",
+    "-e",
+    "--save-file",
+    "-s",
+    "--profile",
+    "--tui",
+    "--remarks",
+    "--record-trace",
+    "--check-delays",
+    "--round-delays",
+    "--check-spawn-order",
+    "--spawn-order",
+    "--strict-gd-limits",
+    "--deny-warnings",
+    "--release",
+    "--docs",
+    "--watch",
+    "--cache-macros",
+    "--no-cache",
+    "--no-item-reuse",
+    "--trigger-origin",
+    "--trigger-max-width",
+    "--trigger-stacking",
+    "--lock-triggers",
+    "--trigger-flags",
+    "--print-depth",
+    "--print-elements",
+    "--metrics",
+    "--verbose",
+    "--error-format",
+    "--paranoid",
+    "--timeout",
+    "--partial-output",
+    "--non-interactive",
+    "--create-level",
+    "--force",
+    "--level-description",
+    "--output-gmd",
+    "--level-from-clipboard",
+    "--copy-to-clipboard",
+    "--emit",
+    "--group-usage",
+    "--manifest",
+    "--only",
+    "--include-path",
+    // the old spelling
+    "--included-path",
+    "-i",
+];
+
+// the flags spwn check knows (the ones of spwn build that are about compiling)
+pub const CHECK_FLAGS: &[&str] = &[
+    "--deny-warnings",
+    "--release",
+    "--error-format",
+    "--include-path",
+    "--included-path",
+    "-i",
+];
+
+// the ones that are followed by a value
+pub const VALUE_FLAGS: &[&str] = &[
+    "--level-name",
+    "-n",
+    "--save-file",
+    "-s",
+    "--profile",
+    "--spawn-order",
+    "--trigger-origin",
+    "--trigger-max-width",
+    "--trigger-stacking",
+    "--lock-triggers",
+    "--trigger-flags",
+    "--print-depth",
+    "--print-elements",
+    "--error-format",
+    "--timeout",
+    "--partial-output",
+    "--level-description",
+    "--output-gmd",
+    "--emit",
+    "--group-usage",
+    "--manifest",
+    "--only",
+    "--include-path",
+    "--included-path",
+    "-i",
+];
+
+// how many letters have to be added, removed or changed to get from a to b
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut previous = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let current = row[j + 1];
+            row[j + 1] = if ca == *cb {
+                previous
+            } else {
+                1 + previous.min(row[j]).min(current)
+            };
+            previous = current;
+        }
+    }
+    row[b.len()]
+}
+
+// the known flag closest to the given one, if it's close enough to be a typo
+pub fn suggest<'a>(flag: &str, known: &[&'a str]) -> Option<&'a str> {
+    // --emit=json is checked as --emit
+    let flag = flag.split('=').next().unwrap_or(flag);
+    // one letter flags are too short to guess
+    if flag.len() <= 2 {
+        return None;
+    }
+    let limit = (flag.len() / 3).max(2);
+    known
+        .iter()
+        .filter(|k| k.len() > 2)
+        .map(|k| (edit_distance(flag, k), *k))
+        .filter(|(d, _)| *d <= limit)
+        .min_by_key(|(d, _)| *d)
+        .map(|(_, k)| k)
+}
+
+pub fn unknown(flag: &str, known: &[&str]) -> String {
+    match suggest(flag, known) {
+        Some(s) => format!("Unknown flag {}, did you mean {}?", flag, s),
+        None if !flag.starts_with('-') => format!(
+            "Unexpected argument {} (values for the script go after --, like -- {}=...)",
+            flag, flag
+        ),
+        None => format!("Unknown flag {} (see spwn help)", flag),
+    }
+}
+
+// name=value after --, or just name (which is true)
+pub fn parse_define(arg: &str) -> (String, Option<String>) {
+    let arg = arg.trim_start_matches('-');
+    match arg.find('=') {
+        Some(pos) => (arg[..pos].to_string(), Some(arg[(pos + 1)..].to_string())),
+        None => (arg.to_string(), None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn typos_get_the_closest_flag() {
+        assert_eq!(
+            unknown("--no-optimise", BUILD_FLAGS),
+            "Unknown flag --no-optimise, did you mean --no-optimize?"
+        );
+        assert_eq!(suggest("--relase", BUILD_FLAGS), Some("--release"));
+        assert_eq!(suggest("--emitt=json", BUILD_FLAGS), Some("--emit"));
+        assert_eq!(suggest("--colour", BUILD_FLAGS), None);
+        assert_eq!(
+            parse_define("difficulty=hard"),
+            ("difficulty".to_string(), Some("hard".to_string()))
+        );
+        assert_eq!(parse_define("--debug"), ("debug".to_string(), None));
+
+        // every flag is in spwn help
+        let help = include_str!("../help.txt");
+        for flag in BUILD_FLAGS
+            .iter()
+            .chain(CHECK_FLAGS)
+            .filter(|f| **f != "--included-path")
+        {
+            assert!(help.contains(flag), "{} isn't in help.txt", flag);
+        }
+    }
+}
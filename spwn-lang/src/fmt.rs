@@ -1,31 +1,213 @@
 // tools for automatically formatting spwn files
 
 use crate::ast::*;
+use crate::compiler_types::ImportType;
+
+use std::cell::RefCell;
+use std::path::Path as FilePath;
 
 pub trait SpwnFmt {
     fn fmt(&self, ind: Indent) -> String;
 }
 
+// one level of nesting is always 4 units of `Indent`,
+// the config decides what that looks like in the output
 type Indent = u16;
 
-fn tabs(mut num: Indent) -> String {
-    let mut out = String::new();
-    while num > 4 {
-        out += "\t";
-        num -= 4;
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BraceStyle {
+    // `f = !{` on one line
+    SameLine,
+    // `!{` on its own line below the definition
+    NextLine,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FmtConfig {
+    pub indent_width: u16,
+    pub hard_tabs: bool,
+    pub max_line_length: usize,
+    pub trigger_fn_brace: BraceStyle,
+    pub sort_obj_keys: bool,
+}
+
+impl Default for FmtConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FmtConfig {
+    pub fn new() -> Self {
+        FmtConfig {
+            indent_width: 4,
+            hard_tabs: false,
+            max_line_length: 100,
+            trigger_fn_brace: BraceStyle::SameLine,
+            sort_obj_keys: false,
+        }
     }
 
-    for _ in 0..num {
-        out += " ";
+    // looks for a .spwnfmt file or a spwn.toml file with a [format] section,
+    // starting in the given folder and going up through its parents
+    pub fn find(start: &FilePath) -> Result<Self, String> {
+        for dir in start.ancestors() {
+            let spwnfmt = dir.join(".spwnfmt");
+            if spwnfmt.is_file() {
+                return Self::from_file(&spwnfmt, None);
+            }
+            let manifest = dir.join("spwn.toml");
+            if manifest.is_file() {
+                return Self::from_file(&manifest, Some("format"));
+            }
+        }
+        Ok(Self::new())
+    }
+
+    fn from_file(path: &FilePath, section: Option<&str>) -> Result<Self, String> {
+        match std::fs::read_to_string(path) {
+            Ok(content) => Self::parse(&content, section)
+                .map_err(|e| format!("Error in {}: {}", path.to_string_lossy(), e)),
+            Err(e) => Err(format!(
+                "Could not read {}: {}",
+                path.to_string_lossy(),
+                e
+            )),
+        }
     }
 
-    out
+    // parses `key = value` lines. if a section is given, only the lines
+    // under `[section]` are read (the rest of spwn.toml is not our business)
+    pub fn parse(content: &str, section: Option<&str>) -> Result<Self, String> {
+        let mut config = Self::new();
+
+        for entry in crate::manifest::read(content, |s| s == section.unwrap_or(""))? {
+            let (key, value) = (entry.key.as_str(), entry.text());
+            let value = value.as_str();
+
+            let invalid = |expected: &str| {
+                entry.error(format!(
+                    "expected {} for `{}`, found `{}`",
+                    expected, key, value
+                ))
+            };
+
+            match key {
+                "indent_width" => {
+                    config.indent_width = value.parse().map_err(|_| invalid("a number"))?
+                }
+                "hard_tabs" => config.hard_tabs = value.parse().map_err(|_| invalid("a boolean"))?,
+                "max_line_length" => {
+                    config.max_line_length = value.parse().map_err(|_| invalid("a number"))?
+                }
+                "trigger_fn_brace" => {
+                    config.trigger_fn_brace = match value {
+                        "same_line" => BraceStyle::SameLine,
+                        "next_line" => BraceStyle::NextLine,
+                        _ => return Err(invalid("\"same_line\" or \"next_line\"")),
+                    }
+                }
+                "sort_obj_keys" => {
+                    config.sort_obj_keys = value.parse().map_err(|_| invalid("a boolean"))?
+                }
+                _ => return Err(entry.error(format!("unknown option `{}`", key))),
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+thread_local! {
+    // the formatter is a big tree of `SpwnFmt` impls, so instead of passing
+    // the config through all of them it is set once by `format`
+    static CONFIG: RefCell<FmtConfig> = RefCell::new(FmtConfig::new());
+}
+
+fn config() -> FmtConfig {
+    CONFIG.with(|c| c.borrow().clone())
+}
+
+fn tabs(num: Indent) -> String {
+    let config = config();
+    let levels = num / 4;
+    if config.hard_tabs {
+        "\t".repeat(levels as usize)
+    } else {
+        " ".repeat((levels * config.indent_width) as usize)
+    }
+}
+
+// newlines in strings are written as this until the end, so they don't get indented
+// (or make a list of things go on multiple lines)
+const STR_NEWLINE: char = '\u{E000}';
+
+fn str_literal(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '\\' => out += "\\\\",
+            '"' => out += "\\\"",
+            '\r' => out += "\\r",
+            '\t' => out += "\\t",
+            '\n' => out.push(STR_NEWLINE),
+            c => out.push(c),
+        }
+    }
+    out + "\""
+}
+
+// `tag` is the attribute at the top of the file
+pub fn format(input: Vec<Statement>, tag: &Attribute, config: &FmtConfig) -> String {
+    CONFIG.with(|c| *c.borrow_mut() = config.clone());
+    let mut out = String::new();
+    if !tag.tags.is_empty() {
+        out += tag.fmt(0).trim_end();
+        out += "\n\n";
+    }
+    out += &CompoundStatement { statements: input }.fmt(0);
+    if !out.is_empty() {
+        out.push('\n');
+    }
+    out.replace(STR_NEWLINE, "\n")
 }
 
-pub fn _format(input: Vec<Statement>) -> String {
+// formats only the top level statements that touch the given (1-based, inclusive)
+// line range, and leaves the rest of the source untouched.
+// the range is widened to whole statements, since half a statement can't be formatted
+pub fn format_range(
+    source: &str,
+    input: Vec<Statement>,
+    range: (usize, usize),
+    config: &FmtConfig,
+) -> String {
+    let selected: Vec<Statement> = input
+        .into_iter()
+        .filter(|s| s.pos.0 .0 <= range.1 && s.pos.1 .0 >= range.0)
+        .collect();
+
+    if selected.is_empty() {
+        return source.to_string();
+    }
+
+    let first_line = selected[0].pos.0 .0;
+    let last_line = selected.last().unwrap().pos.1 .0;
+
+    let formatted = format(selected, &Attribute::new(), config);
+
     let mut out = String::new();
-    for s in input {
-        out += &s.fmt(0).to_string();
+    for (i, line) in source.lines().enumerate() {
+        let line_num = i + 1;
+        if line_num == first_line {
+            out += &formatted;
+        } else if line_num < first_line || line_num > last_line {
+            out += line;
+            out += "\n";
+        }
+    }
+
+    if !source.ends_with('\n') {
+        trim_newline(&mut out);
     }
     out
 }
@@ -48,14 +230,15 @@ fn element_list(elements: &[impl SpwnFmt], open: char, closing: char, ind: Inden
         elem_text.push(text)
     }
 
+    let max = config().max_line_length;
     let vertical = if elements.len() == 1 {
-        sum > 150
+        sum > max + max / 2
     } else {
         elem_text.iter().enumerate().any(|(i, x)| {
             if i != last {
-                x.len() > 50 || x.contains('\n')
+                x.len() > max / 2 || x.contains('\n')
             } else {
-                sum > 100
+                sum > max
             }
         })
     };
@@ -185,30 +368,21 @@ mod tests {
 impl SpwnFmt for Statement {
     fn fmt(&self, ind: Indent) -> String {
         let mut out = String::new();
-        // if let Some(comment) = &self.comment.0 {
-        //     //out += "[stmt pre]";
-
-        //     out += &indent_comment(comment, ind);
-        //     //
-        //     if !comment.ends_with('\n') {
-        //         out += "\n";
-        //     }
-        //     out += &tabs(ind);
-        // }
-        out += &if self.arrow {
-            format!("-> {}\n", self.body.fmt(ind))
-        } else {
-            format!("{}\n", self.body.fmt(ind))
-        };
-        // if let Some(comment) = &self.comment.1 {
-        //     if !comment.starts_with('\n') {
-        //         out += "\n";
-        //     }
-
-        //     //out += &tabs(ind);
-        //     //out += "[stmt post]";
-        //     out += &indent_comment(comment, ind);
-        // }
+        for comment in &self.trivia.comments_before {
+            out += &format!("{}\n{}", comment, tabs(ind));
+        }
+        out += &self.attributes.fmt(ind);
+        if self.arrow {
+            out += "-> ";
+        }
+        out += &self.body.fmt(ind);
+        if let Some(comment) = &self.trivia.comment_after {
+            out += &format!(" {}", comment);
+        }
+        out += "\n";
+        for comment in &self.trivia.comments_end {
+            out += &format!("{}{}\n", tabs(ind), comment);
+        }
         out
     }
 }
@@ -219,7 +393,7 @@ impl SpwnFmt for StatementBody {
             //StatementBody::Definition(def) => format!("{}", def.fmt(ind)),
             StatementBody::Call(call) => call.fmt(ind),
             StatementBody::Expr(x) => x.fmt(ind),
-            StatementBody::TypeDef(x) => format!("type {}", x),
+            StatementBody::TypeDef(x) => format!("type @{}", x),
             StatementBody::Return(x) => match x {
                 Some(expr) => format!("return {}", expr.fmt(ind)),
                 None => "return".to_string(),
@@ -228,9 +402,25 @@ impl SpwnFmt for StatementBody {
             StatementBody::If(x) => x.fmt(ind),
             StatementBody::For(x) => x.fmt(ind),
             StatementBody::Error(x) => x.fmt(ind),
+            StatementBody::Try(x) => x.fmt(ind),
+            StatementBody::Defer(body) => format!(
+                "defer {{\n{}\n{}}}",
+                CompoundStatement {
+                    statements: body.clone()
+                }
+                .fmt(ind + 4),
+                tabs(ind)
+            ),
             StatementBody::Extract(x) => format!("extract {}", x.fmt(ind)),
-            StatementBody::Break => String::from("break"),
-            StatementBody::Continue => String::from("continue"),
+            StatementBody::Break(label) => match label {
+                Some(label) => format!("break {}", label),
+                None => String::from("break"),
+            },
+            StatementBody::Continue(label) => match label {
+                Some(label) => format!("continue {}", label),
+                None => String::from("continue"),
+            },
+            StatementBody::ErrorStatement(source) => source.clone(),
         }
     }
 }
@@ -248,19 +438,38 @@ impl SpwnFmt for ValueBody {
         match self {
             Id(x) => x.fmt(ind),
             Number(x) => format!("{}", x),
-            CmpStmt(x) => format!("{{\n{}\n{}}}", x.fmt(ind + 4), tabs(ind)),
+            CmpStmt(x) => format!(
+                "{}!{{\n{}\n{}}}",
+                match config().trigger_fn_brace {
+                    BraceStyle::SameLine => String::new(),
+                    BraceStyle::NextLine => format!("\n{}", tabs(ind)),
+                },
+                x.fmt(ind + 4),
+                tabs(ind)
+            ),
             Dictionary(x) => element_list(x, '{', '}', ind),
             Array(x) => element_list(x, '[', ']', ind),
             Symbol(x) => x.to_string(),
             Bool(x) => format!("{}", x),
             Expression(x) => format!("({})", x.fmt(ind)),
-            Str(x) => format!("\"{}\"", x),
-            Import(x, f) => format!("import{} {:?}", if *f { "!" } else { "" }, x),
+            Str(x) => str_literal(x),
+            Import(x, f) => format!(
+                "import{} {}",
+                if *f { "!" } else { "" },
+                match x {
+                    ImportType::Script(p) => format!("\"{}\"", p.to_string_lossy()),
+                    _ => crate::compiler::import_name(x),
+                }
+            ),
             Obj(x) => {
+                let mut props = x.props.clone();
+                if config().sort_obj_keys {
+                    props.sort_by_cached_key(|(key, _)| key.fmt(0));
+                }
                 (match x.mode {
                     ObjectMode::Object => "obj".to_string(),
                     ObjectMode::Trigger => "trigger".to_string(),
-                }) + &element_list(&x.props, '{', '}', ind)
+                }) + &element_list(&props, '{', '}', ind)
             }
             Macro(x) => x.fmt(ind),
             Resolved(_) => "<val>".to_string(),
@@ -273,11 +482,43 @@ impl SpwnFmt for ValueBody {
                 t.condition.fmt(ind),
                 t.else_expr.fmt(ind)
             ),
-            Switch(_, _) => "switch".to_string(),
+            Try(t) => format!(
+                "try {{ {} }} catch {}{{ {} }}",
+                t.try_expr.fmt(ind),
+                match &t.catch_symbol {
+                    Some(symbol) => format!("{} ", symbol),
+                    None => String::new(),
+                },
+                t.catch_expr.fmt(ind)
+            ),
+            Switch(value, cases) => format!(
+                "switch {} {}",
+                value.fmt(ind),
+                element_list(cases, '{', '}', ind)
+            ),
+            ErrorExpr(source) => source.clone(),
         }
     }
 }
 
+impl SpwnFmt for Case {
+    fn fmt(&self, ind: Indent) -> String {
+        let pattern = match &self.typ {
+            CaseType::Value(values) => format!(
+                "case {}",
+                values
+                    .iter()
+                    .map(|v| v.fmt(ind))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            CaseType::Pattern(pattern) => pattern.fmt(ind),
+            CaseType::Default => "else".to_string(),
+        };
+        format!("{}: {}", pattern, self.body.fmt(ind))
+    }
+}
+
 impl SpwnFmt for ValueLiteral {
     fn fmt(&self, ind: Indent) -> String {
         self.body.fmt(ind)
@@ -329,7 +570,11 @@ impl SpwnFmt for Call {
 impl SpwnFmt for For {
     fn fmt(&self, ind: Indent) -> String {
         format!(
-            "for {} in {} {{\n{}\n{}}}",
+            "{}for {} in {} {{\n{}\n{}}}",
+            match &self.label {
+                Some(label) => format!("{}: ", label),
+                None => String::new(),
+            },
             self.symbol,
             self.array.fmt(ind),
             CompoundStatement {
@@ -354,6 +599,11 @@ impl SpwnFmt for Variable {
         //     }
         // }
 
+        // macros write their own attribute
+        if !matches!(self.value.body, ValueBody::Macro(_)) {
+            out += &self.tag.fmt(ind);
+        }
+
         if let Some(op) = &self.operator {
             out += &op.fmt(ind);
         }
@@ -463,7 +713,29 @@ impl SpwnFmt for Definition {
 
 impl SpwnFmt for Error {
     fn fmt(&self, ind: Indent) -> String {
-        format!("error {}", self.message.fmt(ind))
+        format!("throw {}", self.message.fmt(ind))
+    }
+}
+
+impl SpwnFmt for Try {
+    fn fmt(&self, ind: Indent) -> String {
+        format!(
+            "try {{\n{}\n{}}} catch {}{{\n{}\n{}}}",
+            CompoundStatement {
+                statements: self.try_body.clone()
+            }
+            .fmt(ind + 4),
+            tabs(ind),
+            match &self.catch_symbol {
+                Some(symbol) => format!("{} ", symbol),
+                None => String::new(),
+            },
+            CompoundStatement {
+                statements: self.catch_body.clone()
+            }
+            .fmt(ind + 4),
+            tabs(ind)
+        )
     }
 }
 
@@ -480,7 +752,10 @@ impl SpwnFmt for CompoundStatement {
     fn fmt(&self, ind: Indent) -> String {
         let mut out = String::new();
 
-        for s in &self.statements {
+        for (i, s) in self.statements.iter().enumerate() {
+            if i > 0 && s.trivia.blank_line_before {
+                out += "\n";
+            }
             out += &format!("{}{}", tabs(ind), s.fmt(ind));
         }
 
@@ -554,7 +829,11 @@ impl SpwnFmt for Macro {
 
 impl SpwnFmt for (String, Vec<Argument>) {
     fn fmt(&self, ind: Indent) -> String {
-        self.0.clone() + &element_list(&self.1, '(', ')', ind)
+        if self.1.is_empty() {
+            self.0.clone()
+        } else {
+            self.0.clone() + &element_list(&self.1, '(', ')', ind)
+        }
     }
 }
 
@@ -564,11 +843,84 @@ impl SpwnFmt for Attribute {
             return String::new();
         }
 
-        let text = String::from("#") + &element_list(&self.tags, '[', ']', ind);
-        if text.len() > 60 {
-            text + "\n" + &tabs(ind)
-        } else {
-            text + " "
+        // the tags aren't separated by commas
+        let tags: Vec<String> = self.tags.iter().map(|t| t.fmt(0)).collect();
+        let text = format!("#[{}]", tags.join(" "));
+        if text.len() <= config().max_line_length * 3 / 5 {
+            return text + " ";
         }
+        let mut out = String::from("#[\n");
+        for tag in &tags {
+            for line in tag.lines() {
+                out += &format!("{}{}\n", tabs(ind + 4), line);
+            }
+        }
+        out + &tabs(ind) + "]\n" + &tabs(ind)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fmt_config_parsing() {
+        let manifest = "
+[package]
+name = \"my_level\"
+indent_width = 8
+
+[format]
+indent_width = 2 # comment
+trigger_fn_brace = \"next_line\"
+sort_obj_keys = true
+";
+        let config = FmtConfig::parse(manifest, Some("format")).unwrap();
+        assert_eq!(config.indent_width, 2);
+        assert_eq!(config.trigger_fn_brace, BraceStyle::NextLine);
+        assert!(config.sort_obj_keys);
+        assert_eq!(config.max_line_length, FmtConfig::new().max_line_length);
+
+        assert!(FmtConfig::parse("hard_tabs = maybe", None).is_err());
+        assert!(FmtConfig::parse("tab_size = 4", None).is_err());
+    }
+
+    fn fmt_source(source: &str) -> String {
+        let (parsed, notes, errors) = crate::parser::parse_spwn_partial(
+            source.to_string(),
+            std::path::PathBuf::from("test.spwn"),
+        );
+        assert!(errors.is_empty(), "{}", source);
+        format(parsed, &notes.tag, &FmtConfig::new())
+    }
+
+    #[test]
+    fn formatting_is_stable() {
+        let source = r#"#[no_std]
+type @thing
+impl @thing {
+    new: #[desc("Makes a thing") example("
+let t = @thing::new()
+    ")] (n: @number) {
+        return @thing::{n: n}
+    },
+    name: #[desc("The name")] "thing\\ \"one\"",
+}
+kind = switch 3 {case 1, 2: "small", @number: "number", else: "other"}
+if kind == "number" {
+    $.print(kind)
+} else {
+    // not a number
+    #[desc("says no")] -> $.print("no")
+}
+"#;
+        let formatted = fmt_source(source);
+        assert!(formatted.contains("type @thing"));
+        // the example isn't indented
+        assert!(formatted.contains("example(\"\nlet t = @thing::new()\n    \")"));
+        assert!(formatted.contains("\"thing\\\\ \\\"one\\\"\""));
+        assert!(formatted.contains("case 1, 2: \"small\""));
+        assert!(formatted.contains("#[desc(\"says no\")] -> $.print"));
+        assert_eq!(fmt_source(&formatted), formatted);
     }
 }
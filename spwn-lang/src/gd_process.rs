@@ -0,0 +1,31 @@
+// checks if geometry dash is open, since it writes its own copy of the
+// savefile when it closes (so anything written while it's open gets lost)
+use std::process::Command;
+
+#[cfg(windows)]
+pub fn geometry_dash_running() -> bool {
+    match Command::new("tasklist")
+        .args(&["/FI", "IMAGENAME eq GeometryDash.exe", "/NH"])
+        .output()
+    {
+        Ok(out) => String::from_utf8_lossy(&out.stdout).contains("GeometryDash.exe"),
+        Err(_) => false,
+    }
+}
+
+#[cfg(target_os = "macos")]
+pub fn geometry_dash_running() -> bool {
+    match Command::new("pgrep").args(&["-x", "Geometry Dash"]).output() {
+        Ok(out) => out.status.success(),
+        Err(_) => false,
+    }
+}
+
+// on linux the game runs through proton (or wine)
+#[cfg(not(any(windows, target_os = "macos")))]
+pub fn geometry_dash_running() -> bool {
+    match Command::new("pgrep").args(["-f", "GeometryDash.exe"]).output() {
+        Ok(out) => out.status.success(),
+        Err(_) => false,
+    }
+}
@@ -8,10 +8,14 @@ use crate::value::*;
 //use std::boxed::Box;
 use crate::compiler_info::CompilerInfo;
 use crate::value_storage::*;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
 
 use crate::compiler::RuntimeError;
+use crate::history::History;
+use crate::macro_cache::MacroCache;
+use crate::parser::FileRange;
+use crate::trace::Trace;
 
 pub struct Globals {
     //counters for arbitrary groups
@@ -21,6 +25,8 @@ pub struct Globals {
     pub closed_items: u16,
 
     pub path: PathBuf,
+    // the main script's folder
+    pub project_root: PathBuf,
 
     pub lowest_y: HashMap<u32, u16>,
     pub stored_values: ValStorage,
@@ -32,7 +38,9 @@ pub struct Globals {
     pub func_ids: Vec<FunctionId>,
     pub objects: Vec<GdObj>,
 
-    pub prev_imports: HashMap<ImportType, (Value, Implementations)>,
+    // by the import and the file it found, since the same relative import
+    // from different folders can be different files
+    pub prev_imports: HashMap<(ImportType, PathBuf), (Value, Implementations)>,
 
     pub trigger_order: usize,
 
@@ -40,15 +48,92 @@ pub struct Globals {
     pub implementations: Implementations,
 
     pub sync_groups: Vec<SyncGroup>,
+
+    pub warnings: Vec<String>,
+    // unused variables and other lints in the script's files
+    pub lints: Vec<crate::lints::Warning>,
+
+    // physics ticks per second the level is made for (#[fps(...)] on the main script)
+    pub fps: f64,
+    // --release: libraries can leave out their runtime checks
+    pub release: bool,
+    // if the script printed anything (a build without objects can still be useful then)
+    pub printed: bool,
+    // what $.print prints goes here instead of stdout when it's set (for the library api)
+    pub output: Option<Vec<String>>,
+    // how much of big values $.print shows
+    pub print_limits: crate::value::PrintLimits,
+    // values made immutable with $.freeze (and copies of them), with where they were frozen
+    pub frozen: HashMap<StoredValue, (PathBuf, FileRange)>,
+    // --timeout: when the build started, and how long it can take
+    pub timeout: Option<(std::time::Instant, std::time::Duration)>,
+    // false with --non-interactive, when nothing should wait for someone to type
+    pub interactive: bool,
+    // the [aliases] of the project, for parsing its files
+    pub aliases: Option<crate::aliases::Aliases>,
+
+    // the files that are being imported right now, with where they were imported from
+    pub import_stack: Vec<(PathBuf, CompilerInfo)>,
+
+    // files that belong to a library (objects added there are counted for the caller)
+    pub lib_files: HashSet<PathBuf>,
+    // where in the script each object (by unique id) was added from
+    pub object_origins: HashMap<usize, (PathBuf, FileRange)>,
+    // for objects added inside a #[budget] statement, the statement in it they came from
+    pub budget_sites: HashMap<usize, (PathBuf, FileRange)>,
+    // the name=value defines after -- in spwn build (just a name has no value)
+    pub defines: Vec<(String, Option<String>)>,
+    // see import_costs.rs
+    pub import_costs: crate::import_costs::ImportCosts,
+    // every file that was imported (canonical), for --watch
+    pub imported_files: HashSet<PathBuf>,
+    // $.build_id, once it's been made
+    pub build_id: Option<String>,
+    // how many try blocks the code being compiled is in (a throw outside of them stops
+    // the build like it always has)
+    pub try_depth: usize,
+    // library folders from [dependencies] in spwn.toml, by the name they're imported as
+    pub dependencies: HashMap<String, PathBuf>,
+
+    // only used by `spwn build --cache-macros`
+    pub macro_cache: Option<MacroCache>,
+    // .spwn-cache/, in spwn build (unless it's built with --no-cache)
+    pub import_cache: Option<crate::import_cache::ImportCache>,
+
+    // only used by `spwn expand`
+    pub trace: Option<Trace>,
+    // only used by `spwn build --record-trace`
+    pub history: Option<History>,
+    // see analyzer.rs
+    pub analyzers: Vec<Box<dyn crate::analyzer::Analyzer>>,
+    // on for the scope compiled next (the script's top level), where failing statements
+    // are skipped so more than one error can be shown
+    pub collect_errors: bool,
+    // the errors after the one the build stopped with
+    pub errors: Vec<RuntimeError>,
 }
 
 impl Globals {
+    // remembers which part of the script the object came from
+    pub fn record_origin(&mut self, unique_id: usize, info: &CompilerInfo) {
+        let origin = if self.lib_files.contains(&info.current_file) {
+            info.origin.clone()
+        } else {
+            (info.current_file.clone(), info.pos)
+        };
+        self.object_origins.insert(unique_id, origin);
+        crate::import_costs::object_added(&info.current_file, self);
+        if let Some((file, _, site)) = &info.budget {
+            self.budget_sites.insert(unique_id, (file.clone(), *site));
+        }
+    }
+
     pub fn get_val_fn_context(
         &self,
         p: StoredValue,
         info: CompilerInfo,
     ) -> Result<Group, RuntimeError> {
-        match self.stored_values.map.get(&p) {
+        match self.stored_values.get(p) {
             Some(val) => Ok(val.fn_context),
             None => Err(RuntimeError::RuntimeError {
                 message: "Pointer points to no data!".to_string(),
@@ -57,7 +142,7 @@ impl Globals {
         }
     }
     pub fn is_mutable(&self, p: StoredValue) -> bool {
-        match self.stored_values.map.get(&p) {
+        match self.stored_values.get(p) {
             Some(val) => val.mutable,
             None => unreachable!(),
         }
@@ -67,6 +152,46 @@ impl Globals {
         self.is_mutable(p)
     }
 
+    // freezes the value and everything in it (or unfreezes it with None)
+    pub fn set_frozen(&mut self, p: StoredValue, site: Option<(PathBuf, FileRange)>) {
+        let inner: Vec<StoredValue> = match &self.stored_values[p] {
+            Value::Array(a) => a.clone(),
+            Value::Dict(d) => d.values().copied().collect(),
+            _ => Vec::new(),
+        };
+        for v in inner {
+            self.set_frozen(v, site.clone());
+        }
+        // macros can't be made mutable
+        if site.is_some() || !matches!(self.stored_values[p], Value::Macro(_)) {
+            self.stored_values.get_mut(p).unwrap().mutable = site.is_none();
+        }
+        match site {
+            Some(site) => self.frozen.insert(p, site),
+            None => self.frozen.remove(&p),
+        };
+    }
+
+    // a copy of a frozen value is frozen too
+    pub fn copy_frozen(&mut self, from: StoredValue, to: StoredValue) {
+        if let Some(site) = self.frozen.get(&from).cloned() {
+            self.set_frozen(to, Some(site));
+        }
+    }
+
+    // to add to the error when a frozen value is changed
+    pub fn frozen_note(&self, p: StoredValue) -> String {
+        match self.frozen.get(&p) {
+            Some((file, pos)) => format!(
+                " (it was frozen with $.freeze at {}:{}:{})",
+                crate::compiler::display_path(file),
+                pos.0 .0,
+                pos.0 .1 + 1
+            ),
+            None => String::new(),
+        }
+    }
+
     // pub fn get_fn_context(&self, p: StoredValue) -> Group {
     //     match self.stored_values.map.get(&p) {
     //         Some(val) => val.fn_context,
@@ -75,7 +200,7 @@ impl Globals {
     // }
 
     pub fn get_lifetime(&self, p: StoredValue) -> u16 {
-        match self.stored_values.map.get(&p) {
+        match self.stored_values.get(p) {
             Some(val) => val.lifetime,
             None => unreachable!(),
         }
@@ -106,6 +231,7 @@ impl Globals {
             closed_colors: 0,
             closed_blocks: 0,
             closed_items: 0,
+            project_root: path.parent().map(|p| p.to_path_buf()).unwrap_or_default(),
             path,
 
             lowest_y: HashMap::new(),
@@ -117,7 +243,7 @@ impl Globals {
             trigger_order: 0,
             uid_counter: 0,
 
-            val_id: storage.map.len(),
+            val_id: storage.len(),
             stored_values: storage,
             func_ids: vec![FunctionId {
                 parent: None,
@@ -130,6 +256,34 @@ impl Globals {
                 parts: vec![0],
                 groups_used: Vec::new(),
             }],
+            warnings: Vec::new(),
+            lints: Vec::new(),
+            fps: crate::timing::DEFAULT_FPS,
+            release: false,
+            printed: false,
+            output: None,
+            print_limits: crate::value::PrintLimits::new(),
+            frozen: HashMap::new(),
+            timeout: None,
+            interactive: true,
+            aliases: None,
+            import_stack: Vec::new(),
+            lib_files: HashSet::new(),
+            object_origins: HashMap::new(),
+            budget_sites: HashMap::new(),
+            defines: Vec::new(),
+            imported_files: HashSet::new(),
+            build_id: None,
+            try_depth: 0,
+            import_costs: Default::default(),
+            dependencies: HashMap::new(),
+            macro_cache: None,
+            import_cache: None,
+            trace: None,
+            history: None,
+            analyzers: Vec::new(),
+            collect_errors: false,
+            errors: Vec::new(),
         };
 
         let mut add_type = |name: &str, id: u16| {
@@ -165,3 +319,14 @@ impl Globals {
         globals
     }
 }
+
+// everything the compiler keeps has to stay Send and Sync, so independent contexts
+// can be evaluated on different threads (this stops compiling if something like an
+// Rc or a raw pointer is added)
+#[allow(dead_code)]
+fn assert_thread_safe() {
+    fn check<T: Send + Sync>() {}
+    check::<Globals>();
+    check::<crate::context::Context>();
+    check::<Value>();
+}
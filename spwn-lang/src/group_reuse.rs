@@ -0,0 +1,306 @@
+// saves groups by giving one group to trigger functions that are live at the same
+// time. a group is live whenever a spawn trigger starts it, which for the groups that
+// are only used to start trigger functions is known: it's the trigger that started the
+// chain of spawns (the first one that isn't in one of these groups), and the spawn
+// delays on the way, in frames. two waits of the same length after the same trigger are
+// live at the same time, and so are `wait(1)` and `wait(0.5); wait(0.5)`.
+// a group can't be given to a function that's live at other times, even after the
+// first one is done with it, since spawning it would run the triggers of both, so
+// only groups with the same lifetime are put together
+use crate::builtin::{Group, Id};
+use crate::levelstring::{GdObj, ObjParam};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+const SPAWN: f64 = 1268.0;
+
+fn is_spawn(obj: &GdObj) -> bool {
+    obj.params.get(&1) == Some(&ObjParam::Number(SPAWN))
+}
+
+// what a spawn trigger does, apart from which group it spawns, where it is and its delay
+fn spawn_signature(obj: &GdObj) -> String {
+    let mut params: Vec<_> = obj
+        .params
+        .iter()
+        .filter(|(k, _)| ![2, 3, 51, 63].contains(*k))
+        .map(|(k, v)| format!("{}:{:?}", k, v))
+        .collect();
+    params.sort();
+    params.join(",")
+}
+
+fn ids(param: &ObjParam) -> Vec<String> {
+    match param {
+        ObjParam::Group(g) => vec![format!("{:?}", g)],
+        ObjParam::GroupList(l) => l.iter().map(|g| format!("{:?}", g)).collect(),
+        ObjParam::Color(c) => vec![format!("{:?}", c)],
+        ObjParam::Block(b) => vec![format!("{:?}", b)],
+        ObjParam::Item(i) => vec![format!("{:?}", i)],
+        _ => Vec::new(),
+    }
+}
+
+// every time a group is spawned: what started it, and how many frames later
+type Lifetime = Vec<(String, i64)>;
+
+fn frames(obj: &GdObj) -> i64 {
+    match obj.params.get(&63) {
+        Some(ObjParam::Number(delay)) => (delay * crate::timing::DEFAULT_FPS).round() as i64,
+        _ => 0,
+    }
+}
+
+// None for the groups that start themselves again, which are live forever
+fn lifetime(
+    g: Group,
+    objects: &[GdObj],
+    spawned_by: &HashMap<Group, Vec<usize>>,
+    plumbing: &HashMap<Group, bool>,
+    known: &mut HashMap<Group, Option<Lifetime>>,
+    visiting: &mut HashSet<Group>,
+) -> Option<Lifetime> {
+    if let Some(l) = known.get(&g) {
+        return l.clone();
+    }
+    if !visiting.insert(g) {
+        return None;
+    }
+    let mut live = Some(Vec::new());
+    for i in spawned_by.get(&g).into_iter().flatten() {
+        let obj = &objects[*i];
+        let parent = match obj.params.get(&57) {
+            Some(ObjParam::Group(p)) if plumbing.get(p) == Some(&true) => Some(*p),
+            _ => None,
+        };
+        let times = match parent {
+            Some(p) => lifetime(p, objects, spawned_by, plumbing, known, visiting).map(|l| {
+                l.into_iter()
+                    .map(|(start, after)| (start, after + frames(obj)))
+                    .collect()
+            }),
+            // the spawn trigger is what started it
+            None => Some(vec![(
+                match obj.params.get(&57) {
+                    Some(_) => spawn_signature(obj),
+                    // it's started by the player getting to it
+                    None => format!("object {}", obj.unique_id),
+                },
+                frames(obj),
+            )]),
+        };
+        live = match (live, times) {
+            (Some(mut l), Some(t)) => {
+                l.extend(t);
+                Some(l)
+            }
+            _ => None,
+        };
+    }
+    visiting.remove(&g);
+    if let Some(l) = &mut live {
+        l.sort();
+    }
+    known.insert(g, live.clone());
+    live
+}
+
+// gives back the spawn triggers that were removed (by unique id, with why)
+pub fn merge_spawned_together(objects: &mut Vec<GdObj>) -> Vec<(usize, String)> {
+    // the groups that are only used to start trigger functions
+    let mut plumbing: HashMap<Group, bool> = HashMap::new();
+    for obj in objects.iter() {
+        for (key, param) in &obj.params {
+            match param {
+                ObjParam::Group(g) if matches!(g.id, Id::Arbitrary(_)) => {
+                    let only_starts = obj.mode == crate::ast::ObjectMode::Trigger
+                        && (*key == 57 || (*key == 51 && is_spawn(obj)));
+                    let entry = plumbing.entry(*g).or_insert(true);
+                    *entry &= only_starts;
+                }
+                ObjParam::GroupList(l) => {
+                    for g in l {
+                        plumbing.insert(*g, false);
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+
+    let mut spawned_by: HashMap<Group, Vec<usize>> = HashMap::new();
+    for (i, obj) in objects.iter().enumerate().filter(|(_, o)| is_spawn(o)) {
+        if let Some(ObjParam::Group(g)) = obj.params.get(&51) {
+            spawned_by.entry(*g).or_default().push(i);
+        }
+    }
+
+    // the groups that are live at the same time
+    let mut alike: BTreeMap<Lifetime, Vec<Group>> = BTreeMap::new();
+    let mut known = HashMap::new();
+    for (g, _) in plumbing.iter().filter(|(_, p)| **p) {
+        if let Some(l) = lifetime(
+            *g,
+            objects,
+            &spawned_by,
+            &plumbing,
+            &mut known,
+            &mut HashSet::new(),
+        ) {
+            if !l.is_empty() {
+                alike.entry(l).or_default().push(*g);
+            }
+        }
+    }
+
+    // what the triggers of each group use, since triggers in one group run in the
+    // order they're placed, which would only matter if they use the same things
+    let mut uses: HashMap<Group, HashSet<String>> = HashMap::new();
+    for obj in objects.iter() {
+        if let Some(ObjParam::Group(g)) = obj.params.get(&57) {
+            let used = uses.entry(*g).or_default();
+            for (key, param) in &obj.params {
+                if *key != 57 {
+                    used.extend(ids(param));
+                }
+            }
+        }
+    }
+
+    let mut replace: HashMap<Group, Group> = HashMap::new();
+    for mut groups in alike.into_values() {
+        groups.sort();
+        let mut kept: Vec<(Group, HashSet<String>)> = Vec::new();
+        for g in groups {
+            let used = uses.remove(&g).unwrap_or_default();
+            match kept.iter_mut().find(|(_, u)| u.is_disjoint(&used)) {
+                Some((into, u)) => {
+                    u.extend(used);
+                    replace.insert(g, *into);
+                }
+                None => kept.push((g, used)),
+            }
+        }
+    }
+
+    let mut remarks = Vec::new();
+    objects.retain(|obj| match obj.params.get(&51) {
+        Some(ObjParam::Group(g)) if is_spawn(obj) && replace.contains_key(g) => {
+            remarks.push((
+                obj.unique_id,
+                "removed: it spawned a group that is always live at the same time as another one, so their triggers were put in one group".to_string(),
+            ));
+            false
+        }
+        _ => true,
+    });
+    for obj in objects.iter_mut() {
+        if let Some(ObjParam::Group(g)) = obj.params.get_mut(&57) {
+            if let Some(into) = replace.get(g) {
+                *g = *into;
+            }
+        }
+    }
+    remarks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::ObjectMode;
+
+    fn arbitrary(id: u16) -> Group {
+        Group {
+            id: Id::Arbitrary(id),
+        }
+    }
+
+    // the level's own group, which starts everything here
+    fn start() -> Group {
+        Group {
+            id: Id::Specific(1),
+        }
+    }
+
+    fn spawn(unique_id: usize, group: Group, target: u16, delay: f64) -> GdObj {
+        GdObj::for_test(
+            unique_id,
+            ObjectMode::Trigger,
+            &[
+                (57, ObjParam::Group(group)),
+                (1, ObjParam::Number(SPAWN)),
+                (51, ObjParam::Group(arbitrary(target))),
+                (63, ObjParam::Number(delay)),
+            ],
+        )
+    }
+
+    // a move trigger in the group (each one moves something else)
+    fn work(unique_id: usize, group: u16) -> GdObj {
+        GdObj::for_test(
+            unique_id,
+            ObjectMode::Trigger,
+            &[
+                (57, ObjParam::Group(arbitrary(group))),
+                (1, ObjParam::Number(901.0)),
+                (
+                    51,
+                    ObjParam::Group(Group {
+                        id: Id::Specific(unique_id as u16),
+                    }),
+                ),
+            ],
+        )
+    }
+
+    // the group each move trigger ends up in
+    fn groups_of_work(objects: &[GdObj]) -> Vec<(usize, Group)> {
+        let mut groups: Vec<_> = objects
+            .iter()
+            .filter(|o| o.params.get(&1) == Some(&ObjParam::Number(901.0)))
+            .filter_map(|o| match o.params.get(&57) {
+                Some(ObjParam::Group(g)) => Some((o.unique_id, *g)),
+                _ => None,
+            })
+            .collect();
+        groups.sort();
+        groups
+    }
+
+    #[test]
+    fn groups_live_at_the_same_time_are_shared() {
+        // wait(1) and wait(0.5); wait(0.5) after the same trigger
+        let mut objects = vec![
+            spawn(1, start(), 1, 1.0),
+            spawn(2, start(), 2, 0.5),
+            spawn(3, arbitrary(2), 3, 0.5),
+            work(10, 1),
+            work(11, 3),
+        ];
+        let removed = merge_spawned_together(&mut objects);
+        assert_eq!(removed.iter().map(|(id, _)| *id).collect::<Vec<_>>(), [3]);
+        assert_eq!(
+            groups_of_work(&objects),
+            [(10, arbitrary(1)), (11, arbitrary(1))]
+        );
+    }
+
+    #[test]
+    fn groups_live_at_other_times_keep_their_own() {
+        let mut objects = vec![
+            // overlapping: 4g runs right away and a second later, 5g only right away
+            spawn(1, start(), 4, 0.0),
+            spawn(2, start(), 4, 1.0),
+            spawn(3, start(), 5, 0.0),
+            // not overlapping: 6g runs after 2 seconds, 7g after 3
+            spawn(4, start(), 6, 2.0),
+            spawn(5, start(), 7, 3.0),
+            work(10, 4),
+            work(11, 5),
+            work(12, 6),
+            work(13, 7),
+        ];
+        let before = groups_of_work(&objects);
+        assert!(merge_spawned_together(&mut objects).is_empty());
+        assert_eq!(groups_of_work(&objects), before);
+    }
+}
@@ -0,0 +1,48 @@
+// explains why a build added nothing (and printed nothing), which usually
+// means the wrong file was built, like a library instead of the script using it
+use crate::ast::*;
+
+fn is_macro(var: &Variable) -> bool {
+    var.path.is_empty() && matches!(var.value.body, ValueBody::Macro(_))
+}
+
+// the value in `name = value`, if it doesn't call anything
+fn definition(e: &Expression) -> Option<&Variable> {
+    if e.operators != [Operator::Assign] {
+        return None;
+    }
+    let calls = e.values[1].path.iter().any(|p| matches!(p, Path::Call(_)));
+    if calls {
+        None
+    } else {
+        Some(&e.values[1])
+    }
+}
+
+pub fn empty_output(statements: &[Statement]) -> Option<String> {
+    if statements.is_empty() {
+        return Some("the script is empty".to_string());
+    }
+
+    let mut macros = 0;
+    for statement in statements {
+        match &statement.body {
+            StatementBody::Expr(e) => match definition(e) {
+                Some(value) => {
+                    if is_macro(value) {
+                        macros += 1;
+                    }
+                }
+                None => return None,
+            },
+            StatementBody::Impl(_) => macros += 1,
+            StatementBody::TypeDef(_) | StatementBody::Extract(_) => (),
+            _ => return None,
+        }
+    }
+    if macros > 0 {
+        Some("the script only defines macros (and types), and never calls them. Call them here, or build the script that uses them".to_string())
+    } else {
+        None
+    }
+}
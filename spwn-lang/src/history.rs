@@ -0,0 +1,452 @@
+// records the value of every variable in each context, statement by statement
+// (spwn build --record-trace), so it can be looked up afterwards (spwn trace query)
+use crate::ast;
+use crate::builtin::*;
+use crate::compiler_info::CompilerInfo;
+use crate::context::Context;
+use crate::fmt::SpwnFmt;
+use crate::globals::Globals;
+use crate::levelstring::GdObj;
+use crate::trace::obj_str;
+use crate::value::Value;
+use crate::value_storage::StoredValue;
+
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+const HEADER: &str = "# spwn trace";
+
+enum Event {
+    Statement {
+        step: usize,
+        location: String,
+        text: String,
+    },
+    Variable {
+        step: usize,
+        context: String,
+        name: String,
+        value: String,
+    },
+    Object {
+        step: usize,
+        context: String,
+        unique_id: usize,
+        // what the object looked like before ids were given out
+        compiled: String,
+    },
+}
+
+#[derive(Default)]
+pub struct History {
+    events: Vec<Event>,
+    step: usize,
+    // the last value recorded for each (context, variable)
+    last: HashMap<(String, String), (StoredValue, String)>,
+    // values that are already recorded in some context (variable, value)
+    seen: HashSet<(String, String)>,
+    // objects up to these are already recorded
+    reported_order: usize,
+    reported_objects: usize,
+}
+
+impl History {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn write(&self, path: &Path, final_objects: Option<&[GdObj]>) -> std::io::Result<()> {
+        let mut by_id = HashMap::<usize, Vec<&GdObj>>::new();
+        for obj in final_objects.unwrap_or(&[]) {
+            by_id.entry(obj.unique_id).or_default().push(obj);
+        }
+
+        let mut out = format!("{}\n", HEADER);
+        for event in &self.events {
+            out += &match event {
+                Event::Statement {
+                    step,
+                    location,
+                    text,
+                } => format!("S\t{}\t{}\t{}\n", step, location, escape(text)),
+                Event::Variable {
+                    step,
+                    context,
+                    name,
+                    value,
+                } => format!("V\t{}\t{}\t{}\t{}\n", step, context, name, escape(value)),
+                Event::Object {
+                    step,
+                    context,
+                    unique_id,
+                    compiled,
+                } => {
+                    let text = match (final_objects, by_id.get(unique_id)) {
+                        (None, _) => compiled.clone(),
+                        (Some(_), Some(list)) => list
+                            .iter()
+                            .map(|obj| obj_str(obj))
+                            .collect::<Vec<_>>()
+                            .join("; "),
+                        (Some(_), None) => format!("{} (removed by the optimizer)", compiled),
+                    };
+                    format!("O\t{}\t{}\t{}\n", step, context, escape(&text))
+                }
+            };
+        }
+        std::fs::write(path, out)
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+}
+
+fn unescape(text: &str) -> String {
+    let mut out = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some('t') => out.push('\t'),
+                Some('n') => out.push('\n'),
+                Some(c) => out.push(c),
+                None => out.push('\\'),
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+fn group_str(group: &Group) -> String {
+    match group.id {
+        Id::Specific(n) => format!("{}g", n),
+        Id::Arbitrary(n) => format!("?{}g", n),
+    }
+}
+
+// contexts are named after the group that starts them
+// (with a number added if several contexts share it)
+fn context_names(contexts: &[Context]) -> Vec<String> {
+    contexts
+        .iter()
+        .enumerate()
+        .map(|(i, c)| {
+            let name = group_str(&c.start_group);
+            let same = contexts
+                .iter()
+                .filter(|other| other.start_group == c.start_group)
+                .count();
+            if same > 1 {
+                let index = contexts[..i]
+                    .iter()
+                    .filter(|other| other.start_group == c.start_group)
+                    .count();
+                format!("{}#{}", name, index + 1)
+            } else {
+                name
+            }
+        })
+        .collect()
+}
+
+pub fn record_statement(
+    statement: &ast::Statement,
+    contexts: &[Context],
+    info: &CompilerInfo,
+    globals: &mut Globals,
+) {
+    // only the script's own statements are recorded, objects added by
+    // libraries are recorded with the statement that used the library
+    if globals.history.is_none() || globals.lib_files.contains(&info.current_file) {
+        return;
+    }
+    let mut history = globals.history.take().unwrap();
+    history.step += 1;
+    let step = history.step;
+
+    let file = info
+        .current_file
+        .file_name()
+        .map(|f| f.to_string_lossy().to_string())
+        .unwrap_or_default();
+    history.events.push(Event::Statement {
+        step,
+        location: format!("{}:{}:{}", file, statement.pos.0 .0, statement.pos.0 .1 + 1),
+        text: statement
+            .body
+            .fmt(0)
+            .lines()
+            .next()
+            .unwrap_or("")
+            .to_string(),
+    });
+
+    let names = context_names(contexts);
+    for (context, context_name) in contexts.iter().zip(&names) {
+        let mut variables: Vec<_> = context.variables.iter().collect();
+        variables.sort();
+        for (name, ptr) in variables {
+            let data = match globals.stored_values.get(*ptr) {
+                Some(d) => d,
+                None => continue,
+            };
+            if let Value::Macro(_)
+            | Value::Builtins
+            | Value::BuiltinFunction(_)
+            | Value::TypeIndicator(_) = data.val
+            {
+                continue;
+            }
+            let key = (context_name.clone(), name.clone());
+            if let Some((last_ptr, _)) = history.last.get(&key) {
+                // constant values can't have changed
+                if last_ptr == ptr && !data.mutable {
+                    continue;
+                }
+            }
+            let value = data.val.to_str(globals);
+            // new contexts get the variables of the context they came from,
+            // there's no need to record them again
+            if !history.last.contains_key(&key)
+                && history.seen.contains(&(name.clone(), value.clone()))
+            {
+                history.last.insert(key, (*ptr, value));
+                continue;
+            }
+            if let Some((_, last_value)) = history.last.get(&key) {
+                if *last_value == value {
+                    history.last.insert(key, (*ptr, value));
+                    continue;
+                }
+            }
+            history.events.push(Event::Variable {
+                step,
+                context: context_name.clone(),
+                name: name.clone(),
+                value: value.clone(),
+            });
+            history.seen.insert((name.clone(), value.clone()));
+            history.last.insert(key, (*ptr, value));
+        }
+    }
+
+    let context_of = |func_id: usize| {
+        contexts
+            .iter()
+            .position(|c| c.func_id == func_id)
+            .map(|i| names[i].clone())
+            .unwrap_or_else(|| "?".to_string())
+    };
+    let mut new_objects = Vec::<(usize, &GdObj)>::new();
+    for func in &globals.func_ids {
+        for (obj, order) in &func.obj_list {
+            if *order > history.reported_order {
+                new_objects.push((*order, obj));
+            }
+        }
+    }
+    new_objects.sort_by_key(|(order, _)| *order);
+    let new_objects = new_objects
+        .into_iter()
+        .map(|(_, obj)| obj)
+        .chain(globals.objects[history.reported_objects..].iter());
+    for obj in new_objects {
+        history.events.push(Event::Object {
+            step,
+            context: context_of(obj.func_id),
+            unique_id: obj.unique_id,
+            compiled: obj_str(obj),
+        });
+    }
+    history.reported_order = globals.trigger_order;
+    history.reported_objects = globals.objects.len();
+
+    globals.history = Some(history);
+}
+
+// a trace file, read back in
+struct Recorded {
+    // step -> (location, statement)
+    statements: HashMap<usize, (String, String)>,
+    // (step, context, name, value)
+    variables: Vec<(usize, String, String, String)>,
+    // (step, context, object)
+    objects: Vec<(usize, String, String)>,
+}
+
+fn read_trace(path: &Path) -> Result<Recorded, String> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| format!("Couldn't read {}: {}", path.to_string_lossy(), e))?;
+    let mut lines = content.lines();
+    if lines.next() != Some(HEADER) {
+        return Err(format!(
+            "{} is not a spwn trace file",
+            path.to_string_lossy()
+        ));
+    }
+    let mut recorded = Recorded {
+        statements: HashMap::new(),
+        variables: Vec::new(),
+        objects: Vec::new(),
+    };
+    for (i, line) in lines.enumerate() {
+        let fields: Vec<&str> = line.split('\t').collect();
+        let step = fields
+            .get(1)
+            .and_then(|s| s.parse::<usize>().ok())
+            .ok_or_else(|| format!("Invalid trace file (line {})", i + 2))?;
+        match (fields[0], fields.len()) {
+            ("S", 4) => {
+                recorded
+                    .statements
+                    .insert(step, (fields[2].to_string(), unescape(fields[3])));
+            }
+            ("V", 5) => recorded.variables.push((
+                step,
+                fields[2].to_string(),
+                fields[3].to_string(),
+                unescape(fields[4]),
+            )),
+            ("O", 4) => recorded
+                .objects
+                .push((step, fields[2].to_string(), unescape(fields[3]))),
+            _ => return Err(format!("Invalid trace file (line {})", i + 2)),
+        }
+    }
+    Ok(recorded)
+}
+
+// whether the object text uses the given id (like "52g"), as opposed to "?52g" or "152g"
+fn mentions(text: &str, id: &str) -> bool {
+    text.split(|c: char| !(c.is_alphanumeric() || c == '?'))
+        .any(|token| token == id)
+}
+
+pub enum Moment {
+    // the first object that uses this group
+    Group(u16),
+    // the first statement at this line (file:line)
+    Line(String),
+    Step(usize),
+}
+
+// answers "what was `variable` at this moment", or gives its whole history if there's no moment
+pub fn query(path: &Path, variable: &str, moment: Option<Moment>) -> Result<String, String> {
+    let recorded = read_trace(path)?;
+    let location = |step: usize| {
+        recorded
+            .statements
+            .get(&step)
+            .map(|(loc, _)| loc.clone())
+            .unwrap_or_default()
+    };
+    let history: Vec<_> = recorded
+        .variables
+        .iter()
+        .filter(|(_, _, name, _)| name == variable)
+        .collect();
+
+    let mut out = String::new();
+    let (step, context) = match moment {
+        None => {
+            if history.is_empty() {
+                return Err(format!("No variable called {} was recorded", variable));
+            }
+            for (step, context, _, value) in &history {
+                out += &format!(
+                    "step {:<5} {:<24} [{}] {} = {}\n",
+                    step,
+                    location(*step),
+                    context,
+                    variable,
+                    value
+                );
+            }
+            return Ok(out);
+        }
+        Some(Moment::Group(id)) => {
+            let id = format!("{}g", id);
+            match recorded
+                .objects
+                .iter()
+                .find(|(_, _, obj)| mentions(obj, &id))
+            {
+                Some((step, context, obj)) => {
+                    out += &format!(
+                        "group {} was first used at step {} ({}), in context {}:\n  {}\n",
+                        id,
+                        step,
+                        location(*step),
+                        context,
+                        obj
+                    );
+                    (*step, Some(context.clone()))
+                }
+                None => return Err(format!("No recorded object uses group {}", id)),
+            }
+        }
+        Some(Moment::Line(line)) => {
+            let prefix = format!("{}:", line);
+            let mut steps: Vec<_> = recorded
+                .statements
+                .iter()
+                .filter(|(_, (loc, _))| loc.starts_with(&prefix))
+                .map(|(step, _)| *step)
+                .collect();
+            steps.sort_unstable();
+            match steps.first() {
+                Some(step) => {
+                    out += &format!("after step {} ({}):\n", step, location(*step));
+                    (*step, None)
+                }
+                None => return Err(format!("No statement was recorded at {}", line)),
+            }
+        }
+        Some(Moment::Step(step)) => {
+            out += &format!("after step {} ({}):\n", step, location(step));
+            (step, None)
+        }
+    };
+
+    // the last value of the variable up to that step, in each context
+    let values_in = |context: Option<&String>| {
+        let mut values = Vec::<(&str, &str, usize)>::new();
+        for (s, c, _, value) in &history {
+            if *s > step {
+                break;
+            }
+            if matches!(context, Some(context) if c != context) {
+                continue;
+            }
+            match values.iter_mut().find(|(other, _, _)| *other == c) {
+                Some(v) => *v = (c, value, *s),
+                None => values.push((c, value, *s)),
+            }
+        }
+        values
+    };
+    let mut values = values_in(context.as_ref());
+    if values.is_empty() && context.is_some() {
+        // the variable might only be defined in the context that made this one
+        values = values_in(None);
+    }
+    if values.is_empty() {
+        out += &format!("  {} wasn't defined yet\n", variable);
+    }
+    for (context, value, set_at) in values {
+        out += &format!(
+            "  [{}] {} = {}   (set at step {}, {})\n",
+            context,
+            variable,
+            value,
+            set_at,
+            location(set_at)
+        );
+    }
+    Ok(out)
+}
@@ -1,5 +1,5 @@
 use crate::builtin::{Group, Id, Item};
-use crate::levelstring::ObjParam;
+use crate::levelstring::{GdObj, ObjParam};
 use crate::optimize::*;
 use cached::proc_macro::cached;
 use std::cmp::{max, min, Ordering};
@@ -1513,86 +1513,132 @@ mod connection_combiner {
     // }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+fn item_str(item: Item) -> String {
+    match item.id {
+        Id::Specific(n) => format!("{}i", n),
+        Id::Arbitrary(n) => format!("?{}i", n),
+    }
+}
 
-    #[test]
-    fn ic_expr_simplify() {
-        use crate::builtin::Id::*;
-        let _a = Item {
-            id: Id::Specific(1),
-        };
-        let _b = Item {
-            id: Id::Specific(2),
-        };
-        // let c = Item {
-        //     id: Id::Specific(3),
-        // };
-        use IcExpr::{Equals, LessThan, MoreThan};
-        fn Or(e1: IcExpr, e2: IcExpr) -> IcExpr {
-            IcExpr::Or(e1.into(), e2.into())
+fn is_pickup(params: &HashMap<u16, ObjParam>) -> bool {
+    params.get(&1) == Some(&ObjParam::Number(1817.0))
+}
+
+// the items that something other than a pickup trigger uses, like a count trigger (which
+// goes off when the item goes past its number) or an item display
+fn read_items<'a>(params: impl Iterator<Item = &'a HashMap<u16, ObjParam>>) -> HashSet<Item> {
+    let mut reads = HashSet::new();
+    for params in params.filter(|p| !is_pickup(p)) {
+        for param in params.values() {
+            if let ObjParam::Item(item) = param {
+                reads.insert(*item);
+            }
         }
-        fn And(e1: IcExpr, e2: IcExpr) -> IcExpr {
-            IcExpr::And(e1.into(), e2.into())
+    }
+    reads
+}
+
+// pickup triggers in the same gang are all activated at once (in the same context), so
+// if nothing in the level reads the item, the pickups that add to it can be merged into
+// one, or removed entirely if they cancel each other out. a reader anywhere counts, since
+// it can see the item in between the pickups
+pub fn remove_dead_stores(
+    network: &mut TriggerNetwork,
+    objects: &mut Triggerlist,
+    level_objects: &[GdObj],
+) {
+    let reads = read_items(
+        network
+            .values()
+            .flat_map(|gang| gang.triggers.iter())
+            .filter(|t| !t.deleted)
+            .map(|t| &objects[t.obj].0.params)
+            .chain(level_objects.iter().map(|obj| &obj.params)),
+    );
+
+    for gang in network.values_mut() {
+        // (item, the pickup triggers for it)
+        let mut stores = Vec::<(Item, Vec<usize>)>::new();
+
+        for (i, trigger) in gang.triggers.iter().enumerate() {
+            if trigger.deleted {
+                continue;
+            }
+            let params = &objects[trigger.obj].0.params;
+            if !is_pickup(params) {
+                continue;
+            }
+            let item = match params.get(&80) {
+                Some(ObjParam::Item(item)) => *item,
+                _ => continue,
+            };
+            // only pickups that are the same except for the amount are merged
+            let same = |other: &HashMap<u16, ObjParam>| {
+                other.len() == params.len()
+                    && params
+                        .iter()
+                        .all(|(key, val)| *key == 77 || other.get(key) == Some(val))
+            };
+            match stores.iter_mut().find(|(it, list)| {
+                *it == item && same(&objects[gang.triggers[list[0]].obj].0.params)
+            }) {
+                Some((_, list)) => list.push(i),
+                None => stores.push((item, vec![i])),
+            }
         }
 
-        let expr = Or(
-            And(
-                And(
-                    And(
-                        And(
-                            Equals(Item { id: Arbitrary(29) }, 0),
-                            LessThan(Item { id: Specific(56) }, 3),
+        for (item, mut list) in stores {
+            if reads.contains(&item) {
+                continue;
+            }
+            let total: f64 = list
+                .iter()
+                .map(|i| match objects[gang.triggers[*i].obj].0.params.get(&77) {
+                    Some(ObjParam::Number(n)) => *n,
+                    _ => 0.0,
+                })
+                .sum();
+
+            list.sort_by_key(|i| gang.triggers[*i].order);
+            let first = list[0];
+            if total == 0.0 {
+                // cancels out
+                gang.triggers[first].deleted = true;
+                for i in &list {
+                    objects.remark(
+                        gang.triggers[*i],
+                        format!(
+                            "removed: the pickups for item {} in this context add up to 0",
+                            item_str(item)
                         ),
-                        MoreThan(Item { id: Arbitrary(27) }, 0),
-                    ),
-                    Equals(Item { id: Arbitrary(20) }, 1),
-                ),
-                Equals(Item { id: Specific(54) }, 0),
-            ),
-            And(
-                And(
-                    And(
-                        And(
-                            Equals(Item { id: Arbitrary(29) }, 0),
-                            LessThan(Item { id: Specific(56) }, 3),
+                    );
+                }
+            } else if list.len() > 1 {
+                objects[gang.triggers[first].obj]
+                    .0
+                    .params
+                    .insert(77, ObjParam::Number(total));
+                for i in &list {
+                    objects.remark(
+                        gang.triggers[*i],
+                        format!(
+                            "merged {} pickups for item {} in this context into one",
+                            list.len(),
+                            item_str(item)
                         ),
-                        LessThan(Item { id: Arbitrary(27) }, 0),
-                    ),
-                    Equals(Item { id: Arbitrary(20) }, 1),
-                ),
-                Equals(Item { id: Specific(54) }, 0),
-            ),
-        );
-
-        println!("len: {:?}\n", get_solve_complexity(&expr));
-
-        /*
-        duplicates removed:
-        Or(And(MoreThan(B, 2), Equals(C, 2)), And(LessThan(C, 2), MoreThan(B, 2)))
-
-        ands decreased: And(MoreThan(B, 2), Or(Equals(C, 2), LessThan(C, 2)))
-
-        simplified: Some(And(MoreThan(B, 2), LessThan(C, 3)))
-
-        ((B > 2) && (C == 2)) || ((B > 2) && (C < 2))
-
-        (B > 2) && ((C == 2) || (C < 2))
-
-        (B > 2) && (C < 3)
-
-        thats pretty epic
-
-        */
-
-        //println!("simplified: {:?}\n", simplify_ic_expr_full(expr));
+                    );
+                }
+            }
+            for i in &list[1..] {
+                gang.triggers[*i].deleted = true;
+            }
+        }
     }
 }
 
 pub fn get_all_ic_connections(
     triggers: &mut TriggerNetwork,
-    objects: &Triggerlist,
+    objects: &mut Triggerlist,
 ) -> Vec<(Group, Group, IcExpr, Trigger)> {
     let mut ictriggers = HashMap::<Group, Vec<(Group, IcExpr, Trigger)>>::new();
     let mut inputs = HashSet::<Group>::new();
@@ -1810,6 +1856,7 @@ pub fn get_all_ic_connections(
         out
     }
 
+    let entry_points = inputs.clone();
     for start in inputs {
         //println!("<{:?}>", start);
         all.extend(traverse(
@@ -1866,6 +1913,8 @@ pub fn get_all_ic_connections(
 
     //println!("finished simplifying");
 
+    reuse_known_conditions(&mut finished_expressions, &entry_points, triggers, objects);
+
     let out = finished_expressions
         .iter()
         .map(|((start, end), (expr, trigger))| (*start, *end, expr.clone(), *trigger))
@@ -1875,6 +1924,143 @@ pub fn get_all_ic_connections(
     out
 }
 
+/*
+when a group is only ever reached through one condition, and nothing in it can change the
+items in that condition, the condition is still known when the group's own instant count
+triggers read the items again:
+
+c.item.if_is(EQUAL_TO, 1, !{
+    10g.move(10, 0)
+    c.item.if_is(EQUAL_TO, 1, !{ // always true here
+        ...
+    })
+})
+
+so those reads can reuse the result instead of being built again
+*/
+// triggers that start the group they target
+const ACTIVATORS: [f64; 6] = [1268.0, 1611.0, 1811.0, 1595.0, 1815.0, 1812.0];
+
+// if a trigger in the group, or in a group it starts right away (through any trigger that
+// starts a group, without a delay), can change one of the items
+fn can_change(
+    start: Group,
+    variables: &HashSet<Item>,
+    network: &TriggerNetwork,
+    objects: &Triggerlist,
+) -> bool {
+    let mut seen = HashSet::new();
+    let mut stack = vec![start];
+    while let Some(group) = stack.pop() {
+        if !seen.insert(group) {
+            continue;
+        }
+        for t in network.get(&group).into_iter().flat_map(|g| &g.triggers) {
+            let params = &objects[t.obj].0.params;
+            let kind = match params.get(&1) {
+                Some(ObjParam::Number(n)) => *n,
+                _ => 0.0,
+            };
+            // count triggers only read them
+            let uses_item = [51, 80].iter().any(|key| match params.get(key) {
+                Some(ObjParam::Item(i)) => variables.contains(i),
+                _ => false,
+            });
+            if uses_item && kind != 1811.0 && kind != 1611.0 {
+                return true;
+            }
+            let delayed = matches!(params.get(&63), Some(ObjParam::Number(d)) if *d > 0.0);
+            if ACTIVATORS.contains(&kind) && !(kind == 1268.0 && delayed) {
+                if let Some(ObjParam::Group(target)) = params.get(&51) {
+                    stack.push(*target);
+                }
+            }
+        }
+    }
+    false
+}
+
+fn reuse_known_conditions(
+    connections: &mut HashMap<(Group, Group), (IcExpr, Trigger)>,
+    entry_points: &HashSet<Group>,
+    network: &TriggerNetwork,
+    objects: &mut Triggerlist,
+) {
+    let mut known = HashMap::<Group, IcExpr>::new();
+    for ((_, end), (expr, _)) in connections.iter() {
+        if entry_points.contains(end)
+            || *end
+                == (Group {
+                    id: Id::Specific(0),
+                })
+            || network[end].connections_in > 0
+        {
+            continue;
+        }
+        if connections
+            .keys()
+            .filter(|(_, e)| e == end)
+            .nth(1)
+            .is_some()
+        {
+            continue;
+        }
+        let variables = expr.get_variables();
+        // the group can't do anything that might change the items before they are read again
+        let untouched = network[end].triggers.iter().all(|t| {
+            t.role == TriggerRole::Output
+                || objects[t.obj].0.params.get(&1) == Some(&ObjParam::Number(1811.0))
+        }) && !can_change(*end, &variables, network, objects);
+        if untouched {
+            known.insert(*end, expr.clone());
+        }
+    }
+
+    for ((start, _), (expr, trigger)) in connections.iter_mut() {
+        let given = match known.get(start) {
+            Some(g) => g.clone(),
+            None => continue,
+        };
+        if given.get_variables().is_disjoint(&expr.get_variables()) {
+            continue;
+        }
+        if equal_behaviour(
+            IcExpr::And(given.clone().into(), expr.clone().into()),
+            IcExpr::False,
+        ) {
+            // can never happen
+            *expr = IcExpr::False;
+            objects.remark(
+                *trigger,
+                "removed: this condition is never true where it's checked".to_string(),
+            );
+            continue;
+        }
+        // leave out the parts of the expression that are already known to be true
+        let needed: Vec<IcExpr> = expr
+            .flatten_and()
+            .into_iter()
+            .filter(|part| {
+                !equal_behaviour(
+                    IcExpr::And(given.clone().into(), part.clone().into()),
+                    given.clone(),
+                )
+            })
+            .collect();
+        if needed.len() < expr.flatten_and().len() {
+            objects.remark(
+                *trigger,
+                "reused the result of an earlier check of the same counter".to_string(),
+            );
+        }
+        *expr = if needed.is_empty() {
+            IcExpr::True
+        } else {
+            IcExpr::stack_and(needed.into_iter())
+        };
+    }
+}
+
 fn overlap(mut expr1: IcExpr, mut expr2: IcExpr) -> IcExpr {
     use IcExpr::*;
     expr1 = simplify_ic_expr_fast(expr1);
@@ -2057,3 +2243,339 @@ fn simplify_ic_expr_fast(mut expr: IcExpr) -> IcExpr {
     //println!("\nend fast: {:?}", expr);
     expr
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::ObjectMode;
+    use crate::compiler_types::FunctionId;
+
+    #[test]
+    fn ic_expr_simplify() {
+        use crate::builtin::Id::*;
+        let _a = Item {
+            id: Id::Specific(1),
+        };
+        let _b = Item {
+            id: Id::Specific(2),
+        };
+        // let c = Item {
+        //     id: Id::Specific(3),
+        // };
+        use IcExpr::{Equals, LessThan, MoreThan};
+        fn Or(e1: IcExpr, e2: IcExpr) -> IcExpr {
+            IcExpr::Or(e1.into(), e2.into())
+        }
+        fn And(e1: IcExpr, e2: IcExpr) -> IcExpr {
+            IcExpr::And(e1.into(), e2.into())
+        }
+
+        let expr = Or(
+            And(
+                And(
+                    And(
+                        And(
+                            Equals(Item { id: Arbitrary(29) }, 0),
+                            LessThan(Item { id: Specific(56) }, 3),
+                        ),
+                        MoreThan(Item { id: Arbitrary(27) }, 0),
+                    ),
+                    Equals(Item { id: Arbitrary(20) }, 1),
+                ),
+                Equals(Item { id: Specific(54) }, 0),
+            ),
+            And(
+                And(
+                    And(
+                        And(
+                            Equals(Item { id: Arbitrary(29) }, 0),
+                            LessThan(Item { id: Specific(56) }, 3),
+                        ),
+                        LessThan(Item { id: Arbitrary(27) }, 0),
+                    ),
+                    Equals(Item { id: Arbitrary(20) }, 1),
+                ),
+                Equals(Item { id: Specific(54) }, 0),
+            ),
+        );
+
+        println!("len: {:?}\n", get_solve_complexity(&expr));
+
+        /*
+        duplicates removed:
+        Or(And(MoreThan(B, 2), Equals(C, 2)), And(LessThan(C, 2), MoreThan(B, 2)))
+
+        ands decreased: And(MoreThan(B, 2), Or(Equals(C, 2), LessThan(C, 2)))
+
+        simplified: Some(And(MoreThan(B, 2), LessThan(C, 3)))
+
+        ((B > 2) && (C == 2)) || ((B > 2) && (C < 2))
+
+        (B > 2) && ((C == 2) || (C < 2))
+
+        (B > 2) && (C < 3)
+
+        thats pretty epic
+
+        */
+
+        //println!("simplified: {:?}\n", simplify_ic_expr_full(expr));
+    }
+
+    fn item(n: u16) -> ObjParam {
+        ObjParam::Item(Item {
+            id: Id::Specific(n),
+        })
+    }
+
+    fn pickup(unique_id: usize, amount: f64) -> GdObj {
+        GdObj::for_test(
+            unique_id,
+            ObjectMode::Trigger,
+            &[
+                (1, ObjParam::Number(1817.0)),
+                (80, item(1)),
+                (77, ObjParam::Number(amount)),
+            ],
+        )
+    }
+
+    // the amounts of the pickups that are left after removing dead stores from the
+    // triggers (by group)
+    fn pickups_after(gangs: Vec<(u16, Vec<GdObj>)>, level_objects: &[GdObj]) -> Vec<f64> {
+        let mut func_ids = vec![FunctionId {
+            parent: None,
+            width: None,
+            obj_list: Vec::new(),
+        }];
+        let mut network = TriggerNetwork::new();
+        for (group, triggers) in gangs {
+            let mut gang = TriggerGang::new(Vec::new());
+            for obj in triggers {
+                gang.triggers.push(Trigger {
+                    obj: (0, func_ids[0].obj_list.len()),
+                    role: TriggerRole::Output,
+                    order: 0,
+                    deleted: false,
+                    optimized: false,
+                });
+                func_ids[0].obj_list.push((obj, 0));
+            }
+            network.insert(
+                Group {
+                    id: Id::Specific(group),
+                },
+                gang,
+            );
+        }
+
+        let mut objects = Triggerlist::new(&mut func_ids, 60.0);
+        remove_dead_stores(&mut network, &mut objects, level_objects);
+        let mut amounts: Vec<f64> = network
+            .values()
+            .flat_map(|gang| gang.triggers.iter())
+            .filter(|t| !t.deleted && is_pickup(&objects[t.obj].0.params))
+            .map(|t| match objects[t.obj].0.params.get(&77) {
+                Some(ObjParam::Number(n)) => *n,
+                _ => 0.0,
+            })
+            .collect();
+        amounts.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        amounts
+    }
+
+    #[test]
+    fn unread_pickups_are_merged() {
+        assert_eq!(
+            pickups_after(vec![(0, vec![pickup(0, 1.0), pickup(1, 2.0)])], &[]),
+            [3.0]
+        );
+        assert!(pickups_after(vec![(0, vec![pickup(0, 1.0), pickup(1, -1.0)])], &[]).is_empty());
+    }
+
+    #[test]
+    fn pickups_read_elsewhere_are_kept() {
+        // a count trigger in another group goes off when the item is 1
+        let count = GdObj::for_test(
+            2,
+            ObjectMode::Trigger,
+            &[
+                (1, ObjParam::Number(1611.0)),
+                (80, item(1)),
+                (77, ObjParam::Number(1.0)),
+            ],
+        );
+        assert_eq!(
+            pickups_after(
+                vec![(0, vec![pickup(0, 1.0), pickup(1, -1.0)]), (5, vec![count])],
+                &[]
+            ),
+            [-1.0, 1.0]
+        );
+        // an item display in the level
+        let display = GdObj::for_test(
+            3,
+            ObjectMode::Object,
+            &[(1, ObjParam::Number(1615.0)), (80, item(1))],
+        );
+        assert_eq!(
+            pickups_after(vec![(0, vec![pickup(0, 1.0), pickup(1, 2.0)])], &[display]),
+            [1.0, 2.0]
+        );
+    }
+
+    fn group(n: u16) -> Group {
+        Group {
+            id: Id::Specific(n),
+        }
+    }
+
+    fn equals(n: u16, value: i32) -> IcExpr {
+        IcExpr::Equals(
+            Item {
+                id: Id::Specific(n),
+            },
+            value,
+        )
+    }
+
+    // 1g checks `check` and goes to 2g, where `triggers` are, and then 2g checks each of
+    // `reads` again. `started` are the triggers of 5g. gives back what those checks are
+    // left as
+    fn reads_after(
+        check: IcExpr,
+        triggers: Vec<GdObj>,
+        started: Vec<GdObj>,
+        reads: Vec<IcExpr>,
+    ) -> Vec<IcExpr> {
+        let in_group = triggers.len();
+        let mut func_ids = vec![FunctionId {
+            parent: None,
+            width: None,
+            obj_list: triggers
+                .into_iter()
+                .chain(started)
+                .map(|o| (o, 0))
+                .collect(),
+        }];
+        // the instant count triggers the checks came from
+        func_ids[0].obj_list.push((
+            GdObj::for_test(100, ObjectMode::Trigger, &[(1, ObjParam::Number(1811.0))]),
+            0,
+        ));
+        let ic_trigger = Trigger {
+            obj: (0, func_ids[0].obj_list.len() - 1),
+            role: TriggerRole::Func,
+            order: 0,
+            deleted: false,
+            optimized: false,
+        };
+        let gang = |objects: std::ops::Range<usize>| {
+            TriggerGang::new(
+                objects
+                    .map(|i| Trigger {
+                        obj: (0, i),
+                        role: TriggerRole::Output,
+                        order: 0,
+                        deleted: false,
+                        optimized: false,
+                    })
+                    .collect(),
+            )
+        };
+        let mut network = TriggerNetwork::new();
+        network.insert(group(2), gang(0..in_group));
+        network.insert(group(5), gang(in_group..func_ids[0].obj_list.len() - 1));
+
+        let mut connections = HashMap::new();
+        connections.insert((group(1), group(2)), (check, ic_trigger));
+        for (i, read) in reads.iter().enumerate() {
+            connections.insert((group(2), group(10 + i as u16)), (read.clone(), ic_trigger));
+            network.insert(group(10 + i as u16), TriggerGang::new(Vec::new()));
+        }
+        let mut objects = Triggerlist::new(&mut func_ids, 60.0);
+        reuse_known_conditions(&mut connections, &HashSet::new(), &network, &mut objects);
+        (0..reads.len())
+            .map(|i| connections[&(group(2), group(10 + i as u16))].0.clone())
+            .collect()
+    }
+
+    fn move_trigger() -> GdObj {
+        GdObj::for_test(
+            0,
+            ObjectMode::Trigger,
+            &[
+                (1, ObjParam::Number(901.0)),
+                (51, ObjParam::Group(group(3))),
+                (28, ObjParam::Number(10.0)),
+            ],
+        )
+    }
+
+    #[test]
+    fn known_conditions_are_reused() {
+        let reads = vec![
+            equals(1, 1),
+            equals(1, 2),
+            IcExpr::And(equals(1, 1).into(), equals(2, 0).into()),
+        ];
+        assert_eq!(
+            reads_after(equals(1, 1), vec![move_trigger()], vec![], reads),
+            [IcExpr::True, IcExpr::False, equals(2, 0)]
+        );
+    }
+
+    #[test]
+    fn unknown_conditions_are_unchanged() {
+        let reads = vec![
+            equals(1, 1),
+            IcExpr::And(equals(1, 1).into(), equals(2, 0).into()),
+        ];
+        // a different item
+        assert_eq!(
+            reads_after(equals(3, 1), vec![move_trigger()], vec![], reads.clone()),
+            reads
+        );
+        // the group changes the item before reading it again
+        let pickup = GdObj::for_test(
+            1,
+            ObjectMode::Trigger,
+            &[
+                (1, ObjParam::Number(1817.0)),
+                (80, item(1)),
+                (77, ObjParam::Number(1.0)),
+            ],
+        );
+        assert_eq!(
+            reads_after(
+                equals(1, 1),
+                vec![move_trigger(), pickup.clone()],
+                vec![],
+                reads.clone()
+            ),
+            reads
+        );
+        // a group it starts right away changes the item (spawned without a delay, or
+        // started by another instant count trigger)
+        for kind in [1268.0, 1811.0].iter() {
+            let start = GdObj::for_test(
+                2,
+                ObjectMode::Trigger,
+                &[
+                    (1, ObjParam::Number(*kind)),
+                    (51, ObjParam::Group(group(5))),
+                    (63, ObjParam::Number(0.0)),
+                ],
+            );
+            assert_eq!(
+                reads_after(
+                    equals(1, 1),
+                    vec![move_trigger(), start],
+                    vec![pickup.clone()],
+                    reads.clone()
+                ),
+                reads
+            );
+        }
+    }
+}
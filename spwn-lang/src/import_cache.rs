@@ -0,0 +1,548 @@
+// .spwn-cache/ (next to the main script): the parsed files that were imported, and what
+// imports export, so the next build can load them instead of compiling them again.
+// entries are found by a hash of the compiler version, the file and the build's options,
+// and they're only used when the files the import imported are still found in the same
+// places with the same content. imports are only kept when compiling them just made
+// values, types and ids (no objects, triggers, printing, reading files or anything else
+// that can be different next time). spwn build --no-cache doesn't use it
+use crate::ast::{Attribute, Statement};
+use crate::builtin::Group;
+use crate::cache_format::{self, Cached, Reader, Writer};
+use crate::compiler_types::{Implementations, ImportType};
+use crate::context::Context;
+use crate::globals::Globals;
+use crate::value::Value;
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+
+pub struct ImportCache {
+    pub dir: PathBuf,
+    // the imports that are being compiled right now, innermost last
+    open: Vec<Recording>,
+    // every import each file made (with the ones in the files it imported)
+    deps: HashMap<PathBuf, Vec<Dep>>,
+    // how many imports were loaded from it
+    pub loaded: usize,
+}
+
+impl ImportCache {
+    pub fn new(dir: PathBuf) -> Self {
+        ImportCache {
+            dir,
+            open: Vec::new(),
+            deps: HashMap::new(),
+            loaded: 0,
+        }
+    }
+}
+
+// an import, the file it's in, and the (canonical) file it found
+type Dep = (ImportType, PathBuf, PathBuf);
+
+// the key of an import in globals.prev_imports
+pub type ImportKey = (ImportType, PathBuf);
+
+// what an import exported, with the implementations it added
+pub type Imported = (ImportKey, Value, Implementations);
+
+type TypeId = (String, (u16, PathBuf, (usize, usize)));
+
+struct Recording {
+    file: PathBuf,
+    deps: Vec<Dep>,
+    // the imports that were compiled (or loaded) inside it, so loading it gives them back
+    // too (they point to the same values)
+    inner: Vec<Imported>,
+    // set when the import did something that loading it again wouldn't do
+    spoiled: bool,
+    ids: [u16; 4],
+    types: (u16, u64),
+    effects: Vec<usize>,
+    lib_files: HashSet<PathBuf>,
+}
+
+struct Exports {
+    // the context it was imported in
+    start_group: Group,
+    // the closed groups, colors, blocks and items before and after it
+    ids: Vec<(u16, u16)>,
+    // how many types there were before it (with a hash of them), and the ones it made
+    types_before: (u16, u64),
+    types: Vec<TypeId>,
+    type_id_count: u16,
+    lib_files: Vec<PathBuf>,
+    // with a hash of the file that was found
+    deps: Vec<(ImportType, PathBuf, PathBuf, u64)>,
+    // the imports inside it
+    inner: Vec<ImportKey>,
+    // what it exported and then what the inner imports did, see
+    // cache_format::values_to_bytes
+    values: Vec<u8>,
+}
+
+impl Cached for Exports {
+    fn write(&self, w: &mut Writer) {
+        self.start_group.write(w);
+        self.ids.write(w);
+        self.types_before.write(w);
+        self.types.write(w);
+        self.type_id_count.write(w);
+        self.lib_files.write(w);
+        self.deps.write(w);
+        self.inner.write(w);
+        self.values.write(w);
+    }
+    fn read(r: &mut Reader) -> Option<Self> {
+        Some(Exports {
+            start_group: Cached::read(r)?,
+            ids: Cached::read(r)?,
+            types_before: Cached::read(r)?,
+            types: Cached::read(r)?,
+            type_id_count: Cached::read(r)?,
+            lib_files: Cached::read(r)?,
+            deps: Cached::read(r)?,
+            inner: Cached::read(r)?,
+            values: Cached::read(r)?,
+        })
+    }
+}
+
+fn hash_of(x: impl Hash) -> u64 {
+    let mut h = DefaultHasher::new();
+    env!("CARGO_PKG_VERSION").hash(&mut h);
+    x.hash(&mut h);
+    h.finish()
+}
+
+// the [aliases] words the file is parsed with
+fn aliases(globals: &Globals, file: &Path) -> Vec<(String, String)> {
+    let mut words: Vec<(String, String)> = crate::aliases::for_file(globals.aliases.as_ref(), file)
+        .into_iter()
+        .map(|(word, (_, meaning))| (word, meaning))
+        .collect();
+    words.sort();
+    words
+}
+
+fn file_hash(globals: &Globals, file: &Path) -> Option<u64> {
+    let content = fs::read_to_string(file).ok()?;
+    Some(hash_of((content, aliases(globals, file))))
+}
+
+fn entry(dir: &Path, key: u64, kind: &str) -> PathBuf {
+    dir.join(format!("{:016x}.{}", key, kind))
+}
+
+// nothing happens if it can't be written, the build just isn't faster next time
+fn save(dir: &Path, key: u64, kind: &str, bytes: &[u8]) {
+    if !dir.is_dir() {
+        if fs::create_dir_all(dir).is_err() {
+            return;
+        }
+        fs::write(dir.join(".gitignore"), "*\n").ok();
+    }
+    fs::write(entry(dir, key, kind), bytes).ok();
+}
+
+// the cache is left out when something watches the compiler work
+pub fn active(globals: &Globals) -> bool {
+    globals.import_cache.is_some()
+        && globals.trace.is_none()
+        && globals.history.is_none()
+        && globals.analyzers.is_empty()
+}
+
+fn counters(globals: &Globals) -> [u16; 4] {
+    [
+        globals.closed_groups,
+        globals.closed_colors,
+        globals.closed_blocks,
+        globals.closed_items,
+    ]
+}
+
+fn types(globals: &Globals) -> (u16, u64) {
+    let mut ids: Vec<(&String, u16)> = globals
+        .type_ids
+        .iter()
+        .map(|(name, (id, _, _))| (name, *id))
+        .collect();
+    ids.sort();
+    (globals.type_id_count, hash_of(ids))
+}
+
+// what an import can do that loading its exports wouldn't
+fn effects(globals: &Globals) -> Vec<usize> {
+    vec![
+        globals.objects.len(),
+        globals.func_ids.len(),
+        globals.func_ids.iter().map(|f| f.obj_list.len()).sum(),
+        globals.uid_counter,
+        globals.trigger_order,
+        globals.sync_groups.len(),
+        globals.warnings.len(),
+        globals.lints.len(),
+        globals.errors.len(),
+        globals.frozen.len(),
+    ]
+}
+
+// `module` is the library the import is in, which decides if its files are library files
+fn exports_key(globals: &Globals, path: &ImportType, file: &Path, module: &str) -> Option<u64> {
+    let mut dependencies: Vec<_> = globals.dependencies.iter().collect();
+    dependencies.sort();
+    Some(hash_of((
+        "exports",
+        path,
+        file,
+        file_hash(globals, file)?,
+        module,
+        globals.release,
+        globals.fps.to_bits(),
+        &globals.defines,
+        dependencies,
+    )))
+}
+
+fn parsed_key(globals: &Globals, file: &Path, content: &str) -> u64 {
+    hash_of(("parsed", content, aliases(globals, file)))
+}
+
+pub fn parsed(
+    globals: &Globals,
+    file: &Path,
+    content: &str,
+) -> Option<(Vec<Statement>, Attribute)> {
+    let cache = globals.import_cache.as_ref()?;
+    let bytes = fs::read(entry(
+        &cache.dir,
+        parsed_key(globals, file, content),
+        "parsed",
+    ))
+    .ok()?;
+    cache_format::from_bytes(&bytes)
+}
+
+// only for files without syntax errors
+pub fn keep_parsed(
+    globals: &Globals,
+    file: &Path,
+    content: &str,
+    parsed: &[Statement],
+    tag: &Attribute,
+) {
+    if let Some(cache) = &globals.import_cache {
+        if let Some(bytes) = cache_format::to_bytes(&(parsed.to_vec(), tag.clone())) {
+            save(
+                &cache.dir,
+                parsed_key(globals, file, content),
+                "parsed",
+                &bytes,
+            );
+        }
+    }
+}
+
+// the implementations that are new or different after compiling a file
+pub fn added_impl(before: &Implementations, after: &Implementations) -> Implementations {
+    let mut added = Implementations::new();
+    for (typ, members) in after {
+        for (name, member) in members {
+            if before.get(typ).and_then(|m| m.get(name)) != Some(member) {
+                added.entry(*typ).or_default().insert(name.clone(), *member);
+            }
+        }
+    }
+    added
+}
+
+fn spoil(cache: &mut ImportCache) {
+    for r in &mut cache.open {
+        r.spoiled = true;
+    }
+}
+
+// for builtins that can give something different next time ($.print, $.time and so on)
+pub fn impure(globals: &mut Globals) {
+    if let Some(cache) = &mut globals.import_cache {
+        spoil(cache);
+    }
+}
+
+// every import, before it's compiled or taken from prev_imports (`remembered`)
+pub fn imported(
+    globals: &mut Globals,
+    path: &ImportType,
+    from: &Path,
+    file: &Path,
+    remembered: bool,
+) {
+    let cache = match &mut globals.import_cache {
+        Some(cache) => cache,
+        None => return,
+    };
+    let known = cache.deps.get(file).cloned();
+    if remembered && known.is_none() {
+        spoil(cache);
+    }
+    for r in &mut cache.open {
+        r.deps
+            .push((path.clone(), from.to_path_buf(), file.to_path_buf()));
+        r.deps.extend(known.iter().flatten().cloned());
+    }
+}
+
+// how many imports are being recorded, for close
+pub fn depth(globals: &Globals) -> usize {
+    globals.import_cache.as_ref().map_or(0, |c| c.open.len())
+}
+
+// stops recording the imports that didn't finish (the ones around it can't be kept
+// either, since the error could be caught)
+pub fn close(globals: &mut Globals, depth: usize, failed: bool) {
+    if let Some(cache) = &mut globals.import_cache {
+        cache.open.truncate(depth);
+        if failed {
+            spoil(cache);
+        }
+    }
+}
+
+// before the file is compiled
+pub fn start(globals: &mut Globals, file: &Path) {
+    if !active(globals) {
+        return;
+    }
+    let r = Recording {
+        file: file.to_path_buf(),
+        deps: Vec::new(),
+        inner: Vec::new(),
+        spoiled: false,
+        ids: counters(globals),
+        types: types(globals),
+        effects: effects(globals),
+        lib_files: globals.lib_files.clone(),
+    };
+    globals.import_cache.as_mut().unwrap().open.push(r);
+}
+
+// after it's compiled, with what it exports and the implementations it added (a
+// library's own ones, without the ones of the file that imported it) if it can be
+// imported again
+pub fn finish(
+    globals: &mut Globals,
+    key: &ImportKey,
+    module: &str,
+    context: &Context,
+    exports: Option<(&Value, &Implementations)>,
+) {
+    if !active(globals) {
+        return;
+    }
+    let cache = globals.import_cache.as_mut().unwrap();
+    match cache.open.last() {
+        Some(r) if r.file == key.1 => (),
+        _ => return,
+    }
+    let r = cache.open.pop().unwrap();
+    let mut deps = r.deps.clone();
+    deps.dedup();
+    cache.deps.insert(key.1.clone(), deps.clone());
+
+    let (val, implementations) = match exports {
+        Some(exports) => exports,
+        None => return,
+    };
+    let this: Imported = (key.clone(), val.clone(), implementations.clone());
+    for outer in &mut cache.open {
+        outer.inner.push(this.clone());
+    }
+    if r.spoiled || effects(globals) != r.effects {
+        return;
+    }
+
+    let mut hashed = Vec::new();
+    for (path, from, found) in deps {
+        match file_hash(globals, &found) {
+            Some(hash) => hashed.push((path, from, found, hash)),
+            None => return,
+        }
+    }
+    let mut new_types: Vec<TypeId> = globals
+        .type_ids
+        .iter()
+        .filter(|(_, (id, _, _))| *id > r.types.0)
+        .map(|(name, t)| (name.clone(), t.clone()))
+        .collect();
+    new_types.sort_by_key(|(_, (id, _, _))| *id);
+    let roots: Vec<(&Value, &Implementations)> = std::iter::once(&this)
+        .chain(&r.inner)
+        .map(|(_, val, implementations)| (val, implementations))
+        .collect();
+    let exports = Exports {
+        start_group: context.start_group,
+        ids: r
+            .ids
+            .iter()
+            .copied()
+            .zip(counters(globals).iter().copied())
+            .collect(),
+        types_before: r.types,
+        types: new_types,
+        type_id_count: globals.type_id_count,
+        lib_files: globals
+            .lib_files
+            .difference(&r.lib_files)
+            .cloned()
+            .collect(),
+        deps: hashed,
+        inner: r.inner.iter().map(|(key, _, _)| key.clone()).collect(),
+        values: match cache_format::values_to_bytes(&roots, globals) {
+            Some(bytes) => bytes,
+            None => return,
+        },
+    };
+    let entry_key = match exports_key(globals, &key.0, &key.1, module) {
+        Some(k) => k,
+        None => return,
+    };
+    if let Some(bytes) = cache_format::to_bytes(&exports) {
+        save(
+            &globals.import_cache.as_ref().unwrap().dir,
+            entry_key,
+            "exports",
+            &bytes,
+        );
+    }
+}
+
+// what the import exported and the implementations it added (then the same for the
+// imports inside it), if it was kept and everything it used is still the same.
+// `resolve` finds an import from a file again
+pub fn load(
+    globals: &mut Globals,
+    key: &ImportKey,
+    module: &str,
+    context: &Context,
+    resolve: impl Fn(&ImportType, &Path, &Globals) -> Option<PathBuf>,
+) -> Option<Vec<Imported>> {
+    if !active(globals) {
+        return None;
+    }
+    let entry_key = exports_key(globals, &key.0, &key.1, module)?;
+    let dir = &globals.import_cache.as_ref()?.dir;
+    let bytes = fs::read(entry(dir, entry_key, "exports")).ok()?;
+    let exports: Exports = cache_format::from_bytes(&bytes)?;
+
+    let before: Vec<u16> = exports.ids.iter().map(|(before, _)| *before).collect();
+    if exports.start_group != context.start_group
+        || before != counters(globals)
+        || exports.types_before != types(globals)
+    {
+        return None;
+    }
+    for (path, from, found, hash) in &exports.deps {
+        if resolve(path, from, globals).as_ref() != Some(found)
+            || file_hash(globals, found) != Some(*hash)
+        {
+            return None;
+        }
+    }
+    let roots = cache_format::values_from_bytes(&exports.values, globals)?;
+    if roots.len() != exports.inner.len() + 1 {
+        return None;
+    }
+    let loaded: Vec<Imported> = std::iter::once(key.clone())
+        .chain(exports.inner)
+        .zip(roots)
+        .map(|(key, (val, implementations))| (key, val, implementations))
+        .collect();
+
+    globals.closed_groups = exports.ids[0].1;
+    globals.closed_colors = exports.ids[1].1;
+    globals.closed_blocks = exports.ids[2].1;
+    globals.closed_items = exports.ids[3].1;
+    globals.type_ids.extend(exports.types);
+    globals.type_id_count = exports.type_id_count;
+    globals.lib_files.extend(exports.lib_files);
+    let deps: Vec<Dep> = exports
+        .deps
+        .into_iter()
+        .map(|(path, from, found, _)| (path, from, found))
+        .collect();
+    globals.imported_files.insert(key.1.clone());
+    globals
+        .imported_files
+        .extend(deps.iter().map(|(_, _, found)| found.clone()));
+
+    let cache = globals.import_cache.as_mut().unwrap();
+    for r in &mut cache.open {
+        r.deps.extend(deps.iter().cloned());
+        r.inner.extend(loaded.iter().cloned());
+    }
+    cache.deps.insert(key.1.clone(), deps);
+    cache.loaded += 1;
+    Some(loaded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::compile_main;
+    use crate::parser::parse_spwn;
+
+    // what a build made, apart from the ids of its values
+    fn build(dir: &Path) -> (usize, usize, [u16; 4], Vec<(String, u16)>, usize) {
+        let path = PathBuf::from("main.spwn");
+        let (statements, notes) = parse_spwn(
+            "$.add(obj{1: 1, 2: 15, 3: 15})\nlet g = ?g\ng.move(10, 0, 0.5)\n".to_string(),
+            path.clone(),
+        )
+        .unwrap();
+        let mut globals = Globals::new(path);
+        globals.output = Some(Vec::new());
+        globals.import_cache = Some(ImportCache::new(dir.to_path_buf()));
+        let includes = vec![PathBuf::from(env!("CARGO_MANIFEST_DIR"))];
+        compile_main(statements, includes, notes, &mut globals).unwrap();
+        let triggers = globals.func_ids.iter().map(|f| f.obj_list.len()).sum();
+        let mut types: Vec<(String, u16)> = globals
+            .type_ids
+            .iter()
+            .map(|(name, (id, _, _))| (name.clone(), *id))
+            .collect();
+        types.sort();
+        (
+            globals.objects.len(),
+            triggers,
+            counters(&globals),
+            types,
+            globals.import_cache.unwrap().loaded,
+        )
+    }
+
+    #[test]
+    fn std_is_loaded_from_the_cache_the_second_time() {
+        let dir = std::env::temp_dir().join(format!("spwn-cache-test-{}", std::process::id()));
+        let built = {
+            let dir = dir.clone();
+            // the compiler needs a bigger stack than tests get
+            std::thread::Builder::new()
+                .stack_size(crate::STACK_SIZE)
+                .spawn(move || (build(&dir), build(&dir)))
+                .unwrap()
+                .join()
+        };
+        fs::remove_dir_all(&dir).ok();
+        let (mut cold, mut warm) = built.unwrap();
+
+        assert_eq!((cold.4, warm.4), (0, 1));
+        cold.4 = 0;
+        warm.4 = 0;
+        assert_eq!(cold, warm);
+        assert!(!cold.3.is_empty());
+    }
+}
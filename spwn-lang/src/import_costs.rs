@@ -0,0 +1,168 @@
+// how much each library adds to a build: the time it took to import (not counting the
+// libraries it imports itself), the values made while importing it, and the objects its
+// macros added over the whole build. shown after the build when an import is slow, and
+// always with --verbose
+use crate::globals::Globals;
+use crate::print_with_color;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use termcolor::Color;
+
+// an import that takes longer than this is pointed out after the build
+pub const SLOW_IMPORT: Duration = Duration::from_secs(1);
+// how many libraries are listed
+const SHOWN: usize = 5;
+
+#[derive(Default, Clone)]
+pub struct Cost {
+    pub time: Duration,
+    pub values: usize,
+    pub objects: usize,
+}
+
+// an import being compiled: when it started, the values made before it, and the time
+// and values of the imports in it
+struct Running {
+    start: Instant,
+    first_value: usize,
+    nested_time: Duration,
+    nested_values: usize,
+}
+
+#[derive(Default)]
+pub struct ImportCosts {
+    pub libraries: HashMap<String, Cost>,
+    // the files of each library
+    files: HashMap<PathBuf, String>,
+    running: Vec<Running>,
+}
+
+pub fn start(globals: &mut Globals) {
+    let first_value = globals.val_id;
+    globals.import_costs.running.push(Running {
+        start: Instant::now(),
+        first_value,
+        nested_time: Duration::ZERO,
+        nested_values: 0,
+    });
+}
+
+// name is None for the files a library imports, which count as part of the library
+pub fn end(name: Option<String>, globals: &mut Globals) {
+    let val_id = globals.val_id;
+    let costs = &mut globals.import_costs;
+    let running = match costs.running.pop() {
+        Some(r) => r,
+        None => return,
+    };
+    let name = match name {
+        Some(name) => name,
+        None => return,
+    };
+    let time = running.start.elapsed();
+    let values = val_id - running.first_value;
+    if let Some(parent) = costs.running.last_mut() {
+        parent.nested_time += time;
+        parent.nested_values += values;
+    }
+    let cost = costs.libraries.entry(name).or_default();
+    cost.time += time.saturating_sub(running.nested_time);
+    cost.values += values.saturating_sub(running.nested_values);
+}
+
+pub fn library_file(file: PathBuf, library: &str, globals: &mut Globals) {
+    globals.import_costs.files.insert(file, library.to_string());
+}
+
+// an object added by the code in a file (only counted if it's a library's)
+pub fn object_added(file: &Path, globals: &mut Globals) {
+    let costs = &mut globals.import_costs;
+    if let Some(library) = costs.files.get(file) {
+        costs.libraries.entry(library.clone()).or_default().objects += 1;
+    }
+}
+
+// the libraries that took the longest, as (name, cost)
+pub fn slowest(globals: &Globals) -> Vec<(String, Cost)> {
+    let mut list: Vec<(String, Cost)> = globals
+        .import_costs
+        .libraries
+        .iter()
+        .map(|(name, cost)| (name.clone(), cost.clone()))
+        .collect();
+    list.sort_by(|a, b| b.1.time.cmp(&a.1.time).then(a.0.cmp(&b.0)));
+    list
+}
+
+// prints the report if an import was slow (or always, with verbose)
+pub fn report(globals: &Globals, verbose: bool) {
+    let list = slowest(globals);
+    let slow = matches!(list.first(), Some((_, c)) if c.time > SLOW_IMPORT);
+    if list.is_empty() || !(slow || verbose) {
+        return;
+    }
+    print_with_color(
+        "Imports (time to import, values made while importing, objects added by the library):",
+        if slow { Color::Yellow } else { Color::White },
+    );
+    let width = list.iter().map(|(n, _)| n.len()).max().unwrap_or(0);
+    for (name, cost) in list.iter().take(SHOWN) {
+        println!(
+            "    {:width$}  {:>6} ms  {:>7} values  {:>6} objects",
+            name,
+            cost.time.as_millis(),
+            cost.values,
+            cost.objects,
+            width = width
+        );
+    }
+    if list.len() > SHOWN {
+        println!("    and {} other imports", list.len() - SHOWN);
+    }
+    if let Some(cache) = globals.import_cache.as_ref().filter(|c| c.loaded > 0) {
+        println!("    ({} loaded from .spwn-cache/)", cache.loaded);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compiler::compile_main;
+    use crate::globals::Globals;
+    use crate::parser::parse_spwn;
+    use std::path::PathBuf;
+
+    #[test]
+    fn objects_are_counted_for_the_library_that_added_them() {
+        // the compiler needs a bigger stack than tests get
+        let build = std::thread::Builder::new()
+            .stack_size(crate::STACK_SIZE)
+            .spawn(|| {
+                let path = PathBuf::from("main.spwn");
+                let (statements, notes) = parse_spwn(
+                    "c = counter()\nc += 1\n$.add(obj{1: 1, 2: 15, 3: 15})\n".to_string(),
+                    path.clone(),
+                )
+                .unwrap();
+                let mut globals = Globals::new(path);
+                globals.output = Some(Vec::new());
+                let includes = vec![PathBuf::from(env!("CARGO_MANIFEST_DIR"))];
+                compile_main(statements, includes, notes, &mut globals).unwrap();
+                let objects = globals.objects.len()
+                    + globals
+                        .func_ids
+                        .iter()
+                        .map(|f| f.obj_list.len())
+                        .sum::<usize>();
+                (globals.import_costs.libraries, objects)
+            })
+            .unwrap();
+        let (libraries, objects) = build.join().unwrap();
+
+        let std = &libraries["std"];
+        assert!(std.values > 0);
+        // everything but the block in the script is from std
+        assert_eq!(std.objects, objects - 1);
+        assert_eq!(libraries.len(), 1);
+    }
+}
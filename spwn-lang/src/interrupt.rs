@@ -0,0 +1,69 @@
+// stopping a build early (with ctrl-c or --timeout) at the next statement, so what was
+// built so far can still be written out with --partial-output
+use crate::globals::Globals;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+// while this is on, ctrl-c stops the build at the next statement instead of right away
+// (pressing it again still stops right away)
+#[cfg(unix)]
+pub fn catch_ctrl_c(on: bool) {
+    extern "C" fn handle(_: libc::c_int) {
+        if INTERRUPTED.swap(true, Ordering::SeqCst) {
+            unsafe { libc::_exit(130) }
+        }
+    }
+    let handler = if on {
+        handle as extern "C" fn(libc::c_int) as libc::sighandler_t
+    } else {
+        libc::SIG_DFL
+    };
+    unsafe {
+        libc::signal(libc::SIGINT, handler);
+    }
+}
+
+#[cfg(windows)]
+pub fn catch_ctrl_c(on: bool) {
+    use winapi::shared::minwindef::{BOOL, DWORD, FALSE, TRUE};
+    use winapi::um::consoleapi::SetConsoleCtrlHandler;
+    use winapi::um::wincon::CTRL_C_EVENT;
+
+    // windows runs this in a thread of its own, the build keeps going
+    unsafe extern "system" fn handle(event: DWORD) -> BOOL {
+        if event != CTRL_C_EVENT {
+            // the next handler (stopping right away)
+            return FALSE;
+        }
+        if INTERRUPTED.swap(true, Ordering::SeqCst) {
+            std::process::exit(130)
+        }
+        TRUE
+    }
+    unsafe {
+        SetConsoleCtrlHandler(Some(handle), if on { TRUE } else { FALSE });
+    }
+}
+
+// ctrl-c always stops right away on other systems
+#[cfg(not(any(unix, windows)))]
+pub fn catch_ctrl_c(_on: bool) {}
+
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+// why the build should stop, if it should
+pub fn stop_reason(globals: &Globals) -> Option<String> {
+    if interrupted() {
+        return Some("The build was stopped with ctrl-c".to_string());
+    }
+    match globals.timeout {
+        Some((start, limit)) if start.elapsed() > limit => Some(format!(
+            "The build took longer than the {} second timeout",
+            limit.as_secs()
+        )),
+        _ => None,
+    }
+}
@@ -0,0 +1,235 @@
+// gives the same item ID to temporaries that can't be in use at the same time. an item
+// counts as a temporary when it's only used by pickup and instant count triggers in one
+// trigger function, and the pickups add up to 0: then it's 0 again every time that
+// function is done, so it's only in use while the function's triggers run. triggers run
+// one group at a time, except for the groups they start (which run before the rest of
+// the group does), so two temporaries can share an ID when neither of their functions
+// can start the other one. turned off with --no-item-reuse, for seeing the counters of
+// each operation in game
+use crate::builtin::{Group, Id, Item};
+use crate::levelstring::{GdObj, ObjParam};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+const PICKUP: f64 = 1817.0;
+const INSTANT_COUNT: f64 = 1811.0;
+
+fn groups(obj: &GdObj) -> Vec<Group> {
+    match obj.params.get(&57) {
+        Some(ObjParam::Group(g)) => vec![*g],
+        Some(ObjParam::GroupList(l)) => l.clone(),
+        _ => Vec::new(),
+    }
+}
+
+// the groups each group can start (or change in some other way), which is more than
+// it can start, but that only means less gets shared
+fn starts(objects: &[GdObj]) -> HashMap<Group, HashSet<Group>> {
+    let mut starts: HashMap<Group, HashSet<Group>> = HashMap::new();
+    for obj in objects {
+        let targets: Vec<Group> = obj
+            .params
+            .iter()
+            .filter(|(k, _)| **k != 57)
+            .flat_map(|(_, p)| match p {
+                ObjParam::Group(g) => vec![*g],
+                ObjParam::GroupList(l) => l.clone(),
+                _ => Vec::new(),
+            })
+            .collect();
+        for g in groups(obj) {
+            starts.entry(g).or_default().extend(targets.iter().copied());
+        }
+    }
+    starts
+}
+
+fn reachable(from: Group, starts: &HashMap<Group, HashSet<Group>>) -> HashSet<Group> {
+    let mut seen = HashSet::new();
+    let mut stack = vec![from];
+    while let Some(g) = stack.pop() {
+        for next in starts.get(&g).into_iter().flatten() {
+            if seen.insert(*next) {
+                stack.push(*next);
+            }
+        }
+    }
+    seen
+}
+
+// the temporaries, with the group they're used in
+fn temporaries(objects: &[GdObj]) -> BTreeMap<u16, Group> {
+    // None once the item is used in a way that doesn't make it a temporary
+    let mut used: HashMap<u16, Option<(Group, f64)>> = HashMap::new();
+    for obj in objects {
+        for param in obj.params.values() {
+            let id = match param {
+                ObjParam::Item(Item {
+                    id: Id::Arbitrary(id),
+                }) => *id,
+                _ => continue,
+            };
+            let kind = obj.params.get(&1);
+            let in_group = match groups(obj)[..] {
+                [g] if obj.mode == crate::ast::ObjectMode::Trigger => Some(g),
+                _ => None,
+            };
+            let added = match (kind, obj.params.get(&77)) {
+                (Some(ObjParam::Number(k)), Some(ObjParam::Number(n))) if *k == PICKUP => *n,
+                (Some(ObjParam::Number(k)), _) if *k == INSTANT_COUNT => 0.0,
+                _ => f64::NAN,
+            };
+            let entry = used.entry(id).or_insert_with(|| in_group.map(|g| (g, 0.0)));
+            *entry = match (*entry, in_group) {
+                (Some((g, sum)), Some(here)) if g == here && !added.is_nan() => {
+                    Some((g, sum + added))
+                }
+                _ => None,
+            };
+        }
+    }
+    used.into_iter()
+        .filter_map(|(id, u)| match u {
+            Some((g, 0.0)) => Some((id, g)),
+            _ => None,
+        })
+        .collect()
+}
+
+// gives back how many item IDs were saved
+pub fn share_temporaries(objects: &mut [GdObj]) -> usize {
+    let starts = starts(objects);
+    let mut reach: HashMap<Group, HashSet<Group>> = HashMap::new();
+
+    // the groups each shared ID is used in, by the first temporary that got it
+    let mut kept: Vec<(u16, Vec<Group>)> = Vec::new();
+    let mut replace: HashMap<u16, u16> = HashMap::new();
+    for (id, group) in temporaries(objects) {
+        for g in [group].iter().chain(kept.iter().flat_map(|(_, l)| l)) {
+            if !reach.contains_key(g) {
+                reach.insert(*g, reachable(*g, &starts));
+            }
+        }
+        let apart = |other: &Group| {
+            *other != group && !reach[other].contains(&group) && !reach[&group].contains(other)
+        };
+        match kept.iter_mut().find(|(_, l)| l.iter().all(apart)) {
+            Some((into, l)) => {
+                l.push(group);
+                replace.insert(id, *into);
+            }
+            None => kept.push((id, vec![group])),
+        }
+    }
+
+    for obj in objects.iter_mut() {
+        for param in obj.params.values_mut() {
+            if let ObjParam::Item(Item {
+                id: Id::Arbitrary(id),
+            }) = param
+            {
+                if let Some(into) = replace.get(id) {
+                    *id = *into;
+                }
+            }
+        }
+    }
+    replace.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::ObjectMode;
+
+    fn group_of(id: u16) -> Group {
+        Group {
+            id: Id::Arbitrary(id),
+        }
+    }
+
+    fn item(id: u16) -> ObjParam {
+        ObjParam::Item(Item {
+            id: Id::Arbitrary(id),
+        })
+    }
+
+    // a temporary in the group: set, read, then emptied again
+    fn uses(group: u16, id: u16, then: u16) -> Vec<GdObj> {
+        vec![
+            GdObj::for_test(
+                0,
+                ObjectMode::Trigger,
+                &[
+                    (57, ObjParam::Group(group_of(group))),
+                    (1, ObjParam::Number(PICKUP)),
+                    (80, item(id)),
+                    (77, ObjParam::Number(5.0)),
+                ],
+            ),
+            GdObj::for_test(
+                0,
+                ObjectMode::Trigger,
+                &[
+                    (57, ObjParam::Group(group_of(group))),
+                    (1, ObjParam::Number(INSTANT_COUNT)),
+                    (80, item(id)),
+                    (51, ObjParam::Group(group_of(then))),
+                ],
+            ),
+            GdObj::for_test(
+                0,
+                ObjectMode::Trigger,
+                &[
+                    (57, ObjParam::Group(group_of(group))),
+                    (1, ObjParam::Number(PICKUP)),
+                    (80, item(id)),
+                    (77, ObjParam::Number(-5.0)),
+                ],
+            ),
+        ]
+    }
+
+    fn ids(objects: &[GdObj]) -> Vec<u16> {
+        let mut ids: Vec<u16> = objects
+            .iter()
+            .filter_map(|o| match o.params.get(&80) {
+                Some(ObjParam::Item(Item {
+                    id: Id::Arbitrary(id),
+                })) => Some(*id),
+                _ => None,
+            })
+            .collect();
+        ids.sort_unstable();
+        ids.dedup();
+        ids
+    }
+
+    #[test]
+    fn temporaries_that_cant_overlap_share_an_id() {
+        // 1g and 2g start 9g, which doesn't use an item, so they can't run inside each other
+        let mut objects = uses(1, 1, 9);
+        objects.extend(uses(2, 2, 9));
+        assert_eq!(share_temporaries(&mut objects), 1);
+        assert_eq!(ids(&objects), [1]);
+    }
+
+    #[test]
+    fn temporaries_that_can_overlap_keep_their_ids() {
+        // 1g starts 2g while 1i is still 5
+        let mut objects = uses(1, 1, 2);
+        objects.extend(uses(2, 2, 9));
+        // 3i is never emptied, so it's not a temporary
+        objects.push(GdObj::for_test(
+            0,
+            ObjectMode::Trigger,
+            &[
+                (57, ObjParam::Group(group_of(3))),
+                (1, ObjParam::Number(PICKUP)),
+                (80, item(3)),
+                (77, ObjParam::Number(1.0)),
+            ],
+        ));
+        assert_eq!(share_temporaries(&mut objects), 0);
+        assert_eq!(ids(&objects), [1, 2, 3]);
+    }
+}
@@ -3,6 +3,10 @@ use crate::ast::ObjectMode;
 use crate::builtin::*;
 use crate::compiler_types::*;
 use crate::context::Context;
+use crate::documentation::json_str;
+#[cfg(feature = "native")]
+use crate::save_format::{Encryption, SaveFormat};
+use crate::trigger_area::{Stacking, TriggerArea};
 use std::collections::{HashMap, HashSet};
 
 #[derive(Clone, PartialEq, Debug)]
@@ -107,6 +111,19 @@ impl GdObj {
 
         (*self).clone()
     }
+
+    // an object with just these properties, for the tests of the passes over objects
+    #[cfg(test)]
+    pub fn for_test(unique_id: usize, mode: ObjectMode, params: &[(u16, ObjParam)]) -> GdObj {
+        GdObj {
+            func_id: 0,
+            params: params.iter().cloned().collect(),
+            mode,
+            unique_id,
+            sync_group: 0,
+            sync_part: 0,
+        }
+    }
 }
 
 pub fn get_used_ids(ls: &str) -> [HashSet<u16>; 4] {
@@ -190,28 +207,7 @@ pub const SPWN_SIGNATURE_GROUP: Group = Group {
 //use crate::ast::ObjectMode;
 
 pub fn remove_spwn_objects(file_content: &mut String) {
-    let spwn_group = match SPWN_SIGNATURE_GROUP.id {
-        Id::Specific(n) => n.to_string(),
-        _ => unreachable!(),
-    };
-    (*file_content) = file_content
-        //remove previous spwn objects
-        .split(';')
-        .map(|obj| {
-            let key_val: Vec<&str> = obj.split(',').collect();
-            let mut ret = obj;
-            for i in (0..key_val.len()).step_by(2) {
-                if key_val[i] == "57" {
-                    let mut groups = key_val[i + 1].split('.');
-                    if groups.any(|x| x == spwn_group) {
-                        ret = "";
-                    }
-                }
-            }
-            ret
-        })
-        .collect::<Vec<&str>>()
-        .join(";");
+    *file_content = crate::ownership::without_spwn_objects(file_content);
 }
 
 //returns the string to be appended to the old string
@@ -219,10 +215,93 @@ pub fn append_objects(
     mut objects: Vec<GdObj>,
     old_ls: &str,
 ) -> Result<(String, [usize; 4]), String> {
+    let used_ids = resolve_ids(&mut objects, old_ls)?;
+
+    fn serialize_obj(mut trigger: GdObj) -> String {
+        let mut obj_string = String::new();
+        match trigger.mode {
+            ObjectMode::Object => {
+                match trigger.params.get_mut(&57) {
+                    Some(ObjParam::GroupList(l)) => (*l).push(SPWN_SIGNATURE_GROUP),
+                    Some(ObjParam::Group(g)) => {
+                        let group = *g;
+                        trigger
+                            .params
+                            .insert(57, ObjParam::GroupList(vec![group, SPWN_SIGNATURE_GROUP]));
+                    }
+                    _ => {
+                        trigger
+                            .params
+                            .insert(57, ObjParam::Group(SPWN_SIGNATURE_GROUP));
+                    }
+                };
+
+                let mut param_list = trigger.params.iter().collect::<Vec<(&u16, &ObjParam)>>();
+
+                param_list.sort_by(|a, b| (*a.0).cmp(b.0));
+
+                for param in param_list {
+                    obj_string += &format!("{},{},", param.0, param.1);
+                }
+
+                obj_string + ";"
+            }
+            ObjectMode::Trigger => {
+                match trigger.params.get_mut(&57) {
+                    Some(ObjParam::GroupList(l)) => {
+                        (*l).push(SPWN_SIGNATURE_GROUP);
+                        //list
+                    }
+                    Some(ObjParam::Group(g)) => {
+                        let group = *g;
+                        trigger
+                            .params
+                            .insert(57, ObjParam::GroupList(vec![group, SPWN_SIGNATURE_GROUP]));
+                    }
+                    _ => {
+                        trigger
+                            .params
+                            .insert(57, ObjParam::Group(SPWN_SIGNATURE_GROUP));
+                        //Vec::new()
+                    }
+                };
+
+                /*let spawned = match trigger.params.get(&62) {
+                    Some(ObjParam::Bool(b)) => *b,
+                    _ => groups.iter().any(|x| x.id != ID::Specific(0)),
+                };
+
+                if spawned {
+                    obj_string += "87,1,";
+                }*/
+
+                let mut param_list = trigger.params.iter().collect::<Vec<(&u16, &ObjParam)>>();
+
+                param_list.sort_by(|a, b| (*a.0).cmp(b.0));
+
+                for param in param_list {
+                    obj_string += &format!("{},{},", param.0, param.1);
+                }
+                obj_string + "108,1;" //linked group
+            }
+        }
+    }
+
+    let mut full_obj_string = String::new();
+
+    for obj in objects {
+        full_obj_string += &serialize_obj(obj)
+    }
+    Ok((full_obj_string, used_ids))
+}
+
+// gives all the arbitrary ids in the objects a specific id that isn't used
+// in the level yet, and returns how many of each id class are used
+pub fn resolve_ids(objects: &mut [GdObj], old_ls: &str) -> Result<[usize; 4], String> {
     let mut closed_ids = get_used_ids(&old_ls);
 
     //collect all specific ids mentioned into closed_[id] lists
-    for obj in &objects {
+    for obj in objects.iter() {
         for prop in obj.params.values() {
             let class_index;
             let id;
@@ -272,7 +351,7 @@ pub fn append_objects(
 
     const ID_MAX: u16 = 999;
 
-    for obj in &mut objects {
+    for obj in objects.iter_mut() {
         for prop in obj.params.values_mut() {
             let class_index;
             let ids: Vec<&mut Id>;
@@ -344,93 +423,470 @@ pub fn append_objects(
 
     //println!("group_map: {:?}", id_maps[0]);
 
-    fn serialize_obj(mut trigger: GdObj) -> String {
-        let mut obj_string = String::new();
-        match trigger.mode {
-            ObjectMode::Object => {
-                match trigger.params.get_mut(&57) {
-                    Some(ObjParam::GroupList(l)) => (*l).push(SPWN_SIGNATURE_GROUP),
-                    Some(ObjParam::Group(g)) => {
-                        let group = *g;
-                        trigger
-                            .params
-                            .insert(57, ObjParam::GroupList(vec![group, SPWN_SIGNATURE_GROUP]));
-                    }
-                    _ => {
-                        trigger
-                            .params
-                            .insert(57, ObjParam::Group(SPWN_SIGNATURE_GROUP));
-                    }
-                };
+    Ok([
+        closed_ids[0].len(),
+        closed_ids[1].len(),
+        closed_ids[2].len(),
+        closed_ids[3].len(),
+    ])
+}
 
-                let mut param_list = trigger.params.iter().collect::<Vec<(&u16, &ObjParam)>>();
+// names of the object properties (same as obj_props in the standard library)
+const PROP_NAMES: &[(u16, &str)] = &[
+    (1, "OBJ_ID"),
+    (2, "X"),
+    (3, "Y"),
+    (4, "HORIZONTAL_FLIP"),
+    (5, "VERTICAL_FLIP"),
+    (6, "ROTATION"),
+    (7, "TRIGGER_RED"),
+    (8, "TRIGGER_GREEN"),
+    (9, "TRIGGER_BLUE"),
+    (10, "DURATION"),
+    (11, "TOUCH_TRIGGERED"),
+    (13, "PORTAL_CHECKED"),
+    (15, "PLAYER_COLOR_1"),
+    (16, "PLAYER_COLOR_2"),
+    (17, "BLENDING"),
+    (20, "EDITOR_LAYER_1"),
+    (21, "COLOR"),
+    (22, "COLOR_2"),
+    (23, "TARGET_COLOR"),
+    (24, "Z_LAYER"),
+    (25, "Z_ORDER"),
+    (28, "MOVE_X"),
+    (29, "MOVE_Y"),
+    (30, "EASING"),
+    (31, "TEXT"),
+    (32, "SCALING"),
+    (34, "GROUP_PARENT"),
+    (35, "OPACITY"),
+    (36, "ACTIVE_TRIGGER"),
+    (41, "HVS_ENABLED"),
+    (42, "COLOR_2_HVS_ENABLED"),
+    (43, "HVS"),
+    (44, "COLOR_2_HVS"),
+    (45, "FADE_IN"),
+    (46, "HOLD"),
+    (47, "FADE_OUT"),
+    (48, "PULSE_HSV"),
+    (49, "COPIED_COLOR_HVS"),
+    (50, "COPIED_COLOR_ID"),
+    (51, "TARGET"),
+    (52, "TARGET_TYPE"),
+    (54, "YELLOW_TELEPORTATION_PORTAL_DISTANCE"),
+    (56, "ACTIVATE_GROUP"),
+    (57, "GROUPS"),
+    (58, "LOCK_TO_PLAYER_X"),
+    (59, "LOCK_TO_PLAYER_Y"),
+    (60, "COPY_OPACITY"),
+    (61, "EDITOR_LAYER_2"),
+    (62, "SPAWN_TRIGGERED"),
+    (63, "SPAWN_DURATION"),
+    (64, "DONT_FADE"),
+    (65, "MAIN_ONLY"),
+    (66, "DETAIL_ONLY"),
+    (67, "DONT_ENTER"),
+    (68, "ROTATE_DEGREES"),
+    (69, "TIMES_360"),
+    (70, "LOCK_OBJECT_ROTATION"),
+    (71, "TARGET_POS"),
+    (72, "X_MOD"),
+    (73, "Y_MOD"),
+    (75, "STRENGTH"),
+    (76, "ANIMATION_ID"),
+    (77, "COUNT"),
+    (78, "SUBTRACT_COUNT"),
+    (79, "PICKUP_MODE"),
+    (80, "ITEM"),
+    (81, "HOLD_MODE"),
+    (82, "TOGGLE_MODE"),
+    (84, "INTERVAL"),
+    (85, "EASING_RATE"),
+    (86, "EXCLUSIVE"),
+    (87, "MULTI_TRIGGER"),
+    (88, "COMPARISON"),
+    (89, "DUAL_MODE"),
+    (90, "SPEED"),
+    (91, "DELAY"),
+    (92, "Y_OFFSET"),
+    (93, "ACTIVATE_ON_EXIT"),
+    (94, "DYNAMIC_BLOCK"),
+    (95, "BLOCK_B"),
+    (96, "GLOW_DISABLED"),
+    (97, "ROTATION_SPEED"),
+    (98, "DISABLE_ROTATION"),
+    (100, "USE_TARGET"),
+    (101, "TARGET_POS_AXES"),
+    (102, "EDITOR_DISABLE"),
+    (103, "HIGH_DETAIL"),
+    (104, "COUNT_MULTI_ACTIVATE"),
+    (105, "MAX_SPEED"),
+    (106, "RANDOMIZE_START"),
+    (107, "ANIMATION_SPEED"),
+    (108, "LINKED_GROUP"),
+];
+
+pub fn prop_name(key: u16) -> Option<&'static str> {
+    PROP_NAMES
+        .iter()
+        .find(|(k, _)| *k == key)
+        .map(|(_, name)| *name)
+}
 
-                param_list.sort_by(|a, b| (*a.0).cmp(b.0));
+// json representation of the objects, for external tools (spwn build --emit=json)
+// the ids should already be resolved (see resolve_ids)
+pub fn objects_to_json(objects: &[GdObj], used_ids: [usize; 4]) -> String {
+    fn id_json(id: &Id) -> String {
+        match id {
+            Id::Specific(n) => n.to_string(),
+            Id::Arbitrary(_) => "null".to_string(),
+        }
+    }
 
-                for param in param_list {
-                    obj_string += &format!("{},{},", param.0, param.1);
+    fn param_json(key: u16, param: &ObjParam) -> String {
+        match param {
+            // groups of an object are always a list (group 0 means no group)
+            ObjParam::Group(g) if key == 57 => param_json(key, &ObjParam::GroupList(vec![*g])),
+            ObjParam::GroupList(list) => format!(
+                "[{}]",
+                list.iter()
+                    .filter(|g| g.id != Id::Specific(0))
+                    .map(|g| id_json(&g.id))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            ObjParam::Group(Group { id })
+            | ObjParam::Color(Color { id })
+            | ObjParam::Block(Block { id })
+            | ObjParam::Item(Item { id }) => id_json(id),
+            ObjParam::Number(n) => {
+                if !n.is_finite() {
+                    "null".to_string()
+                } else if n.fract() == 0.0 && n.abs() < 1e15 {
+                    (*n as i64).to_string()
+                } else {
+                    n.to_string()
                 }
-
-                obj_string + ";"
             }
-            ObjectMode::Trigger => {
-                match trigger.params.get_mut(&57) {
-                    Some(ObjParam::GroupList(l)) => {
-                        (*l).push(SPWN_SIGNATURE_GROUP);
-                        //list
-                    }
-                    Some(ObjParam::Group(g)) => {
-                        let group = *g;
-                        trigger
-                            .params
-                            .insert(57, ObjParam::GroupList(vec![group, SPWN_SIGNATURE_GROUP]));
-                    }
-                    _ => {
-                        trigger
-                            .params
-                            .insert(57, ObjParam::Group(SPWN_SIGNATURE_GROUP));
-                        //Vec::new()
-                    }
-                };
+            ObjParam::Bool(b) => b.to_string(),
+            ObjParam::Text(t) => json_str(t),
+            ObjParam::Epsilon => "\"epsilon\"".to_string(),
+        }
+    }
 
-                /*let spawned = match trigger.params.get(&62) {
-                    Some(ObjParam::Bool(b)) => *b,
-                    _ => groups.iter().any(|x| x.id != ID::Specific(0)),
+    let mut out = String::from("{\n  \"objects\": [\n");
+    for (i, obj) in objects.iter().enumerate() {
+        let mut params = obj.params.iter().collect::<Vec<(&u16, &ObjParam)>>();
+        params.sort_by(|a, b| (*a.0).cmp(b.0));
+
+        let props = params
+            .iter()
+            .map(|(key, param)| {
+                let name = match (key, param) {
+                    (80, ObjParam::Block(_)) => Some("BLOCK_A"),
+                    _ => prop_name(**key),
                 };
+                format!(
+                    "{{\"key\": {}, \"name\": {}, \"value\": {}}}",
+                    key,
+                    match name {
+                        Some(name) => json_str(name),
+                        None => "null".to_string(),
+                    },
+                    param_json(**key, param)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        out += &format!(
+            "    {{\"mode\": {}, \"properties\": [{}]}}{}\n",
+            match obj.mode {
+                ObjectMode::Object => "\"object\"",
+                ObjectMode::Trigger => "\"trigger\"",
+            },
+            props,
+            if i + 1 < objects.len() { "," } else { "" }
+        );
+    }
+    out += &format!(
+        "  ],\n  \"used_ids\": {{\"groups\": {}, \"colors\": {}, \"blocks\": {}, \"items\": {}}}\n}}\n",
+        used_ids[0], used_ids[1], used_ids[2], used_ids[3]
+    );
+    out
+}
 
-                if spawned {
-                    obj_string += "87,1,";
-                }*/
-
-                let mut param_list = trigger.params.iter().collect::<Vec<(&u16, &ObjParam)>>();
+// how many of the objects each script file added, most first
+// (file names are relative to the main script's folder)
+pub fn objects_per_file(
+    objects: &[GdObj],
+    origins: &HashMap<usize, (std::path::PathBuf, crate::parser::FileRange)>,
+    script_path: &std::path::Path,
+) -> Vec<(String, usize)> {
+    let mut counts = HashMap::<String, usize>::new();
+    let folder = script_path.parent().unwrap_or(script_path);
+    for obj in objects {
+        let name = match origins.get(&obj.unique_id) {
+            Some((path, _)) => path
+                .strip_prefix(folder)
+                .unwrap_or(path)
+                .to_string_lossy()
+                .to_string(),
+            // made by the compiler itself (like the spawn triggers for merging contexts)
+            None => "other".to_string(),
+        };
+        *counts.entry(name).or_insert(0) += 1;
+    }
+    let mut counts: Vec<_> = counts.into_iter().collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    counts
+}
 
-                param_list.sort_by(|a, b| (*a.0).cmp(b.0));
+// every group the objects use, with the objects that use it and where they were made
+// (spwn build --group-usage), as json or csv
+// the ids should already be resolved (see resolve_ids)
+pub fn group_usage(
+    objects: &[GdObj],
+    origins: &HashMap<usize, (std::path::PathBuf, crate::parser::FileRange)>,
+    script_path: &std::path::Path,
+    csv: bool,
+) -> String {
+    struct Usage {
+        property: String,
+        obj_id: Option<u16>,
+        file: String,
+        line: usize,
+        column: usize,
+    }
 
-                for param in param_list {
-                    obj_string += &format!("{},{},", param.0, param.1);
+    let folder = script_path.parent().unwrap_or(script_path);
+    let mut usages = std::collections::BTreeMap::<u16, Vec<Usage>>::new();
+    for obj in objects {
+        let (file, (line, column)) = match origins.get(&obj.unique_id) {
+            Some((path, pos)) => (
+                path.strip_prefix(folder)
+                    .unwrap_or(path)
+                    .to_string_lossy()
+                    .to_string(),
+                ((pos.0).0, (pos.0).1 + 1),
+            ),
+            None => ("other".to_string(), (0, 0)),
+        };
+        let obj_id = match obj.params.get(&1) {
+            Some(ObjParam::Number(n)) => Some(*n as u16),
+            _ => None,
+        };
+        let mut params = obj.params.iter().collect::<Vec<(&u16, &ObjParam)>>();
+        params.sort_by(|a, b| (*a.0).cmp(b.0));
+        for (key, param) in params {
+            let groups = match param {
+                ObjParam::Group(g) => vec![*g],
+                ObjParam::GroupList(list) => list.clone(),
+                _ => continue,
+            };
+            let property = prop_name(*key)
+                .map(|name| name.to_string())
+                .unwrap_or_else(|| key.to_string());
+            for g in groups {
+                // group 0 means no group
+                if let Id::Specific(id) = g.id {
+                    if id != 0 {
+                        usages.entry(id).or_default().push(Usage {
+                            property: property.clone(),
+                            obj_id,
+                            file: file.clone(),
+                            line,
+                            column,
+                        });
+                    }
                 }
-                obj_string + "108,1;" //linked group
             }
         }
     }
 
-    let mut full_obj_string = String::new();
+    let obj_id_str = |u: &Usage| match u.obj_id {
+        Some(id) => id.to_string(),
+        None => if csv { "" } else { "null" }.to_string(),
+    };
+    if csv {
+        let mut out = String::from("group,property,object,file,line,column\n");
+        for (id, list) in &usages {
+            for u in list {
+                let file = if u.file.contains(',') || u.file.contains('"') {
+                    format!("\"{}\"", u.file.replace('"', "\"\""))
+                } else {
+                    u.file.clone()
+                };
+                out += &format!(
+                    "{},{},{},{},{},{}\n",
+                    id,
+                    u.property,
+                    obj_id_str(u),
+                    file,
+                    u.line,
+                    u.column
+                );
+            }
+        }
+        return out;
+    }
+
+    let mut out = String::from("{\n  \"groups\": [\n");
+    for (i, (id, list)) in usages.iter().enumerate() {
+        let uses = list
+            .iter()
+            .map(|u| {
+                format!(
+                    "{{\"property\": {}, \"object\": {}, \"file\": {}, \"line\": {}, \"column\": {}}}",
+                    json_str(&u.property),
+                    obj_id_str(u),
+                    json_str(&u.file),
+                    u.line,
+                    u.column
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        out += &format!(
+            "    {{\"id\": {}, \"count\": {}, \"uses\": [{}]}}{}\n",
+            id,
+            list.len(),
+            uses,
+            if i + 1 < usages.len() { "," } else { "" }
+        );
+    }
+    out += "  ]\n}\n";
+    out
+}
+
+// "1-4, 7" for 1, 2, 3, 4 and 7
+fn id_ranges(ids: &std::collections::BTreeSet<u16>) -> String {
+    let mut ranges: Vec<(u16, u16)> = Vec::new();
+    for id in ids {
+        match ranges.last_mut() {
+            Some((_, end)) if *end + 1 == *id => *end = *id,
+            _ => ranges.push((*id, *id)),
+        }
+    }
+    if ranges.is_empty() {
+        return "none".to_string();
+    }
+    ranges
+        .iter()
+        .map(|(a, b)| {
+            if a == b {
+                a.to_string()
+            } else {
+                format!("{}-{}", a, b)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+// what the built objects need from the rest of the level (spwn build --manifest),
+// for the people working on it in the editor
+// the ids should already be resolved (see resolve_ids)
+pub fn manifest(objects: &[GdObj], script_path: &std::path::Path, fps: f64) -> String {
+    use std::collections::BTreeSet;
+    let mut ids = [
+        BTreeSet::new(),
+        BTreeSet::new(),
+        BTreeSet::new(),
+        BTreeSet::new(),
+    ];
+    let mut layers = BTreeSet::new();
+    let mut trigger_area: Option<[f64; 4]> = None;
+    let mut delays = false;
 
     for obj in objects {
-        full_obj_string += &serialize_obj(obj)
+        for (key, param) in &obj.params {
+            let found: Vec<(usize, Id)> = match param {
+                ObjParam::Group(g) => vec![(0, g.id)],
+                ObjParam::GroupList(list) => list.iter().map(|g| (0, g.id)).collect(),
+                ObjParam::Color(c) => vec![(1, c.id)],
+                ObjParam::Block(b) => vec![(2, b.id)],
+                ObjParam::Item(i) => vec![(3, i.id)],
+                _ => Vec::new(),
+            };
+            for (class, id) in found {
+                if let Id::Specific(n) = id {
+                    if n != 0 {
+                        ids[class].insert(n);
+                    }
+                }
+            }
+            if let (20, ObjParam::Number(n)) | (61, ObjParam::Number(n)) = (key, param) {
+                layers.insert(*n as i64);
+            }
+            if let (63, ObjParam::Number(n)) = (key, param) {
+                delays |= *n > 0.0;
+            }
+        }
+        if !obj.params.contains_key(&20) {
+            layers.insert(0);
+        }
+        if obj.mode == ObjectMode::Trigger {
+            if let (Some(ObjParam::Number(x)), Some(ObjParam::Number(y))) =
+                (obj.params.get(&2), obj.params.get(&3))
+            {
+                trigger_area = Some(match trigger_area {
+                    Some([x1, y1, x2, y2]) => [x1.min(*x), y1.min(*y), x2.max(*x), y2.max(*y)],
+                    None => [*x, *y, *x, *y],
+                });
+            }
+        }
+    }
+    // every object gets this group, so it isn't something the script asked for
+    if let Id::Specific(n) = SPWN_SIGNATURE_GROUP.id {
+        ids[0].remove(&n);
+    }
+
+    let mut out = format!(
+        "What {} needs from the level\n\n",
+        script_path
+            .file_name()
+            .map_or(script_path.to_string_lossy(), |n| n.to_string_lossy())
+    );
+    out += &format!("Groups: {}\n", id_ranges(&ids[0]));
+    out += &format!("Colors: {}\n", id_ranges(&ids[1]));
+    out += &format!("Block IDs: {}\n", id_ranges(&ids[2]));
+    out += &format!("Item IDs: {}\n", id_ranges(&ids[3]));
+    out += &format!(
+        "Editor layers: {}\n",
+        layers
+            .iter()
+            .map(|l| l.to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    if let Some([x1, y1, x2, y2]) = trigger_area {
+        out += &format!(
+            "Triggers are placed from x {} to {} and y {} to {} (keep other objects out of there)\n",
+            x1, x2, y1, y2
+        );
     }
-    Ok((
-        full_obj_string,
-        [
-            closed_ids[0].len(),
-            closed_ids[1].len(),
-            closed_ids[2].len(),
-            closed_ids[3].len(),
-        ],
-    ))
+    if delays {
+        out += &format!(
+            "Waits are timed at {} fps from when the level starts, so a song offset moves the music but not the triggers\n",
+            fps
+        );
+    }
+    if let Id::Specific(n) = SPWN_SIGNATURE_GROUP.id {
+        out += &format!(
+            "\nEverything in group {} is made by spwn, and is replaced when the script is built again\n",
+            n
+        );
+    }
+    out
 }
 
-pub fn apply_fn_ids(func_ids: &[FunctionId]) -> Vec<GdObj> {
+// an object, and for triggers where it goes in the grid of triggers
+// (function, column, row from the top, if it's spawned)
+type Placed = (GdObj, Option<(usize, u32, u16, bool)>);
+
+pub fn apply_fn_ids(func_ids: &[FunctionId], area: &TriggerArea) -> Vec<GdObj> {
     //println!("{:?}", trigger);
 
     fn apply_fn_id(
@@ -438,10 +894,10 @@ pub fn apply_fn_ids(func_ids: &[FunctionId]) -> Vec<GdObj> {
         func_ids: &[FunctionId],
         x_offset: u32,
         y_offset: u16,
-    ) -> (Vec<GdObj>, u32) {
+    ) -> (Vec<Placed>, u32) {
         let id = func_ids[id_index].clone();
 
-        let mut objects = Vec::<GdObj>::new();
+        let mut objects = Vec::<Placed>::new();
 
         let mut current_x = 0;
         /*if !id.obj_list.is_empty() {
@@ -462,7 +918,7 @@ pub fn apply_fn_ids(func_ids: &[FunctionId]) -> Vec<GdObj> {
         for (i, (obj, _)) in objectlist.iter().enumerate() {
             match obj.mode {
                 ObjectMode::Object => {
-                    objects.push(obj.clone());
+                    objects.push((obj.clone(), None));
                 }
                 ObjectMode::Trigger => {
                     let y_pos = (i as u16) % possible_height + START_HEIGHT + y_offset;
@@ -487,18 +943,10 @@ pub fn apply_fn_ids(func_ids: &[FunctionId]) -> Vec<GdObj> {
                         new_obj.params.insert(87, ObjParam::Bool(true));
                     }
 
-                    new_obj.params.insert(
-                        2,
-                        if spawned {
-                            ObjParam::Number((x_pos * 30 + 15) as f64)
-                        } else {
-                            ObjParam::Number(0.0)
-                        },
-                    );
-                    new_obj
-                        .params
-                        .insert(3, ObjParam::Number(((80 - y_pos) * 30 + 15) as f64));
-                    objects.push(new_obj);
+                    objects.push((
+                        new_obj,
+                        Some((id_index, x_pos, y_pos - START_HEIGHT, spawned)),
+                    ));
                 }
             }
         }
@@ -521,17 +969,82 @@ pub fn apply_fn_ids(func_ids: &[FunctionId]) -> Vec<GdObj> {
         (objects, current_x)
     }
 
-    let mut full_obj_list = Vec::<GdObj>::new();
+    let mut placed = Vec::<Placed>::new();
 
     let mut current_x = 0;
     for (i, func_id) in func_ids.iter().enumerate() {
         if func_id.parent == None {
             let (objects, new_length) = apply_fn_id(i, &func_ids, current_x, 0);
-            full_obj_list.extend(objects);
+            placed.extend(objects);
 
             current_x += new_length;
         }
     }
+
+    // every function has its own columns, so with a max width the functions that
+    // don't fit are moved to the next band (a function is only split if it's wider
+    // than the max width by itself, since triggers in a group run from left to right)
+    let mut firsts: HashMap<usize, u32> = HashMap::new();
+    let mut widths: HashMap<usize, u32> = HashMap::new();
+    for (_, pos) in &placed {
+        if let Some((f, x, _, _)) = pos {
+            let first = firsts.entry(*f).or_insert(*x);
+            *first = (*first).min(*x);
+        }
+    }
+    for (_, pos) in &placed {
+        if let Some((f, x, _, _)) = pos {
+            let w = widths.entry(*f).or_insert(0);
+            *w = (*w).max(x - firsts[f] + 1);
+        }
+    }
+    let mut starts: Vec<(u32, usize)> = firsts.iter().map(|(f, first)| (*first, *f)).collect();
+    starts.sort_unstable();
+    // where each function's first column goes (band, column in the band)
+    let mut moved: HashMap<usize, (u32, u32)> = HashMap::new();
+    let (mut band, mut cursor) = (0, 0);
+    for (first, f) in starts {
+        if area.max_width == 0 {
+            moved.insert(f, (0, first));
+            continue;
+        }
+        let w = widths[&f];
+        if cursor > 0 && cursor + w > area.max_width {
+            band += 1;
+            cursor = 0;
+        }
+        moved.insert(f, (band, cursor));
+        cursor += w + 1;
+    }
+
+    let height = (MAX_HEIGHT - START_HEIGHT) as u32;
+    let mut full_obj_list = Vec::<GdObj>::new();
+    for (mut obj, pos) in placed {
+        if let Some((f, x, row, spawned)) = pos {
+            let (band, first) = moved[&f];
+            let column = x - firsts[&f] + first;
+            // a function wider than the max width goes on in the next bands
+            let (band, column) = match column.checked_div(area.max_width) {
+                Some(bands) => (band + bands, column % area.max_width),
+                None => (band, column),
+            };
+            let (bx, by) = match area.stacking {
+                Stacking::Horizontal => (
+                    area.x + column,
+                    area.y + band * (height + 1) + (height - 1 - row as u32),
+                ),
+                Stacking::Vertical => (area.x + band * (height + 1) + row as u32, area.y + column),
+            };
+            // the ones that aren't spawned run when the level starts
+            obj.params.insert(
+                2,
+                ObjParam::Number(if spawned { (bx * 30 + 15) as f64 } else { 0.0 }),
+            );
+            obj.params
+                .insert(3, ObjParam::Number((by * 30 + 15) as f64));
+        }
+        full_obj_list.push(obj);
+    }
     full_obj_list
 }
 /* PYTHON CODE IM USING
@@ -686,9 +1199,12 @@ pub fn serialize_triggers_old(func_ids: Vec<FunctionID>) -> String {
 */
 //</OLD>
 
+#[cfg(feature = "native")]
 use libflate::{gzip, zlib};
+#[cfg(feature = "native")]
 use std::io::Read;
 
+#[cfg(feature = "native")]
 fn xor(data: Vec<u8>, key: u8) -> Vec<u8> {
     let mut new_data = Vec::new();
 
@@ -698,6 +1214,7 @@ fn xor(data: Vec<u8>, key: u8) -> Vec<u8> {
     }
     new_data
 }
+#[cfg(feature = "native")]
 fn base_64_decrypt(encoded: Vec<u8>) -> Vec<u8> {
     let mut new_data = encoded;
     while new_data.len() % 4 != 0 {
@@ -706,54 +1223,93 @@ fn base_64_decrypt(encoded: Vec<u8>) -> Vec<u8> {
     base64::decode(String::from_utf8(new_data).unwrap().as_str()).unwrap()
 }
 
+#[cfg(feature = "native")]
 use quick_xml::events::{BytesText, Event};
+#[cfg(feature = "native")]
 use quick_xml::Reader;
 //use std::io::BufReader;
-fn decrypt_savefile(mut sf: Vec<u8>) -> Result<Vec<u8>, String> {
-    if cfg!(target_os = "macos") {
-        use aes::Aes256;
-
-        use block_modes::block_padding::Pkcs7;
-        use block_modes::{BlockMode, Ecb};
+#[cfg(feature = "native")]
+fn decrypt_savefile(mut sf: Vec<u8>, format: &SaveFormat) -> Result<Vec<u8>, String> {
+    match &format.encryption {
+        Encryption::Aes(key) => {
+            use aes::Aes256;
 
-        const IOS_KEY: &[u8] = &[
-            0x69, 0x70, 0x75, 0x39, 0x54, 0x55, 0x76, 0x35, 0x34, 0x79, 0x76, 0x5D, 0x69, 0x73,
-            0x46, 0x4D, 0x68, 0x35, 0x40, 0x3B, 0x74, 0x2E, 0x35, 0x77, 0x33, 0x34, 0x45, 0x32,
-            0x52, 0x79, 0x40, 0x7B,
-        ];
+            use block_modes::block_padding::Pkcs7;
+            use block_modes::{BlockMode, Ecb};
 
-        type AesEcb = Ecb<Aes256, Pkcs7>;
+            type AesEcb = Ecb<Aes256, Pkcs7>;
 
-        // re-create cipher mode instance
-        let cipher = AesEcb::new_var(&IOS_KEY, &[]).unwrap();
+            // re-create cipher mode instance
+            let cipher = AesEcb::new_var(key, &[]).map_err(|e| format!("{}", e))?;
 
-        Ok(match cipher.decrypt(&mut sf) {
-            Ok(v) => v,
-            Err(e) => return Err(format!("{}", e)),
+            Ok(match cipher.decrypt(&mut sf) {
+                Ok(v) => v,
+                Err(e) => return Err(format!("{}", e)),
+            }
+            .to_vec())
+        }
+        Encryption::Xor(key) => {
+            let xor = xor(sf.to_vec(), *key);
+            let replaced = String::from_utf8_lossy(&xor)
+                .replace("-", "+")
+                .replace("_", "/")
+                .replace("\0", "");
+            let b64 = match base64::decode(replaced.as_str()) {
+                Ok(b) => b,
+                Err(e) => return Err(format!("{}", e)),
+            };
+            if !format.compressed {
+                return Ok(b64);
+            }
+            let mut decoder = gzip::Decoder::new(&b64[..]).map_err(|e| {
+                format!(
+                    "{} (the savefile might be encrypted differently, see [savefile] in spwn.toml)",
+                    e
+                )
+            })?;
+            let mut data = Vec::new();
+            decoder
+                .read_to_end(&mut data)
+                .map_err(|e| format!("{}", e))?;
+            Ok(data)
         }
-        .to_vec())
-    } else {
-        let xor = xor(sf.to_vec(), 11);
-        let replaced = String::from_utf8_lossy(&xor)
-            .replace("-", "+")
-            .replace("_", "/")
-            .replace("\0", "");
-        let b64 = match base64::decode(replaced.as_str()) {
-            Ok(b) => b,
-            Err(e) => return Err(format!("{}", e)),
-        };
-        let mut decoder = gzip::Decoder::new(&b64[..]).unwrap();
-        let mut data = Vec::new();
-        decoder.read_to_end(&mut data).unwrap();
-        Ok(data)
     }
 }
-pub fn get_level_string(ls: Vec<u8>, level_name: Option<String>) -> Result<String, String> {
+#[cfg(feature = "native")]
+pub fn get_level_string(
+    ls: Vec<u8>,
+    level_name: Option<String>,
+    format: &SaveFormat,
+) -> Result<String, String> {
     //decrypting the savefile
-    let content = decrypt_savefile(ls)?;
-    let string_content = String::from_utf8_lossy(&content);
+    let content = decrypt_savefile(ls, format)?;
+    match find_level_string(&String::from_utf8_lossy(&content), &level_name)? {
+        Some(level_string) => Ok(level_string),
+        None => match level_name {
+            Some(level_name) => Err(format!("Level named \"{}\" was not found!", level_name)),
+            None => {
+                Err("No level found! Please create a level for SPWN to operate on!".to_string())
+            }
+        },
+    }
+}
 
-    let mut reader = Reader::from_str(&string_content);
+// the decrypted savefile
+#[cfg(feature = "native")]
+pub fn read_savefile(path: &std::path::Path, format: &SaveFormat) -> Result<String, String> {
+    let file_content = fs::read(path).map_err(|e| format!("Couldn't read the savefile: {}", e))?;
+    let content = decrypt_savefile(file_content, format)?;
+    Ok(String::from_utf8_lossy(&content).to_string())
+}
+
+// the level string of the level with that name (or the first level) in the decrypted
+// savefile, or none if there is no such level
+#[cfg(feature = "native")]
+pub fn find_level_string(
+    string_content: &str,
+    level_name: &Option<String>,
+) -> Result<Option<String>, String> {
+    let mut reader = Reader::from_str(string_content);
     reader.trim_text(true);
 
     let mut buf = Vec::new();
@@ -779,8 +1335,8 @@ pub fn get_level_string(ls: Vec<u8>, level_name: Option<String>) -> Result<Strin
                         );
                     }
                 } else if k2_detected {
-                    if let Some(level_name) = level_name.clone() {
-                        if text == level_name {
+                    if let Some(level_name) = level_name {
+                        if text == *level_name {
                             level_detected = true
                         }
                     } else {
@@ -811,13 +1367,7 @@ pub fn get_level_string(ls: Vec<u8>, level_name: Option<String>) -> Result<Strin
             .to_string()
         );
     } else if !k4_detected {
-        if let Some(level_name) = level_name {
-            return Err(format!("Level named \"{}\" was not found!", level_name));
-        } else {
-            return Err(
-                "No level found! Please create a level for SPWN to operate on!".to_string(),
-            );
-        }
+        return Ok(None);
     }
 
     /*let mut k4_detected = false;
@@ -832,9 +1382,15 @@ pub fn get_level_string(ls: Vec<u8>, level_name: Option<String>) -> Result<Strin
             }
         }
     }*/
+    Ok(Some(decode_level_string(&level_string)))
+}
+
+// the other way around from encode_level_string
+#[cfg(feature = "native")]
+fn decode_level_string(encoded: &str) -> String {
     //decrypting level string
     let ls_b64 = base_64_decrypt(
-        level_string
+        encoded
             .replace("-", "+")
             .replace("_", "/")
             .replace("\0", "")
@@ -848,26 +1404,58 @@ pub fn get_level_string(ls: Vec<u8>, level_name: Option<String>) -> Result<Strin
     let mut ls_buf = Vec::new();
     ls_decoder.read_to_end(&mut ls_buf).unwrap();
 
-    Ok(String::from_utf8(ls_buf).unwrap())
+    String::from_utf8(ls_buf).unwrap()
+}
+
+// a level string copied out of the game, either as it is or the way it's stored (then the
+// bool is true). unlike in the savefile, anything could be in it
+#[cfg(feature = "native")]
+pub fn read_copied_level(text: &str) -> Result<(String, bool), String> {
+    let text = text.trim();
+    if !text.starts_with("H4sI") {
+        return if text.contains(';') {
+            Ok((text.to_string(), false))
+        } else {
+            Err("That isn't a level string".to_string())
+        };
+    }
+    let padding = "=".repeat((4 - text.len() % 4) % 4);
+    let error = |e: String| format!("That isn't a level string: {}", e);
+    let bytes = base64::decode(&(text.replace("-", "+").replace("_", "/") + &padding))
+        .map_err(|e| error(format!("{}", e)))?;
+    let mut decoder = gzip::Decoder::new(&bytes[..]).map_err(|e| error(format!("{}", e)))?;
+    let mut level = Vec::new();
+    decoder
+        .read_to_end(&mut level)
+        .map_err(|e| error(format!("{}", e)))?;
+    String::from_utf8(level)
+        .map(|level| (level, true))
+        .map_err(|e| error(format!("{}", e)))
 }
 
+#[cfg(feature = "native")]
 use quick_xml::Writer;
+#[cfg(feature = "native")]
 use std::fs;
+#[cfg(feature = "native")]
 use std::io::Cursor;
+#[cfg(feature = "native")]
 use std::path::PathBuf;
 
+#[cfg(feature = "native")]
 pub fn encrypt_level_string(
     ls: String,
     old_ls: String,
     path: PathBuf,
     level_name: Option<String>,
+    format: &SaveFormat,
 ) -> Result<(), String> {
     let mut file = fs::File::open(path.clone()).unwrap();
     let mut file_content = Vec::new();
     file.read_to_end(&mut file_content).unwrap();
 
     //decrypting the savefile
-    let content = decrypt_savefile(file_content)?;
+    let content = decrypt_savefile(file_content, format)?;
     let string_content = String::from_utf8_lossy(&content);
 
     let mut reader = Reader::from_str(&string_content);
@@ -892,14 +1480,7 @@ pub fn encrypt_level_string(
             Ok(Event::Text(e)) => {
                 let text = e.unescape_and_decode(&reader).unwrap();
                 if k4_detected && level_detected {
-                    let encrypted_ls: String = {
-                        let mut ls_encoder = gzip::Encoder::new(Vec::new()).unwrap();
-                        ls_encoder.write_all(&full_ls.as_bytes()).unwrap();
-                        let b64_encrypted =
-                            base64::encode(&ls_encoder.finish().into_result().unwrap());
-                        let fin = b64_encrypted.replace("+", "-").replace("/", "_");
-                        "H4sIAAAAAAAAC".to_string() + &fin[13..]
-                    };
+                    let encrypted_ls = encode_level_string(&full_ls);
 
                     assert!(writer
                         .write_event(Event::Text(BytesText::from_plain_str(&encrypted_ls)))
@@ -943,53 +1524,283 @@ pub fn encrypt_level_string(
         buf.clear();
     }
     let bytes = writer.into_inner().into_inner();
+    encrypt_savefile(bytes, path, format);
+    Ok(())
+}
+
+// the level string the way it's stored in the savefile (gzipped and in base64)
+#[cfg(feature = "native")]
+pub fn encode_level_string(ls: &str) -> String {
+    use std::io::Write;
+    let mut ls_encoder = gzip::Encoder::new(Vec::new()).unwrap();
+    ls_encoder.write_all(ls.as_bytes()).unwrap();
+    let b64_encrypted = base64::encode(&ls_encoder.finish().into_result().unwrap());
+    let fin = b64_encrypted.replace("+", "-").replace("/", "_");
+    "H4sIAAAAAAAAC".to_string() + &fin[13..]
+}
+
+#[cfg(feature = "native")]
+fn encrypt_savefile(bytes: Vec<u8>, path: PathBuf, format: &SaveFormat) {
     //encrypt level save
     use std::io::Write;
 
-    if cfg!(target_os = "macos") {
-        use aes::Aes256;
+    match &format.encryption {
+        Encryption::Aes(key) => {
+            use aes::Aes256;
 
-        use block_modes::block_padding::Pkcs7;
-        use block_modes::{BlockMode, Ecb};
+            use block_modes::block_padding::Pkcs7;
+            use block_modes::{BlockMode, Ecb};
 
-        const IOS_KEY: &[u8] = &[
-            0x69, 0x70, 0x75, 0x39, 0x54, 0x55, 0x76, 0x35, 0x34, 0x79, 0x76, 0x5D, 0x69, 0x73,
-            0x46, 0x4D, 0x68, 0x35, 0x40, 0x3B, 0x74, 0x2E, 0x35, 0x77, 0x33, 0x34, 0x45, 0x32,
-            0x52, 0x79, 0x40, 0x7B,
-        ];
+            type AesEcb = Ecb<Aes256, Pkcs7>;
 
-        type AesEcb = Ecb<Aes256, Pkcs7>;
+            // re-create cipher mode instance
+            let cipher = AesEcb::new_var(key, &[]).unwrap();
 
-        // re-create cipher mode instance
-        let cipher = AesEcb::new_var(&IOS_KEY, &[]).unwrap();
+            let fin = cipher.encrypt_vec(&bytes);
+            assert!(fs::write(path, fin).is_ok());
+        }
+        Encryption::Xor(key) => {
+            let with_signature = if format.compressed {
+                let mut encoder = zlib::Encoder::new(Vec::new()).unwrap();
+                encoder.write_all(&bytes).unwrap();
+                let compressed = encoder.finish().into_result().unwrap();
+                use crc32fast::Hasher;
+
+                let mut hasher = Hasher::new();
+                hasher.update(&bytes);
+                let checksum = hasher.finalize();
+
+                let data_size = bytes.len() as u32;
+
+                let mut with_signature = b"\x1f\x8b\x08\x00\x00\x00\x00\x00\x00\x0b".to_vec();
+                with_signature.extend(&compressed[2..compressed.len() - 4]);
+                with_signature.extend(checksum.to_le_bytes().to_vec());
+                with_signature.extend(data_size.to_le_bytes().to_vec());
+                with_signature
+            } else {
+                bytes
+            };
+
+            let encoded = base64::encode(&with_signature)
+                .replace("+", "-")
+                .replace("/", "_")
+                .as_bytes()
+                .to_vec();
+
+            let fin = xor(encoded, *key);
+            assert!(fs::write(path, fin).is_ok());
+        }
+    }
+}
 
-        let fin = cipher.encrypt_vec(&bytes);
-        assert!(fs::write(path, fin).is_ok());
-    } else {
-        let mut encoder = zlib::Encoder::new(Vec::new()).unwrap();
-        encoder.write_all(&bytes).unwrap();
-        let compressed = encoder.finish().into_result().unwrap();
-        use crc32fast::Hasher;
+// a level for --create-level
+#[cfg(feature = "native")]
+pub struct NewLevel {
+    pub name: String,
+    pub description: String,
+    // official song (0 is stereo madness)
+    pub song: u32,
+}
+
+// the settings of a new level (default colors, speed and game mode)
+#[cfg(feature = "native")]
+const EMPTY_LEVEL: &str = "kA13,0,kA15,0,kA16,0,kA14,,kA6,0,kA7,0,kA17,0,kA18,0,kS39,0,kA2,0,kA3,0,kA8,0,kA4,0,kA9,0,kA10,0,kA11,0;";
+
+#[cfg(feature = "native")]
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// adds an empty level to the top of the savefile's level list (xml is the decrypted
+// savefile, which doesn't have the level yet), and returns its level string
+#[cfg(feature = "native")]
+pub fn create_level(
+    xml: &str,
+    path: PathBuf,
+    level: &NewLevel,
+    format: &SaveFormat,
+) -> Result<String, String> {
+    const LEVEL_LIST: &str = "<k>LLM_01</k><d>";
+    let mut list_start = match xml.find(LEVEL_LIST) {
+        Some(i) => i + LEVEL_LIST.len(),
+        None => return Err("Couldn't find the list of levels in the savefile".to_string()),
+    };
+    if xml[list_start..].starts_with("<k>_isArr</k><t />") {
+        list_start += "<k>_isArr</k><t />".len();
+    }
 
-        let mut hasher = Hasher::new();
-        hasher.update(&bytes);
-        let checksum = hasher.finalize();
+    // the levels are k_0, k_1, ... with the newest first, so the others move down one
+    let index = regex::Regex::new(r"<k>k_(\d+)</k>").unwrap();
+    let rest = index.replace_all(&xml[list_start..], |caps: &regex::Captures| {
+        format!("<k>k_{}</k>", caps[1].parse::<usize>().unwrap() + 1)
+    });
+    let entry = format!("<k>k_0</k><d>{}</d>", level_entry(level, EMPTY_LEVEL));
 
-        let data_size = bytes.len() as u32;
+    let new_xml = format!("{}{}{}", &xml[..list_start], entry, rest);
+    encrypt_savefile(new_xml.into_bytes(), path, format);
+    Ok(EMPTY_LEVEL.to_string())
+}
 
-        let mut with_signature = b"\x1f\x8b\x08\x00\x00\x00\x00\x00\x00\x0b".to_vec();
-        with_signature.extend(&compressed[2..compressed.len() - 4]);
-        with_signature.extend(checksum.to_le_bytes().to_vec());
-        with_signature.extend(data_size.to_le_bytes().to_vec());
+// .gmd files are single levels (like GDShare exports them): the level's entry from the
+// savefile, in a plist of its own
+#[cfg(feature = "native")]
+pub fn is_gmd(path: &std::path::Path) -> bool {
+    matches!(path.extension(), Some(e) if e.eq_ignore_ascii_case("gmd"))
+}
 
-        let encoded = base64::encode(&with_signature)
+#[cfg(feature = "native")]
+fn level_entry(level: &NewLevel, level_string: &str) -> String {
+    // a level string without the settings at the start (when there was no level to build
+    // into) gets the ones of a new level
+    let level_string = if level_string.starts_with("kA") || level_string.starts_with("kS") {
+        level_string.to_string()
+    } else {
+        EMPTY_LEVEL.to_string() + level_string
+    };
+    format!(
+        "<k>kCEK</k><i>4</i><k>k2</k><s>{}</s><k>k3</k><s>{}</s><k>k4</k><s>{}</s><k>k8</k><i>{}</i><k>k13</k><t /><k>k21</k><i>2</i><k>k50</k><i>35</i>",
+        xml_escape(&level.name),
+        base64::encode(level.description.as_bytes())
             .replace("+", "-")
-            .replace("/", "_")
-            .as_bytes()
-            .to_vec();
+            .replace("/", "_"),
+        encode_level_string(&level_string),
+        level.song
+    )
+}
+
+#[cfg(feature = "native")]
+pub fn write_gmd(
+    path: &std::path::Path,
+    level: &NewLevel,
+    level_string: &str,
+) -> Result<(), String> {
+    let content = format!(
+        "<?xml version=\"1.0\"?><plist version=\"1.0\" gjver=\"2.0\"><dict>{}</dict></plist>",
+        level_entry(level, level_string)
+    );
+    fs::write(path, content)
+        .map_err(|e| format!("Couldn't write {}: {}", path.to_string_lossy(), e))
+}
 
-        let fin = xor(encoded, 11);
-        assert!(fs::write(path, fin).is_ok());
+// the level in a .gmd file, and its level string
+#[cfg(feature = "native")]
+pub fn read_gmd(path: &std::path::Path) -> Result<(NewLevel, String), String> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("Couldn't read {}: {}", path.to_string_lossy(), e))?;
+    let mut reader = Reader::from_str(&content);
+    reader.trim_text(true);
+    let mut buf = Vec::new();
+    let mut values = HashMap::<String, String>::new();
+    let mut key: Option<String> = None;
+    let mut in_key = false;
+    loop {
+        match reader.read_event(&mut buf) {
+            Ok(Event::Start(e)) => in_key = e.name() == b"k",
+            Ok(Event::Text(e)) => {
+                let text = e
+                    .unescape_and_decode(&reader)
+                    .map_err(|e| format!("{} isn't a level: {}", path.to_string_lossy(), e))?;
+                if in_key {
+                    key = Some(text);
+                } else if let Some(k) = key.take() {
+                    values.entry(k).or_insert(text);
+                }
+            }
+            Ok(Event::Eof) => break,
+            Err(e) => return Err(format!("{} isn't a level: {}", path.to_string_lossy(), e)),
+            _ => (),
+        }
+        buf.clear();
+    }
+    let encoded = match values.get("k4") {
+        Some(ls) => ls,
+        None => {
+            return Err(format!(
+                "{} has no level data (open the level in Geometry Dash and save it once before exporting it)",
+                path.to_string_lossy()
+            ))
+        }
+    };
+    let description = values
+        .get("k3")
+        .and_then(|d| {
+            // gd leaves out the padding sometimes
+            let padding = "=".repeat((4 - d.len() % 4) % 4);
+            base64::decode(&(d.replace("-", "+").replace("_", "/") + &padding)).ok()
+        })
+        .map(|d| String::from_utf8_lossy(&d).to_string())
+        .unwrap_or_default();
+    let level = NewLevel {
+        name: values.get("k2").cloned().unwrap_or_default(),
+        description,
+        song: values.get("k8").and_then(|s| s.parse().ok()).unwrap_or(0),
+    };
+    Ok((level, decode_level_string(encoded)))
+}
+
+#[cfg(all(test, feature = "native"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gmd_files_keep_the_level() {
+        let path = std::env::temp_dir().join(format!("spwn-test-{}.gmd", std::process::id()));
+        let level = NewLevel {
+            name: "a <b> level".to_string(),
+            description: "made with spwn".to_string(),
+            song: 3,
+        };
+        // nothing repeats in the level, since libflate 0.1's decoder trips the checks of
+        // debug builds when it has to copy a repeated part
+        let level_string = "kS38,1;1,1,2,15,3,45;";
+        write_gmd(&path, &level, level_string).unwrap();
+        let (read, read_string) = read_gmd(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(read.name, level.name);
+        assert_eq!(read.description, level.description);
+        assert_eq!(read.song, 3);
+        assert_eq!(read_string, level_string);
+        assert!(is_gmd(std::path::Path::new("level.GMD")));
+    }
+
+    #[test]
+    fn copied_levels_can_be_encoded_or_not() {
+        let level_string = "kS38,1;1,1,2,15,3,45;";
+        assert_eq!(
+            read_copied_level(level_string),
+            Ok((level_string.to_string(), false))
+        );
+        assert_eq!(
+            read_copied_level(&encode_level_string(level_string)),
+            Ok((level_string.to_string(), true))
+        );
+        assert!(read_copied_level("hello").is_err());
+        assert!(read_copied_level("H4sIAAAA").is_err());
+    }
+
+    #[test]
+    fn levels_are_found_by_their_name() {
+        let level_string = "kS38,1;1,1,2,15,3,45;";
+        let savefile = format!(
+            "<?xml version=\"1.0\"?><plist version=\"1.0\" gjver=\"2.0\"><dict><k>LLM_01</k><d><k>_isArr</k><t /><k>k_0</k><d><k>k2</k><s>other level</s><k>k4</k><s>{}</s></d><k>k_1</k><d><k>k2</k><s>a &lt;b&gt; level</s><k>k4</k><s>{}</s></d></d></dict></plist>",
+            encode_level_string("kS38,2;"),
+            encode_level_string(level_string)
+        );
+        let name = |n: &str| Some(n.to_string());
+        assert_eq!(
+            find_level_string(&savefile, &name("a <b> level")),
+            Ok(Some(level_string.to_string()))
+        );
+        assert_eq!(
+            find_level_string(&savefile, &None),
+            Ok(Some("kS38,2;".to_string()))
+        );
+        // not a level that only starts with the name
+        assert_eq!(find_level_string(&savefile, &name("a <b>")), Ok(None));
+        assert_eq!(find_level_string(&savefile, &name("level")), Ok(None));
     }
-    Ok(())
 }
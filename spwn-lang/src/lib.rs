@@ -0,0 +1,126 @@
+// the compiler as a library, for tools that build spwn scripts without running the spwn
+// binary (the binary in main.rs uses it too). Spwn (in api.rs) is the way in:
+//
+// let level = spwn::Spwn::builder()
+//     .include_path("libraries")
+//     .optimize(true)
+//     .compile_file("main.spwn")?;
+//
+// the modules are public for the binary, but the api is what's meant to be used
+pub mod aliases;
+pub mod analyzer;
+pub mod ast;
+pub mod budget;
+pub mod builtin;
+pub mod cache_format;
+pub mod centers;
+#[cfg(feature = "native")]
+pub mod clipboard;
+pub mod colors;
+pub mod compiler;
+pub mod compiler_info;
+pub mod compiler_types;
+pub mod deadcode;
+pub mod decompile;
+pub mod diagnostics;
+#[cfg(feature = "native")]
+pub mod doc_server;
+pub mod documentation;
+pub mod files;
+pub mod flags;
+pub mod fmt;
+#[cfg(feature = "native")]
+pub mod gd_process;
+pub mod globals;
+pub mod group_reuse;
+pub mod hints;
+pub mod history;
+pub mod icalgebra;
+pub mod import_cache;
+pub mod import_costs;
+pub mod interrupt;
+pub mod item_reuse;
+pub mod levelstring;
+pub mod limits;
+pub mod lints;
+pub mod lock;
+pub mod loop_hoist;
+pub mod lower;
+#[cfg(feature = "native")]
+pub mod lsp;
+pub mod macro_cache;
+pub mod manifest;
+pub mod noise;
+pub mod nullcheck;
+pub mod ownership;
+#[cfg(feature = "native")]
+pub mod packages;
+pub mod parser;
+pub mod partial;
+pub mod peephole;
+pub mod preview;
+pub mod project;
+#[cfg(feature = "native")]
+pub mod save_format;
+pub mod shapes;
+pub mod spawn_order;
+#[cfg(feature = "native")]
+pub mod sync;
+pub mod timing;
+pub mod trace;
+pub mod trigger_area;
+#[cfg(feature = "native")]
+pub mod tui;
+pub mod typeset;
+pub mod value;
+#[cfg(feature = "native")]
+pub mod watch;
+
+pub mod context;
+#[cfg(feature = "native")]
+#[cfg_attr(target_os = "macos", path = "editorlive_mac.rs")]
+#[cfg_attr(windows, path = "editorlive_win.rs")]
+#[cfg_attr(
+    not(any(target_os = "macos", windows)),
+    path = "editorlive_unavailable.rs"
+)]
+pub mod editorlive;
+pub mod optimize;
+pub mod value_storage;
+
+mod api;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+pub use api::{CompiledLevel, Diagnostics, Remarks, Spwn};
+
+use std::env;
+use std::io::Write;
+use termcolor::{Color, ColorChoice, ColorSpec, StandardStream, WriteColor};
+
+pub const STD_PATH: &str = "std";
+
+// the parser and the compiler recurse for every nested expression, statement
+// and macro call, which runs out of the default stack (1 MB on windows) quickly
+pub const STACK_SIZE: usize = 512 * 1024 * 1024;
+
+// plain text when NO_COLOR is set, the terminal can't show colors,
+// or the output goes to a file or another program
+pub fn color_choice(stream: &impl std::io::IsTerminal) -> ColorChoice {
+    let no_color = matches!(env::var_os("NO_COLOR"), Some(v) if !v.is_empty());
+    let dumb_terminal = matches!(env::var("TERM"), Ok(t) if t == "dumb");
+    if no_color || dumb_terminal || !stream.is_terminal() {
+        ColorChoice::Never
+    } else {
+        ColorChoice::Always
+    }
+}
+
+pub fn print_with_color(text: &str, color: Color) {
+    let mut stdout = StandardStream::stdout(color_choice(&std::io::stdout()));
+    stdout
+        .set_color(ColorSpec::new().set_fg(Some(color)))
+        .unwrap();
+    writeln!(&mut stdout, "{}", text).unwrap();
+    stdout.set_color(&ColorSpec::new()).unwrap();
+}
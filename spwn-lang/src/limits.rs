@@ -0,0 +1,193 @@
+// things the game can't do, which only show up as glitches when the level is played
+// (spwn build --strict-gd-limits makes them errors)
+use crate::ast::ObjectMode;
+use crate::builtin::{Group, Id};
+use crate::levelstring::{GdObj, ObjParam};
+
+use std::collections::{HashMap, HashSet};
+
+// including the group every spwn object gets (see SPWN_SIGNATURE_GROUP)
+const MAX_GROUPS: usize = 10;
+const MAX_USER_GROUPS: usize = MAX_GROUPS - 1;
+
+// what the editor allows, other scales change when the object is edited
+const MIN_SCALE: f64 = 0.5;
+const MAX_SCALE: f64 = 2.0;
+
+const MAX_TEXT_LENGTH: usize = 255;
+
+// triggers started in one frame by spawning without a delay, more than this
+// makes that frame lag
+const MAX_TRIGGERS_PER_FRAME: usize = 1000;
+
+// triggers that start the group they target
+const ACTIVATORS: [f64; 6] = [1268.0, 1611.0, 1811.0, 1595.0, 1815.0, 1812.0];
+
+fn number(obj: &GdObj, key: u16) -> Option<f64> {
+    match obj.params.get(&key) {
+        Some(ObjParam::Number(n)) => Some(*n),
+        _ => None,
+    }
+}
+
+fn groups(obj: &GdObj) -> Vec<Group> {
+    let list = match obj.params.get(&57) {
+        Some(ObjParam::Group(g)) => vec![*g],
+        Some(ObjParam::GroupList(l)) => l.clone(),
+        _ => Vec::new(),
+    };
+    list.into_iter()
+        .filter(|g| g.id != Id::Specific(0))
+        .collect()
+}
+
+// the group a spawn trigger starts on the same frame, if it doesn't have a delay
+// (the other activators only start their group if something happens)
+fn starts_now(obj: &GdObj) -> Option<Group> {
+    if number(obj, 1) != Some(1268.0) {
+        return None;
+    }
+    let delay = match obj.params.get(&63) {
+        Some(ObjParam::Number(d)) => *d,
+        Some(ObjParam::Epsilon) => return None,
+        _ => 0.0,
+    };
+    if delay > 0.0 {
+        return None;
+    }
+    match obj.params.get(&51) {
+        Some(ObjParam::Group(g)) => Some(*g),
+        _ => None,
+    }
+}
+
+fn check_object(obj: &GdObj, out: &mut Vec<(usize, String)>) {
+    let group_count = groups(obj).len();
+    if group_count > MAX_USER_GROUPS {
+        out.push((
+            obj.unique_id,
+            format!(
+                "object has {} groups, but only {} fit (objects can have {}, and spwn uses one)",
+                group_count, MAX_USER_GROUPS, MAX_GROUPS
+            ),
+        ));
+    }
+
+    if let Some(scale) = number(obj, 32) {
+        if !(MIN_SCALE..=MAX_SCALE).contains(&scale) {
+            out.push((
+                obj.unique_id,
+                format!(
+                    "scale of {} is outside what the editor allows ({} to {})",
+                    scale, MIN_SCALE, MAX_SCALE
+                ),
+            ));
+        }
+    }
+
+    for (key, param) in &obj.params {
+        if let ObjParam::Number(n) = param {
+            if !n.is_finite() {
+                out.push((
+                    obj.unique_id,
+                    format!("property {} is {}, which can't be saved in a level", key, n),
+                ));
+            }
+        }
+    }
+
+    // text is stored in base64
+    if let Some(ObjParam::Text(t)) = obj.params.get(&31) {
+        let length = base64::decode(t).map_or(t.len(), |s| s.len());
+        if length > MAX_TEXT_LENGTH {
+            out.push((
+                obj.unique_id,
+                format!(
+                    "text is {} characters long, but text objects can only have {}",
+                    length, MAX_TEXT_LENGTH
+                ),
+            ));
+        }
+    }
+}
+
+// how many triggers run on the frame a group is started, or none for
+// a loop of spawns without a delay (which freezes the game)
+fn triggers_in_frame(
+    g: Group,
+    triggers: &HashMap<Group, Vec<&GdObj>>,
+    known: &mut HashMap<Group, Option<usize>>,
+    visiting: &mut HashSet<Group>,
+) -> Option<usize> {
+    if let Some(count) = known.get(&g) {
+        return *count;
+    }
+    if !visiting.insert(g) {
+        return None;
+    }
+    let mut count = Some(0);
+    for obj in triggers.get(&g).map(|v| &v[..]).unwrap_or(&[]) {
+        count = count.map(|c| c + 1);
+        if let Some(target) = starts_now(obj) {
+            count = match (count, triggers_in_frame(target, triggers, known, visiting)) {
+                (Some(a), Some(b)) => Some(a + b),
+                _ => None,
+            };
+        }
+    }
+    visiting.remove(&g);
+    known.insert(g, count);
+    count
+}
+
+// warnings for everything that goes over the game's limits: (unique id, message)
+pub fn check_limits(objects: &[GdObj]) -> Vec<(usize, String)> {
+    let mut out = Vec::new();
+    for obj in objects {
+        check_object(obj, &mut out);
+    }
+
+    let mut triggers = HashMap::<Group, Vec<&GdObj>>::new();
+    let mut started_now = HashSet::new();
+    for obj in objects {
+        if obj.mode != ObjectMode::Trigger {
+            continue;
+        }
+        for g in groups(obj) {
+            triggers.entry(g).or_default().push(obj);
+        }
+        if let Some(target) = starts_now(obj) {
+            started_now.insert(target);
+        }
+    }
+
+    // only warn where the frame starts (after a delay, or from something that isn't a spawn),
+    // not for every spawn along the way
+    let mut known = HashMap::new();
+    for obj in objects {
+        if !matches!(number(obj, 1), Some(id) if ACTIVATORS.contains(&id))
+            || groups(obj).iter().any(|g| started_now.contains(g))
+        {
+            continue;
+        }
+        let target = match obj.params.get(&51) {
+            Some(ObjParam::Group(g)) => *g,
+            _ => continue,
+        };
+        match triggers_in_frame(target, &triggers, &mut known, &mut HashSet::new()) {
+            None => out.push((
+                obj.unique_id,
+                "starts a loop of spawns without a delay, which freezes the game".to_string(),
+            )),
+            Some(count) if count > MAX_TRIGGERS_PER_FRAME => out.push((
+                obj.unique_id,
+                format!(
+                    "starts {} triggers in the same frame (more than {} lags the game)",
+                    count, MAX_TRIGGERS_PER_FRAME
+                ),
+            )),
+            _ => (),
+        }
+    }
+    out
+}
@@ -0,0 +1,433 @@
+// warnings about code that is probably a mistake: variables, macro arguments and imports
+// that are never used, statements that can never run and definitions that hide another
+// one with the same name. like nullcheck, these only look at the script's own files.
+// #[allow(unused)], #[allow(unreachable)] or #[allow(shadowing)] before a statement (or at
+// the start of the file) turns them off for it, and --deny-warnings makes them errors
+use crate::ast::*;
+use crate::compiler_types::ImportType;
+use crate::parser::FileRange;
+
+use std::path::PathBuf;
+
+pub const UNUSED: &str = "unused";
+pub const UNREACHABLE: &str = "unreachable";
+pub const SHADOWING: &str = "shadowing";
+
+pub struct Warning {
+    // which of the lints above it is
+    pub lint: &'static str,
+    pub message: String,
+    pub file: PathBuf,
+    pub pos: FileRange,
+}
+
+struct Definition {
+    name: String,
+    pos: FileRange,
+    // "variable", "argument" or the file for an import
+    kind: String,
+    used: bool,
+    allowed: bool,
+}
+
+struct Checker<'a> {
+    scopes: Vec<Vec<Definition>>,
+    // the lints that are turned off where the checker is
+    allowed: Vec<&'a str>,
+    warnings: Vec<(&'static str, FileRange, String)>,
+}
+
+// the lints an attribute turns off, like #[allow(unused, shadowing)]
+fn allowed_lints(attribute: &Attribute) -> Vec<&str> {
+    let mut out = Vec::new();
+    for (name, args) in &attribute.tags {
+        if name == "allow" {
+            for arg in args {
+                if let Some(Variable {
+                    value:
+                        ValueLiteral {
+                            body: ValueBody::Symbol(lint),
+                        },
+                    ..
+                }) = arg.value.values.first()
+                {
+                    out.push(lint.as_str());
+                }
+            }
+        }
+    }
+    out
+}
+
+// `name = value` or `let name = value`
+pub fn definition(expr: &Expression) -> Option<(&Variable, &String, &[Variable])> {
+    if expr.operators.first() != Some(&Operator::Assign) {
+        return None;
+    }
+    let symbol = &expr.values[0];
+    match &symbol.value.body {
+        ValueBody::Symbol(name) if symbol.path.is_empty() => {
+            Some((symbol, name, &expr.values[1..]))
+        }
+        _ => None,
+    }
+}
+
+impl<'a> Checker<'a> {
+    fn warn(&mut self, lint: &'static str, pos: FileRange, message: String) {
+        if !self.allowed.contains(&lint) {
+            self.warnings.push((lint, pos, message));
+        }
+    }
+
+    fn find(&mut self, name: &str) -> Option<(usize, &mut Definition)> {
+        let depth = self.scopes.len();
+        self.scopes
+            .iter_mut()
+            .enumerate()
+            .rev()
+            .find_map(|(i, scope)| {
+                scope
+                    .iter_mut()
+                    .rev()
+                    .find(|d| d.name == name)
+                    .map(|d| (depth - 1 - i, d))
+            })
+    }
+
+    fn define(&mut self, name: &str, pos: FileRange, kind: String, shadow_check: bool) {
+        if shadow_check {
+            if let Some((outer, d)) = self.find(name) {
+                if outer > 0 {
+                    let message = format!(
+                        "{} hides the {} with the same name on line {}",
+                        name, d.kind, d.pos.0 .0
+                    );
+                    // the one that's hidden might still be used before this
+                    self.warn(SHADOWING, pos, message);
+                }
+            }
+        }
+        let allowed = self.allowed.contains(&UNUSED) || name.starts_with('_') || name == "self";
+        self.scopes.last_mut().unwrap().push(Definition {
+            name: name.to_string(),
+            pos,
+            kind,
+            used: false,
+            allowed,
+        });
+    }
+
+    fn use_name(&mut self, name: &str) {
+        if let Some((_, d)) = self.find(name) {
+            d.used = true;
+        }
+    }
+
+    fn block(&mut self, statements: &'a [Statement], defined: Vec<(String, FileRange, String)>) {
+        self.scopes.push(Vec::new());
+        for (name, pos, kind) in defined {
+            self.define(&name, pos, kind, true);
+        }
+        self.statements(statements);
+        self.end_scope();
+    }
+
+    fn end_scope(&mut self) {
+        for d in self.scopes.pop().unwrap_or_default() {
+            if !d.used && !d.allowed {
+                let message = match d.kind.as_str() {
+                    "variable" | "argument" => format!("{} {} is never used", d.kind, d.name),
+                    file => format!("{} is imported as {} but never used", file, d.name),
+                };
+                self.warnings.push((UNUSED, d.pos, message));
+            }
+        }
+    }
+
+    fn statements(&mut self, statements: &'a [Statement]) {
+        let mut ended: Option<&str> = None;
+        for statement in statements {
+            let allowed_before = self.allowed.len();
+            self.allowed.extend(allowed_lints(&statement.attributes));
+
+            // only the first statement that can't run is pointed out
+            if let Some(how) = ended.take() {
+                self.warn(
+                    UNREACHABLE,
+                    statement.pos,
+                    format!(
+                        "this can never run, since the {} before it always ends the block",
+                        how
+                    ),
+                );
+            }
+            self.statement(statement);
+            if !statement.arrow {
+                ended = match &statement.body {
+                    StatementBody::Return(_) => Some("return"),
+                    StatementBody::Break(_) => Some("break"),
+                    StatementBody::Continue(_) => Some("continue"),
+                    StatementBody::Error(_) => Some("throw"),
+                    _ => None,
+                };
+            }
+            self.allowed.truncate(allowed_before);
+        }
+    }
+
+    fn statement(&mut self, statement: &'a Statement) {
+        match &statement.body {
+            StatementBody::Expr(e) => match definition(e) {
+                Some((symbol, name, value)) => {
+                    let is_let = symbol.operator == Some(UnaryOperator::Let);
+                    let kind = match &value[0].value.body {
+                        ValueBody::Import(ImportType::Script(path), _) if value.len() == 1 => {
+                            path.to_string_lossy().to_string()
+                        }
+                        _ => "variable".to_string(),
+                    };
+                    if self.find(name).is_some() && !is_let {
+                        // assigning to a variable that is already there
+                        self.expression(value);
+                    } else if value.len() == 1
+                        && matches!(
+                            value[0].value.body,
+                            ValueBody::Macro(_) | ValueBody::CmpStmt(_)
+                        )
+                    {
+                        // macros and trigger functions can use themselves
+                        self.define(name, symbol.pos, kind, is_let);
+                        self.expression(value);
+                    } else {
+                        self.expression(value);
+                        self.define(name, symbol.pos, kind, is_let);
+                    }
+                }
+                None => self.expression(&e.values),
+            },
+            StatementBody::Call(c) => self.variable(&c.function),
+            StatementBody::Return(Some(e)) | StatementBody::Extract(e) => {
+                self.expression(&e.values)
+            }
+            StatementBody::Error(e) => self.expression(&e.message.values),
+            StatementBody::Impl(imp) => {
+                self.variable(&imp.symbol);
+                self.dict(&imp.members);
+            }
+            StatementBody::If(i) => {
+                self.expression(&i.condition.values);
+                self.block(&i.if_body, Vec::new());
+                if let Some(body) = &i.else_body {
+                    self.block(body, Vec::new());
+                }
+            }
+            StatementBody::For(f) => {
+                self.expression(&f.array.values);
+                self.block(
+                    &f.body,
+                    vec![(f.symbol.clone(), statement.pos, "variable".to_string())],
+                );
+            }
+            StatementBody::Try(t) => {
+                self.block(&t.try_body, Vec::new());
+                let caught = t
+                    .catch_symbol
+                    .iter()
+                    .map(|s| (s.clone(), statement.pos, "variable".to_string()))
+                    .collect();
+                self.block(&t.catch_body, caught);
+            }
+            StatementBody::Defer(body) => self.block(body, Vec::new()),
+            _ => (),
+        }
+    }
+
+    fn expression(&mut self, values: &'a [Variable]) {
+        for v in values {
+            self.variable(v);
+        }
+    }
+
+    fn dict(&mut self, dict: &'a [DictDef]) {
+        for def in dict {
+            match def {
+                DictDef::Def((_, e)) | DictDef::Extract(e) => self.expression(&e.values),
+            }
+        }
+    }
+
+    fn variable(&mut self, var: &'a Variable) {
+        let allowed_before = self.allowed.len();
+        self.allowed.extend(allowed_lints(&var.tag));
+        match &var.value.body {
+            ValueBody::Symbol(name) => self.use_name(name),
+            ValueBody::CmpStmt(c) => self.block(&c.statements, Vec::new()),
+            ValueBody::Macro(m) => {
+                self.allowed.extend(allowed_lints(&m.properties));
+                let mut args = Vec::new();
+                for (name, default, attribute, typ) in &m.args {
+                    if let Some(e) = typ {
+                        self.expression(&e.values);
+                    }
+                    if let Some(e) = default {
+                        self.expression(&e.values);
+                    }
+                    // an empty macro (like a default callback) doesn't have to use them
+                    if !m.body.statements.is_empty() && !allowed_lints(attribute).contains(&UNUSED)
+                    {
+                        args.push((name.clone(), var.pos, "argument".to_string()));
+                    }
+                }
+                self.block(&m.body.statements, args);
+            }
+            ValueBody::Dictionary(d) => self.dict(d),
+            ValueBody::Expression(e) => self.expression(&e.values),
+            ValueBody::Switch(e, cases) => {
+                self.expression(&e.values);
+                for case in cases {
+                    match &case.typ {
+                        CaseType::Value(values) => {
+                            for v in values {
+                                self.expression(&v.values)
+                            }
+                        }
+                        CaseType::Pattern(e) => self.expression(&e.values),
+                        CaseType::Default => (),
+                    }
+                    self.expression(&case.body.values);
+                }
+            }
+            ValueBody::Array(arr) => {
+                for e in arr {
+                    self.expression(&e.values)
+                }
+            }
+            ValueBody::Obj(o) => {
+                for (k, v) in &o.props {
+                    self.expression(&k.values);
+                    self.expression(&v.values);
+                }
+            }
+            ValueBody::Ternary(t) => {
+                self.expression(&t.if_expr.values);
+                self.expression(&t.condition.values);
+                self.expression(&t.else_expr.values);
+            }
+            ValueBody::Try(t) => {
+                self.expression(&t.try_expr.values);
+                self.scopes.push(Vec::new());
+                if let Some(symbol) = &t.catch_symbol {
+                    self.define(symbol, var.pos, "variable".to_string(), true);
+                }
+                self.expression(&t.catch_expr.values);
+                self.end_scope();
+            }
+            _ => (),
+        }
+        for p in &var.path {
+            match p {
+                Path::Index(e) => self.expression(&e.values),
+                Path::Call(args) => {
+                    for arg in args {
+                        self.expression(&arg.value.values);
+                    }
+                }
+                Path::Constructor(d) => self.dict(d),
+                _ => (),
+            }
+        }
+        self.allowed.truncate(allowed_before);
+    }
+}
+
+pub fn check(
+    statements: &[Statement],
+    file_tag: &Attribute,
+) -> Vec<(&'static str, FileRange, String)> {
+    let mut checker = Checker {
+        scopes: vec![Vec::new()],
+        allowed: allowed_lints(file_tag),
+        warnings: Vec::new(),
+    };
+    checker.statements(statements);
+    checker.end_scope();
+    checker.warnings.sort_by_key(|w| w.1);
+    checker.warnings
+}
+
+// adds the warnings for a script file to the compiler's
+pub fn check_file(
+    statements: &[Statement],
+    file_tag: &Attribute,
+    file: &std::path::Path,
+    warnings: &mut Vec<Warning>,
+) {
+    for (lint, pos, message) in check(statements, file_tag) {
+        warnings.push(Warning {
+            lint,
+            message,
+            file: file.to_path_buf(),
+            pos,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_spwn;
+
+    fn warnings(source: &str) -> Vec<String> {
+        let (statements, notes) = parse_spwn(source.to_string(), "test.spwn".into()).unwrap();
+        check(&statements, &notes.tag)
+            .into_iter()
+            .map(|(lint, pos, message)| format!("{} {}: {}", lint, pos.0 .0, message))
+            .collect()
+    }
+
+    #[test]
+    fn mistakes_are_found() {
+        let found = warnings(
+            "a = 1
+b = 2
+lib = import \"lib.spwn\"
+f = (x, y, _z) {
+    let a = x
+    return a
+    $.print(a)
+}
+$.print(f(b, 3))
+",
+        );
+        assert_eq!(
+            found,
+            vec![
+                "unused 1: variable a is never used",
+                "unused 3: lib.spwn is imported as lib but never used",
+                "unused 4: argument y is never used",
+                "shadowing 5: a hides the variable with the same name on line 1",
+                "unreachable 7: this can never run, since the return before it always ends the block",
+            ]
+        );
+    }
+
+    #[test]
+    fn allow_turns_lints_off() {
+        let found = warnings(
+            "#[allow(shadowing)]
+a = 1
+#[allow(unused)] b = 2
+count = (n) {
+    let a = n
+    return a
+}
+$.print(a, count(1))
+",
+        );
+        assert!(found.is_empty(), "{:?}", found);
+        // recursive macros use themselves, and assigning again isn't a new variable
+        let found = warnings("f = (n) { if n > 0 { f(n - 1) } }\nc = 0\nc = 1\n");
+        assert_eq!(found, vec!["unused 2: variable c is never used"]);
+    }
+}
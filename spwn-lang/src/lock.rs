@@ -0,0 +1,44 @@
+// keeps the triggers spwn makes from being moved around by accident when working on the
+// level in the editor
+// the game can't lock single objects, only whole editor layers, so the triggers are put
+// on a layer of their own that can be locked (the lock button next to the layer number)
+use crate::ast::ObjectMode;
+use crate::levelstring::{GdObj, ObjParam};
+
+const EDITOR_LAYER_1: u16 = 20;
+
+// the other editor properties that can be set on the triggers
+const FLAGS: [(&str, u16); 3] = [("dont_fade", 64), ("dont_enter", 67), ("group_parent", 34)];
+
+// a list like "dont_fade,dont_enter"
+pub fn parse_flags(list: &str) -> Result<Vec<u16>, String> {
+    list.split(',')
+        .map(|name| name.trim())
+        .filter(|name| !name.is_empty())
+        .map(|name| match FLAGS.iter().find(|(n, _)| *n == name) {
+            Some((_, key)) => Ok(*key),
+            None => Err(format!(
+                "Unknown trigger flag: {} (expected dont_fade, dont_enter or group_parent)",
+                name
+            )),
+        })
+        .collect()
+}
+
+// gives back how many triggers were changed
+// a layer the script set on a trigger itself is kept
+pub fn lock_triggers(objects: &mut [GdObj], layer: Option<u16>, flags: &[u16]) -> usize {
+    let mut changed = 0;
+    for obj in objects.iter_mut().filter(|o| o.mode == ObjectMode::Trigger) {
+        if let Some(layer) = layer {
+            obj.params
+                .entry(EDITOR_LAYER_1)
+                .or_insert(ObjParam::Number(layer as f64));
+        }
+        for key in flags {
+            obj.params.insert(*key, ObjParam::Bool(true));
+        }
+        changed += 1;
+    }
+    changed
+}
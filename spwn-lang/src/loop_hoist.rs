@@ -0,0 +1,260 @@
+// moves triggers that do the same thing on every run of a runtime loop (a spawn cycle)
+// out of it, so they run once before the loop instead. that's only the same when:
+// - the trigger sets something instead of changing it (a toggle, or an alpha or color
+//   trigger without a fade), so running it again does nothing
+// - nothing else in the level touches what it sets, so it stays set
+// - it's in the group the loop is started with, so it runs on the first run too (the
+//   body of a while loop can run zero times, but its condition can't)
+// - the loop is started by one spawn trigger without a delay, which it's moved next to
+//   (and made to start like)
+use crate::builtin::Group;
+use crate::levelstring::{GdObj, ObjParam};
+use std::collections::{HashMap, HashSet};
+
+const SPAWN: f64 = 1268.0;
+const TOGGLE: f64 = 1049.0;
+const ALPHA: f64 = 1007.0;
+const COLOR: f64 = 899.0;
+const INSTANT_COUNT: f64 = 1811.0;
+
+// triggers that start the group they target
+const ACTIVATORS: [f64; 6] = [SPAWN, 1611.0, INSTANT_COUNT, 1595.0, 1815.0, 1812.0];
+
+fn number(obj: &GdObj, key: u16) -> Option<f64> {
+    match obj.params.get(&key) {
+        Some(ObjParam::Number(n)) => Some(*n),
+        _ => None,
+    }
+}
+
+fn group(obj: &GdObj, key: u16) -> Option<Group> {
+    match obj.params.get(&key) {
+        Some(ObjParam::Group(g)) => Some(*g),
+        _ => None,
+    }
+}
+
+// the key of what the trigger sets, if running it twice is the same as once
+fn sets(obj: &GdObj) -> Option<u16> {
+    let instant = number(obj, 10).unwrap_or(0.0) == 0.0;
+    match number(obj, 1)? {
+        k if k == TOGGLE => Some(51),
+        k if k == ALPHA && instant => Some(51),
+        // unless it copies another color, which can change
+        k if k == COLOR && instant && !obj.params.contains_key(&50) => Some(23),
+        _ => None,
+    }
+}
+
+fn reachable(from: Group, starts: &HashMap<Group, Vec<Group>>) -> HashSet<Group> {
+    let mut seen = HashSet::new();
+    let mut stack = vec![from];
+    while let Some(g) = stack.pop() {
+        for next in starts.get(&g).into_iter().flatten() {
+            if seen.insert(*next) {
+                stack.push(*next);
+            }
+        }
+    }
+    seen
+}
+
+// gives back the triggers that were moved (by unique id, with why)
+pub fn hoist_invariants(objects: &mut [GdObj]) -> Vec<(usize, String)> {
+    // what each group starts, and what starts it
+    let mut starts: HashMap<Group, Vec<Group>> = HashMap::new();
+    let mut started_by: HashMap<Group, Vec<usize>> = HashMap::new();
+    for (i, obj) in objects.iter().enumerate() {
+        if !matches!(number(obj, 1), Some(k) if ACTIVATORS.contains(&k)) {
+            continue;
+        }
+        if let Some(target) = group(obj, 51) {
+            if let Some(g) = group(obj, 57) {
+                starts.entry(g).or_default().push(target);
+            }
+            started_by.entry(target).or_default().push(i);
+        }
+    }
+
+    // how many triggers use each thing (apart from being in it)
+    let mut users: HashMap<String, usize> = HashMap::new();
+    for obj in objects
+        .iter()
+        .filter(|o| o.mode == crate::ast::ObjectMode::Trigger)
+    {
+        for (key, param) in &obj.params {
+            if *key != 57 && matches!(param, ObjParam::Group(_) | ObjParam::Color(_)) {
+                *users.entry(format!("{:?}", param)).or_default() += 1;
+            }
+        }
+    }
+
+    let mut moves = Vec::new();
+    let mut reach: HashMap<Group, HashSet<Group>> = HashMap::new();
+    for (i, obj) in objects.iter().enumerate() {
+        let (key, header) = match (sets(obj), group(obj, 57)) {
+            (Some(key), Some(g)) => (key, g),
+            _ => continue,
+        };
+        let target = match obj.params.get(&key) {
+            Some(t) if users.get(&format!("{:?}", t)) == Some(&1) => t,
+            _ => continue,
+        };
+        let in_loop = reach
+            .entry(header)
+            .or_insert_with(|| reachable(header, &starts));
+        if !in_loop.contains(&header) {
+            continue;
+        }
+        let in_loop = in_loop.clone();
+        // the loop is the groups the header starts that start it again
+        let loop_groups: HashSet<Group> = in_loop
+            .into_iter()
+            .filter(|g| {
+                reach
+                    .entry(*g)
+                    .or_insert_with(|| reachable(*g, &starts))
+                    .contains(&header)
+            })
+            .collect();
+        if let ObjParam::Group(g) = target {
+            if loop_groups.contains(g) {
+                continue;
+            }
+        }
+
+        // the one spawn trigger that starts the loop from outside
+        let mut entries = started_by[&header]
+            .iter()
+            .filter(|s| !matches!(group(&objects[**s], 57), Some(g) if loop_groups.contains(&g)));
+        let (entry, started_at) = match (entries.next(), entries.next()) {
+            (Some(s), None) => (&objects[*s], *s),
+            _ => continue,
+        };
+        let before = match group(entry, 57) {
+            Some(g)
+                if number(entry, 1) == Some(SPAWN)
+                    && number(entry, 63).unwrap_or(0.0) == 0.0
+                    && Some(&ObjParam::Group(g)) != Some(target) =>
+            {
+                g
+            }
+            _ => continue,
+        };
+        moves.push((i, before, started_at));
+    }
+
+    let mut remarks = Vec::new();
+    for (i, before, started_at) in moves {
+        objects[i].params.insert(57, ObjParam::Group(before));
+        // it's started the same way as the loop (by being spawned, or by the player
+        // getting to it when the loop is started at the beginning of the level)
+        for key in [2, 62, 87].iter() {
+            match objects[started_at].params.get(key).cloned() {
+                Some(p) => objects[i].params.insert(*key, p),
+                None => objects[i].params.remove(key),
+            };
+        }
+        remarks.push((
+            objects[i].unique_id,
+            "moved out of the loop: it does the same thing every time the loop runs".to_string(),
+        ));
+    }
+    remarks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::ObjectMode;
+    use crate::builtin::Id;
+
+    fn g(id: u16) -> Group {
+        Group {
+            id: Id::Arbitrary(id),
+        }
+    }
+
+    fn starts(unique_id: usize, group: u16, kind: f64, target: u16, delay: f64) -> GdObj {
+        GdObj::for_test(
+            unique_id,
+            ObjectMode::Trigger,
+            &[
+                (57, ObjParam::Group(g(group))),
+                (1, ObjParam::Number(kind)),
+                (51, ObjParam::Group(g(target))),
+                (63, ObjParam::Number(delay)),
+            ],
+        )
+    }
+
+    fn toggle(unique_id: usize, group: u16, target: u16) -> GdObj {
+        GdObj::for_test(
+            unique_id,
+            ObjectMode::Trigger,
+            &[
+                (57, ObjParam::Group(g(group))),
+                (1, ObjParam::Number(TOGGLE)),
+                (51, ObjParam::Group(g(target))),
+                (56, ObjParam::Bool(false)),
+            ],
+        )
+    }
+
+    fn group_of(objects: &[GdObj], unique_id: usize) -> Option<Group> {
+        objects
+            .iter()
+            .find(|o| o.unique_id == unique_id)
+            .and_then(|o| group(o, 57))
+    }
+
+    #[test]
+    fn toggles_in_a_loop_run_once_before_it() {
+        // 1g starts the loop 2g, which turns 9g off and starts itself again
+        let mut objects = vec![
+            starts(1, 1, SPAWN, 2, 0.0),
+            toggle(2, 2, 9),
+            starts(3, 2, SPAWN, 2, 0.1),
+        ];
+        let moved = hoist_invariants(&mut objects);
+        assert_eq!(moved.iter().map(|(id, _)| *id).collect::<Vec<_>>(), [2]);
+        assert_eq!(group_of(&objects, 2), Some(g(1)));
+    }
+
+    #[test]
+    fn triggers_that_can_change_stay_in_the_loop() {
+        let mut objects = vec![
+            // 1g starts a while loop: 2g checks the condition and starts the body 3g
+            starts(1, 1, SPAWN, 2, 0.0),
+            starts(2, 2, INSTANT_COUNT, 3, 0.0),
+            starts(3, 3, SPAWN, 2, 0.1),
+            // the body can run zero times
+            toggle(4, 3, 9),
+            // something else turns 8g back on
+            toggle(5, 2, 8),
+            GdObj::for_test(
+                6,
+                ObjectMode::Trigger,
+                &[
+                    (57, ObjParam::Group(g(4))),
+                    (1, ObjParam::Number(TOGGLE)),
+                    (51, ObjParam::Group(g(8))),
+                    (56, ObjParam::Bool(true)),
+                ],
+            ),
+            // a move does something every time
+            GdObj::for_test(
+                7,
+                ObjectMode::Trigger,
+                &[
+                    (57, ObjParam::Group(g(2))),
+                    (1, ObjParam::Number(901.0)),
+                    (51, ObjParam::Group(g(7))),
+                ],
+            ),
+        ];
+        assert!(hoist_invariants(&mut objects).is_empty());
+        assert_eq!(group_of(&objects, 4), Some(g(3)));
+        assert_eq!(group_of(&objects, 5), Some(g(2)));
+    }
+}
@@ -0,0 +1,315 @@
+// spwn build --emit=lowered: the script with its shorthand written out with simpler
+// syntax, as spwn code, to see what a piece of syntax means (and for bug reports about it).
+//  - switch: a macro that checks the cases one by one with if statements
+//  - ternaries (a if b else c): the same with one if statement
+//  - (x) => expr macros: a macro that returns expr (the parser already reads them like this)
+// this is what the syntax means, not how it's compiled (a switch on a counter is compiled
+// to a binary search, for example, and a switch with no matching case gives no value at all
+// instead of null)
+use crate::ast::*;
+
+const SWITCH_VALUE: &str = "switch_value";
+
+pub fn lower(statements: &mut Vec<Statement>) {
+    for statement in statements {
+        lower_statement(statement);
+    }
+}
+
+fn lower_statement(statement: &mut Statement) {
+    match &mut statement.body {
+        StatementBody::Call(c) => lower_variable(&mut c.function),
+        StatementBody::Expr(e) | StatementBody::Extract(e) => lower_expression(e),
+        StatementBody::Return(Some(e)) => lower_expression(e),
+        StatementBody::Impl(imp) => {
+            lower_variable(&mut imp.symbol);
+            lower_dict(&mut imp.members);
+        }
+        StatementBody::If(i) => {
+            lower_expression(&mut i.condition);
+            lower(&mut i.if_body);
+            if let Some(body) = &mut i.else_body {
+                lower(body);
+            }
+        }
+        StatementBody::For(f) => {
+            lower_expression(&mut f.array);
+            lower(&mut f.body);
+        }
+        StatementBody::Error(e) => lower_expression(&mut e.message),
+        StatementBody::Try(t) => {
+            lower(&mut t.try_body);
+            lower(&mut t.catch_body);
+        }
+        StatementBody::Defer(body) => lower(body),
+        _ => (),
+    }
+}
+
+fn lower_expression(expr: &mut Expression) {
+    for value in &mut expr.values {
+        lower_variable(value);
+    }
+}
+
+fn lower_dict(dict: &mut Vec<DictDef>) {
+    for def in dict {
+        match def {
+            DictDef::Def((_, e)) | DictDef::Extract(e) => lower_expression(e),
+        }
+    }
+}
+
+fn lower_variable(var: &mut Variable) {
+    // the insides first
+    match &mut var.value.body {
+        ValueBody::CmpStmt(c) => lower(&mut c.statements),
+        ValueBody::Macro(m) => {
+            for (_, default, _, typ) in &mut m.args {
+                if let Some(e) = typ {
+                    lower_expression(e);
+                }
+                if let Some(e) = default {
+                    lower_expression(e);
+                }
+            }
+            lower(&mut m.body.statements);
+        }
+        ValueBody::Dictionary(d) => lower_dict(d),
+        ValueBody::Expression(e) => lower_expression(e),
+        ValueBody::Switch(e, cases) => {
+            lower_expression(e);
+            for case in cases {
+                match &mut case.typ {
+                    CaseType::Value(values) => values.iter_mut().for_each(lower_expression),
+                    CaseType::Pattern(e) => lower_expression(e),
+                    CaseType::Default => (),
+                }
+                lower_expression(&mut case.body);
+            }
+        }
+        ValueBody::Array(arr) => arr.iter_mut().for_each(lower_expression),
+        ValueBody::Obj(o) => {
+            for (k, v) in &mut o.props {
+                lower_expression(k);
+                lower_expression(v);
+            }
+        }
+        ValueBody::Ternary(t) => {
+            lower_expression(&mut t.if_expr);
+            lower_expression(&mut t.condition);
+            lower_expression(&mut t.else_expr);
+        }
+        ValueBody::Try(t) => {
+            lower_expression(&mut t.try_expr);
+            lower_expression(&mut t.catch_expr);
+        }
+        _ => (),
+    }
+    for p in &mut var.path {
+        match p {
+            Path::Index(e) => lower_expression(e),
+            Path::Call(args) => {
+                for arg in args {
+                    lower_expression(&mut arg.value);
+                }
+            }
+            Path::Constructor(d) => lower_dict(d),
+            _ => (),
+        }
+    }
+
+    let lowered = match &var.value.body {
+        ValueBody::Switch(value, cases) => lower_switch(value, cases),
+        ValueBody::Ternary(t) => call(
+            Vec::new(),
+            vec![if_statement(
+                t.condition.clone(),
+                vec![return_statement(t.if_expr.clone())],
+                Some(vec![return_statement(t.else_expr.clone())]),
+            )],
+            Vec::new(),
+        ),
+        _ => return,
+    };
+    if var.operator.is_none() && var.path.is_empty() {
+        let pos = var.pos;
+        *var = as_variable(&lowered);
+        var.pos = pos;
+    } else {
+        var.value.body = ValueBody::Expression(lowered);
+    }
+}
+
+// switch v { case 1, 2: a, @number: b, else: c } is
+// ((switch_value) {
+//     if switch_value == 1 || switch_value == 2 { return a }
+//     if $.matches(switch_value, @number) { return b }
+//     return c
+// })(v)
+fn lower_switch(value: &Expression, cases: &[Case]) -> Expression {
+    let mut body = Vec::new();
+    for case in cases {
+        let condition = match &case.typ {
+            CaseType::Value(values) => {
+                let mut condition = Expression {
+                    values: Vec::new(),
+                    operators: Vec::new(),
+                };
+                for v in values {
+                    if !condition.values.is_empty() {
+                        condition.operators.push(Operator::Or);
+                    }
+                    let matches = case_value_matches(v);
+                    condition.values.extend(matches.values);
+                    condition.operators.extend(matches.operators);
+                }
+                condition
+            }
+            CaseType::Pattern(p) => builtin_call(
+                "matches",
+                vec![symbol(SWITCH_VALUE).to_expression(), p.clone()],
+            )
+            .to_expression(),
+            CaseType::Default => {
+                body.push(return_statement(case.body.clone()));
+                break;
+            }
+        };
+        body.push(if_statement(
+            condition,
+            vec![return_statement(case.body.clone())],
+            None,
+        ));
+    }
+    call(vec![SWITCH_VALUE.to_string()], body, vec![value.clone()])
+}
+
+// ranges like 1..10 match the numbers from the start up to (not including) the end
+fn case_value_matches(v: &Expression) -> Expression {
+    let value = symbol(SWITCH_VALUE);
+    if v.values.len() == 2 && v.operators == [Operator::Range] {
+        Expression {
+            values: vec![
+                value.clone(),
+                v.values[0].clone(),
+                value,
+                v.values[1].clone(),
+            ],
+            operators: vec![Operator::MoreOrEqual, Operator::And, Operator::Less],
+        }
+    } else {
+        Expression {
+            values: vec![value, as_variable(v)],
+            operators: vec![Operator::Equal],
+        }
+    }
+}
+
+// without brackets around it if it doesn't need them
+fn as_variable(expr: &Expression) -> Variable {
+    if expr.values.len() == 1 && expr.operators.is_empty() {
+        expr.values[0].clone()
+    } else {
+        expr.to_variable()
+    }
+}
+
+fn symbol(name: &str) -> Variable {
+    ValueBody::Symbol(name.to_string()).to_variable()
+}
+
+fn builtin_call(name: &str, args: Vec<Expression>) -> Variable {
+    let mut var = symbol("$");
+    var.path = vec![
+        Path::Member(name.to_string()),
+        Path::Call(
+            args.into_iter()
+                .map(|value| Argument {
+                    symbol: None,
+                    value,
+                })
+                .collect(),
+        ),
+    ];
+    var
+}
+
+fn statement(body: StatementBody) -> Statement {
+    Statement {
+        body,
+        arrow: false,
+        pos: ((0, 0), (0, 0)),
+        trivia: Trivia::new(),
+        attributes: Attribute::new(),
+    }
+}
+
+fn return_statement(value: Expression) -> Statement {
+    statement(StatementBody::Return(Some(value)))
+}
+
+fn if_statement(
+    condition: Expression,
+    if_body: Vec<Statement>,
+    else_body: Option<Vec<Statement>>,
+) -> Statement {
+    statement(StatementBody::If(If {
+        condition,
+        if_body,
+        else_body,
+    }))
+}
+
+// ((args) { body })(values)
+fn call(args: Vec<String>, body: Vec<Statement>, values: Vec<Expression>) -> Expression {
+    let mut var = ValueBody::Macro(Macro {
+        args: args
+            .into_iter()
+            .map(|a| (a, None, Attribute::new(), None))
+            .collect(),
+        body: CompoundStatement { statements: body },
+        properties: Attribute::new(),
+    })
+    .to_variable();
+    var.value.body = ValueBody::Expression(var.to_expression());
+    var.path = vec![Path::Call(
+        values
+            .into_iter()
+            .map(|value| Argument {
+                symbol: None,
+                value,
+            })
+            .collect(),
+    )];
+    var.to_expression()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fmt::SpwnFmt;
+    use crate::parser::parse_spwn;
+
+    fn lowered(source: &str) -> String {
+        let (mut statements, _) = parse_spwn(source.to_string(), "test.spwn".into()).unwrap();
+        lower(&mut statements);
+        statements
+            .iter()
+            .map(|s| s.fmt(0))
+            .collect::<Vec<_>>()
+            .join("")
+    }
+
+    #[test]
+    fn switches_are_lowered_to_ifs() {
+        let out = lowered("a = switch n { case 1, 2..4: \"x\", @number: \"y\", else: \"z\" }\n");
+        assert!(!out.contains("switch n"));
+        assert!(out.contains("if switch_value == 1 || switch_value >= 2 && switch_value < 4 {"));
+        assert!(out.contains("$.matches(switch_value, @number)"));
+        assert!(out.contains("return \"z\""));
+        assert!(out.contains("})(n)\n"));
+        // the lowered code is valid spwn
+        parse_spwn(out, "lowered.spwn".into()).unwrap();
+    }
+}
@@ -0,0 +1,1275 @@
+// spwn lsp: a language server for editors, on stdin and stdout (json-rpc messages that each
+// start with a Content-Length header, see the language server protocol)
+//  - diagnostics: the syntax errors and lints (see lints.rs) of a file every time it changes.
+//    the script isn't built for them, since that can take long, so errors that only happen
+//    when it runs still show up when building
+//  - go to definition for variables, macros and their arguments, types (type @x and
+//    impl @x) and the members given to types in impl blocks
+//  - hover: the type of a value when it can be seen without running the script (a literal,
+//    a macro or a trigger function), the arguments of macros, and the comments and
+//    #[desc] before a definition
+//  - completion for $. builtins, members of imported libraries, object properties in
+//    obj { } and trigger { }, type names after @ and the variables in scope
+use crate::ast::*;
+use crate::builtin::{BUILTIN_LIST, TYPE_MEMBER_NAME};
+use crate::compiler_types::ImportType;
+use crate::diagnostics::Diagnostic;
+use crate::documentation::json_str;
+use crate::fmt::SpwnFmt;
+use crate::parser::{parse_spwn_partial_with, FileRange};
+
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
+
+// the json in the messages from the editor
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    Str(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+static NULL: Json = Json::Null;
+
+type Chars<'a> = std::iter::Peekable<std::str::Chars<'a>>;
+
+fn skip_space(chars: &mut Chars) {
+    while matches!(chars.peek(), Some(c) if c.is_whitespace()) {
+        chars.next();
+    }
+}
+
+fn parse_value(chars: &mut Chars) -> Option<Json> {
+    skip_space(chars);
+    match *chars.peek()? {
+        '{' => {
+            chars.next();
+            let mut members = Vec::new();
+            skip_space(chars);
+            if chars.peek() == Some(&'}') {
+                chars.next();
+                return Some(Json::Object(members));
+            }
+            loop {
+                let key = match parse_value(chars)? {
+                    Json::Str(s) => s,
+                    _ => return None,
+                };
+                skip_space(chars);
+                if chars.next()? != ':' {
+                    return None;
+                }
+                members.push((key, parse_value(chars)?));
+                skip_space(chars);
+                match chars.next()? {
+                    ',' => (),
+                    '}' => return Some(Json::Object(members)),
+                    _ => return None,
+                }
+            }
+        }
+        '[' => {
+            chars.next();
+            let mut elements = Vec::new();
+            skip_space(chars);
+            if chars.peek() == Some(&']') {
+                chars.next();
+                return Some(Json::Array(elements));
+            }
+            loop {
+                elements.push(parse_value(chars)?);
+                skip_space(chars);
+                match chars.next()? {
+                    ',' => (),
+                    ']' => return Some(Json::Array(elements)),
+                    _ => return None,
+                }
+            }
+        }
+        '"' => {
+            chars.next();
+            parse_string(chars).map(Json::Str)
+        }
+        c if c.is_alphabetic() => {
+            let mut word = String::new();
+            while let Some(c) = chars.peek().filter(|c| c.is_alphabetic()) {
+                word.push(*c);
+                chars.next();
+            }
+            match word.as_str() {
+                "true" => Some(Json::Bool(true)),
+                "false" => Some(Json::Bool(false)),
+                "null" => Some(Json::Null),
+                _ => None,
+            }
+        }
+        _ => {
+            let mut number = String::new();
+            while let Some(c) = chars
+                .peek()
+                .filter(|c| c.is_ascii_digit() || "+-.eE".contains(**c))
+            {
+                number.push(*c);
+                chars.next();
+            }
+            number.parse().ok().map(Json::Number)
+        }
+    }
+}
+
+fn hex4(chars: &mut Chars) -> Option<u32> {
+    let hex: String = (0..4).filter_map(|_| chars.next()).collect();
+    u32::from_str_radix(&hex, 16).ok()
+}
+
+// after the opening "
+fn parse_string(chars: &mut Chars) -> Option<String> {
+    let mut out = String::new();
+    loop {
+        match chars.next()? {
+            '"' => return Some(out),
+            '\\' => match chars.next()? {
+                'n' => out.push('\n'),
+                't' => out.push('\t'),
+                'r' => out.push('\r'),
+                'b' => out.push('\u{8}'),
+                'f' => out.push('\u{c}'),
+                'u' => {
+                    let mut code = hex4(chars)?;
+                    // characters outside the basic plane are two escapes
+                    if (0xd800..0xdc00).contains(&code) {
+                        if chars.next()? != '\\' || chars.next()? != 'u' {
+                            return None;
+                        }
+                        let low = hex4(chars)?.checked_sub(0xdc00)?;
+                        code = 0x10000 + ((code - 0xd800) << 10) + low;
+                    }
+                    out.push(std::char::from_u32(code)?);
+                }
+                c => out.push(c),
+            },
+            c => out.push(c),
+        }
+    }
+}
+
+impl Json {
+    pub fn parse(text: &str) -> Option<Json> {
+        let mut chars = text.chars().peekable();
+        let value = parse_value(&mut chars)?;
+        skip_space(&mut chars);
+        match chars.next() {
+            Some(_) => None,
+            None => Some(value),
+        }
+    }
+
+    // a member of an object, or null
+    pub fn get(&self, key: &str) -> &Json {
+        match self {
+            Json::Object(members) => members
+                .iter()
+                .find(|(k, _)| k == key)
+                .map(|(_, v)| v)
+                .unwrap_or(&NULL),
+            _ => &NULL,
+        }
+    }
+
+    // like get, with a path like "textDocument.uri"
+    pub fn at(&self, path: &str) -> &Json {
+        path.split('.').fold(self, |json, key| json.get(key))
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::Str(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_usize(&self) -> Option<usize> {
+        match self {
+            Json::Number(n) if *n >= 0.0 => Some(*n as usize),
+            _ => None,
+        }
+    }
+
+    // for request ids, which are sent back the way they came
+    fn id_json(&self) -> String {
+        match self {
+            Json::Number(n) => n.to_string(),
+            Json::Str(s) => json_str(s),
+            _ => "null".to_string(),
+        }
+    }
+}
+
+// headers, an empty line, then Content-Length bytes of json
+fn read_message(input: &mut impl BufRead) -> std::io::Result<Option<String>> {
+    let mut length = 0;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some(n) = line.strip_prefix("Content-Length:") {
+            length = n.trim().parse().unwrap_or(0);
+        }
+    }
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    Ok(Some(String::from_utf8_lossy(&body).to_string()))
+}
+
+fn send(out: &mut impl Write, body: &str) -> std::io::Result<()> {
+    write!(out, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    out.flush()
+}
+
+fn uri_to_path(uri: &str) -> PathBuf {
+    let bytes = uri.strip_prefix("file://").unwrap_or(uri).as_bytes();
+    let mut decoded = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes
+            .get((i + 1)..(i + 3))
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match (bytes[i], escaped) {
+            (b'%', Some(b)) => {
+                decoded.push(b);
+                i += 3;
+            }
+            (b, _) => {
+                decoded.push(b);
+                i += 1;
+            }
+        }
+    }
+    let path = String::from_utf8_lossy(&decoded).to_string();
+    // file:///C:/... on windows
+    if path.starts_with('/') && path.get(2..3) == Some(":") {
+        PathBuf::from(&path[1..])
+    } else {
+        PathBuf::from(path)
+    }
+}
+
+// positions in spwn are (line from 1, byte in the line), and in the protocol they're
+// (line from 0, utf-16 character in the line)
+fn lsp_position(text: &str, (line, col): (usize, usize)) -> String {
+    let line_text = text.lines().nth(line.saturating_sub(1)).unwrap_or("");
+    let character: usize = line_text
+        .char_indices()
+        .take_while(|(i, _)| *i < col)
+        .map(|(_, c)| c.len_utf16())
+        .sum();
+    format!(
+        "{{\"line\": {}, \"character\": {}}}",
+        line.saturating_sub(1),
+        character
+    )
+}
+
+fn lsp_range(text: &str, pos: FileRange) -> String {
+    format!(
+        "{{\"start\": {}, \"end\": {}}}",
+        lsp_position(text, pos.0),
+        lsp_position(text, pos.1)
+    )
+}
+
+fn file_position(text: &str, position: &Json) -> Option<(usize, usize)> {
+    let line = position.get("line").as_usize()?;
+    let character = position.get("character").as_usize()?;
+    let line_text = text.lines().nth(line).unwrap_or("");
+    let mut units = 0;
+    let mut col = line_text.len();
+    for (i, c) in line_text.char_indices() {
+        if units >= character {
+            col = i;
+            break;
+        }
+        units += c.len_utf16();
+    }
+    Some((line + 1, col))
+}
+
+fn contains(range: FileRange, at: (usize, usize)) -> bool {
+    range.0 <= at && at <= range.1
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Kind {
+    Variable,
+    Argument,
+    // in an impl block
+    Member,
+    Type,
+}
+
+struct Definition {
+    name: String,
+    kind: Kind,
+    pos: FileRange,
+    // where it can be used
+    scope: FileRange,
+    typ: Option<String>,
+    // (a, b: @number = 10) for macros
+    signature: Option<String>,
+    // the library, for `name = import library`
+    library: Option<String>,
+    doc: Vec<String>,
+}
+
+const WHOLE_FILE: FileRange = ((0, 0), (usize::MAX, usize::MAX));
+
+// the type of a value that can be seen without running it
+fn infer_type(values: &[Variable]) -> Option<String> {
+    let value = match values {
+        [v] if v.path.is_empty() && v.operator.is_none() => v,
+        _ => return None,
+    };
+    let typ = match &value.value.body {
+        ValueBody::Id(id) => match id.class_name {
+            IdClass::Group => "group",
+            IdClass::Color => "color",
+            IdClass::Item => "item",
+            IdClass::Block => "block",
+        },
+        ValueBody::Number(_) => "number",
+        ValueBody::Str(_) => "string",
+        ValueBody::Bool(_) => "bool",
+        ValueBody::Array(_) => "array",
+        ValueBody::Dictionary(_) => "dictionary",
+        ValueBody::Macro(_) => "macro",
+        ValueBody::CmpStmt(_) => "trigger_function",
+        ValueBody::Obj(o) => match o.mode {
+            ObjectMode::Object => "object",
+            ObjectMode::Trigger => "trigger",
+        },
+        ValueBody::TypeIndicator(_) => "type_indicator",
+        ValueBody::Null => "NULL",
+        ValueBody::Expression(e) if e.operators.is_empty() => return infer_type(&e.values),
+        _ => return None,
+    };
+    Some(format!("@{}", typ))
+}
+
+fn signature(values: &[Variable]) -> Option<String> {
+    let m = match values {
+        [Variable {
+            value: ValueLiteral {
+                body: ValueBody::Macro(m),
+            },
+            ..
+        }] => m,
+        _ => return None,
+    };
+    let args: Vec<String> = m
+        .args
+        .iter()
+        .map(|(name, default, _, typ)| {
+            let mut arg = name.clone();
+            if let Some(t) = typ {
+                arg += &format!(": {}", t.fmt(0));
+            }
+            if let Some(d) = default {
+                arg += &format!(" = {}", d.fmt(0));
+            }
+            arg
+        })
+        .collect();
+    Some(format!("({})", args.join(", ")))
+}
+
+// the comments before a statement and its #[desc]
+fn statement_doc(statement: &Statement, values: &[Variable]) -> Vec<String> {
+    let mut doc = statement.trivia.doc_lines();
+    doc.extend(statement.attributes.get_desc());
+    if let [Variable {
+        value: ValueLiteral {
+            body: ValueBody::Macro(m),
+        },
+        ..
+    }] = values
+    {
+        doc.extend(m.properties.get_desc());
+    }
+    doc
+}
+
+struct Collector {
+    // (where the scope is, the names defined in it)
+    scopes: Vec<(FileRange, Vec<String>)>,
+    definitions: Vec<Definition>,
+}
+
+impl Collector {
+    fn define(
+        &mut self,
+        name: &str,
+        kind: Kind,
+        pos: FileRange,
+        values: &[Variable],
+        doc: Vec<String>,
+    ) {
+        let (scope, names) = self.scopes.last_mut().unwrap();
+        if matches!(kind, Kind::Variable | Kind::Argument) {
+            names.push(name.to_string());
+        }
+        let library = match values {
+            [v] => match &v.value.body {
+                ValueBody::Import(ImportType::Lib(lib), _) => Some(lib.clone()),
+                _ => None,
+            },
+            _ => None,
+        };
+        self.definitions.push(Definition {
+            name: name.to_string(),
+            kind,
+            pos,
+            scope: *scope,
+            typ: infer_type(values),
+            signature: signature(values),
+            library,
+            doc,
+        });
+    }
+
+    fn is_defined(&self, name: &str) -> bool {
+        self.scopes
+            .iter()
+            .any(|(_, names)| names.iter().any(|n| n == name))
+    }
+
+    fn block(&mut self, scope: FileRange, statements: &[Statement], defined: &[String]) {
+        self.scopes.push((scope, Vec::new()));
+        for name in defined {
+            self.define(name, Kind::Variable, scope, &[], Vec::new());
+        }
+        for statement in statements {
+            self.statement(statement);
+        }
+        self.scopes.pop();
+    }
+
+    fn statement(&mut self, statement: &Statement) {
+        match &statement.body {
+            StatementBody::Expr(e) => match crate::lints::definition(e) {
+                Some((symbol, name, values)) => {
+                    if symbol.operator == Some(UnaryOperator::Let) || !self.is_defined(name) {
+                        let doc = statement_doc(statement, values);
+                        self.define(name, Kind::Variable, symbol.pos, values, doc);
+                    }
+                    self.expression(values);
+                }
+                None => self.expression(&e.values),
+            },
+            StatementBody::TypeDef(name) => {
+                let doc = statement_doc(statement, &[]);
+                self.define(name, Kind::Type, statement.pos, &[], doc);
+            }
+            StatementBody::Impl(imp) => {
+                if let ValueBody::TypeIndicator(name) = &imp.symbol.value.body {
+                    let doc = statement_doc(statement, &[]);
+                    self.define(name, Kind::Type, imp.symbol.pos, &[], doc);
+                }
+                for def in &imp.members {
+                    if let DictDef::Def((name, e)) = def {
+                        let pos = e.values.first().map(|v| v.pos).unwrap_or(statement.pos);
+                        let mut doc = Vec::new();
+                        if let [Variable {
+                            value:
+                                ValueLiteral {
+                                    body: ValueBody::Macro(m),
+                                },
+                            ..
+                        }] = &e.values[..]
+                        {
+                            doc.extend(m.properties.get_desc());
+                        }
+                        self.define(name, Kind::Member, pos, &e.values, doc);
+                    }
+                }
+                self.dict(&imp.members);
+            }
+            StatementBody::Call(c) => self.variable(&c.function),
+            StatementBody::Return(Some(e)) | StatementBody::Extract(e) => {
+                self.expression(&e.values)
+            }
+            StatementBody::Error(e) => self.expression(&e.message.values),
+            StatementBody::If(i) => {
+                self.expression(&i.condition.values);
+                self.block(statement.pos, &i.if_body, &[]);
+                if let Some(body) = &i.else_body {
+                    self.block(statement.pos, body, &[]);
+                }
+            }
+            StatementBody::For(f) => {
+                self.expression(&f.array.values);
+                self.block(statement.pos, &f.body, std::slice::from_ref(&f.symbol));
+            }
+            StatementBody::Try(t) => {
+                self.block(statement.pos, &t.try_body, &[]);
+                let caught: Vec<String> = t.catch_symbol.iter().cloned().collect();
+                self.block(statement.pos, &t.catch_body, &caught);
+            }
+            StatementBody::Defer(body) => self.block(statement.pos, body, &[]),
+            _ => (),
+        }
+    }
+
+    fn expression(&mut self, values: &[Variable]) {
+        for v in values {
+            self.variable(v);
+        }
+    }
+
+    fn dict(&mut self, dict: &[DictDef]) {
+        for def in dict {
+            match def {
+                DictDef::Def((_, e)) | DictDef::Extract(e) => self.expression(&e.values),
+            }
+        }
+    }
+
+    fn variable(&mut self, var: &Variable) {
+        match &var.value.body {
+            ValueBody::CmpStmt(c) => self.block(var.pos, &c.statements, &[]),
+            ValueBody::Macro(m) => {
+                self.scopes.push((var.pos, Vec::new()));
+                for (name, default, _, typ) in &m.args {
+                    for e in typ.iter().chain(default.iter()) {
+                        self.expression(&e.values);
+                    }
+                    self.define(name, Kind::Argument, var.pos, &[], Vec::new());
+                }
+                for statement in &m.body.statements {
+                    self.statement(statement);
+                }
+                self.scopes.pop();
+            }
+            ValueBody::Dictionary(d) => self.dict(d),
+            ValueBody::Expression(e) => self.expression(&e.values),
+            ValueBody::Switch(e, cases) => {
+                self.expression(&e.values);
+                for case in cases {
+                    match &case.typ {
+                        CaseType::Value(values) => {
+                            for v in values {
+                                self.expression(&v.values)
+                            }
+                        }
+                        CaseType::Pattern(e) => self.expression(&e.values),
+                        CaseType::Default => (),
+                    }
+                    self.expression(&case.body.values);
+                }
+            }
+            ValueBody::Array(arr) => {
+                for e in arr {
+                    self.expression(&e.values)
+                }
+            }
+            ValueBody::Obj(o) => {
+                for (k, v) in &o.props {
+                    self.expression(&k.values);
+                    self.expression(&v.values);
+                }
+            }
+            ValueBody::Ternary(t) => {
+                self.expression(&t.if_expr.values);
+                self.expression(&t.condition.values);
+                self.expression(&t.else_expr.values);
+            }
+            ValueBody::Try(t) => {
+                self.expression(&t.try_expr.values);
+                self.scopes.push((var.pos, Vec::new()));
+                if let Some(symbol) = &t.catch_symbol {
+                    self.define(symbol, Kind::Variable, var.pos, &[], Vec::new());
+                }
+                self.expression(&t.catch_expr.values);
+                self.scopes.pop();
+            }
+            _ => (),
+        }
+        for p in &var.path {
+            match p {
+                Path::Index(e) => self.expression(&e.values),
+                Path::Call(args) => {
+                    for arg in args {
+                        self.expression(&arg.value.values);
+                    }
+                }
+                Path::Constructor(d) => self.dict(d),
+                _ => (),
+            }
+        }
+    }
+}
+
+fn definitions(statements: &[Statement]) -> Vec<Definition> {
+    let mut collector = Collector {
+        scopes: Vec::new(),
+        definitions: Vec::new(),
+    };
+    collector.block(WHOLE_FILE, statements, &[]);
+    collector.definitions
+}
+
+fn is_name(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+// the name at a position, where it is in the line, and the character before it
+// ('@' for types, '.' for members)
+fn word_at(
+    text: &str,
+    (line, col): (usize, usize),
+) -> Option<(&str, (usize, usize), Option<char>)> {
+    let line_text = text.lines().nth(line.checked_sub(1)?)?;
+    let col = col.min(line_text.len());
+    let start = line_text[..col]
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| is_name(*c))
+        .last()
+        .map(|(i, _)| i)
+        .unwrap_or(col);
+    let end = line_text[col..]
+        .find(|c| !is_name(c))
+        .map(|i| col + i)
+        .unwrap_or_else(|| line_text.len());
+    if start == end {
+        return None;
+    }
+    Some((
+        &line_text[start..end],
+        (start, end),
+        line_text[..start].chars().last(),
+    ))
+}
+
+// the name before a . that is just before the given column
+fn base_name(line_text: &str, dot: usize) -> &str {
+    let before = &line_text[..dot];
+    let start = before
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| is_name(*c) || *c == '$')
+        .last()
+        .map(|(i, _)| i)
+        .unwrap_or(dot);
+    &before[start..]
+}
+
+// the variable a name refers to at a position: the one in the innermost scope, defined
+// last before the position (or first after it, for macros used before they're defined)
+fn visible<'a>(defs: &'a [Definition], name: &str, at: (usize, usize)) -> Option<&'a Definition> {
+    defs.iter()
+        .filter(|d| {
+            d.name == name
+                && matches!(d.kind, Kind::Variable | Kind::Argument)
+                && contains(d.scope, at)
+        })
+        .max_by_key(|d| {
+            let before = d.pos.0 <= at;
+            let order = if before {
+                d.pos.0
+            } else {
+                (usize::MAX - d.pos.0 .0, usize::MAX - d.pos.0 .1)
+            };
+            (d.scope.0, before, order)
+        })
+}
+
+// whether the end of the text is where a key of an obj { } or trigger { } goes
+fn in_object_keys(text: &str) -> bool {
+    // 'o' for the braces of objects
+    let mut brackets = Vec::new();
+    let mut key_position = false;
+    let mut word = String::new();
+    let mut last_word = String::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if is_name(c) {
+            word.push(c);
+            continue;
+        }
+        if !word.is_empty() {
+            last_word = std::mem::take(&mut word);
+        }
+        match c {
+            '"' | '\'' => {
+                while let Some(s) = chars.next() {
+                    if s == '\\' {
+                        chars.next();
+                    } else if s == c {
+                        break;
+                    }
+                }
+                key_position = false;
+            }
+            '/' if chars.peek() == Some(&'/') => {
+                for s in chars.by_ref() {
+                    if s == '\n' {
+                        break;
+                    }
+                }
+            }
+            '{' => {
+                let object = last_word == "obj" || last_word == "trigger";
+                brackets.push(if object { 'o' } else { '{' });
+                key_position = object;
+            }
+            '[' | '(' => {
+                brackets.push(c);
+                key_position = false;
+            }
+            '}' | ']' | ')' => {
+                brackets.pop();
+                key_position = false;
+            }
+            ',' => key_position = brackets.last() == Some(&'o'),
+            c if c.is_whitespace() => (),
+            _ => key_position = false,
+        }
+        if !c.is_whitespace() && !is_name(c) {
+            last_word.clear();
+        }
+    }
+    key_position && brackets.last() == Some(&'o')
+}
+
+struct Member {
+    name: String,
+    typ: String,
+    doc: Option<String>,
+}
+
+#[derive(Default)]
+struct Server {
+    // uri -> the text of the open files
+    documents: HashMap<String, String>,
+    // library -> its exported members
+    libraries: HashMap<String, Vec<Member>>,
+    object_properties: Option<Vec<String>>,
+}
+
+// parses an open file (with the aliases of its project)
+fn parse(
+    uri: &str,
+    text: &str,
+) -> (
+    Vec<Statement>,
+    crate::parser::ParseNotes,
+    Vec<crate::parser::SyntaxError>,
+) {
+    let path = uri_to_path(uri);
+    let dir = path.parent().map(PathBuf::from).unwrap_or_default();
+    let aliases = crate::aliases::Aliases::find(&dir).unwrap_or(None);
+    parse_spwn_partial_with(text.to_string(), path, aliases.as_ref())
+}
+
+fn completion_item(label: &str, kind: u8, detail: Option<&str>, doc: Option<&str>) -> String {
+    let mut item = format!("{{\"label\": {}, \"kind\": {}", json_str(label), kind);
+    if let Some(detail) = detail {
+        item += &format!(", \"detail\": {}", json_str(detail));
+    }
+    if let Some(doc) = doc {
+        item += &format!(", \"documentation\": {}", json_str(doc));
+    }
+    item + "}"
+}
+
+// completion item kinds in the protocol
+const FUNCTION: u8 = 3;
+const FIELD: u8 = 5;
+const VARIABLE: u8 = 6;
+const PROPERTY: u8 = 10;
+const TYPE: u8 = 22;
+
+impl Server {
+    fn diagnostics(&self, uri: &str) -> String {
+        let text = self.documents.get(uri).map(|t| t.as_str()).unwrap_or("");
+        let (statements, notes, errors) = parse(uri, text);
+        let mut out = Vec::new();
+        let mut add = |pos: FileRange, severity: u8, code: &str, message: &str| {
+            out.push(format!(
+                "{{\"range\": {}, \"severity\": {}, \"code\": {}, \"source\": \"spwn\", \"message\": {}}}",
+                lsp_range(text, pos),
+                severity,
+                json_str(code),
+                json_str(message)
+            ))
+        };
+        for err in &errors {
+            let diagnostic = Diagnostic::from(err);
+            add(diagnostic.pos, 1, diagnostic.code, &diagnostic.message);
+        }
+        // a half written file would have a lot of them
+        if errors.is_empty() {
+            for (lint, pos, message) in crate::lints::check(&statements, &notes.tag) {
+                add(pos, 2, lint, &message);
+            }
+        }
+        format!(
+            "{{\"jsonrpc\": \"2.0\", \"method\": \"textDocument/publishDiagnostics\", \"params\": {{\"uri\": {}, \"diagnostics\": [{}]}}}}",
+            json_str(uri),
+            out.join(", ")
+        )
+    }
+
+    // notifications from the editor, and the ones sent back
+    fn notify(&mut self, method: &str, params: &Json) -> Vec<String> {
+        let uri = params
+            .at("textDocument.uri")
+            .as_str()
+            .unwrap_or("")
+            .to_string();
+        match method {
+            "textDocument/didOpen" => {
+                let text = params.at("textDocument.text").as_str().unwrap_or("");
+                self.documents.insert(uri.clone(), text.to_string());
+                vec![self.diagnostics(&uri)]
+            }
+            "textDocument/didChange" => {
+                // the whole file is sent every time
+                if let Json::Array(changes) = params.get("contentChanges") {
+                    if let Some(text) = changes.last().and_then(|c| c.get("text").as_str()) {
+                        self.documents.insert(uri.clone(), text.to_string());
+                    }
+                }
+                vec![self.diagnostics(&uri)]
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+                vec![self.diagnostics(&uri)]
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    fn respond(&mut self, method: &str, params: &Json) -> Result<String, String> {
+        let uri = params.at("textDocument.uri").as_str().unwrap_or("");
+        let text = self.documents.get(uri).cloned().unwrap_or_default();
+        let at = file_position(&text, params.get("position"));
+        match (method, at) {
+            ("initialize", _) => Ok(format!(
+                "{{\"capabilities\": {{\"textDocumentSync\": 1, \"definitionProvider\": true, \"hoverProvider\": true, \"completionProvider\": {{\"triggerCharacters\": [\".\", \"@\"]}}}}, \"serverInfo\": {{\"name\": \"spwn\", \"version\": {}}}}}",
+                json_str(env!("CARGO_PKG_VERSION"))
+            )),
+            ("shutdown", _) => Ok("null".to_string()),
+            ("textDocument/definition", Some(at)) => Ok(definition(uri, &text, at)),
+            ("textDocument/hover", Some(at)) => Ok(hover(&text, at)),
+            ("textDocument/completion", Some(at)) => Ok(self.completion(uri, &text, at)),
+            (
+                "textDocument/definition" | "textDocument/hover" | "textDocument/completion",
+                None,
+            ) => Ok("null".to_string()),
+            (m, _) => Err(format!("Unknown method: {}", m)),
+        }
+    }
+
+    // the members a library exports (it's built the first time)
+    fn library(&mut self, name: &str) -> &[Member] {
+        self.libraries.entry(name.to_string()).or_insert_with(|| {
+            let (mut globals, exports) = match crate::documentation::import_lib(name) {
+                Ok(lib) => lib,
+                Err(_) => return Vec::new(),
+            };
+            let dict = match exports {
+                crate::value::Value::Dict(d) => d,
+                _ => return Vec::new(),
+            };
+            let mut members: Vec<Member> = dict
+                .iter()
+                .filter(|(k, _)| *k != TYPE_MEMBER_NAME)
+                .map(|(k, v)| {
+                    let val = globals.stored_values[*v].clone();
+                    let typ = val
+                        .member(
+                            TYPE_MEMBER_NAME.to_string(),
+                            &crate::context::Context::new(),
+                            &mut globals,
+                        )
+                        .map(|t| globals.stored_values[t].to_str(&globals))
+                        .unwrap_or_default();
+                    let doc = match &val {
+                        crate::value::Value::Macro(m) => m.tag.get_desc(),
+                        _ => None,
+                    };
+                    Member {
+                        name: k.clone(),
+                        typ,
+                        doc,
+                    }
+                })
+                .collect();
+            members.sort_by(|a, b| a.name.cmp(&b.name));
+            members
+        })
+    }
+
+    // the keys in std's obj_props
+    fn object_properties(&mut self) -> &[String] {
+        self.object_properties.get_or_insert_with(|| {
+            let (globals, exports) = match crate::documentation::import_lib("std") {
+                Ok(lib) => lib,
+                Err(_) => return Vec::new(),
+            };
+            let props = match exports {
+                crate::value::Value::Dict(d) => d.get("obj_props").copied(),
+                _ => None,
+            };
+            let mut keys: Vec<String> = match props.map(|p| &globals.stored_values[p]) {
+                Some(crate::value::Value::Dict(d)) => d
+                    .keys()
+                    .filter(|k| *k != TYPE_MEMBER_NAME)
+                    .cloned()
+                    .collect(),
+                _ => Vec::new(),
+            };
+            keys.sort();
+            keys
+        })
+    }
+
+    fn completion(&mut self, uri: &str, text: &str, at: (usize, usize)) -> String {
+        let line_text = text.lines().nth(at.0 - 1).unwrap_or("");
+        let before = &line_text[..at.1.min(line_text.len())];
+        let typed = before
+            .char_indices()
+            .rev()
+            .take_while(|(_, c)| is_name(*c))
+            .last()
+            .map(|(i, _)| i)
+            .unwrap_or(before.len());
+        let (statements, _, _) = parse(uri, text);
+        let defs = definitions(&statements);
+
+        let mut items = Vec::new();
+        match before[..typed].chars().last() {
+            Some('.') => {
+                let base = base_name(before, typed - 1);
+                let library = visible(&defs, base, at).and_then(|d| d.library.clone());
+                if base == "$" {
+                    for name in BUILTIN_LIST {
+                        items.push(completion_item(name, FUNCTION, Some("builtin"), None));
+                    }
+                } else if let Some(library) = library {
+                    for m in self.library(&library) {
+                        items.push(completion_item(
+                            &m.name,
+                            FIELD,
+                            Some(&m.typ),
+                            m.doc.as_deref(),
+                        ));
+                    }
+                } else {
+                    // it could be anything, so all the members given to types in the file
+                    for d in defs.iter().filter(|d| d.kind == Kind::Member) {
+                        items.push(completion_item(
+                            &d.name,
+                            FIELD,
+                            d.typ.as_deref(),
+                            d.doc.first().map(|s| s.as_str()),
+                        ));
+                    }
+                }
+            }
+            Some('@') => {
+                let mut types: Vec<String> = crate::globals::Globals::new(PathBuf::new())
+                    .type_ids
+                    .keys()
+                    .cloned()
+                    .collect();
+                types.extend(
+                    defs.iter()
+                        .filter(|d| d.kind == Kind::Type)
+                        .map(|d| d.name.clone()),
+                );
+                types.sort();
+                types.dedup();
+                for t in types {
+                    items.push(completion_item(&t, TYPE, None, None));
+                }
+            }
+            _ if in_object_keys(&text_before(text, (at.0, typed))) => {
+                for key in self.object_properties() {
+                    items.push(completion_item(
+                        key,
+                        PROPERTY,
+                        Some("object property"),
+                        None,
+                    ));
+                }
+            }
+            _ => {
+                let mut shown = Vec::new();
+                for d in &defs {
+                    if shown.contains(&&d.name) || visible(&defs, &d.name, at).is_none() {
+                        continue;
+                    }
+                    shown.push(&d.name);
+                    let kind = if d.signature.is_some() {
+                        FUNCTION
+                    } else {
+                        VARIABLE
+                    };
+                    items.push(completion_item(
+                        &d.name,
+                        kind,
+                        d.signature.as_deref().or(d.typ.as_deref()),
+                        d.doc.first().map(|s| s.as_str()),
+                    ));
+                }
+            }
+        }
+        format!("[{}]", items.join(", "))
+    }
+}
+
+// the text of the file up to a position
+fn text_before(text: &str, (line, col): (usize, usize)) -> String {
+    let mut out = String::new();
+    for (i, line_text) in text.lines().enumerate().take(line) {
+        if i + 1 == line {
+            out += &line_text[..col.min(line_text.len())];
+        } else {
+            out += line_text;
+            out += "\n";
+        }
+    }
+    out
+}
+
+fn location(uri: &str, text: &str, pos: FileRange) -> String {
+    format!(
+        "{{\"uri\": {}, \"range\": {}}}",
+        json_str(uri),
+        lsp_range(text, pos)
+    )
+}
+
+fn definition(uri: &str, text: &str, at: (usize, usize)) -> String {
+    let (word, _, before) = match word_at(text, at) {
+        Some(w) => w,
+        None => return "null".to_string(),
+    };
+    let (statements, _, _) = parse(uri, text);
+    let defs = definitions(&statements);
+    let found: Vec<&Definition> = match before {
+        Some('@') => defs
+            .iter()
+            .filter(|d| d.kind == Kind::Type && d.name == word)
+            .collect(),
+        Some('.') => defs
+            .iter()
+            .filter(|d| d.kind == Kind::Member && d.name == word)
+            .collect(),
+        _ => visible(&defs, word, at).into_iter().collect(),
+    };
+    if found.is_empty() {
+        return "null".to_string();
+    }
+    let locations: Vec<String> = found.iter().map(|d| location(uri, text, d.pos)).collect();
+    format!("[{}]", locations.join(", "))
+}
+
+fn hover(text: &str, at: (usize, usize)) -> String {
+    let (word, (start, end), before) = match word_at(text, at) {
+        Some(w) => w,
+        None => return "null".to_string(),
+    };
+    let (statements, _, _) = parse("", text);
+    let defs = definitions(&statements);
+    let line_text = text.lines().nth(at.0 - 1).unwrap_or("");
+
+    let (code, doc) = match before {
+        Some('.') if base_name(line_text, start - 1) == "$" => {
+            if !BUILTIN_LIST.contains(&word) {
+                return "null".to_string();
+            }
+            (format!("$.{}", word), vec!["builtin".to_string()])
+        }
+        Some('@') => {
+            let doc = defs
+                .iter()
+                .filter(|d| d.kind == Kind::Type && d.name == word)
+                .flat_map(|d| d.doc.iter().cloned())
+                .collect();
+            (format!("@{}", word), doc)
+        }
+        _ => {
+            let d = match before {
+                Some('.') => defs
+                    .iter()
+                    .find(|d| d.kind == Kind::Member && d.name == word),
+                // or the key in an impl block
+                _ => visible(&defs, word, at).or_else(|| {
+                    defs.iter()
+                        .find(|d| d.kind == Kind::Member && d.name == word)
+                }),
+            };
+            let d = match d {
+                Some(d) => d,
+                None => return "null".to_string(),
+            };
+            let code = match (&d.signature, &d.typ, d.kind) {
+                (Some(signature), _, _) => format!("{} = {} {{ ... }}", d.name, signature),
+                (_, Some(typ), _) => format!("{}: {}", d.name, typ),
+                (_, _, Kind::Argument) => format!("{} (argument)", d.name),
+                _ => d.name.clone(),
+            };
+            (code, d.doc.clone())
+        }
+    };
+    let mut markdown = format!("```spwn\n{}\n```", code);
+    if !doc.is_empty() {
+        markdown += &format!("\n\n{}", doc.join("\n"));
+    }
+    format!(
+        "{{\"contents\": {{\"kind\": \"markdown\", \"value\": {}}}, \"range\": {}}}",
+        json_str(&markdown),
+        lsp_range(text, ((at.0, start), (at.0, end)))
+    )
+}
+
+pub fn run() -> Result<(), Box<dyn std::error::Error>> {
+    let stdin = std::io::stdin();
+    let mut input = stdin.lock();
+    let stdout = std::io::stdout();
+    let mut server = Server::default();
+
+    while let Some(message) = read_message(&mut input)? {
+        let message = match Json::parse(&message) {
+            Some(m) => m,
+            None => continue,
+        };
+        let method = message.get("method").as_str().unwrap_or("");
+        if method == "exit" {
+            break;
+        }
+        let params = message.get("params");
+        let mut out = stdout.lock();
+        for notification in server.notify(method, params) {
+            send(&mut out, &notification)?;
+        }
+        // requests have an id, notifications don't
+        let id = message.get("id");
+        if *id != Json::Null && !method.is_empty() {
+            let body = match server.respond(method, params) {
+                Ok(result) => format!(
+                    "{{\"jsonrpc\": \"2.0\", \"id\": {}, \"result\": {}}}",
+                    id.id_json(),
+                    result
+                ),
+                Err(message) => format!(
+                    "{{\"jsonrpc\": \"2.0\", \"id\": {}, \"error\": {{\"code\": -32601, \"message\": {}}}}}",
+                    id.id_json(),
+                    json_str(&message)
+                ),
+            };
+            send(&mut out, &body)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCRIPT: &str = "// adds two numbers
+add = (a, b: @number = 1) {
+    let a = a + b
+    return a
+}
+type @thing
+impl @thing {
+    size: 10,
+}
+total = add(1, 2)
+x = obj {
+    X: total,
+
+}
+";
+
+    #[test]
+    fn json_is_read() {
+        let json = Json::parse(
+            r#"{"id": 3, "params": {"text": "a\n\"b\" \u00e9\ud83d\ude00", "list": [1, true, null]}}"#,
+        )
+        .unwrap();
+        assert_eq!(json.get("id").as_usize(), Some(3));
+        assert_eq!(json.at("params.text").as_str(), Some("a\n\"b\" é😀"));
+        assert_eq!(
+            json.at("params.list"),
+            &Json::Array(vec![Json::Number(1.0), Json::Bool(true), Json::Null])
+        );
+        assert_eq!(json.at("params.missing.x"), &Json::Null);
+        assert!(Json::parse("{\"a\": }").is_none());
+    }
+
+    #[test]
+    fn definitions_are_found() {
+        let uri = "file:///project/main.spwn";
+        // `add` in `total = add(1, 2)`
+        let found = definition(uri, SCRIPT, (10, 9));
+        assert!(
+            found.contains("\"start\": {\"line\": 1, \"character\": 0}"),
+            "{}",
+            found
+        );
+        // the inner a in `return a`
+        let found = definition(uri, SCRIPT, (4, 12));
+        assert!(
+            found.contains("\"start\": {\"line\": 2, \"character\": 4}"),
+            "{}",
+            found
+        );
+        // type @thing and impl @thing
+        let found = definition(uri, SCRIPT, (7, 7));
+        assert_eq!(found.matches("\"uri\"").count(), 2);
+
+        let shown = hover(SCRIPT, (10, 9));
+        assert!(
+            shown.contains("add = (a, b: @number = 1) { ... }"),
+            "{}",
+            shown
+        );
+        assert!(shown.contains("adds two numbers"));
+        assert!(hover(SCRIPT, (10, 1)).contains("total"));
+        assert!(hover(SCRIPT, (8, 5)).contains("size: @number"));
+    }
+
+    #[test]
+    fn completion_contexts() {
+        assert!(in_object_keys("x = obj {\n    X: 10,\n    "));
+        assert!(in_object_keys("x = trigger{"));
+        assert!(!in_object_keys("x = obj { X: "));
+        assert!(!in_object_keys("x = obj { GROUPS: [1g, "));
+        assert!(!in_object_keys("x = { a: 1, "));
+        assert!(!in_object_keys("x = obj { X: 1 }\n"));
+
+        let mut server = Server::default();
+        let items = server.completion("", SCRIPT, (11, 0));
+        assert!(items.contains("\"label\": \"add\""));
+        assert!(items.contains("\"label\": \"total\""));
+        // the arguments of add aren't in scope there
+        assert!(!items.contains("\"label\": \"b\""));
+        let items = server.completion("", "$.pr", (1, 4));
+        assert!(items.contains("\"label\": \"print\""));
+    }
+}
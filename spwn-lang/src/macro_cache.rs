@@ -0,0 +1,555 @@
+// spwn build --cache-macros: when a macro is called again with the same body, arguments
+// and captured variables, the triggers it made the first time are copied (with new
+// arbitrary IDs) instead of running the macro again
+// calls are only remembered if copying them is the same as running them again, so
+// calls that change something outside of the macro (printing, changing a captured value,
+// waiting, adding an implementation and so on) always run
+use crate::builtin::*;
+use crate::compiler_types::{FunctionId, Returns};
+use crate::context::Context;
+use crate::fmt::SpwnFmt;
+use crate::globals::Globals;
+use crate::levelstring::{GdObj, ObjParam};
+use crate::parser::FileRange;
+use crate::value::{Macro, Value};
+use crate::value_storage::{store_const_value, StoredValue};
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::path::PathBuf;
+
+// how deep macros inside the captured variables are followed before giving up
+const MAX_DEPTH: usize = 6;
+
+#[derive(Default)]
+pub struct MacroCache {
+    entries: HashMap<u64, Entry>,
+    // the code of each macro (by where it's defined) and the names in it
+    bodies: HashMap<(PathBuf, FileRange), (String, HashSet<String>)>,
+    // set by builtins that can give something different every call ($.time, $.get_input)
+    pub impure: bool,
+    pub reused: usize,
+}
+
+// objects with their trigger order
+type ObjList = Vec<(GdObj, usize)>;
+
+// a return value without any stored values in it
+enum Plain {
+    Value(Value),
+    Array(Vec<Plain>),
+    Dict(Vec<(String, Plain)>),
+}
+
+struct Entry {
+    start_group: Group,
+    func_id: usize,
+    first_func_id: usize,
+    // closed groups, colors, blocks and items before the call, and how many it used
+    ids: [(u16, u16); 4],
+    uids: (usize, usize),
+    orders: (usize, usize),
+    // added to the function the macro was called in
+    triggers: ObjList,
+    // trigger functions made in the macro (by their parent)
+    functions: Vec<(Option<usize>, ObjList)>,
+    objects: Vec<GdObj>,
+    origins: HashMap<usize, (PathBuf, FileRange)>,
+    result: Plain,
+}
+
+pub struct Recording {
+    key: u64,
+    start: Context,
+    ids: [u16; 4],
+    func_ids: usize,
+    other_triggers: usize,
+    calling_triggers: usize,
+    objects: usize,
+    uids: usize,
+    orders: usize,
+    warnings: usize,
+    sync_groups: usize,
+    type_ids: u16,
+    implementations: u64,
+    // what the flags were outside of the call
+    printed: bool,
+    impure: bool,
+}
+
+fn body_of(cache: &mut MacroCache, m: &Macro) -> (String, HashSet<String>) {
+    cache
+        .bodies
+        .entry((m.def_file.clone(), m.def_pos))
+        .or_insert_with(|| {
+            let code: String = m.body.iter().map(|s| s.fmt(0)).collect();
+            let words = crate::partial::words(&code);
+            (code, words)
+        })
+        .clone()
+}
+
+fn implementations(globals: &Globals) -> u64 {
+    // the same no matter what order the map is in
+    let mut sum = 0u64;
+    for (typ, members) in &globals.implementations {
+        for (name, (val, _)) in members {
+            let mut h = DefaultHasher::new();
+            (typ, name, val).hash(&mut h);
+            sum = sum.wrapping_add(h.finish());
+        }
+    }
+    sum
+}
+
+fn fingerprint(
+    val: StoredValue,
+    cache: &mut MacroCache,
+    globals: &Globals,
+    depth: usize,
+    out: &mut String,
+) -> Option<()> {
+    if depth > MAX_DEPTH {
+        return None;
+    }
+    match &globals.stored_values[val] {
+        Value::Array(a) => {
+            out.push('[');
+            for v in a {
+                fingerprint(*v, cache, globals, depth + 1, out)?;
+                out.push(',');
+            }
+            out.push(']');
+        }
+        Value::Dict(d) => {
+            let mut keys: Vec<&String> = d.keys().collect();
+            keys.sort();
+            out.push('{');
+            for k in keys {
+                out.push_str(k);
+                out.push(':');
+                fingerprint(d[k], cache, globals, depth + 1, out)?;
+                out.push(',');
+            }
+            out.push('}');
+        }
+        Value::Macro(m) => {
+            out.push_str("macro(");
+            for (name, default, _, pattern) in &m.args {
+                out.push_str(name);
+                for v in default.iter().chain(pattern.iter()) {
+                    out.push('=');
+                    fingerprint(*v, cache, globals, depth + 1, out)?;
+                }
+                out.push(',');
+            }
+            out.push(')');
+            captured(m, &m.def_context, cache, globals, depth + 1, out)?;
+        }
+        other => out.push_str(&format!("{:?}", other)),
+    }
+    Some(())
+}
+
+// the macro's code and the values of the variables it uses
+fn captured(
+    m: &Macro,
+    context: &Context,
+    cache: &mut MacroCache,
+    globals: &Globals,
+    depth: usize,
+    out: &mut String,
+) -> Option<()> {
+    let (code, words) = body_of(cache, m);
+    out.push_str(&format!("{}{}", m.def_file.to_string_lossy(), code));
+
+    let mut names: Vec<&String> = context
+        .variables
+        .keys()
+        .filter(|n| words.contains(*n))
+        .collect();
+    names.sort();
+    for name in names {
+        let val = context.variables[name];
+        let data = globals.stored_values.get(val)?;
+        // changing it is only allowed in the context it was made in
+        out.push_str(&format!(
+            "{}({},{})=",
+            name,
+            data.fn_context == context.start_group,
+            data.mutable
+        ));
+        fingerprint(val, cache, globals, depth, out)?;
+        out.push(';');
+    }
+    Some(())
+}
+
+// what the call is cached by, if it can be cached
+pub fn key(m: &Macro, context: &Context, globals: &mut Globals) -> Option<u64> {
+    if globals.trace.is_some() || globals.history.is_some() {
+        return None;
+    }
+    let mut cache = globals.macro_cache.take()?;
+    let mut out = format!(
+        "{}:{}:{}:{}|",
+        context.start_group.id == Id::Specific(0),
+        context.sync_group,
+        context.sync_part,
+        implementations(globals)
+    );
+    let found = captured(m, context, &mut cache, globals, 0, &mut out);
+    globals.macro_cache = Some(cache);
+    found?;
+
+    let mut h = DefaultHasher::new();
+    out.hash(&mut h);
+    Some(h.finish())
+}
+
+fn remap_id(id: Id, (before, used): (u16, u16), now: u16) -> Id {
+    match id {
+        Id::Arbitrary(n) if n > before && n <= before + used => Id::Arbitrary(n - before + now),
+        a => a,
+    }
+}
+
+struct Remap {
+    ids: [(u16, u16); 4],
+    now: [u16; 4],
+}
+
+impl Remap {
+    fn group(&self, g: Group) -> Group {
+        Group {
+            id: remap_id(g.id, self.ids[0], self.now[0]),
+        }
+    }
+    fn param(&self, p: &ObjParam) -> ObjParam {
+        match p {
+            ObjParam::Group(g) => ObjParam::Group(self.group(*g)),
+            ObjParam::GroupList(l) => {
+                ObjParam::GroupList(l.iter().map(|g| self.group(*g)).collect())
+            }
+            ObjParam::Color(c) => ObjParam::Color(Color {
+                id: remap_id(c.id, self.ids[1], self.now[1]),
+            }),
+            ObjParam::Block(b) => ObjParam::Block(Block {
+                id: remap_id(b.id, self.ids[2], self.now[2]),
+            }),
+            ObjParam::Item(i) => ObjParam::Item(Item {
+                id: remap_id(i.id, self.ids[3], self.now[3]),
+            }),
+            p => p.clone(),
+        }
+    }
+    fn value(&self, v: &Value) -> Value {
+        match v {
+            Value::Group(g) => Value::Group(self.group(*g)),
+            Value::TriggerFunc(f) => Value::TriggerFunc(crate::value::TriggerFunction {
+                start_group: self.group(f.start_group),
+            }),
+            Value::Color(_) | Value::Block(_) | Value::Item(_) => {
+                let p = match v {
+                    Value::Color(c) => self.param(&ObjParam::Color(*c)),
+                    Value::Block(b) => self.param(&ObjParam::Block(*b)),
+                    Value::Item(i) => self.param(&ObjParam::Item(*i)),
+                    _ => unreachable!(),
+                };
+                match p {
+                    ObjParam::Color(c) => Value::Color(c),
+                    ObjParam::Block(b) => Value::Block(b),
+                    ObjParam::Item(i) => Value::Item(i),
+                    _ => unreachable!(),
+                }
+            }
+            Value::Obj(params, mode) => Value::Obj(
+                params.iter().map(|(k, p)| (*k, self.param(p))).collect(),
+                *mode,
+            ),
+            other => other.clone(),
+        }
+    }
+}
+
+fn plain(val: StoredValue, globals: &Globals, depth: usize) -> Option<Plain> {
+    if depth > MAX_DEPTH {
+        return None;
+    }
+    Some(match &globals.stored_values[val] {
+        Value::Array(a) => Plain::Array(
+            a.iter()
+                .map(|v| plain(*v, globals, depth + 1))
+                .collect::<Option<_>>()?,
+        ),
+        Value::Dict(d) => Plain::Dict(
+            d.iter()
+                .map(|(k, v)| Some((k.clone(), plain(*v, globals, depth + 1)?)))
+                .collect::<Option<_>>()?,
+        ),
+        v @ Value::Group(_)
+        | v @ Value::Color(_)
+        | v @ Value::Block(_)
+        | v @ Value::Item(_)
+        | v @ Value::Number(_)
+        | v @ Value::Bool(_)
+        | v @ Value::Str(_)
+        | v @ Value::Obj(..)
+        | v @ Value::TriggerFunc(_)
+        | v @ Value::TypeIndicator(_)
+        | v @ Value::Range(..)
+        | v @ Value::Null => Plain::Value(v.clone()),
+        _ => return None,
+    })
+}
+
+fn mentions(p: &Plain, g: Group) -> bool {
+    match p {
+        Plain::Value(Value::Group(a)) => *a == g,
+        Plain::Value(Value::TriggerFunc(f)) => f.start_group == g,
+        Plain::Value(Value::Obj(params, _)) => params.iter().any(|(_, p)| param_mentions(p, g)),
+        Plain::Value(_) => false,
+        Plain::Array(a) => a.iter().any(|p| mentions(p, g)),
+        Plain::Dict(d) => d.iter().any(|(_, p)| mentions(p, g)),
+    }
+}
+
+fn param_mentions(p: &ObjParam, g: Group) -> bool {
+    match p {
+        ObjParam::Group(a) => *a == g,
+        ObjParam::GroupList(l) => l.contains(&g),
+        _ => false,
+    }
+}
+
+fn counters(globals: &Globals) -> [u16; 4] {
+    [
+        globals.closed_groups,
+        globals.closed_colors,
+        globals.closed_blocks,
+        globals.closed_items,
+    ]
+}
+
+// starts remembering what a call does
+pub fn start(key: u64, start: Context, globals: &mut Globals) -> Recording {
+    let cache = globals.macro_cache.as_mut().unwrap();
+    let impure = cache.impure;
+    cache.impure = false;
+    let printed = globals.printed;
+    globals.printed = false;
+
+    let calling_triggers = globals.func_ids[start.func_id].obj_list.len();
+    Recording {
+        key,
+        ids: counters(globals),
+        func_ids: globals.func_ids.len(),
+        other_triggers: globals
+            .func_ids
+            .iter()
+            .map(|f| f.obj_list.len())
+            .sum::<usize>()
+            - calling_triggers,
+        calling_triggers,
+        objects: globals.objects.len(),
+        uids: globals.uid_counter,
+        orders: globals.trigger_order,
+        warnings: globals.warnings.len(),
+        sync_groups: globals.sync_groups.len(),
+        type_ids: globals.type_id_count,
+        implementations: implementations(globals),
+        printed,
+        impure,
+        start,
+    }
+}
+
+// keeps the call if it can be copied later
+pub fn finish(r: Recording, m: &Macro, returns: &Returns, globals: &mut Globals) {
+    let cache = globals.macro_cache.as_mut().unwrap();
+    let impure = cache.impure;
+    cache.impure |= r.impure;
+    let printed = globals.printed;
+    globals.printed |= r.printed;
+
+    let f = r.start.func_id;
+    let same = !impure
+        && !printed
+        && returns.len() == 1
+        && returns[0].1.start_group == r.start.start_group
+        && returns[0].1.func_id == f
+        && returns[0].1.sync_group == r.start.sync_group
+        && returns[0].1.sync_part == r.start.sync_part
+        && globals.warnings.len() == r.warnings
+        && globals.sync_groups.len() == r.sync_groups
+        && globals.type_id_count == r.type_ids
+        && implementations(globals) == r.implementations
+        && globals.func_ids[..r.func_ids]
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| *i != f)
+            .map(|(_, f)| f.obj_list.len())
+            .sum::<usize>()
+            == r.other_triggers;
+    if !same {
+        return;
+    }
+    // the macro can't have changed anything it can see
+    if key(m, &r.start, globals) != Some(r.key) {
+        return;
+    }
+    let result = match plain(returns[0].0, globals, 0) {
+        Some(p) => p,
+        None => return,
+    };
+
+    let triggers: ObjList = globals.func_ids[f].obj_list[r.calling_triggers..].to_vec();
+    let functions: Vec<(Option<usize>, ObjList)> = globals.func_ids[r.func_ids..]
+        .iter()
+        .map(|f| (f.parent, f.obj_list.clone()))
+        .collect();
+    let objects = globals.objects[r.objects..].to_vec();
+
+    // the start group can only be copied where it's the group of the triggers
+    let start_group = r.start.start_group;
+    let elsewhere = triggers.iter().any(|(o, _)| {
+        o.params
+            .iter()
+            .any(|(k, p)| *k != 57 && param_mentions(p, start_group))
+    }) || functions
+        .iter()
+        .flat_map(|(_, l)| l.iter().map(|(o, _)| o))
+        .chain(objects.iter())
+        .any(|o| o.params.values().any(|p| param_mentions(p, start_group)))
+        || mentions(&result, start_group);
+    if elsewhere {
+        return;
+    }
+
+    let now = counters(globals);
+    let mut ids = [(0, 0); 4];
+    for i in 0..4 {
+        ids[i] = (r.ids[i], now[i] - r.ids[i]);
+    }
+    let origins = (r.uids + 1..=globals.uid_counter)
+        .filter_map(|u| globals.object_origins.get(&u).map(|o| (u, o.clone())))
+        .collect();
+    let entry = Entry {
+        start_group,
+        func_id: f,
+        first_func_id: r.func_ids,
+        ids,
+        uids: (r.uids, globals.uid_counter - r.uids),
+        orders: (r.orders, globals.trigger_order - r.orders),
+        triggers,
+        functions,
+        objects,
+        origins,
+        result,
+    };
+    globals
+        .macro_cache
+        .as_mut()
+        .unwrap()
+        .entries
+        .insert(r.key, entry);
+}
+
+fn store_plain(p: &Plain, remap: &Remap, globals: &mut Globals, context: &Context) -> StoredValue {
+    let val = match p {
+        Plain::Value(v) => remap.value(v),
+        Plain::Array(a) => Value::Array(
+            a.iter()
+                .map(|p| store_plain(p, remap, globals, context))
+                .collect(),
+        ),
+        Plain::Dict(d) => Value::Dict(
+            d.iter()
+                .map(|(k, p)| (k.clone(), store_plain(p, remap, globals, context)))
+                .collect(),
+        ),
+    };
+    store_const_value(val, 1, globals, context)
+}
+
+// copies what a cached call did into the context, and gives back what it returned
+pub fn replay(key: u64, context: &Context, globals: &mut Globals) -> Option<StoredValue> {
+    let mut cache = globals.macro_cache.take()?;
+    let entry = match cache.entries.get(&key) {
+        Some(e) => e,
+        None => {
+            globals.macro_cache = Some(cache);
+            return None;
+        }
+    };
+
+    let now = counters(globals);
+    let remap = Remap {
+        ids: entry.ids,
+        now,
+    };
+    globals.closed_groups += entry.ids[0].1;
+    globals.closed_colors += entry.ids[1].1;
+    globals.closed_blocks += entry.ids[2].1;
+    globals.closed_items += entry.ids[3].1;
+
+    let first_func_id = globals.func_ids.len();
+    let func = |f: usize| {
+        if f == entry.func_id {
+            context.func_id
+        } else if f >= entry.first_func_id {
+            f - entry.first_func_id + first_func_id
+        } else {
+            f
+        }
+    };
+    let (uid_before, uid_now) = (entry.uids.0, globals.uid_counter);
+    let (order_before, order_now) = (entry.orders.0, globals.trigger_order);
+    let mut origins = Vec::new();
+    let mut copy = |o: &GdObj| {
+        let unique_id = o.unique_id - uid_before + uid_now;
+        if let Some(origin) = entry.origins.get(&o.unique_id) {
+            origins.push((unique_id, origin.clone()));
+        }
+        GdObj {
+            params: o.params.iter().map(|(k, p)| (*k, remap.param(p))).collect(),
+            func_id: func(o.func_id),
+            unique_id,
+            ..o.clone()
+        }
+    };
+
+    for (o, order) in &entry.triggers {
+        let mut obj = copy(o);
+        if obj.params.get(&57) == Some(&ObjParam::Group(entry.start_group)) {
+            obj.params.insert(57, ObjParam::Group(context.start_group));
+        }
+        globals.func_ids[context.func_id]
+            .obj_list
+            .push((obj, order - order_before + order_now));
+    }
+    for (parent, list) in &entry.functions {
+        let obj_list = list
+            .iter()
+            .map(|(o, order)| (copy(o), order - order_before + order_now))
+            .collect();
+        globals.func_ids.push(FunctionId {
+            parent: parent.map(func),
+            width: None,
+            obj_list,
+        });
+    }
+    for o in &entry.objects {
+        let obj = copy(o);
+        globals.objects.push(obj);
+    }
+    globals.object_origins.extend(origins);
+    globals.uid_counter += entry.uids.1;
+    globals.trigger_order += entry.orders.1;
+
+    let result = store_plain(&entry.result, &remap, globals, context);
+    cache.reused += 1;
+    globals.macro_cache = Some(cache);
+    Some(result)
+}
@@ -1,51 +1,24 @@
 //#![feature(arbitrary_enum_discriminant)]
 
-mod ast;
-mod builtin;
-mod compiler;
-mod compiler_info;
-mod compiler_types;
-mod documentation;
-mod fmt;
-mod globals;
-mod icalgebra;
-mod levelstring;
-mod parser;
-mod value;
-
-mod context;
-#[cfg_attr(target_os = "macos", path = "editorlive_mac.rs")]
-#[cfg_attr(windows, path = "editorlive_win.rs")]
-#[cfg_attr(
-    not(any(target_os = "macos", windows)),
-    path = "editorlive_unavailable.rs"
-)]
-mod editorlive;
-mod optimize;
-mod value_storage;
-
-use optimize::optimize;
-
-use parser::*;
+use spwn::parser::*;
+use spwn::*;
 
 use std::env;
 use std::path::PathBuf;
 
 use std::fs;
 
-use editorlive::editor_paste;
-
-pub const STD_PATH: &str = "std";
+use spwn::editorlive::editor_paste;
 
 const ERROR_EXIT_CODE: i32 = 1;
 
 use std::io::Write;
-use termcolor::{Color, ColorChoice, ColorSpec, StandardStream, WriteColor};
+use termcolor::{Color, ColorSpec, StandardStream, WriteColor};
 
 const HELP: &str = include_str!("../help.txt");
 
-fn print_with_color(text: &str, color: Color) {
-    let mut stdout = StandardStream::stdout(ColorChoice::Always);
+fn eprint_with_color(text: &str, color: Color) {
+    let mut stdout = StandardStream::stderr(color_choice(&std::io::stderr()));
     stdout
         .set_color(ColorSpec::new().set_fg(Some(color)))
         .unwrap();
@@ -53,16 +26,248 @@ fn print_with_color(text: &str, color: Color) {
     stdout.set_color(&ColorSpec::new()).unwrap();
 }
 
-fn eprint_with_color(text: &str, color: Color) {
-    let mut stdout = StandardStream::stderr(ColorChoice::Always);
-    stdout
-        .set_color(ColorSpec::new().set_fg(Some(color)))
-        .unwrap();
-    writeln!(&mut stdout, "{}", text).unwrap();
-    stdout.set_color(&ColorSpec::new()).unwrap();
+// prints messages about objects (like what the optimizer did to them),
+// grouped by where in the script the objects came from
+// a syntax or compiler error, as text or as json (--error-format=json)
+fn print_error<'a, E>(err: &'a E)
+where
+    E: std::fmt::Display,
+    diagnostics::Diagnostic: From<&'a E>,
+{
+    if diagnostics::json() {
+        eprintln!("{}", diagnostics::Diagnostic::from(err).to_json());
+    } else {
+        eprint_with_color(&format!("{}\n", err), Color::White);
+    }
+}
+
+fn print_error_count(count: usize) {
+    if count > 1 && !diagnostics::json() {
+        eprint_with_color(&format!("{} errors\n", count), Color::Red);
+    }
+}
+
+fn print_by_origin(
+    title: &str,
+    (severity, code): (&'static str, &'static str),
+    messages: &[(usize, String)],
+    origins: &std::collections::HashMap<usize, (PathBuf, FileRange)>,
+    script_path: &std::path::Path,
+) {
+    if diagnostics::json() {
+        // (file, position, remark) -> how many triggers it applies to
+        let mut grouped = std::collections::BTreeMap::<(PathBuf, FileRange, &str), usize>::new();
+        for (unique_id, remark) in messages {
+            let (file, pos) = origins
+                .get(unique_id)
+                .cloned()
+                .unwrap_or_else(|| (PathBuf::from("<compiler>"), ((0, 0), (0, 0))));
+            *grouped.entry((file, pos, remark)).or_insert(0) += 1;
+        }
+        for ((file, pos, remark), count) in grouped {
+            let diagnostic = diagnostics::Diagnostic {
+                severity,
+                code,
+                message: remark.to_string(),
+                file,
+                pos,
+                notes: if count > 1 {
+                    vec![format!("applies to {} triggers", count)]
+                } else {
+                    Vec::new()
+                },
+            };
+            eprintln!("{}", diagnostic.to_json());
+        }
+        return;
+    }
+    let folder = script_path.parent().unwrap_or(script_path);
+    // (file, position, remark) -> how many triggers it applies to
+    let mut grouped = std::collections::BTreeMap::<(String, (usize, usize), &str), usize>::new();
+    for (unique_id, remark) in messages {
+        let (file, pos) = match origins.get(unique_id) {
+            Some((path, pos)) => (
+                path.strip_prefix(folder)
+                    .unwrap_or(path)
+                    .to_string_lossy()
+                    .to_string(),
+                pos.0,
+            ),
+            None => ("<compiler>".to_string(), (0, 0)),
+        };
+        *grouped.entry((file, pos, remark)).or_insert(0) += 1;
+    }
+
+    print_with_color(&format!("\n{}:", title), Color::Magenta);
+    if grouped.is_empty() {
+        print_with_color("(none)", Color::White);
+    }
+    for ((file, (line, col), remark), count) in grouped {
+        print_with_color(
+            &if count > 1 {
+                format!("{}:{}:{}: {} (x{})", file, line, col + 1, remark, count)
+            } else {
+                format!("{}:{}:{}: {}", file, line, col + 1, remark)
+            },
+            Color::White,
+        );
+    }
+    println!();
+}
+
+// the [triggers] section of the spwn.toml next to the script (or in a folder above it)
+fn find_trigger_area(
+    script_path: &std::path::Path,
+) -> Result<trigger_area::TriggerArea, Box<dyn std::error::Error>> {
+    let dir = fs::canonicalize(script_path)
+        .ok()
+        .and_then(|p| p.parent().map(|p| p.to_path_buf()))
+        .unwrap_or_default();
+    Ok(trigger_area::TriggerArea::find(&dir)?)
+}
+
+// the savefile format from the spwn.toml next to the script (or in a folder above it), with
+// the profile from --profile or the one spwn.toml chooses
+fn find_save_format(
+    dir: &std::path::Path,
+    profile: Option<&str>,
+) -> Result<save_format::SaveFormat, Box<dyn std::error::Error>> {
+    let dir = fs::canonicalize(dir).unwrap_or_else(|_| dir.to_path_buf());
+    Ok(save_format::SaveFormat::find(&dir, profile)?)
+}
+
+// the [project] section of the spwn.toml next to the script (or in a folder above it)
+fn find_project(
+    script_path: &std::path::Path,
+) -> Result<Option<project::Project>, Box<dyn std::error::Error>> {
+    let dir = fs::canonicalize(script_path)
+        .ok()
+        .and_then(|p| p.parent().map(|p| p.to_path_buf()))
+        .unwrap_or_default();
+    Ok(project::Project::find(&dir)?)
+}
+
+// the script given to build (or check), and the project it's in. without a script, the
+// entry of the spwn.toml project in this folder
+fn script_or_project(
+    args_iter: &mut std::slice::Iter<String>,
+) -> Result<(PathBuf, Option<project::Project>), Box<dyn std::error::Error>> {
+    match args_iter.clone().next() {
+        Some(a) if !a.starts_with('-') => {
+            args_iter.next();
+            let path = PathBuf::from(a);
+            let project = find_project(&path)?;
+            Ok((path, project))
+        }
+        _ => {
+            let dir = std::env::current_dir()?;
+            match project::Project::find(&dir)? {
+                Some(project) => match &project.entry {
+                    Some(entry) => Ok((entry.clone(), Some(project))),
+                    None => Err(Box::from("The [project] in spwn.toml has no entry file")),
+                },
+                None => Err(Box::from(
+                    "Expected script file argument (or a spwn.toml with a [project] section, see spwn init)",
+                )),
+            }
+        }
+    }
+}
+
+// the [aliases] section of the spwn.toml next to the script, for the files parsed after this
+fn find_aliases(
+    script_path: &std::path::Path,
+) -> Result<Option<aliases::Aliases>, Box<dyn std::error::Error>> {
+    let dir = fs::canonicalize(script_path)
+        .ok()
+        .and_then(|p| p.parent().map(|p| p.to_path_buf()))
+        .unwrap_or_default();
+    Ok(aliases::Aliases::find(&dir)?)
+}
+
+// spwn build, check and preview build with the same steps as the library
+fn builder(
+    included_paths: Vec<PathBuf>,
+    script_path: &std::path::Path,
+) -> Result<Spwn, Box<dyn std::error::Error>> {
+    let mut spwn = Spwn::builder();
+    for path in included_paths {
+        spwn = spwn.include_path(path);
+    }
+    if let Some(aliases) = find_aliases(script_path)? {
+        spwn = spwn.aliases(aliases);
+    }
+    Ok(spwn)
+}
+
+// --partial-output: the objects that were built before the build failed or was stopped,
+// as json (like --emit=json) marked as incomplete
+fn write_partial(
+    globals: globals::Globals,
+    area: &trigger_area::TriggerArea,
+    reason: &str,
+    path: &std::path::Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut objects = levelstring::apply_fn_ids(&globals.func_ids, area);
+    objects.extend(globals.objects);
+    let used_ids = levelstring::resolve_ids(&mut objects, "")?;
+    let json = levelstring::objects_to_json(&objects, used_ids).replacen(
+        "{\n",
+        &format!("{{\n  \"incomplete\": {},\n", documentation::json_str(reason)),
+        1,
+    );
+    fs::write(path, json)?;
+    print_with_color(
+        &format!(
+            "The {} objects built before it stopped were written to {}",
+            objects.len(),
+            path.to_string_lossy()
+        ),
+        Color::Yellow,
+    );
+    Ok(())
+}
+
+// asks a yes or no question in the terminal (no if there is no one to answer it)
+fn confirm(question: &str, interactive: bool) -> bool {
+    use std::io::IsTerminal;
+    if !interactive || !std::io::stdin().is_terminal() {
+        return false;
+    }
+    print!("{} [y/N] ", question);
+    std::io::stdout().flush().unwrap();
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer).unwrap_or(0);
+    matches!(answer.trim(), "y" | "Y" | "yes")
+}
+
+// where the game keeps the levels
+fn default_save_file() -> PathBuf {
+    if cfg!(target_os = "windows") {
+        PathBuf::from(std::env::var("localappdata").expect("No local app data"))
+            .join("GeometryDash/CCLocalLevels.dat")
+    } else if cfg!(target_os = "macos") {
+        PathBuf::from(std::env::var("HOME").expect("No home directory"))
+            .join("Library/Application Support/GeometryDash/CCLocalLevels.dat")
+    } else if cfg!(target_os = "linux") {
+        PathBuf::from(std::env::var("HOME").expect("No home directory"))
+            .join(".steam/steam/steamapps/compatdata/322170/pfx/drive_c/users/steamuser/Local Settings/Application Data/GeometryDash/CCLocalLevels.dat")
+    } else {
+        panic!("Unsupported operating system");
+    }
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let thread = std::thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(|| run().map_err(|e| e.to_string()))?;
+    match thread.join() {
+        Ok(result) => result.map_err(Box::from),
+        Err(panic) => std::panic::resume_unwind(panic),
+    }
+}
+
+fn run() -> Result<(), Box<dyn std::error::Error>> {
     let args: Vec<String> = env::args().collect();
     let mut args_iter = args.iter();
     args_iter.next();
@@ -78,28 +283,96 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                     println!("v{}", env!("CARGO_PKG_VERSION"));
                     Ok(())
                 }
-                "build" | "b" => {
-                    let script_path = match args_iter.next() {
-                        Some(a) => PathBuf::from(a),
-                        None => return Err(std::boxed::Box::from("Expected script file argument")),
+                "init" => {
+                    let dir = args_iter
+                        .next()
+                        .map(PathBuf::from)
+                        .unwrap_or_else(|| PathBuf::from("."));
+                    for path in project::init(&dir)? {
+                        print_with_color(
+                            &format!("Made {}", path.to_string_lossy()),
+                            Color::Green,
+                        );
+                    }
+                    println!("Build it with `spwn build` in that folder");
+                    Ok(())
+                }
+                "install" => {
+                    let dir = std::env::current_dir()?;
+                    let project = match project::Project::find(&dir)? {
+                        Some(project) => project,
+                        None => {
+                            return Err(Box::from(
+                                "There is no spwn.toml with a [project] section here (see spwn init)",
+                            ))
+                        }
                     };
+                    let fetched = packages::install(&project)?;
+                    for (name, version) in &fetched {
+                        print_with_color(&format!("Fetched {} {}", name, version), Color::Green);
+                    }
+                    if fetched.is_empty() {
+                        println!("All packages are installed");
+                    }
+                    Ok(())
+                }
+                "build" | "b" => {
+                    let (script_path, project) = script_or_project(&mut args_iter)?;
 
                     let mut gd_enabled = true;
                     let mut opti_enabled = true;
                     let mut compile_only = false;
+                    let mut force = false;
                     let mut level_name = None;
                     let mut live_editor = false;
+                    let mut emit = None;
+                    let mut group_usage = None;
+                    let mut manifest = None;
+                    let mut only = None;
+                    let mut tui_enabled = false;
+                    let mut show_remarks = false;
+                    let mut record_trace = false;
+                    let mut check_delays = false;
+                    let mut round_delays = false;
+                    let mut check_spawn_order = false;
+                    let mut spawn_order_fix = None;
+                    let mut strict_limits = false;
+                    let mut deny_warnings = false;
+                    let mut release = false;
+                    let mut docs = false;
+                    let mut watch = false;
+                    let mut defines = Vec::new();
+                    let mut cache_macros = false;
+                    let mut cache_imports = true;
+                    let mut reuse_items = true;
+                    // trigger area options that go over the ones in spwn.toml
+                    let mut area_options = Vec::new();
+                    let mut lock_layer = None;
+                    let mut print_limits = value::PrintLimits::new();
+                    let mut timeout = None;
+                    let mut metrics = false;
+                    let mut verbose = false;
+                    let mut paranoid = false;
+                    let mut partial_output = None;
+                    let mut trigger_flags = Vec::new();
+                    let mut interactive = true;
+                    let mut create_level = false;
+                    let mut level_description = None;
+                    let mut output_gmd = None;
+                    let mut level_from_clipboard = false;
+                    let mut copy_to_clipboard = false;
+
+                    if let Some(project) = &project {
+                        opti_enabled = project.optimize.unwrap_or(true);
+                        release = project.release.unwrap_or(false);
+                        level_name = project.level_name.clone();
+                        docs = project.docs.unwrap_or(false);
+                    }
 
                     let mut save_file = None;
-                    let mut included_paths = vec![
-                        std::env::current_dir().expect("Cannot access current directory"),
-                        std::env::current_exe()
-                            .expect("Cannot access directory of executable")
-                            .parent()
-                            .expect("Executable must be in some directory")
-                            .to_path_buf(),
-                    ];
-                    //change to current_exe before release (from current_dir)
+                    let mut profile = None;
+                    // after the current folder and the folder of the executable
+                    let mut included_paths = Vec::new();
 
                     while let Some(arg) = args_iter.next() {
                         match arg.as_ref() {
@@ -112,31 +385,217 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                             "--level-name" | "-n" => level_name = args_iter.next().cloned(),
                             "--live-editor" | "-e" => live_editor = true,
                             "--save-file" | "-s" => save_file = args_iter.next().cloned(),
-                            "--included-path" | "-i" => included_paths.push({
+                            "--profile" => profile = args_iter.next().cloned(),
+                            "--tui" => tui_enabled = true,
+                            "--remarks" => show_remarks = true,
+                            "--record-trace" => record_trace = true,
+                            "--check-delays" => check_delays = true,
+                            "--round-delays" => round_delays = true,
+                            "--check-spawn-order" => check_spawn_order = true,
+                            "--spawn-order" => {
+                                spawn_order_fix = Some(spawn_order::parse_fix(
+                                    &args_iter.next().cloned().unwrap_or_default(),
+                                )?)
+                            }
+                            "--strict-gd-limits" => strict_limits = true,
+                            "--deny-warnings" => deny_warnings = true,
+                            "--release" => release = true,
+                            "--docs" => docs = true,
+                            "--watch" => watch = true,
+                            "--cache-macros" => cache_macros = true,
+                            "--no-cache" => cache_imports = false,
+                            "--no-item-reuse" => reuse_items = false,
+                            "--trigger-origin" => {
+                                let origin = args_iter.next().cloned().unwrap_or_default();
+                                let mut parts = origin.splitn(2, ',');
+                                area_options.push(("x", parts.next().unwrap_or("").to_string()));
+                                area_options.push(("y", parts.next().unwrap_or("").to_string()));
+                            }
+                            "--trigger-max-width" => area_options
+                                .push(("max_width", args_iter.next().cloned().unwrap_or_default())),
+                            "--trigger-stacking" => area_options
+                                .push(("stacking", args_iter.next().cloned().unwrap_or_default())),
+                            "--lock-triggers" => {
+                                let layer = args_iter.next().cloned().unwrap_or_default();
+                                lock_layer = Some(layer.parse::<u16>().map_err(|_| {
+                                    format!("Expected an editor layer number, found {}", layer)
+                                })?);
+                            }
+                            "--trigger-flags" => {
+                                trigger_flags = lock::parse_flags(
+                                    &args_iter.next().cloned().unwrap_or_default(),
+                                )?
+                            }
+                            "--print-depth" | "--print-elements" => {
+                                let n = args_iter.next().cloned().unwrap_or_default();
+                                let n = n.parse::<usize>().map_err(|_| {
+                                    format!("Expected a number after {}, found {}", arg, n)
+                                })?;
+                                if arg == "--print-depth" {
+                                    print_limits.depth = n
+                                } else {
+                                    print_limits.elements = n
+                                }
+                            }
+                            "--metrics" => metrics = true,
+                            "--verbose" => verbose = true,
+                            "--error-format" => diagnostics::set_json(diagnostics::parse_format(
+                                &args_iter.next().cloned().unwrap_or_default(),
+                            )?),
+                            a if a.starts_with("--error-format=") => diagnostics::set_json(
+                                diagnostics::parse_format(&a["--error-format=".len()..])?,
+                            ),
+                            "--paranoid" => paranoid = true,
+                            "--timeout" => {
+                                let secs = args_iter.next().cloned().unwrap_or_default();
+                                timeout = Some(std::time::Duration::from_secs(
+                                    secs.parse::<u64>().map_err(|_| {
+                                        format!("Expected a number of seconds, found {}", secs)
+                                    })?,
+                                ));
+                            }
+                            "--partial-output" => {
+                                partial_output = args_iter.next().map(PathBuf::from)
+                            }
+                            "--non-interactive" => interactive = false,
+                            "--create-level" => create_level = true,
+                            "--force" => force = true,
+                            "--level-description" => {
+                                level_description = args_iter.next().cloned()
+                            }
+                            "--output-gmd" => output_gmd = args_iter.next().map(PathBuf::from),
+                            // these leave the save file alone
+                            "--level-from-clipboard" => {
+                                level_from_clipboard = true;
+                                gd_enabled = false;
+                            }
+                            "--copy-to-clipboard" => {
+                                copy_to_clipboard = true;
+                                gd_enabled = false;
+                            }
+                            "--emit" => emit = args_iter.next().cloned(),
+                            "--group-usage" => group_usage = args_iter.next().map(PathBuf::from),
+                            "--manifest" => manifest = args_iter.next().map(PathBuf::from),
+                            "--only" => only = args_iter.next().cloned(),
+                            a if a.starts_with("--emit=") => {
+                                emit = Some(a["--emit=".len()..].to_string())
+                            }
+                            "--include-path" | "--included-path" | "-i" => included_paths.push({
                                 let path = PathBuf::from(
                                     args_iter.next().cloned().expect("No path provided"),
                                 );
                                 if path.exists() {
                                     path
                                 } else {
-                                    return Err(Box::from("Invalid path".to_string()));
+                                    return Err(Box::from(format!(
+                                        "Include path {} doesn't exist",
+                                        path.to_string_lossy()
+                                    )));
                                 }
                             }),
-                            _ => (),
+                            // the rest are defines for the script
+                            "--" => {
+                                defines.extend(args_iter.by_ref().map(|a| flags::parse_define(a)))
+                            }
+                            a => return Err(Box::from(flags::unknown(a, flags::BUILD_FLAGS))),
                         };
                     }
 
-                    print_with_color("Parsing ...", Color::Green);
+                    if watch {
+                        return watch::run(watch::build_args(&args[1..]), &script_path);
+                    }
+
+                    if let Some(project) = &project {
+                        for path in &project.include_paths {
+                            if !path.exists() {
+                                return Err(Box::from(format!(
+                                    "Include path {} (from spwn.toml) doesn't exist",
+                                    path.to_string_lossy()
+                                )));
+                            }
+                        }
+                        included_paths.extend(project.include_paths.iter().cloned());
+                    }
+
+                    let mut trigger_area = find_trigger_area(&script_path)?;
+                    for (key, value) in area_options {
+                        trigger_area.set(key, &value)?;
+                    }
+                    let mut spwn = builder(included_paths, &script_path)?
+                        .optimize(opti_enabled)
+                        .reuse_items(reuse_items)
+                        .release(release)
+                        .trigger_area(trigger_area);
+                    if let Some(timeout) = timeout {
+                        spwn = spwn.timeout(timeout);
+                    }
+
+                    let json_output = match emit.as_deref() {
+                        Some("json") => {
+                            gd_enabled = false;
+                            Some(script_path.with_extension("json"))
+                        }
+                        Some("lowered") => None,
+                        Some(a) => {
+                            return Err(Box::from(format!(
+                                "Unknown output format: {} (expected json or lowered)",
+                                a
+                            )))
+                        }
+                        None => None,
+                    };
+
+                    let mut dashboard = if tui_enabled {
+                        Some(tui::Dashboard::new(&script_path.to_string_lossy()))
+                    } else {
+                        None
+                    };
+
+                    if let Some(d) = &mut dashboard {
+                        d.start_phase("Parsing");
+                    }
+                    // --emit=lowered only prints the code
+                    if emit.as_deref() != Some("lowered") {
+                        print_with_color("Parsing ...", Color::Green);
+                    }
                     let unparsed = fs::read_to_string(script_path.clone())?;
 
-                    let (statements, notes) = match parse_spwn(unparsed, script_path.clone()) {
-                        Err(err) => {
-                            eprint_with_color(&format!("{}\n", err), Color::White);
-                            std::process::exit(ERROR_EXIT_CODE);
+                    // every syntax error in the file is shown, not just the first
+                    let (statements, notes, errors) = spwn.parse(unparsed, script_path.clone());
+                    if !errors.is_empty() {
+                        for err in &errors {
+                            print_error(err);
                         }
-                        Ok(p) => p,
+                        print_error_count(errors.len());
+                        std::process::exit(ERROR_EXIT_CODE);
+                    }
+                    let statements = match &only {
+                        Some(name) => {
+                            let (mut definitions, call) = partial::only(statements, name)?;
+                            match parse_spwn(call, PathBuf::from("<only>")) {
+                                Ok((call, _)) => definitions.extend(call),
+                                Err(err) => {
+                                    print_error(&err);
+                                    std::process::exit(ERROR_EXIT_CODE);
+                                }
+                            }
+                            print_with_color(&format!("Only building {}", name), Color::Yellow);
+                            definitions
+                        }
+                        None => statements,
                     };
 
+                    if emit.as_deref() == Some("lowered") {
+                        let mut statements = statements;
+                        lower::lower(&mut statements);
+                        let config = script_path
+                            .parent()
+                            .map(fmt::FmtConfig::find)
+                            .unwrap_or_else(|| Ok(fmt::FmtConfig::new()))?;
+                        print!("{}", fmt::format(statements, &notes.tag, &config));
+                        return Ok(());
+                    }
+
                     let tags = notes.tag.tags.iter();
                     for tag in tags {
                         match tag.0.as_str() {
@@ -144,66 +603,288 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                             "no_level" => {
                                 gd_enabled = false;
                                 compile_only = true;
+                                print_with_color(
+                                    "No level will be made, since the script has #[no_level]",
+                                    Color::White,
+                                );
                             }
                             _ => (),
                         }
                     }
 
+                    let save_format = if gd_enabled {
+                        let dir = script_path.parent().unwrap_or(&script_path);
+                        find_save_format(dir, profile.as_deref())?
+                    } else {
+                        save_format::SaveFormat::new()
+                    };
                     let gd_path = if gd_enabled {
-                        Some(if save_file != None {
-                            PathBuf::from(save_file.expect("what"))
-                        } else if cfg!(target_os = "windows") {
-                            PathBuf::from(std::env::var("localappdata").expect("No local app data"))
-                                .join("GeometryDash/CCLocalLevels.dat")
-                        } else if cfg!(target_os = "macos") {
-                            PathBuf::from(std::env::var("HOME").expect("No home directory"))
-                                .join("Library/Application Support/GeometryDash/CCLocalLevels.dat")
-                        } else if cfg!(target_os = "linux") {
-                            PathBuf::from(std::env::var("HOME").expect("No home directory"))
-                                .join(".steam/steam/steamapps/compatdata/322170/pfx/drive_c/users/steamuser/Local Settings/Application Data/GeometryDash/CCLocalLevels.dat")
-                        } else {
-                            panic!("Unsupported operating system");
+                        Some(match save_file {
+                            Some(f) => PathBuf::from(f),
+                            None => save_format
+                                .save_file
+                                .clone()
+                                .unwrap_or_else(default_save_file),
                         })
                     } else {
                         None
                     };
 
-                    let mut compiled = match compiler::compile_spwn(
+                    // checked before building, so nothing is built for nothing
+                    // (a .gmd file isn't touched by the game)
+                    if matches!(&gd_path, Some(p) if !levelstring::is_gmd(p))
+                        && !live_editor
+                        && !compile_only
+                        && !force
+                        && gd_process::geometry_dash_running()
+                    {
+                        eprint_with_color(
+                            "Geometry Dash is open, and it would overwrite the level when it closes. Close it and build again (or use --force to write anyway)",
+                            Color::Red,
+                        );
+                        std::process::exit(ERROR_EXIT_CODE);
+                    }
+
+                    if let Some(d) = &mut dashboard {
+                        d.start_phase("Building");
+                    }
+                    let empty_hint = hints::empty_output(&statements);
+                    // --docs documents the lib.spwn next to spwn.toml (or the script)
+                    let docs = if docs {
+                        let root = match &project {
+                            Some(project) => project.root.clone(),
+                            None => script_path.parent().map(PathBuf::from).unwrap_or_default(),
+                        };
+                        let mut docs_globals = globals::Globals::new(root.join("lib.spwn"));
+                        if let Some(project) = &project {
+                            docs_globals.dependencies = project.dependency_folders()?;
+                        }
+                        Some((root, docs_globals, spwn.include_paths().to_vec()))
+                    } else {
+                        None
+                    };
+                    let mut globals = spwn.globals(script_path);
+                    globals.defines = defines;
+                    if let Some(project) = project {
+                        globals.dependencies = project.dependency_folders()?;
+                    }
+                    globals.interactive = interactive;
+                    globals.print_limits = print_limits;
+                    if cache_macros {
+                        globals.macro_cache = Some(macro_cache::MacroCache::default());
+                    }
+                    if cache_imports {
+                        let dir = globals.project_root.join(".spwn-cache");
+                        globals.import_cache = Some(import_cache::ImportCache::new(dir));
+                    }
+                    if record_trace {
+                        globals.history = Some(history::History::new());
+                    }
+                    if metrics {
+                        globals
+                            .analyzers
+                            .push(Box::new(analyzer::Metrics::default()));
+                    }
+                    interrupt::catch_ctrl_c(partial_output.is_some());
+                    let built = compiler::compile_spwn_with(
+                        globals,
                         statements,
-                        script_path,
-                        included_paths,
+                        spwn.include_paths().to_vec(),
                         notes,
-                    ) {
-                        Err(err) => {
-                            eprint_with_color(&format!("{}\n", err), Color::White);
+                    );
+                    interrupt::catch_ctrl_c(false);
+                    match &built {
+                        Ok(globals) => watch::report_files(globals),
+                        Err((_, partial)) => watch::report_files(partial),
+                    }
+                    let mut compiled = match built {
+                        Err((err, partial)) => {
+                            print_error(&err);
+                            for err in &partial.errors {
+                                print_error(err);
+                            }
+                            print_error_count(partial.errors.len() + 1);
+                            if let Some(path) = &partial_output {
+                                write_partial(*partial, &trigger_area, &err.message(), path)?;
+                            }
                             std::process::exit(ERROR_EXIT_CODE);
                         }
                         Ok(p) => p,
                     };
+                    analyzer::build_finished(&mut compiled);
+                    import_costs::report(&compiled, verbose);
+                    if let Some((root, docs_globals, includes)) = docs {
+                        match documentation::document_project(&root, docs_globals, includes) {
+                            Ok(true) => print_with_color(
+                                &format!(
+                                    "The exports changed, so the docs in {} were written again",
+                                    root.join("docs").to_string_lossy()
+                                ),
+                                Color::Green,
+                            ),
+                            Ok(false) => {
+                                print_with_color("The docs are up to date", Color::White)
+                            }
+                            Err(err) => {
+                                // errors in lib.spwn keep their position, the rest are
+                                // about the docs folder
+                                match err.downcast_ref::<compiler::RuntimeError>() {
+                                    Some(err) => print_error(err),
+                                    None => eprint_with_color(&format!("{}\n", err), Color::Red),
+                                }
+                                std::process::exit(ERROR_EXIT_CODE);
+                            }
+                        }
+                    }
+                    if let Some(d) = &mut dashboard {
+                        d.add_warnings(&compiled.warnings);
+                        let lints: Vec<String> = compiled
+                            .lints
+                            .iter()
+                            .map(|w| {
+                                format!(
+                                    "{}:{}:{}: {}",
+                                    compiler::display_path(&w.file),
+                                    w.pos.0 .0,
+                                    w.pos.0 .1 + 1,
+                                    w.message
+                                )
+                            })
+                            .collect();
+                        d.add_warnings(&lints);
+                    }
+                    let warning_count = compiled.warnings.len() + compiled.lints.len();
+                    if deny_warnings && warning_count > 0 {
+                        eprint_with_color(
+                            &format!(
+                                "Stopped, since there {} (and --deny-warnings is on)",
+                                if warning_count == 1 {
+                                    "is a warning".to_string()
+                                } else {
+                                    format!("are {} warnings", warning_count)
+                                }
+                            ),
+                            Color::Red,
+                        );
+                        std::process::exit(ERROR_EXIT_CODE);
+                    }
+                    let trace_path = compiled.path.with_extension("spwntrace");
+                    if compile_only {
+                        if let Some(history) = compiled.history.take() {
+                            history.write(&trace_path, None)?;
+                            print_with_color(
+                                &format!("Trace written to {}", trace_path.to_string_lossy()),
+                                Color::Green,
+                            );
+                        }
+                    }
 
                     if !compile_only {
+                        // the level as it was before building, for --paranoid
+                        let mut original_level = None;
+                        // the level in the .gmd file given as the save file
+                        let mut gmd_level = None;
+                        let mut clipboard_encoded = false;
                         let level_string = if let Some(gd_path) = &gd_path {
-                            print_with_color("Reading savefile...", Color::Cyan);
-                            let mut file = fs::File::open(gd_path)?;
-                            let mut file_content = Vec::new();
-                            use std::io::Read;
-                            file.read_to_end(&mut file_content)
-                                .expect("Problem reading savefile");
-                            let mut level_string = match levelstring::get_level_string(
-                                file_content,
-                                level_name.clone(),
-                            ) {
-                                Ok(s) => s,
-                                Err(e) => {
-                                    eprint_with_color(
-                                        &format!("Error reading level:\n{}", e),
-                                        Color::Red,
-                                    );
+                            let mut level_string = if levelstring::is_gmd(gd_path) {
+                                print_with_color("Reading level file...", Color::Cyan);
+                                let (level, level_string) = if create_level && !gd_path.exists() {
+                                    let level = levelstring::NewLevel {
+                                        name: level_name.clone().unwrap_or_default(),
+                                        description: "Made with SPWN".to_string(),
+                                        song: 0,
+                                    };
+                                    (level, String::new())
+                                } else {
+                                    levelstring::read_gmd(gd_path)?
+                                };
+                                gmd_level = Some(level);
+                                level_string
+                            } else {
+                                print_with_color("Reading savefile...", Color::Cyan);
+                                let savefile = levelstring::read_savefile(gd_path, &save_format)?;
+                                match levelstring::find_level_string(&savefile, &level_name) {
+                                    Ok(Some(s)) => s,
+                                    // a level that isn't in the savefile can be made instead
+                                    Ok(None) if create_level || level_name.is_some() => {
+                                        let name = match &level_name {
+                                            Some(n) => n.clone(),
+                                            None => {
+                                                return Err(Box::from(
+                                                    "--create-level needs the name of the level (--level-name [name])",
+                                                ))
+                                            }
+                                        };
+                                        if !create_level
+                                            && !confirm(
+                                                &format!(
+                                                    "There is no level called {} in the savefile. Make it?",
+                                                    name
+                                                ),
+                                                interactive,
+                                            )
+                                        {
+                                            return Err(Box::from(format!(
+                                                "There is no level called {} in the savefile (use --create-level to make it)",
+                                                name
+                                            )));
+                                        }
+                                        let new_level = levelstring::NewLevel {
+                                            name,
+                                            description: level_description
+                                                .clone()
+                                                .unwrap_or_else(|| "Made with SPWN".to_string()),
+                                            song: 0,
+                                        };
+                                        let level_string = levelstring::create_level(
+                                            &savefile,
+                                            gd_path.clone(),
+                                            &new_level,
+                                            &save_format,
+                                        )?;
+                                        print_with_color(
+                                            &format!(
+                                                "Created a new level called {}",
+                                                new_level.name
+                                            ),
+                                            Color::Green,
+                                        );
+                                        level_string
+                                    }
+                                    Ok(None) => {
+                                        eprint_with_color(
+                                            "Error reading level:\nNo level found! Please create a level for SPWN to operate on!",
+                                            Color::Red,
+                                        );
+                                        std::process::exit(ERROR_EXIT_CODE);
+                                    }
+                                    Err(e) => {
+                                        eprint_with_color(
+                                            &format!("Error reading level:\n{}", e),
+                                            Color::Red,
+                                        );
 
-                                    std::process::exit(ERROR_EXIT_CODE);
+                                        std::process::exit(ERROR_EXIT_CODE);
+                                    }
                                 }
                             };
-                            if level_string.is_empty() {}
+                            if paranoid {
+                                original_level = Some(level_string.clone());
+                            }
+                            levelstring::remove_spwn_objects(&mut level_string);
+                            level_string
+                        } else if level_from_clipboard {
+                            print_with_color(
+                                "Reading the level from the clipboard...",
+                                Color::Cyan,
+                            );
+                            let (mut level_string, encoded) =
+                                levelstring::read_copied_level(&clipboard::read()?)?;
+                            // copied back the way it came
+                            clipboard_encoded = encoded;
+                            if paranoid {
+                                original_level = Some(level_string.clone());
+                            }
                             levelstring::remove_spwn_objects(&mut level_string);
                             level_string
                         } else {
@@ -211,19 +892,233 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                         };
                         let has_stuff = compiled.func_ids.iter().any(|x| !x.obj_list.is_empty());
                         if opti_enabled && has_stuff {
+                            if let Some(d) = &mut dashboard {
+                                d.start_phase("Optimizing");
+                            }
                             print_with_color("Optimizing triggers...", Color::Cyan);
-                            compiled.func_ids = optimize(compiled.func_ids, compiled.closed_groups);
                         }
 
-                        let mut objects = levelstring::apply_fn_ids(&compiled.func_ids);
+                        let (mut objects, remarks) = spwn.objects(&mut compiled, &trigger_area);
+
+                        if show_remarks {
+                            if opti_enabled && has_stuff {
+                                print_by_origin(
+                                    "Optimization remarks",
+                                    ("note", "optimization_remark"),
+                                    &remarks.optimized,
+                                    &compiled.object_origins,
+                                    &compiled.path,
+                                );
+                            }
+                            if !remarks.shared_groups.is_empty() {
+                                print_by_origin(
+                                    "Shared groups",
+                                    ("note", "shared_group"),
+                                    &remarks.shared_groups,
+                                    &compiled.object_origins,
+                                    &compiled.path,
+                                );
+                            }
+                            if !remarks.hoisted.is_empty() {
+                                print_by_origin(
+                                    "Moved out of loops",
+                                    ("note", "loop_hoist"),
+                                    &remarks.hoisted,
+                                    &compiled.object_origins,
+                                    &compiled.path,
+                                );
+                            }
+                            if remarks.items_saved > 0 {
+                                print_with_color(
+                                    &format!(
+                                        "{} item IDs saved by sharing temporaries",
+                                        remarks.items_saved
+                                    ),
+                                    Color::White,
+                                );
+                            }
+                        }
+
+                        if lock_layer.is_some() || !trigger_flags.is_empty() {
+                            let changed =
+                                lock::lock_triggers(&mut objects, lock_layer, &trigger_flags);
+                            if let Some(layer) = lock_layer {
+                                print_with_color(
+                                    &format!(
+                                        "{} triggers put on editor layer {} (lock it in the editor to keep them in place)",
+                                        changed, layer
+                                    ),
+                                    Color::White,
+                                );
+                            }
+                        }
+
+                        if check_spawn_order {
+                            print_by_origin(
+                                "Triggers that depend on the spawn order",
+                                ("warning", "spawn_order"),
+                                &spawn_order::check_order(&objects, compiled.fps),
+                                &compiled.object_origins,
+                                &compiled.path,
+                            );
+                        }
+                        if let Some(fix) = spawn_order_fix {
+                            let changed = spawn_order::fix_order(&mut objects, compiled.fps, fix);
+                            print_with_color(
+                                &format!(
+                                    "{} spawn triggers changed to run in the script's order",
+                                    changed
+                                ),
+                                Color::White,
+                            );
+                        }
 
-                        objects.extend(compiled.objects);
+                        if round_delays {
+                            let changed = timing::round_delays(&mut objects, compiled.fps);
+                            print_with_color(
+                                &format!("{} spawn delays rounded to whole frames", changed),
+                                Color::White,
+                            );
+                        }
+                        if check_delays {
+                            print_by_origin(
+                                "Inexact spawn delays",
+                                ("warning", "inexact_delay"),
+                                &timing::check_delays(&objects, compiled.fps),
+                                &compiled.object_origins,
+                                &compiled.path,
+                            );
+                        }
+
+                        let level = if level_string.is_empty() {
+                            None
+                        } else {
+                            Some(level_string.as_str())
+                        };
+                        let center_groups = centers::check_center_groups(&objects, level);
+                        if !center_groups.is_empty() {
+                            print_by_origin(
+                                "Rotate and follow triggers",
+                                ("warning", "center_group"),
+                                &center_groups,
+                                &compiled.object_origins,
+                                &compiled.path,
+                            );
+                        }
+
+                        let over_limits = limits::check_limits(&objects);
+                        if !over_limits.is_empty() {
+                            print_by_origin(
+                                "Over the game's limits",
+                                ("warning", "over_limit"),
+                                &over_limits,
+                                &compiled.object_origins,
+                                &compiled.path,
+                            );
+                            if strict_limits {
+                                eprint_with_color(
+                                    "Stopped, since the level goes over the game's limits (and --strict-gd-limits is on)",
+                                    Color::Red,
+                                );
+                                std::process::exit(ERROR_EXIT_CODE);
+                            }
+                        }
 
                         print_with_color(&format!("{} objects added", objects.len()), Color::White);
+                        if objects.is_empty() && !compiled.printed {
+                            if let Some(hint) = &empty_hint {
+                                print_with_color(
+                                    &format!("Nothing was built: {}", hint),
+                                    Color::Yellow,
+                                );
+                            }
+                        }
+                        for (file, count) in levelstring::objects_per_file(
+                            &objects,
+                            &compiled.object_origins,
+                            &compiled.path,
+                        ) {
+                            print_with_color(
+                                &format!("  {}: {} objects", file, count),
+                                Color::White,
+                            );
+                        }
+
+                        if let Some(history) = compiled.history.take() {
+                            // the trace shows the ids the objects end up with
+                            levelstring::resolve_ids(&mut objects, &level_string)?;
+                            history.write(&trace_path, Some(&objects))?;
+                            print_with_color(
+                                &format!("Trace written to {}", trace_path.to_string_lossy()),
+                                Color::Green,
+                            );
+                        }
+
+                        if let Some(usage_path) = &group_usage {
+                            levelstring::resolve_ids(&mut objects, &level_string)?;
+                            let csv = matches!(usage_path.extension(), Some(e) if e == "csv");
+                            fs::write(
+                                usage_path,
+                                levelstring::group_usage(
+                                    &objects,
+                                    &compiled.object_origins,
+                                    &compiled.path,
+                                    csv,
+                                ),
+                            )?;
+                            print_with_color(
+                                &format!("Group usage written to {}", usage_path.to_string_lossy()),
+                                Color::Green,
+                            );
+                        }
+
+                        if let Some(manifest_path) = &manifest {
+                            levelstring::resolve_ids(&mut objects, &level_string)?;
+                            fs::write(
+                                manifest_path,
+                                levelstring::manifest(&objects, &compiled.path, compiled.fps),
+                            )?;
+                            print_with_color(
+                                &format!(
+                                    "Level requirements written to {}",
+                                    manifest_path.to_string_lossy()
+                                ),
+                                Color::Green,
+                            );
+                        }
+
+                        if let Some(d) = &mut dashboard {
+                            d.set_objects(objects.len());
+                            d.start_phase("Writing");
+                        }
+
+                        if let Some(json_path) = json_output {
+                            let used_ids = levelstring::resolve_ids(&mut objects, &level_string)?;
+                            fs::write(
+                                &json_path,
+                                levelstring::objects_to_json(&objects, used_ids),
+                            )?;
+                            print_with_color(
+                                &format!("Written to {}", json_path.to_string_lossy()),
+                                Color::Green,
+                            );
+                            if let Some(d) = &mut dashboard {
+                                d.set_used_ids(used_ids);
+                                d.finish();
+                                if interactive {
+                                    tui::browse(&compiled.func_ids);
+                                }
+                            }
+                            return Ok(());
+                        }
 
                         let (new_ls, used_ids) =
                             levelstring::append_objects(objects, &level_string)?;
 
+                        if let Some(d) = &mut dashboard {
+                            d.set_used_ids(used_ids);
+                        }
+
                         print_with_color("\nLevel:", Color::Magenta);
                         for (i, len) in used_ids.iter().enumerate() {
                             if *len > 0 {
@@ -238,6 +1133,21 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                             }
                         }
                         //println!("level_string: {}", level_string);
+                        // for .gmd files: --level-name and --level-description go over the
+                        // name and description of the .gmd the level was read from
+                        let script_name = compiled.path.file_stem().unwrap_or_default();
+                        let gmd_settings = levelstring::NewLevel {
+                            name: level_name
+                                .clone()
+                                .or_else(|| gmd_level.as_ref().map(|l| l.name.clone()))
+                                .unwrap_or_else(|| script_name.to_string_lossy().to_string()),
+                            description: level_description
+                                .clone()
+                                .or_else(|| gmd_level.as_ref().map(|l| l.description.clone()))
+                                .unwrap_or_else(|| "Made with SPWN".to_string()),
+                            song: gmd_level.as_ref().map_or(0, |l| l.song),
+                        };
+                        let full_level = level_string.clone() + &new_ls;
                         if live_editor {
                             match editor_paste(&new_ls) {
                                 Err(e) => {
@@ -255,31 +1165,293 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                         } else {
                             match gd_path {
                                 Some(gd_path) => {
-                                    print_with_color("\nWriting back to savefile...", Color::Cyan);
-                                    levelstring::encrypt_level_string(
-                                        new_ls,
-                                        level_string,
-                                        gd_path,
-                                        level_name,
-                                    )?;
-
-                                    print_with_color(
-                                        "Written to save. You can now open Geometry Dash again!",
-                                        Color::Green,
-                                    );
+                                    if let Some(original) = &original_level {
+                                        ownership::check_unchanged(original, &full_level)
+                                        .map_err(|e| {
+                                            format!("Not writing the level (--paranoid): {}", e)
+                                        })?;
+                                    }
+                                    let gmd = levelstring::is_gmd(&gd_path);
+                                    if gmd {
+                                        print_with_color(
+                                            "\nWriting the level file...",
+                                            Color::Cyan,
+                                        );
+                                        levelstring::write_gmd(
+                                            &gd_path,
+                                            &gmd_settings,
+                                            &full_level,
+                                        )?;
+                                    } else {
+                                        print_with_color(
+                                            "\nWriting back to savefile...",
+                                            Color::Cyan,
+                                        );
+                                        levelstring::encrypt_level_string(
+                                            new_ls,
+                                            level_string,
+                                            gd_path.clone(),
+                                            level_name.clone(),
+                                            &save_format,
+                                        )?;
+                                    }
+                                    if let Some(original) = &original_level {
+                                        // read it back to check what actually got written
+                                        let written = if gmd {
+                                            levelstring::read_gmd(&gd_path)?.1
+                                        } else {
+                                            levelstring::get_level_string(
+                                                fs::read(&gd_path)?,
+                                                level_name,
+                                                &save_format,
+                                            )?
+                                        };
+                                        ownership::check_unchanged(original, &written).map_err(
+                                            |e| {
+                                                format!(
+                                                    "The level was written, but (--paranoid): {}",
+                                                    e
+                                                )
+                                            },
+                                        )?;
+                                        print_with_color(
+                                            "Checked that no hand-placed objects were changed",
+                                            Color::Green,
+                                        );
+                                    }
+
+                                    if gmd {
+                                        print_with_color(
+                                            &format!("Written to {}", gd_path.to_string_lossy()),
+                                            Color::Green,
+                                        );
+                                    } else {
+                                        print_with_color(
+                                            "Written to save. You can now open Geometry Dash again!",
+                                            Color::Green,
+                                        );
+                                    }
                                 }
 
-                                None => println!("Output: {}", new_ls),
+                                None if output_gmd.is_none() && !copy_to_clipboard => {
+                                    println!("Output: {}", new_ls)
+                                }
+                                None => (),
                             };
                         }
+                        if copy_to_clipboard {
+                            if let Some(original) = &original_level {
+                                ownership::check_unchanged(original, &full_level).map_err(|e| {
+                                    format!("Not copying the level (--paranoid): {}", e)
+                                })?;
+                            }
+                            clipboard::write(&if clipboard_encoded {
+                                levelstring::encode_level_string(&full_level)
+                            } else {
+                                full_level.clone()
+                            })?;
+                            print_with_color("Copied the level to the clipboard", Color::Green);
+                        }
+                        if let Some(path) = &output_gmd {
+                            levelstring::write_gmd(path, &gmd_settings, &full_level)?;
+                            print_with_color(
+                                &format!("Level written to {}", path.to_string_lossy()),
+                                Color::Green,
+                            );
+                        }
                     };
 
-                    let mut stdout = StandardStream::stdout(ColorChoice::Always);
+                    if let Some(d) = &mut dashboard {
+                        d.finish();
+                        if interactive {
+                            tui::browse(&compiled.func_ids);
+                        }
+                    }
+
+                    let mut stdout = StandardStream::stdout(color_choice(&std::io::stdout()));
                     stdout.set_color(&ColorSpec::new()).unwrap();
 
                     Ok(())
                 }
 
+                "check" => {
+                    let (script_path, project) = script_or_project(&mut args_iter)?;
+
+                    let mut deny_warnings = false;
+                    let mut release = project
+                        .as_ref()
+                        .and_then(|p| p.release)
+                        .unwrap_or(false);
+                    let mut defines = Vec::new();
+                    // after the current folder and the folder of the executable
+                    let mut included_paths = Vec::new();
+
+                    while let Some(arg) = args_iter.next() {
+                        match arg.as_ref() {
+                            "--deny-warnings" => deny_warnings = true,
+                            "--release" => release = true,
+                            "--error-format" => diagnostics::set_json(diagnostics::parse_format(
+                                &args_iter.next().cloned().unwrap_or_default(),
+                            )?),
+                            a if a.starts_with("--error-format=") => diagnostics::set_json(
+                                diagnostics::parse_format(&a["--error-format=".len()..])?,
+                            ),
+                            "--include-path" | "--included-path" | "-i" => included_paths.push({
+                                let path = PathBuf::from(
+                                    args_iter.next().cloned().expect("No path provided"),
+                                );
+                                if path.exists() {
+                                    path
+                                } else {
+                                    return Err(Box::from(format!(
+                                        "Include path {} doesn't exist",
+                                        path.to_string_lossy()
+                                    )));
+                                }
+                            }),
+                            "--" => {
+                                defines.extend(args_iter.by_ref().map(|a| flags::parse_define(a)))
+                            }
+                            a => return Err(Box::from(flags::unknown(a, flags::CHECK_FLAGS))),
+                        };
+                    }
+                    if let Some(project) = &project {
+                        included_paths.extend(project.include_paths.iter().cloned());
+                    }
+
+                    let spwn = builder(included_paths, &script_path)?.release(release);
+                    let unparsed = fs::read_to_string(script_path.clone())?;
+                    let (statements, notes, errors) = spwn.parse(unparsed, script_path.clone());
+                    if !errors.is_empty() {
+                        for err in &errors {
+                            print_error(err);
+                        }
+                        print_error_count(errors.len());
+                        std::process::exit(ERROR_EXIT_CODE);
+                    }
+
+                    // no savefile, no optimizing and no level string, just the compiler
+                    let mut globals = spwn.globals(script_path);
+                    globals.defines = defines;
+                    globals.interactive = false;
+                    if let Some(project) = project {
+                        globals.dependencies = project.dependency_folders()?;
+                    }
+                    let includes = spwn.include_paths().to_vec();
+                    let compiled =
+                        match compiler::compile_spwn_with(globals, statements, includes, notes) {
+                            Err((err, partial)) => {
+                                print_error(&err);
+                                for err in &partial.errors {
+                                    print_error(err);
+                                }
+                                print_error_count(partial.errors.len() + 1);
+                                std::process::exit(ERROR_EXIT_CODE);
+                            }
+                            Ok(p) => p,
+                        };
+                    let warning_count = compiled.warnings.len() + compiled.lints.len();
+                    if deny_warnings && warning_count > 0 {
+                        eprint_with_color(
+                            &format!(
+                                "Failed, since there {} (and --deny-warnings is on)",
+                                if warning_count == 1 {
+                                    "is a warning".to_string()
+                                } else {
+                                    format!("are {} warnings", warning_count)
+                                }
+                            ),
+                            Color::Red,
+                        );
+                        std::process::exit(ERROR_EXIT_CODE);
+                    }
+                    Ok(())
+                }
+
+                "preview" => {
+                    let script_path = match args_iter.next() {
+                        Some(a) => PathBuf::from(a),
+                        None => return Err(std::boxed::Box::from("Expected script file argument")),
+                    };
+
+                    let mut output = None;
+                    let mut opti_enabled = true;
+                    // after the current folder and the folder of the executable
+                    let mut included_paths = Vec::new();
+
+                    while let Some(arg) = args_iter.next() {
+                        match arg.as_ref() {
+                            "--output" | "-o" => output = args_iter.next().map(PathBuf::from),
+                            "--no-optimize" => opti_enabled = false,
+                            "--include-path" | "--included-path" | "-i" => included_paths.push({
+                                let path = PathBuf::from(
+                                    args_iter.next().cloned().expect("No path provided"),
+                                );
+                                if path.exists() {
+                                    path
+                                } else {
+                                    return Err(Box::from(format!(
+                                        "Include path {} doesn't exist",
+                                        path.to_string_lossy()
+                                    )));
+                                }
+                            }),
+                            _ => (),
+                        };
+                    }
+
+                    let trigger_area = find_trigger_area(&script_path)?;
+                    let output = output.unwrap_or_else(|| script_path.with_extension("png"));
+                    let svg = match output.extension().and_then(|e| e.to_str()) {
+                        Some("png") => false,
+                        Some("svg") => true,
+                        _ => return Err(Box::from("The output file must be a .png or .svg file")),
+                    };
+
+                    print_with_color("Parsing ...", Color::Green);
+                    let spwn = builder(included_paths, &script_path)?.optimize(opti_enabled);
+                    let unparsed = fs::read_to_string(script_path.clone())?;
+
+                    let (statements, notes, mut errors) =
+                        spwn.parse(unparsed, script_path.clone());
+                    if !errors.is_empty() {
+                        print_error(&errors.remove(0));
+                        std::process::exit(ERROR_EXIT_CODE);
+                    }
+
+                    let globals = spwn.globals(script_path);
+                    let includes = spwn.include_paths().to_vec();
+                    let mut compiled =
+                        match compiler::compile_spwn_with(globals, statements, includes, notes) {
+                            Err((err, _)) => {
+                                print_error(&err);
+                                std::process::exit(ERROR_EXIT_CODE);
+                            }
+                            Ok(p) => p,
+                        };
+
+                    // same objects as build --emit=json gives
+                    if opti_enabled && compiled.func_ids.iter().any(|x| !x.obj_list.is_empty()) {
+                        print_with_color("Optimizing triggers...", Color::Cyan);
+                    }
+                    let (mut objects, _) = spwn.objects(&mut compiled, &trigger_area);
+                    levelstring::resolve_ids(&mut objects, "")?;
+
+                    if svg {
+                        fs::write(&output, preview::render_svg(&objects))?;
+                    } else {
+                        fs::write(&output, preview::render_png(&objects))?;
+                    }
+                    print_with_color(
+                        &format!(
+                            "Preview of {} objects written to {}",
+                            objects.len(),
+                            output.to_string_lossy()
+                        ),
+                        Color::Green,
+                    );
+                    Ok(())
+                }
                 "doc" => {
                     //use std::fs::File;
 
@@ -290,6 +1462,26 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                         }
                     };
 
+                    let mut serve = false;
+                    let mut port = 8080;
+
+                    while let Some(arg) = args_iter.next() {
+                        match arg.as_ref() {
+                            "--serve" => serve = true,
+                            "--port" | "-p" => {
+                                port = match args_iter.next().map(|p| p.parse::<u16>()) {
+                                    Some(Ok(p)) => p,
+                                    _ => return Err(Box::from("Expected a port number")),
+                                }
+                            }
+                            _ => (),
+                        };
+                    }
+
+                    if serve {
+                        return doc_server::serve(lib_path, port);
+                    }
+
                     match documentation::document_lib(lib_path) {
                         Ok(_) => (),
                         Err(e) => {
@@ -302,33 +1494,451 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
                     Ok(())
                 }
-                // "format" => {
-                //     use std::fs::File;
-                //     //use std::io::Write;
-                //     let script_path = match args_iter.next() {
-                //         Some(a) => PathBuf::from(a),
-                //         None => return Err(std::boxed::Box::from("Expected script file argument")),
-                //     };
+                "test" => {
+                    let lib_path = match args_iter.next() {
+                        Some(a) => a,
+                        None => {
+                            return Err(std::boxed::Box::from("Expected library name argument"))
+                        }
+                    };
 
-                //     println!("Formatting is not good yet, i will finish it before the final version is released.");
+                    match documentation::test_examples(lib_path) {
+                        Ok(0) => Ok(()),
+                        Ok(_) => std::process::exit(ERROR_EXIT_CODE),
+                        Err(e) => {
+                            eprint_with_color(&format!("{}\n", e), Color::Red);
+                            std::process::exit(ERROR_EXIT_CODE);
+                        }
+                    }
+                }
+                "deadcode" => {
+                    let lib_path = match args_iter.next() {
+                        Some(a) => a,
+                        None => {
+                            return Err(std::boxed::Box::from("Expected library name argument"))
+                        }
+                    };
+                    let project = env::current_dir()?;
 
-                //     let unparsed = fs::read_to_string(script_path.clone())?;
+                    let (unused, checked) = match deadcode::find_unused(lib_path, &project) {
+                        Ok(r) => r,
+                        Err(e) => {
+                            eprint_with_color(&format!("{}\n", e), Color::Red);
+                            std::process::exit(ERROR_EXIT_CODE);
+                        }
+                    };
+                    for item in &unused {
+                        print_with_color(
+                            &format!(
+                                "{}:{}:{}: {} {} never used",
+                                item.file
+                                    .strip_prefix(&project)
+                                    .unwrap_or(&item.file)
+                                    .to_string_lossy(),
+                                item.pos.0 .0,
+                                item.pos.0 .1 + 1,
+                                item.names.join(", "),
+                                if item.names.len() > 1 { "are" } else { "is" }
+                            ),
+                            Color::White,
+                        );
+                    }
+                    print_with_color(
+                        &format!(
+                            "{} of {} exported macros are never used in {}",
+                            unused.len(),
+                            checked,
+                            project.to_string_lossy()
+                        ),
+                        if unused.is_empty() {
+                            Color::Green
+                        } else {
+                            Color::Yellow
+                        },
+                    );
+                    Ok(())
+                }
+                "trace" => {
+                    match args_iter.next().map(|a| a.as_str()) {
+                        Some("query") => (),
+                        _ => return Err(Box::from("Expected `spwn trace query`")),
+                    };
+                    let trace_path = match args_iter.next() {
+                        Some(a) => PathBuf::from(a),
+                        None => return Err(Box::from("Expected trace file argument")),
+                    };
+                    let variable = match args_iter.next() {
+                        Some(a) => a.clone(),
+                        None => return Err(Box::from("Expected variable name argument")),
+                    };
 
-                //     let (parsed, _) = match parse_spwn(unparsed, script_path) {
-                //         Err(err) => {
-                //             eprintln!("{}\n", err);
-                //             std::process::exit(ERROR_EXIT_CODE);
-                //         }
-                //         Ok(p) => p,
-                //     };
+                    let mut moment = None;
+                    while let Some(arg) = args_iter.next() {
+                        moment = Some(match arg.as_ref() {
+                            "--group" | "-g" => match args_iter
+                                .next()
+                                .map(|a| a.trim_end_matches('g').parse::<u16>())
+                            {
+                                Some(Ok(n)) => history::Moment::Group(n),
+                                _ => return Err(Box::from("Expected a group id")),
+                            },
+                            "--at" => match args_iter.next() {
+                                Some(line) => history::Moment::Line(line.clone()),
+                                None => return Err(Box::from("Expected a location (file:line)")),
+                            },
+                            "--step" => match args_iter.next().map(|a| a.parse::<usize>()) {
+                                Some(Ok(n)) => history::Moment::Step(n),
+                                _ => return Err(Box::from("Expected a step number")),
+                            },
+                            a => return Err(Box::from(format!("Unknown option: {}", a))),
+                        });
+                    }
 
-                //     let formatted = fmt::format(parsed);
+                    match history::query(&trace_path, &variable, moment) {
+                        Ok(answer) => print!("{}", answer),
+                        Err(e) => {
+                            eprint_with_color(&format!("{}\n", e), Color::Red);
+                            std::process::exit(ERROR_EXIT_CODE);
+                        }
+                    }
+                    Ok(())
+                }
 
-                //     let mut output_file = File::create("test/formatted.spwn")?;
-                //     output_file.write_all(formatted.as_bytes())?;
+                "expand" => {
+                    let script_path = match args_iter.next() {
+                        Some(a) => PathBuf::from(a),
+                        None => return Err(std::boxed::Box::from("Expected script file argument")),
+                    };
+
+                    let mut call = None;
+                    let mut included_paths = vec![
+                        std::env::current_dir().expect("Cannot access current directory"),
+                        std::env::current_exe()
+                            .expect("Cannot access directory of executable")
+                            .parent()
+                            .expect("Executable must be in some directory")
+                            .to_path_buf(),
+                    ];
+
+                    while let Some(arg) = args_iter.next() {
+                        match arg.as_ref() {
+                            "--call" => call = args_iter.next().cloned(),
+                            "--include-path" | "--included-path" | "-i" => included_paths.push({
+                                let path = PathBuf::from(
+                                    args_iter.next().cloned().expect("No path provided"),
+                                );
+                                if path.exists() {
+                                    path
+                                } else {
+                                    return Err(Box::from(format!(
+                                        "Include path {} doesn't exist",
+                                        path.to_string_lossy()
+                                    )));
+                                }
+                            }),
+                            _ => (),
+                        };
+                    }
 
-                //     Ok(())
-                // }
+                    let call = match call {
+                        Some(c) => c,
+                        None => return Err(Box::from("Expected a macro call (--call)")),
+                    };
+
+                    let aliases = find_aliases(&script_path)?;
+                    let unparsed = fs::read_to_string(script_path.clone())?;
+                    let (statements, notes, mut errors) =
+                        parse_spwn_partial_with(unparsed, script_path.clone(), aliases.as_ref());
+                    if !errors.is_empty() {
+                        print_error(&errors.remove(0));
+                        std::process::exit(ERROR_EXIT_CODE);
+                    }
+                    let (call, _) = match parse_spwn(call, PathBuf::from(trace::CALL_SOURCE)) {
+                        Err(err) => {
+                            print_error(&err);
+                            std::process::exit(ERROR_EXIT_CODE);
+                        }
+                        Ok(p) => p,
+                    };
+
+                    let lines = match trace::expand_call(
+                        statements,
+                        script_path,
+                        included_paths,
+                        notes,
+                        call,
+                        aliases,
+                    ) {
+                        Err(err) => {
+                            print_error(&*err);
+                            std::process::exit(ERROR_EXIT_CODE);
+                        }
+                        Ok(l) => l,
+                    };
+
+                    for line in lines {
+                        let text = format!("{}{}", "    ".repeat(line.depth), line.text);
+                        match line.span {
+                            Some((file, pos)) => {
+                                println!("{:<60} // {}:{}:{}", text, file, pos.0 .0, pos.0 .1 + 1)
+                            }
+                            None => println!("{}", text),
+                        }
+                    }
+                    Ok(())
+                }
+                "lsp" => lsp::run(),
+                "decompile" => {
+                    let mut input = None;
+                    let mut output = None;
+                    let mut save_file = None;
+                    let mut profile = None;
+                    let mut level_name = None;
+                    while let Some(arg) = args_iter.next() {
+                        match arg.as_ref() {
+                            "--output" | "-o" => output = args_iter.next().map(PathBuf::from),
+                            "--save-file" | "-s" => save_file = args_iter.next().map(PathBuf::from),
+                            "--profile" => profile = args_iter.next().cloned(),
+                            "--level-name" | "-n" => level_name = args_iter.next().cloned(),
+                            a if !a.starts_with('-') && input.is_none() => {
+                                input = Some(PathBuf::from(a))
+                            }
+                            a => return Err(Box::from(format!("Unknown flag: {}", a))),
+                        }
+                    }
+                    // a .gmd file, a file with a level string, or a level in the save file
+                    let (level_string, source) = match &input {
+                        Some(path) if levelstring::is_gmd(path) => {
+                            (levelstring::read_gmd(path)?.1, path.to_string_lossy().to_string())
+                        }
+                        Some(path) => (
+                            levelstring::read_copied_level(&fs::read_to_string(path)?)?.0,
+                            path.to_string_lossy().to_string(),
+                        ),
+                        None => {
+                            let save_format =
+                                find_save_format(std::path::Path::new("."), profile.as_deref())?;
+                            let gd_path = save_file
+                                .or_else(|| save_format.save_file.clone())
+                                .unwrap_or_else(default_save_file);
+                            let level_string = levelstring::get_level_string(
+                                fs::read(&gd_path)?,
+                                level_name.clone(),
+                                &save_format,
+                            )?;
+                            let source = match &level_name {
+                                Some(name) => format!("the level {}", name),
+                                None => "the newest level".to_string(),
+                            };
+                            (level_string, source)
+                        }
+                    };
+                    let output = output.unwrap_or_else(|| match &input {
+                        Some(path) => path.with_extension("spwn"),
+                        None => PathBuf::from("decompiled.spwn"),
+                    });
+                    let (spwn, count) = decompile::to_spwn(&level_string, &source);
+                    fs::write(&output, spwn)?;
+                    print_with_color(
+                        &format!("{} objects written to {}", count, output.to_string_lossy()),
+                        Color::Green,
+                    );
+                    Ok(())
+                }
+                "sync" => {
+                    let mut layer = None;
+                    let mut output = None;
+                    let mut save_file = None;
+                    let mut profile = None;
+                    let mut level_name = None;
+                    let mut force = false;
+                    while let Some(arg) = args_iter.next() {
+                        match arg.as_ref() {
+                            "--layer" => {
+                                let n = args_iter.next().cloned().unwrap_or_default();
+                                layer = Some(n.parse::<u16>().map_err(|_| {
+                                    format!("Expected an editor layer number, found {}", n)
+                                })?);
+                            }
+                            "--output" | "-o" => output = args_iter.next().map(PathBuf::from),
+                            "--save-file" | "-s" => save_file = args_iter.next().map(PathBuf::from),
+                            "--profile" => profile = args_iter.next().cloned(),
+                            "--level-name" | "-n" => level_name = args_iter.next().cloned(),
+                            "--force" => force = true,
+                            a => return Err(Box::from(format!("Unknown flag: {}", a))),
+                        }
+                    }
+                    let layer = match layer {
+                        Some(l) => l,
+                        None => return Err(Box::from("Expected --layer [editor layer]")),
+                    };
+                    let output =
+                        output.unwrap_or_else(|| PathBuf::from(format!("layer_{}.spwn", layer)));
+                    let save_format =
+                        find_save_format(std::path::Path::new("."), profile.as_deref())?;
+                    let gd_path = save_file
+                        .or_else(|| save_format.save_file.clone())
+                        .unwrap_or_else(default_save_file);
+
+                    if !force && gd_process::geometry_dash_running() {
+                        eprint_with_color(
+                            "Geometry Dash is open, and it would overwrite the level when it closes. Close it and sync again (or use --force to write anyway)",
+                            Color::Red,
+                        );
+                        std::process::exit(ERROR_EXIT_CODE);
+                    }
+
+                    let level_string = levelstring::get_level_string(
+                        fs::read(&gd_path)?,
+                        level_name.clone(),
+                        &save_format,
+                    )?;
+                    let (spwn, count) = sync::to_spwn(&level_string, layer);
+                    fs::write(&output, spwn)?;
+                    levelstring::encrypt_level_string(
+                        String::new(),
+                        sync::give_to_spwn(&level_string, layer),
+                        gd_path,
+                        level_name,
+                        &save_format,
+                    )?;
+                    print_with_color(
+                        &format!(
+                            "{} objects on editor layer {} written to {} (import it in the script to build them)",
+                            count,
+                            layer,
+                            output.to_string_lossy()
+                        ),
+                        Color::Green,
+                    );
+                    Ok(())
+                }
+                "fmt" | "format" => {
+                    let mut script_paths = Vec::new();
+                    let mut from_stdin = false;
+                    let mut range = None;
+                    let mut check = false;
+                    let mut write = false;
+
+                    while let Some(arg) = args_iter.next() {
+                        match arg.as_ref() {
+                            "--stdin" => from_stdin = true,
+                            "--check" => check = true,
+                            "--write" | "-w" => write = true,
+                            "--error-format" => diagnostics::set_json(diagnostics::parse_format(
+                                &args_iter.next().cloned().unwrap_or_default(),
+                            )?),
+                            a if a.starts_with("--error-format=") => diagnostics::set_json(
+                                diagnostics::parse_format(&a["--error-format=".len()..])?,
+                            ),
+                            "--range" | "-r" => {
+                                let text = match args_iter.next() {
+                                    Some(a) => a,
+                                    None => return Err(Box::from("Expected range argument")),
+                                };
+                                let parsed = match text.split_once(':') {
+                                    Some((a, b)) => match (a.parse::<usize>(), b.parse::<usize>()) {
+                                        (Ok(a), Ok(b)) if a > 0 && a <= b => Some((a, b)),
+                                        _ => None,
+                                    },
+                                    None => None,
+                                };
+                                match parsed {
+                                    Some(r) => range = Some(r),
+                                    None => {
+                                        return Err(Box::from(format!(
+                                            "Invalid range: {} (expected [start line]:[end line])",
+                                            text
+                                        )))
+                                    }
+                                }
+                            }
+                            a => script_paths.push(PathBuf::from(a)),
+                        };
+                    }
+
+                    let inputs = if from_stdin {
+                        if write {
+                            return Err(Box::from("--write can't be used with --stdin"));
+                        }
+                        use std::io::Read;
+                        let mut buf = String::new();
+                        std::io::stdin().read_to_string(&mut buf)?;
+                        vec![(buf, PathBuf::from("<stdin>"))]
+                    } else if script_paths.is_empty() {
+                        return Err(Box::from("Expected script file argument or --stdin"));
+                    } else {
+                        if script_paths.len() > 1 && !check && !write {
+                            return Err(Box::from(
+                                "Use --write or --check to format more than one file",
+                            ));
+                        }
+                        let mut inputs = Vec::new();
+                        for p in script_paths {
+                            inputs.push((fs::read_to_string(&p)?, p));
+                        }
+                        inputs
+                    };
+                    if range.is_some() && inputs.len() > 1 {
+                        return Err(Box::from("--range can only be used with one file"));
+                    }
+
+                    let mut unformatted = 0;
+                    for (unparsed, script_path) in inputs {
+                        let config_dir = if from_stdin {
+                            std::env::current_dir()?
+                        } else {
+                            script_path
+                                .parent()
+                                .map(|p| p.to_path_buf())
+                                .unwrap_or_default()
+                        };
+                        let config = match fmt::FmtConfig::find(&config_dir) {
+                            Ok(c) => c,
+                            Err(e) => {
+                                eprint_with_color(&e, Color::Red);
+                                std::process::exit(ERROR_EXIT_CODE);
+                            }
+                        };
+
+                        let normalized = unparsed.replace("\r\n", "\n");
+                        // statements with syntax errors are kept as they are
+                        let (parsed, notes, errors) =
+                            parse_spwn_partial(normalized.clone(), script_path.clone());
+                        let has_errors = !errors.is_empty();
+                        for err in errors {
+                            print_error(&err);
+                        }
+
+                        let formatted = match range {
+                            Some(range) => fmt::format_range(&normalized, parsed, range, &config),
+                            None => fmt::format(parsed, &notes.tag, &config),
+                        };
+
+                        let name = script_path.to_string_lossy();
+                        if check {
+                            if formatted != unparsed || has_errors {
+                                unformatted += 1;
+                                print_with_color(
+                                    &format!("{} is not formatted", name),
+                                    Color::Yellow,
+                                );
+                            }
+                        } else if write {
+                            if formatted != unparsed {
+                                fs::write(&script_path, &formatted)?;
+                                print_with_color(&format!("Formatted {}", name), Color::Green);
+                            }
+                        } else {
+                            print!("{}", formatted);
+                        }
+                    }
+
+                    if unformatted > 0 {
+                        std::process::exit(ERROR_EXIT_CODE);
+                    }
+                    Ok(())
+                }
                 a => {
                     eprint_with_color(&format!("Unknown subcommand: {}", a), Color::Red);
                     println!("{}", HELP);
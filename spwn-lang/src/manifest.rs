@@ -0,0 +1,193 @@
+// the `key = value` lines of spwn.toml, for the parts of spwn that keep their options in
+// it ([format], [triggers], [aliases], [savefile], [project] and [dependencies]). it
+// isn't all of toml, just the lines those sections use: comments, [section] headers,
+// and values that are "strings", 'strings', numbers, booleans, [lists] and { tables }
+// on one line
+use std::fmt::Display;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub line: usize,
+    pub section: String,
+    pub key: String,
+    // as it's written, so a quoted string still has its quotes
+    pub value: String,
+}
+
+impl Entry {
+    // the value without its quotes
+    pub fn text(&self) -> String {
+        unquote(&self.value)
+    }
+
+    pub fn error(&self, message: impl Display) -> String {
+        format!("line {}: {}", self.line, message)
+    }
+}
+
+// the position of the first `c` that isn't in a string
+pub fn find(s: &str, c: char) -> Option<usize> {
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, ch) in s.char_indices() {
+        match quote {
+            Some(q) => {
+                if escaped {
+                    escaped = false
+                } else if ch == '\\' && q == '"' {
+                    escaped = true
+                } else if ch == q {
+                    quote = None
+                }
+            }
+            None if ch == c => return Some(i),
+            None if ch == '"' || ch == '\'' => quote = Some(ch),
+            None => (),
+        }
+    }
+    None
+}
+
+// splits at every `separator` that isn't in a string
+pub fn split(s: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut rest = s;
+    while let Some(pos) = find(rest, separator) {
+        parts.push(&rest[..pos]);
+        rest = &rest[(pos + separator.len_utf8())..];
+    }
+    parts.push(rest);
+    parts
+}
+
+// "text" or 'text' -> text (anything else is left as it is)
+pub fn unquote(s: &str) -> String {
+    let s = s.trim();
+    if s.len() >= 2 && s.starts_with('\'') && s.ends_with('\'') {
+        return s[1..s.len() - 1].to_string();
+    }
+    if !(s.len() >= 2 && s.starts_with('"') && s.ends_with('"')) {
+        return s.to_string();
+    }
+    let mut out = String::new();
+    let mut chars = s[1..s.len() - 1].chars();
+    while let Some(c) = chars.next() {
+        out.push(match c {
+            '\\' => match chars.next() {
+                Some('n') => '\n',
+                Some('t') => '\t',
+                Some(c) => c,
+                None => '\\',
+            },
+            c => c,
+        });
+    }
+    out
+}
+
+// the lines that aren't empty, without their comments (numbered from 1)
+fn lines(content: &str) -> impl Iterator<Item = (usize, &str)> {
+    content
+        .lines()
+        .map(|line| match find(line, '#') {
+            Some(pos) => &line[..pos],
+            None => line,
+        })
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty())
+}
+
+fn header(line: &str) -> Option<&str> {
+    if line.starts_with('[') && line.ends_with(']') {
+        Some(line[1..line.len() - 1].trim())
+    } else {
+        None
+    }
+}
+
+// the names of all the [sections], even the empty ones
+pub fn sections(content: &str) -> Vec<String> {
+    lines(content)
+        .filter_map(|(_, line)| header(line))
+        .map(|name| name.to_string())
+        .collect()
+}
+
+// the lines of the sections `wanted` says yes to, in order. the other sections are
+// skipped without looking at them, since they can belong to other tools
+pub fn read(content: &str, wanted: impl Fn(&str) -> bool) -> Result<Vec<Entry>, String> {
+    let mut entries = Vec::new();
+    // the lines before the first [section] are in ""
+    let mut section = if wanted("") {
+        Some(String::new())
+    } else {
+        None
+    };
+
+    for (number, line) in lines(content) {
+        if let Some(name) = header(line) {
+            section = if wanted(name) {
+                Some(name.to_string())
+            } else {
+                None
+            };
+            continue;
+        }
+        let section = match &section {
+            Some(s) => s,
+            None => continue,
+        };
+
+        match find(line, '=') {
+            Some(pos) => entries.push(Entry {
+                line: number,
+                section: section.clone(),
+                key: unquote(&line[..pos]),
+                value: line[(pos + 1)..].trim().to_string(),
+            }),
+            None => return Err(format!("line {}: expected `key = value`", number)),
+        }
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hashes_in_strings_are_not_comments() {
+        let content = "# a comment\n\
+                       [other]\n\
+                       not toml at all\n\
+                       [project]\n\
+                       level_name = \"level #2\" # the second one\n\
+                       registry = 'https://example.com/#spwn'\n\
+                       \"quoted key\" = \"say \\\"hi\\\"\"\n\
+                       include_paths = [\"a,b\", \"#c\"]\n";
+        let entries = read(content, |s| s == "project").unwrap();
+        let values: Vec<_> = entries
+            .iter()
+            .map(|e| (e.line, e.key.as_str(), e.text()))
+            .collect();
+        assert_eq!(
+            values,
+            [
+                (5, "level_name", "level #2".to_string()),
+                (6, "registry", "https://example.com/#spwn".to_string()),
+                (7, "quoted key", "say \"hi\"".to_string()),
+                (8, "include_paths", "[\"a,b\", \"#c\"]".to_string()),
+            ]
+        );
+        assert_eq!(
+            split(&entries[3].value[1..entries[3].value.len() - 1], ','),
+            ["\"a,b\"", " \"#c\""]
+        );
+        assert_eq!(
+            read("[project]\nentry\n", |s| s == "project"),
+            Err("line 2: expected `key = value`".to_string())
+        );
+        assert_eq!(sections(content), ["other", "project"]);
+    }
+}
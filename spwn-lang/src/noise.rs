@@ -0,0 +1,92 @@
+// noise for generating terrain and decorations ($.noise), the same for the same
+// seed every build
+
+// a number from 0 to 1 (not including 1) for some integers and a seed
+pub fn hash(values: &[i64], seed: i64) -> f64 {
+    // splitmix64 for every value
+    let mut h = seed as u64 ^ 0x9e37_79b9_7f4a_7c15;
+    for v in values {
+        h = h
+            .wrapping_add(*v as u64)
+            .wrapping_add(0x9e37_79b9_7f4a_7c15);
+        h = (h ^ (h >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        h = (h ^ (h >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        h ^= h >> 31;
+    }
+    (h >> 11) as f64 / (1u64 << 53) as f64
+}
+
+// hashes any number (not just integers) by its bits
+pub fn hash_number(value: f64, seed: i64) -> f64 {
+    hash(&[value.to_bits() as i64], seed)
+}
+
+fn gradient(ix: i64, iy: i64, seed: i64) -> (f64, f64) {
+    let angle = hash(&[ix, iy], seed) * std::f64::consts::PI * 2.0;
+    (angle.cos(), angle.sin())
+}
+
+fn fade(t: f64) -> f64 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+// from about -1 to 1
+pub fn perlin(x: f64, y: f64, seed: i64) -> f64 {
+    let (x0, y0) = (x.floor(), y.floor());
+    let (ix, iy) = (x0 as i64, y0 as i64);
+    let (fx, fy) = (x - x0, y - y0);
+
+    let dot = |cx: i64, cy: i64, dx: f64, dy: f64| {
+        let (gx, gy) = gradient(ix + cx, iy + cy, seed);
+        gx * dx + gy * dy
+    };
+    let (u, v) = (fade(fx), fade(fy));
+    let bottom = dot(0, 0, fx, fy) + u * (dot(1, 0, fx - 1.0, fy) - dot(0, 0, fx, fy));
+    let top =
+        dot(0, 1, fx, fy - 1.0) + u * (dot(1, 1, fx - 1.0, fy - 1.0) - dot(0, 1, fx, fy - 1.0));
+    // the most a 2d perlin value can be is sqrt(1/2)
+    (bottom + v * (top - bottom)) * std::f64::consts::SQRT_2
+}
+
+// from about -1 to 1
+pub fn simplex(x: f64, y: f64, seed: i64) -> f64 {
+    let f2 = 0.5 * (3f64.sqrt() - 1.0);
+    let g2 = (3.0 - 3f64.sqrt()) / 6.0;
+
+    let s = (x + y) * f2;
+    let (i, j) = ((x + s).floor(), (y + s).floor());
+    let t = (i + j) * g2;
+    let (x0, y0) = (x - (i - t), y - (j - t));
+    let (i1, j1) = if x0 > y0 { (1.0, 0.0) } else { (0.0, 1.0) };
+
+    let corners = [
+        (x0, y0, 0.0, 0.0),
+        (x0 - i1 + g2, y0 - j1 + g2, i1, j1),
+        (x0 - 1.0 + 2.0 * g2, y0 - 1.0 + 2.0 * g2, 1.0, 1.0),
+    ];
+    let mut total = 0.0;
+    for (dx, dy, ci, cj) in &corners {
+        let falloff = 0.5 - dx * dx - dy * dy;
+        if falloff > 0.0 {
+            let (gx, gy) = gradient((i + ci) as i64, (j + cj) as i64, seed);
+            total += falloff.powi(4) * (gx * dx + gy * dy);
+        }
+    }
+    // scaled so it reaches about -1 and 1
+    total * 99.2
+}
+
+// how far (x, y) is from the closest of a scattered set of points,
+// one in every 1 by 1 square
+pub fn voronoi(x: f64, y: f64, seed: i64) -> f64 {
+    let (cx, cy) = (x.floor() as i64, y.floor() as i64);
+    let mut closest = f64::INFINITY;
+    for ix in cx - 1..=cx + 1 {
+        for iy in cy - 1..=cy + 1 {
+            let px = ix as f64 + hash(&[ix, iy, 0], seed);
+            let py = iy as f64 + hash(&[ix, iy, 1], seed);
+            closest = closest.min(((px - x).powi(2) + (py - y).powi(2)).sqrt());
+        }
+    }
+    closest
+}
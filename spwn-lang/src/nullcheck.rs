@@ -0,0 +1,310 @@
+// warns about variables that might be null being used without a null check first,
+// like `i = arr.index(x)` followed by `arr[i + 1]` (the most common runtime error)
+use crate::ast::*;
+use crate::parser::FileRange;
+
+use std::collections::HashMap;
+
+// methods that give back null when they don't find anything
+const NULLABLE_METHODS: [&str; 1] = ["index"];
+
+// variable -> where the null could come from
+type Nullable = HashMap<String, String>;
+
+pub fn check(statements: &[Statement]) -> Vec<(FileRange, String)> {
+    let mut warnings = Vec::new();
+    check_block(statements, &mut Nullable::new(), &mut warnings);
+    warnings
+}
+
+// adds the warnings for a script file to the compiler's warnings
+pub fn check_file(statements: &[Statement], file: &std::path::Path, warnings: &mut Vec<String>) {
+    for (pos, warning) in check(statements) {
+        warnings.push(format!(
+            "{}:{}:{}: {}",
+            crate::compiler::display_path(file),
+            pos.0 .0,
+            pos.0 .1 + 1,
+            warning
+        ));
+    }
+}
+
+fn check_block(
+    statements: &[Statement],
+    nullable: &mut Nullable,
+    warnings: &mut Vec<(FileRange, String)>,
+) {
+    for statement in statements {
+        let pos = statement.pos;
+        match &statement.body {
+            StatementBody::Expr(e) => {
+                if let Some((name, value)) = assignment(e) {
+                    check_var(value, pos, nullable, warnings);
+                    match null_source(value) {
+                        Some(source) => {
+                            nullable.insert(name, source);
+                        }
+                        None => {
+                            nullable.remove(&name);
+                        }
+                    }
+                } else {
+                    check_expr(e, pos, nullable, warnings)
+                }
+            }
+            StatementBody::Call(c) => check_var(&c.function, pos, nullable, warnings),
+            StatementBody::Return(Some(e)) | StatementBody::Extract(e) => {
+                check_expr(e, pos, nullable, warnings)
+            }
+            StatementBody::Error(e) => check_expr(&e.message, pos, nullable, warnings),
+            StatementBody::If(i) => {
+                check_expr(&i.condition, pos, nullable, warnings);
+
+                let (non_null_in_if, non_null_in_else) = match null_comparison(&i.condition) {
+                    Some((name, Operator::NotEqual)) => (Some(name), None),
+                    Some((name, _)) => (None, Some(name)),
+                    None => (None, None),
+                };
+
+                let mut in_if = nullable.clone();
+                if let Some(name) = &non_null_in_if {
+                    in_if.remove(name);
+                }
+                check_block(&i.if_body, &mut in_if, warnings);
+
+                let mut in_else = nullable.clone();
+                if let Some(name) = &non_null_in_else {
+                    in_else.remove(name);
+                }
+                if let Some(body) = &i.else_body {
+                    check_block(body, &mut in_else, warnings);
+                }
+
+                // `if x == null { return }` means x isn't null after the if
+                if let Some(name) = non_null_in_else {
+                    if exits(&i.if_body) {
+                        nullable.remove(&name);
+                    }
+                }
+            }
+            StatementBody::For(f) => {
+                check_expr(&f.array, pos, nullable, warnings);
+                let mut inside = nullable.clone();
+                inside.remove(&f.symbol);
+                check_block(&f.body, &mut inside, warnings);
+            }
+            StatementBody::Try(t) => {
+                check_block(&t.try_body, &mut nullable.clone(), warnings);
+                check_block(&t.catch_body, &mut nullable.clone(), warnings);
+            }
+            StatementBody::Defer(body) => check_block(body, &mut nullable.clone(), warnings),
+            StatementBody::Impl(imp) => {
+                for def in &imp.members {
+                    let (DictDef::Def((_, e)) | DictDef::Extract(e)) = def;
+                    check_expr(e, pos, nullable, warnings)
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+// `x = value` and `let x = value`
+fn assignment(e: &Expression) -> Option<(String, &Variable)> {
+    if e.operators != [Operator::Assign] {
+        return None;
+    }
+    match &e.values[0] {
+        Variable {
+            value: ValueLiteral {
+                body: ValueBody::Symbol(name),
+            },
+            path,
+            operator: None | Some(UnaryOperator::Let),
+            ..
+        } if path.is_empty() => Some((name.clone(), &e.values[1])),
+        _ => None,
+    }
+}
+
+fn null_source(value: &Variable) -> Option<String> {
+    if value.operator.is_some() {
+        return None;
+    }
+    if value.path.is_empty() {
+        // (value)
+        if let ValueBody::Expression(e) = &value.value.body {
+            if e.operators.is_empty() {
+                return null_source(&e.values[0]);
+            }
+        }
+        if let ValueBody::Null = value.value.body {
+            return Some("it's set to null".to_string());
+        }
+        return None;
+    }
+    match &value.path[..] {
+        [.., Path::Member(method), Path::Call(_)]
+            if NULLABLE_METHODS.contains(&method.as_str()) =>
+        {
+            Some(format!(
+                "`.{}(...)` gives null when nothing is found",
+                method
+            ))
+        }
+        // dict.get(key, default = null)
+        [.., Path::Member(method), Path::Call(args)]
+            if method == "get" && args.len() == 2 && is_null_literal(&args[1].value) =>
+        {
+            Some("`.get(...)` gives the null default when the key is missing".to_string())
+        }
+        _ => None,
+    }
+}
+
+fn is_null_literal(e: &Expression) -> bool {
+    e.operators.is_empty()
+        && e.values[0].path.is_empty()
+        && e.values[0].operator.is_none()
+        && e.values[0].value.body == ValueBody::Null
+}
+
+// `x == null` / `x != null` (either way around)
+fn null_comparison(e: &Expression) -> Option<(String, Operator)> {
+    if e.values.len() != 2 {
+        return None;
+    }
+    let op = match e.operators[0] {
+        op @ (Operator::Equal | Operator::NotEqual) => op,
+        _ => return None,
+    };
+    let symbol = |v: &Variable| match &v.value.body {
+        ValueBody::Symbol(s) if v.path.is_empty() && v.operator.is_none() => Some(s.clone()),
+        _ => None,
+    };
+    let is_null = |v: &Variable| v.path.is_empty() && v.value.body == ValueBody::Null;
+    match (&e.values[0], &e.values[1]) {
+        (a, b) if is_null(b) => symbol(a).map(|s| (s, op)),
+        (a, b) if is_null(a) => symbol(b).map(|s| (s, op)),
+        _ => None,
+    }
+}
+
+fn exits(body: &[Statement]) -> bool {
+    matches!(
+        body.last().map(|s| &s.body),
+        Some(StatementBody::Return(_))
+            | Some(StatementBody::Error(_))
+            | Some(StatementBody::Break(_))
+            | Some(StatementBody::Continue(_))
+    )
+}
+
+fn warn(
+    name: &str,
+    pos: FileRange,
+    nullable: &mut Nullable,
+    warnings: &mut Vec<(FileRange, String)>,
+) {
+    // only warn once per variable
+    if let Some(source) = nullable.remove(name) {
+        warnings.push((
+            pos,
+            format!(
+                "{} might be null here ({}), check it with `if {} != null` first",
+                name, source, name
+            ),
+        ));
+    }
+}
+
+fn bare_symbol(var: &Variable) -> Option<&str> {
+    match &var.value.body {
+        ValueBody::Symbol(s) if var.path.is_empty() => Some(s),
+        _ => None,
+    }
+}
+
+fn check_expr(
+    e: &Expression,
+    pos: FileRange,
+    nullable: &mut Nullable,
+    warnings: &mut Vec<(FileRange, String)>,
+) {
+    for (i, value) in e.values.iter().enumerate() {
+        // null can be compared and passed around, but not calculated with
+        let calculated = [i.checked_sub(1), Some(i)].iter().any(|op| {
+            !matches!(
+                op.and_then(|op| e.operators.get(op)),
+                None | Some(
+                    Operator::Equal
+                        | Operator::NotEqual
+                        | Operator::Assign
+                        | Operator::Or
+                        | Operator::And
+                        | Operator::Either
+                )
+            )
+        });
+        match bare_symbol(value) {
+            Some(name) if calculated => warn(name, pos, nullable, warnings),
+            _ => check_var(value, pos, nullable, warnings),
+        }
+    }
+}
+
+fn check_var(
+    var: &Variable,
+    pos: FileRange,
+    nullable: &mut Nullable,
+    warnings: &mut Vec<(FileRange, String)>,
+) {
+    if let (ValueBody::Symbol(name), Some(first)) = (&var.value.body, var.path.first()) {
+        if matches!(
+            first,
+            Path::Member(_) | Path::Associated(_) | Path::Index(_) | Path::Call(_)
+        ) {
+            warn(name, pos, nullable, warnings);
+        }
+    }
+
+    for path in &var.path {
+        match path {
+            Path::Index(e) => {
+                if let (Some(name), true) =
+                    (e.values.first().and_then(bare_symbol), e.values.len() == 1)
+                {
+                    warn(name, pos, nullable, warnings);
+                }
+                check_expr(e, pos, nullable, warnings)
+            }
+            Path::Call(args) => {
+                for arg in args {
+                    check_expr(&arg.value, pos, nullable, warnings);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    match &var.value.body {
+        ValueBody::Expression(e) => check_expr(e, pos, nullable, warnings),
+        ValueBody::Array(values) => {
+            for e in values {
+                check_expr(e, pos, nullable, warnings);
+            }
+        }
+        ValueBody::Dictionary(defs) => {
+            for def in defs {
+                let (DictDef::Def((_, e)) | DictDef::Extract(e)) = def;
+                check_expr(e, pos, nullable, warnings)
+            }
+        }
+        // trigger functions run right where they are
+        ValueBody::CmpStmt(c) => check_block(&c.statements, &mut nullable.clone(), warnings),
+        // macros run later, when the variables could have been checked already
+        ValueBody::Macro(m) => check_block(&m.body.statements, &mut Nullable::new(), warnings),
+        _ => (),
+    }
+}
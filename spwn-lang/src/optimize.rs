@@ -2,7 +2,8 @@ use crate::ast::ObjectMode;
 use crate::builtin::{Block, Group, Id, Item};
 use crate::compiler_types::FunctionId;
 use crate::icalgebra::{
-    build_ic_connections, build_instant_count_network, get_all_ic_connections, IcExpr,
+    build_ic_connections, build_instant_count_network, get_all_ic_connections, remove_dead_stores,
+    IcExpr,
 };
 use crate::levelstring::{GdObj, ObjParam};
 use std::collections::{HashMap, HashSet};
@@ -49,7 +50,7 @@ pub struct TriggerGang {
 }
 
 impl TriggerGang {
-    fn new(triggers: Vec<Trigger>) -> Self {
+    pub fn new(triggers: Vec<Trigger>) -> Self {
         TriggerGang {
             triggers,
             connections_in: 0,
@@ -69,6 +70,25 @@ pub struct Trigger {
 
 pub struct Triggerlist<'a> {
     list: &'a mut Vec<FunctionId>,
+    // (unique id of the object, what happened to it)
+    remarks: Vec<(usize, String)>,
+    fps: f64,
+}
+
+impl<'a> Triggerlist<'a> {
+    pub fn new(list: &'a mut Vec<FunctionId>, fps: f64) -> Self {
+        Triggerlist {
+            list,
+            remarks: Vec::new(),
+            fps,
+        }
+    }
+
+    // explains what the optimizer did to a trigger (shown with `spwn build --remarks`)
+    pub fn remark(&mut self, trigger: Trigger, message: String) {
+        let unique_id = self[trigger.obj].0.unique_id;
+        self.remarks.push((unique_id, message));
+    }
 }
 
 impl<'a> std::ops::Index<ObjPtr> for Triggerlist<'a> {
@@ -88,7 +108,25 @@ const NO_GROUP: Group = Group {
     id: Id::Specific(0),
 };
 
-pub fn optimize(mut obj_in: Vec<FunctionId>, mut closed_group: u16) -> Vec<FunctionId> {
+// level_objects are the objects that aren't in a trigger function (like item displays),
+// which aren't optimized but can still read items
+pub fn optimize(
+    obj_in: Vec<FunctionId>,
+    closed_group: u16,
+    fps: f64,
+    level_objects: &[GdObj],
+) -> Vec<FunctionId> {
+    optimize_with_remarks(obj_in, closed_group, fps, level_objects).0
+}
+
+// also gives back what the optimizer did (or couldn't do) to each trigger, by unique id
+pub fn optimize_with_remarks(
+    mut obj_in: Vec<FunctionId>,
+    mut closed_group: u16,
+    fps: f64,
+    level_objects: &[GdObj],
+) -> (Vec<FunctionId>, Vec<(usize, String)>) {
+    let peephole_remarks = crate::peephole::peephole(&mut obj_in);
     let mut network = TriggerNetwork::new();
 
     // sort all triggers by their group
@@ -128,7 +166,18 @@ pub fn optimize(mut obj_in: Vec<FunctionId>, mut closed_group: u16) -> Vec<Funct
     //optimize
     //optimize_network(&mut network);
 
-    let mut objects = Triggerlist { list: &mut obj_in };
+    // output triggers that are in the level before optimizing (unique id, object id)
+    let outputs: Vec<(usize, Option<ObjParam>)> = network
+        .values()
+        .flat_map(|gang| gang.triggers.iter())
+        .filter(|t| t.role == TriggerRole::Output)
+        .map(|t| {
+            let obj = &obj_in[t.obj.0].obj_list[t.obj.1].0;
+            (obj.unique_id, obj.params.get(&1).cloned())
+        })
+        .collect();
+
+    let mut objects = Triggerlist::new(&mut obj_in, fps);
 
     clean_network(&mut network, &objects, true);
 
@@ -143,6 +192,8 @@ pub fn optimize(mut obj_in: Vec<FunctionId>, mut closed_group: u16) -> Vec<Funct
 
     clean_network(&mut network, &objects, false);
 
+    remove_dead_stores(&mut network, &mut objects, level_objects);
+
     instant_count_optimization(&mut network, &mut objects, &mut closed_group);
 
     // //cleanup
@@ -157,7 +208,25 @@ pub fn optimize(mut obj_in: Vec<FunctionId>, mut closed_group: u16) -> Vec<Funct
 
     //instant_count_optimization(&mut network, &mut objects, &mut closed_group);
 
-    rebuild(&network, &obj_in)
+    let mut remarks = peephole_remarks;
+    remarks.extend(std::mem::take(&mut objects.remarks));
+    let out = rebuild(&network, &obj_in);
+
+    for (unique_id, obj_id) in outputs {
+        let kept = out.iter().any(|f| {
+            f.obj_list
+                .iter()
+                .any(|(obj, _)| obj.unique_id == unique_id && obj.params.get(&1) == obj_id.as_ref())
+        });
+        if !kept && !remarks.iter().any(|(id, _)| *id == unique_id) {
+            remarks.push((
+                unique_id,
+                "removed: this trigger can never be activated".to_string(),
+            ));
+        }
+    }
+
+    (out, remarks)
 }
 
 fn spawn_and_dead_code_optimization(
@@ -239,7 +308,7 @@ fn instant_count_optimization(
     objects: &mut Triggerlist,
     closed_group: &mut u16,
 ) {
-    let c = get_all_ic_connections(network, &objects);
+    let c = get_all_ic_connections(network, objects);
     build_ic_connections(network, objects, closed_group, c);
 }
 
@@ -312,9 +381,27 @@ pub fn connect(
         for trigger in &mut (*network.get_mut(&to).unwrap()).triggers {
             (*trigger).optimized = true;
         }
+        objects.remark(
+            reference_trigger,
+            "spawn chain merged into the group that activates it".to_string(),
+        );
 
     //continue;
     } else {
+        objects.remark(
+            reference_trigger,
+            if delay != 0 {
+                format!(
+                    "could not merge spawn chain: it has a delay of {}s",
+                    delay as f64 / 1000.0
+                )
+            } else {
+                format!(
+                    "could not merge spawn chain: the target group is activated from {} places",
+                    network[&to].connections_in
+                )
+            },
+        );
         create_spawn_trigger(
             reference_trigger,
             to,
@@ -414,7 +501,7 @@ fn get_targets<'a>(
         Some(ObjParam::Number(n)) => (*n * 1000.0) as u32,
         Some(ObjParam::Epsilon) => {
             if delay == 0 {
-                50
+                (crate::timing::min_delay(objects.fps) * 1000.0) as u32
             } else {
                 0
             }
@@ -750,7 +837,7 @@ fn fix_read_write_order(
                     *trigger,
                     new_group,
                     current_group,
-                    0.05,
+                    crate::timing::min_delay(objects.fps),
                     objects,
                     &mut new_network,
                     (false, true),
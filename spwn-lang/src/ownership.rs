@@ -0,0 +1,155 @@
+// which objects in a level spwn added (the ones in its signature group) and which were
+// placed by hand, so building again only ever removes spwn's own objects
+// with --paranoid, the hand-placed objects are compared before and after writing the level
+use crate::builtin::Id;
+use crate::levelstring::SPWN_SIGNATURE_GROUP;
+use std::collections::HashMap;
+
+pub fn signature() -> String {
+    match SPWN_SIGNATURE_GROUP.id {
+        Id::Specific(n) => n.to_string(),
+        _ => unreachable!(),
+    }
+}
+
+// the level settings come first, then the objects
+fn split_level(ls: &str) -> (&str, impl Iterator<Item = &str>) {
+    let mut parts = ls.split(';');
+    let settings = parts.next().unwrap_or("");
+    (settings, parts.filter(|obj| !obj.is_empty()))
+}
+
+pub fn properties(obj: &str) -> Vec<(&str, &str)> {
+    let key_val: Vec<&str> = obj.split(',').collect();
+    key_val
+        .chunks(2)
+        .map(|kv| (kv[0], kv.get(1).copied().unwrap_or("")))
+        .collect()
+}
+
+pub fn is_spwn_object(obj: &str) -> bool {
+    let spwn_group = signature();
+    properties(obj)
+        .iter()
+        .any(|(key, groups)| *key == "57" && groups.split('.').any(|g| g == spwn_group))
+}
+
+// the level without the objects spwn added last time
+pub fn without_spwn_objects(ls: &str) -> String {
+    if ls.is_empty() {
+        return String::new();
+    }
+    let (settings, objects) = split_level(ls);
+    let mut out = settings.to_string() + ";";
+    for obj in objects.filter(|obj| !is_spwn_object(obj)) {
+        out += obj;
+        out += ";";
+    }
+    out
+}
+
+// the hand-placed objects, with their properties sorted (the game doesn't keep them in
+// the same order when it saves the level)
+fn hand_placed(ls: &str) -> Vec<String> {
+    let (_, objects) = split_level(ls);
+    let mut out: Vec<String> = objects
+        .filter(|obj| !is_spwn_object(obj))
+        .map(|obj| {
+            let mut props = properties(obj);
+            props.sort_unstable();
+            props
+                .iter()
+                .map(|(k, v)| format!("{},{}", k, v))
+                .collect::<Vec<_>>()
+                .join(",")
+        })
+        .collect();
+    out.sort_unstable();
+    out
+}
+
+// how many hand-placed objects are in each group
+pub fn hand_placed_groups(ls: &str) -> HashMap<u16, usize> {
+    let (_, objects) = split_level(ls);
+    let mut out = HashMap::new();
+    for obj in objects.filter(|obj| !is_spwn_object(obj)) {
+        for (key, groups) in properties(obj) {
+            if key == "57" {
+                for g in groups.split('.').filter_map(|g| g.parse().ok()) {
+                    *out.entry(g).or_insert(0) += 1;
+                }
+            }
+        }
+    }
+    out
+}
+
+// makes sure every hand-placed object in the level from before is still there, unchanged,
+// and that no new ones showed up
+pub fn check_unchanged(before: &str, after: &str) -> Result<(), String> {
+    let before = hand_placed(before);
+    let after = hand_placed(after);
+    if before == after {
+        return Ok(());
+    }
+    let mut missing = Vec::new();
+    let mut added = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < before.len() || j < after.len() {
+        match (before.get(i), after.get(j)) {
+            (Some(a), Some(b)) if a == b => {
+                i += 1;
+                j += 1;
+            }
+            (Some(a), Some(b)) if a < b => {
+                missing.push(a);
+                i += 1;
+            }
+            (Some(a), None) => {
+                missing.push(a);
+                i += 1;
+            }
+            (_, Some(b)) => {
+                added.push(b);
+                j += 1;
+            }
+            (None, None) => unreachable!(),
+        }
+    }
+    let mut message = format!(
+        "{} hand-placed objects would be removed or changed, and {} would be added",
+        missing.len(),
+        added.len()
+    );
+    if let Some(obj) = missing.first() {
+        message += &format!("\nFirst one removed or changed: {}", obj);
+    }
+    if let Some(obj) = added.first() {
+        message += &format!("\nFirst one added: {}", obj);
+    }
+    Err(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LEVEL: &str =
+        "kS38,1,kA2,0;1,1,2,15,3,15;1,1,2,45,57,1001;1,901,2,0,57,3.1001,108,1;1,2,2,30,57,1001.2;";
+
+    #[test]
+    fn only_spwn_objects_are_removed() {
+        assert_eq!(without_spwn_objects(LEVEL), "kS38,1,kA2,0;1,1,2,15,3,15;");
+        assert_eq!(without_spwn_objects(""), "");
+    }
+
+    #[test]
+    fn hand_placed_objects_are_compared() {
+        let rebuilt = without_spwn_objects(LEVEL) + "1,1,2,90,57,1001;";
+        assert_eq!(check_unchanged(LEVEL, &rebuilt), Ok(()));
+        // the game saving the properties in another order doesn't count
+        assert_eq!(check_unchanged(LEVEL, "kS38,1;3,15,2,15,1,1;"), Ok(()));
+        assert!(check_unchanged(LEVEL, "kS38,1;1,1,2,16,3,15;").is_err());
+        assert!(check_unchanged(LEVEL, "kS38,1;").is_err());
+    }
+}
@@ -0,0 +1,335 @@
+// spwn install: fetches the packages in [dependencies] (and the ones in their spwn.toml)
+// into the libraries folder of the project, and writes the versions it chose to spwn.lock
+// so builds use the same ones. a version is a git tag like v1.2.0 or 1.2.0, and the
+// version in spwn.toml says which ones fit:
+//  - "1.2" or "^1.2": 1.2.0 and newer, but not 2.0.0 (for 0.x, not 0.(x+1).0)
+//  - "~1.2": 1.2.x
+//  - "=1.2.0": only that one
+//  - "*": any (the default)
+// every package is fetched once, so when two packages need versions of the same one that
+// don't fit together (or get it from different places), installing stops with an error
+use crate::project::{self, Dependency, Project, PACKAGE_FOLDER};
+
+use std::collections::{HashMap, VecDeque};
+use std::path::{Component, Path, PathBuf};
+use std::process::Command;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Version(u64, u64, u64);
+
+impl std::fmt::Display for Version {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.0, self.1, self.2)
+    }
+}
+
+// 1, 1.2 and 1.2.3 (with or without a v in front)
+pub fn parse_version(text: &str) -> Option<Version> {
+    let text = text.strip_prefix('v').unwrap_or(text);
+    let mut parts = text.split('.').map(|p| p.parse::<u64>());
+    let version = Version(
+        parts.next()?.ok()?,
+        parts.next().unwrap_or(Ok(0)).ok()?,
+        parts.next().unwrap_or(Ok(0)).ok()?,
+    );
+    match parts.next() {
+        Some(_) => None,
+        None => Some(version),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Requirement {
+    Any,
+    Exact(Version),
+    // the same minor version
+    Tilde(Version),
+    // nothing that breaks it
+    Caret(Version),
+}
+
+pub fn parse_requirement(text: &str) -> Result<Requirement, String> {
+    let text = text.trim();
+    let version = |v: &str| {
+        parse_version(v.trim()).ok_or_else(|| format!("`{}` isn't a version, like 1.2.0", text))
+    };
+    Ok(if text == "*" {
+        Requirement::Any
+    } else if let Some(v) = text.strip_prefix('=') {
+        Requirement::Exact(version(v)?)
+    } else if let Some(v) = text.strip_prefix('~') {
+        Requirement::Tilde(version(v)?)
+    } else {
+        Requirement::Caret(version(text.strip_prefix('^').unwrap_or(text))?)
+    })
+}
+
+impl Requirement {
+    pub fn matches(&self, v: Version) -> bool {
+        match *self {
+            Requirement::Any => true,
+            Requirement::Exact(r) => v == r,
+            Requirement::Tilde(r) => v >= r && (v.0, v.1) == (r.0, r.1),
+            Requirement::Caret(r) => {
+                v >= r
+                    && if r.0 > 0 {
+                        v.0 == r.0
+                    } else if r.1 > 0 {
+                        (v.0, v.1) == (0, r.1)
+                    } else {
+                        (v.0, v.1, v.2) == (0, 0, r.2)
+                    }
+            }
+        }
+    }
+}
+
+fn git(args: &[&str]) -> Result<String, String> {
+    let output = Command::new("git")
+        .args(args)
+        .output()
+        .map_err(|e| format!("Could not run git (is it installed?): {}", e))?;
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    } else {
+        Err(format!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+    }
+}
+
+// the versions a repository has tags for: (version, tag), newest first
+fn remote_versions(url: &str) -> Result<Vec<(Version, String)>, String> {
+    let mut versions: Vec<(Version, String)> = git(&["ls-remote", "--tags", "--refs", "--", url])?
+        .lines()
+        .filter_map(|line| line.split("refs/tags/").nth(1))
+        .filter_map(|tag| parse_version(tag).map(|v| (v, tag.to_string())))
+        .collect();
+    versions.sort_by(|a, b| b.cmp(a));
+    Ok(versions)
+}
+
+// a package is put in the folder with its name, so the name can't be a path that goes
+// somewhere else (like ../../x)
+fn check_name(name: &str, required_by: &str) -> Result<(), String> {
+    let mut components = Path::new(name).components();
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(_)), None) if !name.contains(['/', '\\']) => Ok(()),
+        _ => Err(format!(
+            "`{}` (needed by {}) can't be the name of a package, it has to be a folder name",
+            name, required_by
+        )),
+    }
+}
+
+// the git url of a package
+fn source(
+    name: &str,
+    git: &Option<String>,
+    registry: &Option<String>,
+    required_by: &str,
+) -> Result<String, String> {
+    let url = match (git, registry) {
+        (Some(url), _) => url.clone(),
+        (None, Some(registry)) => format!("{}/{}", registry, name),
+        (None, None) => return Err(format!(
+            "{} (needed by {}) has no git url, and there is no registry in [project] to get it from",
+            name, required_by
+        )),
+    };
+    // git would take it as an option
+    if url.starts_with('-') {
+        return Err(format!(
+            "{} (needed by {}) has the git url `{}`, which can't start with -",
+            name, required_by, url
+        ));
+    }
+    Ok(url)
+}
+
+struct Resolved {
+    git: String,
+    version: Version,
+    // who needs it, and which versions
+    required: Vec<(String, String)>,
+}
+
+// fetches the packages the project needs, and writes spwn.lock. returns the packages that
+// were fetched as (name, version), and leaves the ones that were already there
+pub fn install(project: &Project) -> Result<Vec<(String, Version)>, String> {
+    let folder = project.root.join(PACKAGE_FOLDER);
+    let locked = project::read_lock(&project.root)?;
+
+    let mut resolved = HashMap::<String, Resolved>::new();
+    let mut fetched = Vec::new();
+    // (name, git, version, needed by, registry)
+    let mut queue = VecDeque::new();
+    let requests = |project: &Project, by: &str| {
+        let mut names: Vec<_> = project.dependencies.iter().collect();
+        names.sort_by(|a, b| a.0.cmp(b.0));
+        names
+            .into_iter()
+            .filter_map(|(name, dependency)| match dependency {
+                Dependency::Package { git, version } => Some((
+                    name.clone(),
+                    git.clone(),
+                    version.clone(),
+                    by.to_string(),
+                    project.registry.clone(),
+                )),
+                Dependency::Path(_) => None,
+            })
+            .collect::<Vec<_>>()
+    };
+    queue.extend(requests(project, "spwn.toml"));
+
+    while let Some((name, git, version, by, registry)) = queue.pop_front() {
+        check_name(&name, &by)?;
+        let registry = registry.or_else(|| project.registry.clone());
+        let url = source(&name, &git, &registry, &by)?;
+        let requirement =
+            parse_requirement(&version).map_err(|e| format!("{} (for {}): {}", e, name, by))?;
+
+        if let Some(r) = resolved.get_mut(&name) {
+            if r.git != url {
+                return Err(format!(
+                    "{} is needed from two places: {} (by {}) and {} (by {})",
+                    name, r.git, r.required[0].0, url, by
+                ));
+            }
+            if !requirement.matches(r.version) {
+                let mut message = format!(
+                    "Version conflict for {}: {} needs {}, but {} was already chosen for",
+                    name, by, version, r.version
+                );
+                for (who, wanted) in &r.required {
+                    message += &format!("\n    {} (which needs {})", who, wanted);
+                }
+                return Err(message);
+            }
+            r.required.push((by, version));
+            continue;
+        }
+
+        // the locked version is kept if it still fits
+        let (chosen, tag) = match locked.get(&name) {
+            Some((git, v))
+                if *git == url
+                    && matches!(parse_version(v), Some(v) if requirement.matches(v))
+                    && folder.join(&name).is_dir() =>
+            {
+                (parse_version(v).unwrap(), None)
+            }
+            _ => {
+                let versions = remote_versions(&url)?;
+                match versions.into_iter().find(|(v, _)| requirement.matches(*v)) {
+                    Some((v, tag)) => (v, Some(tag)),
+                    None => {
+                        return Err(format!(
+                            "{} has no version that fits {} (needed by {})",
+                            name, version, by
+                        ))
+                    }
+                }
+            }
+        };
+
+        let target = folder.join(&name);
+        if let Some(tag) = &tag {
+            fetch(&url, tag, &target)?;
+            fetched.push((name.clone(), chosen));
+        }
+
+        // and the packages it needs
+        if let Some(package) = Project::find(&target)? {
+            if package.root.canonicalize().ok() == target.canonicalize().ok() {
+                queue.extend(requests(&package, &format!("{} {}", name, chosen)));
+            }
+        }
+        resolved.insert(
+            name,
+            Resolved {
+                git: url,
+                version: chosen,
+                required: vec![(by, version)],
+            },
+        );
+    }
+
+    let mut lock: Vec<(String, String, String)> = resolved
+        .into_iter()
+        .map(|(name, r)| (name, r.git, r.version.to_string()))
+        .collect();
+    lock.sort();
+    project::write_lock(&project.root, &lock)?;
+    Ok(fetched)
+}
+
+// clones the tag into the folder (replacing what was there)
+fn fetch(url: &str, tag: &str, target: &Path) -> Result<(), String> {
+    let parent = target.parent().map(PathBuf::from).unwrap_or_default();
+    std::fs::create_dir_all(&parent)
+        .map_err(|e| format!("Could not make {}: {}", parent.to_string_lossy(), e))?;
+    let temporary = parent.join(format!(
+        ".{}-fetching",
+        target.file_name().unwrap_or_default().to_string_lossy()
+    ));
+    let _ = std::fs::remove_dir_all(&temporary);
+    git(&[
+        "clone",
+        "--quiet",
+        "--depth",
+        "1",
+        "--branch",
+        tag,
+        "--",
+        url,
+        &temporary.to_string_lossy(),
+    ])?;
+    let _ = std::fs::remove_dir_all(temporary.join(".git"));
+    if target.exists() {
+        std::fs::remove_dir_all(target)
+            .map_err(|e| format!("Could not replace {}: {}", target.to_string_lossy(), e))?;
+    }
+    std::fs::rename(&temporary, target)
+        .map_err(|e| format!("Could not move to {}: {}", target.to_string_lossy(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requirements_match_versions() {
+        let v = |s| parse_version(s).unwrap();
+        let r = |s| parse_requirement(s).unwrap();
+        assert_eq!(v("v1.2"), Version(1, 2, 0));
+        assert_eq!(parse_version("1.2.x"), None);
+
+        assert!(r("1.2").matches(v("1.9.1")));
+        assert!(!r("^1.2").matches(v("2.0.0")));
+        assert!(!r("^1.2").matches(v("1.1.9")));
+        assert!(r("^0.3").matches(v("0.3.4")));
+        assert!(!r("^0.3").matches(v("0.4.0")));
+        assert!(r("~1.2").matches(v("1.2.7")));
+        assert!(!r("~1.2").matches(v("1.3.0")));
+        assert!(r("=1.2.0").matches(v("1.2.0")));
+        assert!(!r("=1.2.0").matches(v("1.2.1")));
+        assert!(r("*").matches(v("0.0.1")));
+        assert!(parse_requirement("latest").is_err());
+    }
+
+    #[test]
+    fn names_and_urls_are_checked() {
+        assert!(check_name("std-extras", "spwn.toml").is_ok());
+        for name in &["../../x", "..", ".", "a/b", "a\\b", "/x", ""] {
+            assert!(check_name(name, "spwn.toml").is_err(), "{}", name);
+        }
+        let url = |s: &str| Some(s.to_string());
+        assert!(source("a", &url("https://example.com/a"), &None, "spwn.toml").is_ok());
+        assert!(source("a", &url("--upload-pack=x"), &None, "spwn.toml").is_err());
+        assert!(source("a", &None, &url("-x"), "spwn.toml").is_err());
+    }
+}
@@ -5,7 +5,7 @@ use pest_derive::Parser;*/
 
 use crate::builtin::BUILTIN_LIST;
 
-//use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::path::PathBuf;
 
 //use ast::ValueLiteral;
@@ -74,33 +74,35 @@ pub fn is_valid_symbol(name: &str, tokens: &Tokens, notes: &ParseNotes) -> Resul
     }
 }
 
-impl fmt::Display for SyntaxError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        //write!(f, "SuperErrorSideKick is here!")
+impl SyntaxError {
+    // the message without where the error is
+    pub fn message(&self) -> String {
         match self {
             SyntaxError::ExpectedErr {
-                expected,
-                found,
-                pos,
-                file,
-            } => {
-                print_error_intro(*pos, file);
-                write!(f, "SyntaxError: Expected {}, found {}", expected, found)
-            }
-
-            SyntaxError::UnexpectedErr { found, pos, file } => {
-                print_error_intro(*pos, file);
-                write!(f, "SyntaxError: Unexpected {}", found)
-            }
+                expected, found, ..
+            } => format!("SyntaxError: Expected {}, found {}", expected, found),
+            SyntaxError::UnexpectedErr { found, .. } => format!("SyntaxError: Unexpected {}", found),
+            SyntaxError::SyntaxError { message, .. } => format!("SyntaxError: {}", message),
+        }
+    }
 
-            SyntaxError::SyntaxError { message, pos, file } => {
-                print_error_intro(*pos, file);
-                write!(f, "SyntaxError: {}", message)
-            }
+    pub fn location(&self) -> (&PathBuf, FileRange) {
+        match self {
+            SyntaxError::ExpectedErr { pos, file, .. }
+            | SyntaxError::UnexpectedErr { pos, file, .. }
+            | SyntaxError::SyntaxError { pos, file, .. } => (file, *pos),
         }
     }
 }
 
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (file, pos) = self.location();
+        print_error_intro(pos, file);
+        write!(f, "{}", self.message())
+    }
+}
+
 impl Error for SyntaxError {
     fn source(&self) -> Option<&(dyn Error + 'static)> {
         None
@@ -282,6 +284,15 @@ pub enum Token {
     #[token("throw")]
     ErrorStatement,
 
+    #[token("try")]
+    Try,
+
+    #[token("catch")]
+    Catch,
+
+    #[token("defer")]
+    Defer,
+
     #[token("if")]
     If,
 
@@ -334,8 +345,12 @@ pub enum Token {
     #[regex(r"[\n\r;]+")]
     StatementSeparator,
 
+    // never reaches the parser, see `Tokens::inner_next`
+    #[regex(r"/\*[^*]*\*(([^/\*][^\*]*)?\*)*/|//[^\n]*")]
+    Comment,
+
     #[error]
-    #[regex(r"[ \t\f]+|/\*[^*]*\*(([^/\*][^\*]*)?\*)*/|//[^\n]*", logos::skip)]
+    #[regex(r"[ \t\f]+", logos::skip)]
     Error,
 }
 
@@ -361,11 +376,12 @@ impl Token {
                 "reserved keyword (not currently in use, but may be used in future updates)"
             }
 
-            Return | Implement | For | In | ErrorStatement | If | Else | Object | Trigger
-            | Import | Extract | Null | Type | Let | SelfVal | Break | Continue | Switch | Case => {
+            Return | Implement | For | In | ErrorStatement | Try | Catch | Defer | If | Else | Object
+            | Trigger | Import | Extract | Null | Type | Let | SelfVal | Break | Continue
+            | Switch | Case => {
                 "keyword"
             }
-            //Comment | MultiCommentStart | MultiCommentEnd => "comment",
+            Comment => "comment",
             StatementSeparator => "statement separator",
             Error => "unknown",
         }
@@ -375,6 +391,8 @@ impl Token {
 pub struct ParseNotes {
     pub tag: ast::Attribute,
     pub file: PathBuf,
+    // errors the parser has recovered from
+    pub errors: Vec<SyntaxError>,
 }
 
 impl ParseNotes {
@@ -382,6 +400,7 @@ impl ParseNotes {
         ParseNotes {
             tag: ast::Attribute::new(),
             file: path,
+            errors: Vec::new(),
         }
     }
 }
@@ -393,6 +412,10 @@ pub struct Tokens<'a> {
     line_breaks: Vec<u32>,
     //index 0 = element of iter / last element in stack
     index: usize,
+    // comments are taken out of the token stream and attached to the ast afterwards
+    comments: Vec<(String, core::ops::Range<usize>)>,
+    // keyword aliases from spwn.toml (see aliases.rs)
+    aliases: HashMap<String, (Token, String)>,
 }
 
 impl<'a> Tokens<'a> {
@@ -402,15 +425,28 @@ impl<'a> Tokens<'a> {
             stack: Vec::new(),
             line_breaks: vec![0],
             index: 0,
+            comments: Vec::new(),
+            aliases: HashMap::new(),
         }
     }
 
     fn inner_next(&mut self) -> Option<Token> {
         if self.index == 0 {
-            let next_elem = self.iter.next();
+            let mut next_elem = self.iter.next();
+            while next_elem == Some(Token::Comment) {
+                self.comments
+                    .push((self.iter.slice().to_string(), self.iter.span()));
+                next_elem = self.iter.next();
+            }
 
-            let slice = self.iter.slice().to_string();
+            let mut slice = self.iter.slice().to_string();
             let range = self.iter.span();
+            if next_elem == Some(Token::Symbol) {
+                if let Some((token, word)) = self.aliases.get(&slice) {
+                    next_elem = Some(*token);
+                    slice = word.clone();
+                }
+            }
 
             self.stack.push((next_elem, slice, range));
             next_elem
@@ -505,51 +541,49 @@ impl<'a> Tokens<'a> {
         self.stack[self.stack.len() - self.index - 1].1.clone()
     }
 
+    // number of tokens before the current position, used to go back to a known point
+    fn consumed(&self) -> usize {
+        self.stack.len() - self.index
+    }
+
+    fn rewind_to(&mut self, consumed: usize) {
+        self.index = self.stack.len() - consumed;
+    }
+
+    fn span(&self) -> core::ops::Range<usize> {
+        self.stack[self.stack.len() - self.index - 1].2.clone()
+    }
+
     fn position(&self) -> ((usize, usize), (usize, usize)) {
         if self.stack.len() - self.index == 0 {
             return ((1, 0), (1, 0));
         }
-        let file_pos1 = self.stack[self.stack.len() - self.index - 1].2.start;
-        let file_pos2 = self.stack[self.stack.len() - self.index - 1].2.end;
-        /*println!(
-            "file pos: {}, line breaks: {:?}",
-            file_pos, self.line_breaks
-        );*/
-        let mut found_pos_1 = false;
-        let mut found_pos_2 = false;
-        let mut out = ((1, file_pos1), (1, file_pos2));
+        let range = &self.stack[self.stack.len() - self.index - 1].2;
+        (self.file_pos(range.start), self.file_pos(range.end))
+    }
 
+    // (line, column) of a byte offset in the file
+    fn file_pos(&self, offset: usize) -> (usize, usize) {
         for (i, lb) in self.line_breaks.iter().enumerate() {
-            let line_break = *lb as usize;
-            if !found_pos_1 && line_break >= file_pos1 {
-                if i == 0 {
-                    out.0 = (1, file_pos1);
-                } else {
-                    out.0 = (i + 1, file_pos1 - self.line_breaks[i - 1] as usize - 1);
-                }
-                found_pos_1 = true;
-            }
-
-            if !found_pos_2 && line_break >= file_pos2 {
-                if i == 0 {
-                    out.1 = (1, file_pos2);
+            if *lb as usize >= offset {
+                return if i == 0 {
+                    (1, offset)
                 } else {
-                    out.1 = (i + 1, file_pos2 - self.line_breaks[i - 1] as usize - 1);
-                }
-                found_pos_2 = true;
+                    (i + 1, offset - self.line_breaks[i - 1] as usize - 1)
+                };
             }
         }
-
-        out
+        // after the last line break (the end of a file that ends with one)
+        match self.line_breaks.last() {
+            Some(lb) => (self.line_breaks.len() + 1, offset - *lb as usize - 1),
+            None => (1, offset),
+        }
     }
 
     /*fn abs_position(&self) -> usize {
         self.stack[self.stack.len() - self.index - 1].2.start
     }*/
 
-    /*fn span(&self) -> core::ops::Range<usize> {
-        self.stack[self.stack.len() - self.index - 1].2.clone()
-    }*/
 }
 
 //type TokenList = Peekable<Lexer<Token>>;
@@ -557,14 +591,40 @@ impl<'a> Tokens<'a> {
 const STATEMENT_SEPARATOR_DESC: &str = "Statement separator (line-break or ';')";
 
 pub fn parse_spwn(
-    mut unparsed: String,
+    unparsed: String,
     path: PathBuf,
 ) -> Result<(Vec<ast::Statement>, ParseNotes), SyntaxError> {
-    unparsed = unparsed.replace("\r\n", "\n");
+    let (statements, notes, mut errors) = parse_spwn_partial(unparsed, path);
+    if errors.is_empty() {
+        Ok((statements, notes))
+    } else {
+        Err(errors.remove(0))
+    }
+}
+
+// parses as much as possible instead of stopping at the first error.
+// the parts that couldn't be parsed become `ErrorStatement`/`ErrorExpr` nodes,
+// so tools like the formatter can still work on files that are being edited
+pub fn parse_spwn_partial(
+    unparsed: String,
+    path: PathBuf,
+) -> (Vec<ast::Statement>, ParseNotes, Vec<SyntaxError>) {
+    parse_spwn_partial_with(unparsed, path, None)
+}
+
+// same as parse_spwn_partial, with the [aliases] of the project the file is in
+pub fn parse_spwn_partial_with(
+    mut unparsed: String,
+    path: PathBuf,
+    aliases: Option<&crate::aliases::Aliases>,
+) -> (Vec<ast::Statement>, ParseNotes, Vec<SyntaxError>) {
+    // windows editors often save with a byte order mark
+    unparsed = unparsed.trim_start_matches('\u{feff}').replace("\r\n", "\n");
 
     let tokens_iter = Token::lexer(&unparsed);
 
     let mut tokens = Tokens::new(tokens_iter);
+    tokens.aliases = crate::aliases::for_file(aliases, &path);
 
     let mut statements = Vec::<ast::Statement>::new();
 
@@ -581,8 +641,27 @@ pub fn parse_spwn(
 
     tokens.line_breaks = line_breaks;
 
-    let start_tag = check_for_tag(&mut tokens, &mut notes)?;
-    notes.tag = start_tag;
+    let blank_lines: HashSet<usize> = unparsed
+        .lines()
+        .enumerate()
+        .filter(|(_, l)| l.trim().is_empty())
+        .map(|(i, _)| i + 1)
+        .collect();
+    // lines that close a block, like `} else {`
+    let closing_lines: HashSet<usize> = unparsed
+        .lines()
+        .enumerate()
+        .filter(|(_, l)| l.trim_start().starts_with('}'))
+        .map(|(i, _)| i + 1)
+        .collect();
+
+    match check_for_tag(&mut tokens, &mut notes) {
+        Ok(start_tag) => notes.tag = start_tag,
+        Err(e) => {
+            notes.errors.push(e);
+            statements.push(recover_statement(&mut tokens, 0, false));
+        }
+    }
     loop {
         //+ do something if we have tokens. if no more tokens, leave loop
         match tokens.next(false) {
@@ -591,13 +670,14 @@ pub fn parse_spwn(
                 tokens.previous_no_ignore(false); //bring tokens back to original
 
                 //+ we are going to parse the tokens
-                let parsed = parse_statement(&mut tokens, &mut notes)?;
-                // if parsed.comment.0 == None && !statements.is_empty() {
-                //     parsed.comment.0 = statements.last().unwrap().comment.1.clone();
-                //     (*statements.last_mut().unwrap()).comment.1 = None;
-                // }
-
-                statements.push(parsed)
+                let start = tokens.consumed();
+                match parse_statement(&mut tokens, &mut notes) {
+                    Ok(parsed) => statements.push(parsed),
+                    Err(e) => {
+                        notes.errors.push(e);
+                        statements.push(recover_statement(&mut tokens, start, false));
+                    }
+                }
             }
             None => break, //+ no more tokens, probably end of file
         }
@@ -606,18 +686,211 @@ pub fn parse_spwn(
         match tokens.next(true) {
             Some(Token::StatementSeparator) => {}
             Some(a) => {
-                return Err(SyntaxError::ExpectedErr {
+                notes.errors.push(SyntaxError::ExpectedErr {
                     expected: STATEMENT_SEPARATOR_DESC.to_string(),
                     found: format!("{}: \"{}\"", a.typ(), tokens.slice()),
                     pos: tokens.position(),
-                    file: notes.file,
-                })
+                    file: notes.file.clone(),
+                });
+                tokens.previous_no_ignore(true);
+                let start = tokens.consumed();
+                statements.push(recover_statement(&mut tokens, start, false));
+                if tokens.next(true).is_none() {
+                    break;
+                }
             }
             None => break,
         }
     }
 
-    Ok((statements, notes))
+    let mut comments: VecDeque<(String, FileRange)> = tokens
+        .comments
+        .iter()
+        .map(|(text, range)| {
+            (
+                text.clone(),
+                (tokens.file_pos(range.start), tokens.file_pos(range.end)),
+            )
+        })
+        .collect();
+    attach_trivia(
+        &mut statements,
+        &mut comments,
+        None,
+        &blank_lines,
+        &closing_lines,
+    );
+
+    let errors = std::mem::take(&mut notes.errors);
+    (statements, notes, errors)
+}
+
+fn is_closing(t: Token) -> bool {
+    matches!(
+        t,
+        Token::ClosingBracket | Token::ClosingCurlyBracket | Token::ClosingSquareBracket
+    )
+}
+
+// skips tokens until `stop` returns true. `stop` gets the token, and how many curly
+// brackets and other brackets are currently open.
+// returns the source text and position of what was skipped
+fn skip_tokens(
+    tokens: &mut Tokens,
+    stop: impl Fn(Token, usize, usize) -> bool,
+) -> Option<(String, FileRange)> {
+    let mut curly_depth = 0;
+    let mut depth = 0;
+    let mut skipped: Option<core::ops::Range<usize>> = None;
+    let mut skipped_pos: Option<FileRange> = None;
+
+    while let Some(t) = tokens.next(true) {
+        if t == Token::StatementSeparator && skipped.is_none() {
+            // separators before the broken code
+            continue;
+        }
+        if stop(t, curly_depth, depth) {
+            tokens.previous_no_ignore(true);
+            break;
+        }
+        match t {
+            Token::StatementSeparator => continue,
+            Token::OpenCurlyBracket => curly_depth += 1,
+            Token::ClosingCurlyBracket if curly_depth > 0 => curly_depth -= 1,
+            Token::OpenBracket | Token::OpenSquareBracket => depth += 1,
+            Token::ClosingBracket | Token::ClosingSquareBracket if depth > 0 => depth -= 1,
+            _ => (),
+        }
+        let span = tokens.span();
+        let pos = tokens.position();
+        skipped = Some(match skipped {
+            Some(r) => r.start..span.end,
+            None => span,
+        });
+        skipped_pos = Some(match skipped_pos {
+            Some(p) => (p.0, pos.1),
+            None => pos,
+        });
+    }
+
+    let source: &str = tokens.iter.source();
+    skipped.map(|r| (source[r].to_string(), skipped_pos.unwrap()))
+}
+
+// goes back to where a failed statement started, and skips to its end
+// so the parser can continue with the next one
+fn recover_statement(tokens: &mut Tokens, start: usize, in_block: bool) -> ast::Statement {
+    tokens.rewind_to(start);
+
+    // only curly brackets are counted, an unclosed '(' shouldn't take the rest of the file with it
+    let (text, pos) = skip_tokens(tokens, |t, curly_depth, _| {
+        curly_depth == 0
+            && (t == Token::StatementSeparator || (in_block && t == Token::ClosingCurlyBracket))
+    })
+    .unwrap_or_else(|| (String::new(), tokens.position()));
+
+    ast::Statement {
+        body: ast::StatementBody::ErrorStatement(text),
+        arrow: false,
+        pos,
+        trivia: ast::Trivia::new(),
+        attributes: ast::Attribute::new(),
+    }
+}
+
+// skips the rest of a broken expression, up to where the surrounding
+// code (an argument list, a dictionary, etc.) can continue
+fn recover_expression(tokens: &mut Tokens, start: usize) -> ast::Variable {
+    tokens.rewind_to(start);
+
+    let (text, pos) = skip_tokens(tokens, |t, curly_depth, depth| {
+        curly_depth == 0
+            && (t == Token::StatementSeparator
+                || (depth == 0 && (t == Token::Comma || is_closing(t))))
+    })
+    .unwrap_or_else(|| (String::new(), tokens.position()));
+
+    ast::Variable {
+        operator: None,
+        value: ast::ValueLiteral::new(ast::ValueBody::ErrorExpr(text)),
+        path: Vec::new(),
+        pos,
+        tag: ast::Attribute::new(),
+    }
+}
+
+// gives every comment in the file a home in the ast. `end` is where the block ends,
+// anything after that belongs to the parent statement
+fn attach_trivia(
+    statements: &mut [ast::Statement],
+    comments: &mut VecDeque<(String, FileRange)>,
+    end: Option<(usize, usize)>,
+    blank_lines: &HashSet<usize>,
+    closing_lines: &HashSet<usize>,
+) {
+    let mut prev_end_line = None;
+    for statement in statements.iter_mut() {
+        let (start, stmt_end) = statement.pos;
+        let mut first_line = start.0;
+
+        let mut before = Vec::new();
+        while matches!(comments.front(), Some((_, (c_start, _))) if *c_start < start) {
+            let (text, (c_start, _)) = comments.pop_front().unwrap();
+            first_line = first_line.min(c_start.0);
+            before.push(text);
+        }
+
+        let mut blocks = statement.blocks_mut();
+        blocks.sort_by_key(|b| b.first().map(|s| s.pos.0));
+        let block_starts: Vec<_> = blocks
+            .iter()
+            .map(|b| b.first().map(|s| s.pos.0))
+            .collect();
+        for (i, block) in blocks.into_iter().enumerate() {
+            let block_end = block_starts[(i + 1)..]
+                .iter()
+                .flatten()
+                .next()
+                .copied()
+                .unwrap_or(stmt_end);
+            attach_trivia(
+                block,
+                comments,
+                Some(block_end),
+                blank_lines,
+                closing_lines,
+            );
+        }
+
+        // comments inside the statement that didn't fit in any block (e.g. in a dictionary)
+        while matches!(comments.front(), Some((_, (c_start, _))) if *c_start < stmt_end) {
+            before.push(comments.pop_front().unwrap().0);
+        }
+
+        statement.trivia.comments_before = before;
+
+        if matches!(comments.front(), Some((_, (c_start, _))) if c_start.0 == stmt_end.0) {
+            statement.trivia.comment_after = Some(comments.pop_front().unwrap().0);
+        }
+
+        if let Some(prev) = prev_end_line {
+            statement.trivia.blank_line_before =
+                ((prev + 1)..first_line).any(|l| blank_lines.contains(&l));
+        }
+        prev_end_line = Some(stmt_end.0);
+    }
+
+    // a comment between two blocks (like after `} else {`) belongs to the block that
+    // hasn't been closed yet where it is
+    let in_block = |c_start: &(usize, usize)| match end {
+        Some(e) => *c_start < e && (c_start.0..=e.0).any(|l| closing_lines.contains(&l)),
+        None => true,
+    };
+    if let Some(last) = statements.last_mut() {
+        while matches!(comments.front(), Some((_, c))  if in_block(&c.0)) {
+            last.trivia.comments_end.push(comments.pop_front().unwrap().0);
+        }
+    }
 }
 
 fn parse_cmp_stmt(
@@ -631,14 +904,14 @@ fn parse_cmp_stmt(
             Some(_) => {
                 tokens.previous_no_ignore(false);
 
-                let parsed = parse_statement(tokens, notes)?;
-                // if parsed.comment.0 == None && !statements.is_empty() {
-                //     parsed.comment.0 = statements.last().unwrap().comment.1.clone();
-                //     (*statements.last_mut().unwrap()).comment.1 = None;
-                // }
-
-                statements.push(parsed) // add to big statement list
-                                        //println!("statement done");
+                let start = tokens.consumed();
+                match parse_statement(tokens, notes) {
+                    Ok(parsed) => statements.push(parsed), // add to big statement list
+                    Err(e) => {
+                        notes.errors.push(e);
+                        statements.push(recover_statement(tokens, start, true));
+                    }
+                }
             }
             None => {
                 return Err(SyntaxError::SyntaxError {
@@ -652,6 +925,17 @@ fn parse_cmp_stmt(
         match tokens.next(true) {
             Some(Token::StatementSeparator) => {}
             Some(Token::ClosingCurlyBracket) => break,
+            Some(a) => {
+                notes.errors.push(SyntaxError::ExpectedErr {
+                    expected: STATEMENT_SEPARATOR_DESC.to_string(),
+                    found: format!("{}: \"{}\"", a.typ(), tokens.slice()),
+                    pos: tokens.position(),
+                    file: notes.file.clone(),
+                });
+                tokens.previous_no_ignore(true);
+                let start = tokens.consumed();
+                statements.push(recover_statement(tokens, start, true));
+            }
             a => expected!(STATEMENT_SEPARATOR_DESC.to_string(), tokens, notes, a),
         }
     }
@@ -659,6 +943,25 @@ fn parse_cmp_stmt(
     Ok(statements)
 }
 
+// whether the symbol that was just read is a label, like `outer: for ...`
+fn labels_loop(tokens: &mut Tokens) -> bool {
+    let start = tokens.consumed();
+    let found = tokens.next(false) == Some(Token::Colon) && tokens.next(false) == Some(Token::For);
+    tokens.rewind_to(start);
+    found
+}
+
+// the label after break or continue, on the same line
+fn loop_label(tokens: &mut Tokens) -> Option<String> {
+    match tokens.next(true) {
+        Some(Token::Symbol) => Some(tokens.slice()),
+        _ => {
+            tokens.previous();
+            None
+        }
+    }
+}
+
 pub fn parse_statement(
     tokens: &mut Tokens,
     notes: &mut ParseNotes,
@@ -671,6 +974,16 @@ pub fn parse_statement(
 
     let (start_pos, _) = tokens.position();
 
+    // any statement can have attributes, like #[deprecated] -> spawn_it()
+    // (the ones at the very start of a file are the file's)
+    let (mut attributes, first) = if first == Some(Token::Hash) {
+        tokens.previous_no_ignore(false);
+        let attributes = check_for_tag(tokens, notes)?;
+        (attributes, tokens.next(false))
+    } else {
+        (ast::Attribute::new(), first)
+    };
+
     let mut arrow = false;
     let body = match first {
         // ooh what type of token is it
@@ -690,6 +1003,7 @@ pub fn parse_statement(
             let rest_of_statement = parse_statement(tokens, notes)?; // recursion moment
 
             arrow = true;
+            attributes.tags.extend(rest_of_statement.attributes.tags);
             rest_of_statement.body
 
             /* Summary:
@@ -731,8 +1045,8 @@ pub fn parse_statement(
             */
         }
 
-        Some(Token::Break) => ast::StatementBody::Break, // its just break
-        Some(Token::Continue) => ast::StatementBody::Continue,
+        Some(Token::Break) => ast::StatementBody::Break(loop_label(tokens)),
+        Some(Token::Continue) => ast::StatementBody::Continue(loop_label(tokens)),
 
         Some(Token::If) => {
             //parse if statement
@@ -843,6 +1157,7 @@ pub fn parse_statement(
             let body = parse_cmp_stmt(tokens, notes)?; // parse whats in the for loop
 
             ast::StatementBody::For(ast::For {
+                label: None,
                 symbol,
                 array,
                 body,
@@ -867,6 +1182,47 @@ pub fn parse_statement(
             //i dont think a summary is needed for this
         }
 
+        Some(Token::Defer) => {
+            match tokens.next(false) {
+                Some(Token::OpenCurlyBracket) => (),
+                a => expected!("'{'".to_string(), tokens, notes, a),
+            }
+            ast::StatementBody::Defer(parse_cmp_stmt(tokens, notes)?)
+        }
+
+        Some(Token::Try) => {
+            match tokens.next(false) {
+                Some(Token::OpenCurlyBracket) => (),
+                a => expected!("'{'".to_string(), tokens, notes, a),
+            }
+            let try_body = parse_cmp_stmt(tokens, notes)?;
+
+            match tokens.next(false) {
+                Some(Token::Catch) => (),
+                a => expected!("'catch'".to_string(), tokens, notes, a),
+            }
+            // catch { ... } or catch err { ... }
+            let catch_symbol = match tokens.next(false) {
+                Some(Token::Symbol) => {
+                    let symbol = tokens.slice();
+                    match tokens.next(false) {
+                        Some(Token::OpenCurlyBracket) => (),
+                        a => expected!("'{'".to_string(), tokens, notes, a),
+                    }
+                    Some(symbol)
+                }
+                Some(Token::OpenCurlyBracket) => None,
+                a => expected!("error variable name or '{'".to_string(), tokens, notes, a),
+            };
+            let catch_body = parse_cmp_stmt(tokens, notes)?;
+
+            ast::StatementBody::Try(ast::Try {
+                try_body,
+                catch_symbol,
+                catch_body,
+            })
+        }
+
         Some(Token::Type) => {
             // defining a new type
             match tokens.next(false) {
@@ -922,6 +1278,19 @@ pub fn parse_statement(
             // too basic to have a summary,
         }
 
+        Some(Token::Symbol) if labels_loop(tokens) => {
+            // `label: for ...`
+            let label = tokens.slice();
+            tokens.next(false); // the colon
+            match parse_statement(tokens, notes)?.body {
+                ast::StatementBody::For(f) => ast::StatementBody::For(ast::For {
+                    label: Some(label),
+                    ..f
+                }),
+                _ => unreachable!(),
+            }
+        }
+
         Some(_) => {
             //either expression, call or definition, FIGURE OUT
             //parse it
@@ -974,7 +1343,8 @@ pub fn parse_statement(
         body,
         arrow,
         pos: (start_pos, end_pos),
-        // comment: (preceding_comment, comment_after),
+        trivia: ast::Trivia::new(),
+        attributes,
     })
 }
 
@@ -1126,12 +1496,17 @@ fn parse_cases(tokens: &mut Tokens, notes: &mut ParseNotes) -> Result<Vec<ast::C
                         file: notes.file.clone(),
                     });
                 }
-                let val = parse_expr(tokens, notes, false, true)?;
+                let mut values = vec![parse_expr(tokens, notes, false, true)?];
+                // case 1, 3, 5:
+                while tokens.next(false) == Some(Token::Comma) {
+                    values.push(parse_expr(tokens, notes, false, true)?);
+                }
+                tokens.previous();
                 match tokens.next(false) {
                     Some(Token::Colon) => {
                         let expr = parse_expr(tokens, notes, false, true)?; // parse whats after the :
                         cases.push(ast::Case {
-                            typ: ast::CaseType::Value(val),
+                            typ: ast::CaseType::Value(values),
                             body: expr,
                         });
 
@@ -1215,7 +1590,14 @@ fn parse_expr(
             };
 
             operators.push(op);
-            values.push(parse_variable(tokens, notes, allow_macro_def)?);
+            let start = tokens.consumed();
+            match parse_variable(tokens, notes, allow_macro_def) {
+                Ok(val) => values.push(val),
+                Err(e) => {
+                    notes.errors.push(e);
+                    values.push(recover_expression(tokens, start));
+                }
+            }
         } else {
             break;
         }
@@ -1894,8 +2276,9 @@ fn parse_variable(
                     let macro_body = vec![ast::Statement {
                         body: ast::StatementBody::Return(Some(expr)),
                         arrow: false,
-                        //comment: (None, None),
                         pos: (start, end),
+                        trivia: ast::Trivia::new(),
+                        attributes: ast::Attribute::new(),
                     }];
 
                     ast::ValueBody::Macro(ast::Macro {
@@ -1953,7 +2336,26 @@ fn parse_variable(
                     forced,
                 ),
                 Some(Token::Symbol) => {
-                    ast::ValueBody::Import(ImportType::Lib(tokens.slice()), forced)
+                    let name = tokens.slice();
+                    if name == "project" && tokens.next(false) == Some(Token::DoubleColon) {
+                        let mut parts = Vec::new();
+                        loop {
+                            match tokens.next(false) {
+                                Some(Token::Symbol) => parts.push(tokens.slice()),
+                                a => expected!("file or folder name".to_string(), tokens, notes, a),
+                            }
+                            if tokens.next(false) != Some(Token::DoubleColon) {
+                                tokens.previous();
+                                break;
+                            }
+                        }
+                        ast::ValueBody::Import(ImportType::Project(parts), forced)
+                    } else {
+                        if name == "project" {
+                            tokens.previous();
+                        }
+                        ast::ValueBody::Import(ImportType::Lib(name), forced)
+                    }
                 }
                 a => expected!("literal string".to_string(), tokens, notes, a),
             }
@@ -1968,6 +2370,46 @@ fn parse_variable(
             ast::ValueBody::TypeIndicator(type_name)
         }
 
+        Some(Token::Try) => {
+            // try { value } catch err { other value }
+            let braced = |tokens: &mut Tokens,
+                          notes: &mut ParseNotes|
+             -> Result<ast::Expression, SyntaxError> {
+                let expr = parse_expr(tokens, notes, true, true)?;
+                match tokens.next(false) {
+                    Some(Token::ClosingCurlyBracket) => Ok(expr),
+                    a => expected!("'}'".to_string(), tokens, notes, a),
+                }
+            };
+            match tokens.next(false) {
+                Some(Token::OpenCurlyBracket) => (),
+                a => expected!("'{'".to_string(), tokens, notes, a),
+            }
+            let try_expr = braced(tokens, notes)?;
+            match tokens.next(false) {
+                Some(Token::Catch) => (),
+                a => expected!("'catch'".to_string(), tokens, notes, a),
+            }
+            let catch_symbol = match tokens.next(false) {
+                Some(Token::Symbol) => {
+                    let symbol = tokens.slice();
+                    match tokens.next(false) {
+                        Some(Token::OpenCurlyBracket) => (),
+                        a => expected!("'{'".to_string(), tokens, notes, a),
+                    }
+                    Some(symbol)
+                }
+                Some(Token::OpenCurlyBracket) => None,
+                a => expected!("error variable name or '{'".to_string(), tokens, notes, a),
+            };
+            let catch_expr = braced(tokens, notes)?;
+            ast::ValueBody::Try(ast::TryExpr {
+                try_expr,
+                catch_symbol,
+                catch_expr,
+            })
+        }
+
         Some(Token::Switch) => {
             let value = parse_expr(tokens, notes, true, false)?; // what are we switching?
 
@@ -1995,8 +2437,9 @@ fn parse_variable(
                             vec![ast::Statement {
                                 body: ast::StatementBody::Return(Some(expr)),
                                 arrow: false,
-                                //comment: (None, None),
                                 pos: (start, end),
+                                trivia: ast::Trivia::new(),
+                                attributes: ast::Attribute::new(),
                             }]
                         }
                         a => expected!("'{'".to_string(), tokens, notes, a),
@@ -2011,8 +2454,14 @@ fn parse_variable(
 
                 let mut test_tokens = tokens.clone();
 
-                match parse_expr(&mut test_tokens, notes, true, true) {
-                    Ok(expr) => {
+                // errors in the test parse don't count, it's just used to figure out what this is
+                let error_count = notes.errors.len();
+                let test_parse = parse_expr(&mut test_tokens, notes, true, true);
+                let recovered = notes.errors.len() > error_count;
+                notes.errors.truncate(error_count);
+
+                match test_parse {
+                    Ok(expr) if !recovered => {
                         //macro def
                         match test_tokens.next(false) {
                             Some(Token::ClosingBracket) => match test_tokens.next(false) {
@@ -2037,7 +2486,7 @@ fn parse_variable(
                         }
                     }
 
-                    Err(_) => match parse_macro_def(tokens, notes) {
+                    _ => match parse_macro_def(tokens, notes) {
                         Ok(mac) => mac,
                         Err(e) => return Err(e),
                     },
@@ -2158,3 +2607,121 @@ fn parse_variable(
         tag: properties,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // what each statement became, and the errors as "line: message"
+    fn partial(source: &str) -> (Vec<String>, Vec<String>) {
+        let (statements, _, errors) = parse_spwn_partial(source.to_string(), "test.spwn".into());
+        (
+            statements.iter().map(|s| describe(&s.body)).collect(),
+            errors
+                .iter()
+                .map(|e| format!("{}: {}", e.location().1 .0 .0, e.message()))
+                .collect(),
+        )
+    }
+
+    fn describe(body: &ast::StatementBody) -> String {
+        match body {
+            ast::StatementBody::ErrorStatement(text) => format!("error statement `{}`", text),
+            ast::StatementBody::Expr(e) => match &e.values.last().unwrap().value.body {
+                ast::ValueBody::ErrorExpr(text) => format!("error expression `{}`", text),
+                ast::ValueBody::Macro(m) => format!(
+                    "macro [{}]",
+                    m.body
+                        .statements
+                        .iter()
+                        .map(|s| describe(&s.body))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+                _ => "expression".to_string(),
+            },
+            _ => "other".to_string(),
+        }
+    }
+
+    #[test]
+    fn broken_statements_are_skipped() {
+        let (statements, errors) = partial("a = 1\n) oops\nc = 3\n");
+        assert_eq!(
+            statements,
+            vec!["expression", "error statement `) oops`", "expression"]
+        );
+        assert_eq!(
+            errors,
+            vec!["2: SyntaxError: Expected a value, found terminator: \")\""]
+        );
+    }
+
+    #[test]
+    fn broken_values_become_error_expressions() {
+        let (statements, errors) = partial("a = * 2\nb = 2\n");
+        assert_eq!(statements, vec!["error expression `* 2`", "expression"]);
+        assert_eq!(
+            errors,
+            vec!["1: SyntaxError: Expected a value, found operator: \"*\""]
+        );
+    }
+
+    #[test]
+    fn broken_statements_in_blocks_are_skipped() {
+        let (statements, errors) = partial("f = () {\n    ) bad\n    y = 2\n}\nz = 1\n");
+        assert_eq!(
+            statements,
+            vec!["macro [error statement `) bad`, expression]", "expression"]
+        );
+        assert_eq!(
+            errors,
+            vec!["2: SyntaxError: Expected a value, found terminator: \")\""]
+        );
+    }
+
+    #[test]
+    fn every_error_in_a_file_is_found() {
+        let source = "a = * 1\n) oops\nc = 1 d\ne = 2\n";
+        let (statements, errors) = partial(source);
+        assert_eq!(
+            statements,
+            vec![
+                "error expression `* 1`",
+                "error statement `) oops`",
+                "expression",
+                "error statement `d`",
+                "expression",
+            ]
+        );
+        assert_eq!(
+            errors,
+            vec![
+                "1: SyntaxError: Expected a value, found operator: \"*\"",
+                "2: SyntaxError: Expected a value, found terminator: \")\"",
+                "3: SyntaxError: Expected Statement separator (line-break or ';'), found identifier: \"d\"",
+            ]
+        );
+        // parse_spwn stops at the first one
+        match parse_spwn(source.to_string(), "test.spwn".into()) {
+            Err(err) => assert_eq!(err.location().1 .0 .0, 1),
+            Ok(_) => panic!("parse_spwn should fail"),
+        }
+    }
+
+    #[test]
+    fn errors_from_a_failed_test_parse_are_dropped() {
+        // `(...)` is first parsed as an expression to see if it's a macro definition.
+        // the error that parse finds (at the '*') isn't kept, only the one from
+        // parsing it as a macro definition is
+        let (statements, errors) = partial("x = (1 + * 2)\ny = 1\n");
+        assert_eq!(
+            statements,
+            vec!["error expression `(1 + * 2)`", "expression"]
+        );
+        assert_eq!(
+            errors,
+            vec!["1: SyntaxError: Expected comma (',') or ')', found operator: \"+\""]
+        );
+    }
+}
@@ -0,0 +1,109 @@
+// spwn build --only: builds one top-level macro (or trigger function) of a script,
+// keeping the definitions it could need but leaving out everything else the script does
+use crate::ast::*;
+use crate::fmt::SpwnFmt;
+use std::collections::HashSet;
+
+// the name in `name = value` or `let name = value`
+fn defined_name(e: &Expression) -> Option<&str> {
+    if e.operators.first() != Some(&Operator::Assign) || !e.values[0].path.is_empty() {
+        return None;
+    }
+    match &e.values[0].value.body {
+        ValueBody::Symbol(name) => Some(name),
+        _ => None,
+    }
+}
+
+// trigger functions are built where they're defined, so they're only kept if they're used
+fn trigger_function(statement: &Statement) -> Option<&str> {
+    match &statement.body {
+        StatementBody::Expr(e) if e.values.len() == 2 => match &e.values[1].value.body {
+            ValueBody::CmpStmt(_) if e.values[1].path.is_empty() => defined_name(e),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+pub fn words(code: &str) -> HashSet<String> {
+    code.split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .map(|w| w.to_string())
+        .collect()
+}
+
+fn is_definition(statement: &Statement) -> bool {
+    match &statement.body {
+        StatementBody::Expr(e) => defined_name(e).is_some(),
+        StatementBody::TypeDef(_) | StatementBody::Impl(_) | StatementBody::Extract(_) => true,
+        _ => false,
+    }
+}
+
+// the definitions of the script, and the code that calls the macro
+pub fn only(statements: Vec<Statement>, name: &str) -> Result<(Vec<Statement>, String), String> {
+    let mut call = None;
+    for statement in &statements {
+        if let StatementBody::Expr(e) = &statement.body {
+            if defined_name(e) == Some(name) && e.values.len() == 2 {
+                call = match &e.values[1].value.body {
+                    ValueBody::Macro(_) if e.values[1].path.is_empty() => {
+                        Some(format!("{}()", name))
+                    }
+                    ValueBody::CmpStmt(_) if e.values[1].path.is_empty() => {
+                        Some(format!("{}!", name))
+                    }
+                    _ => {
+                        return Err(format!(
+                            "{} isn't a macro or a trigger function, so it can't be built on its own (only macros without arguments and trigger functions can)",
+                            name
+                        ))
+                    }
+                };
+            }
+        }
+    }
+    let call = match call {
+        Some(c) => c,
+        None => {
+            return Err(format!(
+                "There is no macro or trigger function called {} at the top of the script",
+                name
+            ))
+        }
+    };
+
+    // the trigger functions the macro uses, and the ones they use
+    let mut used = HashSet::new();
+    used.insert(name.to_string());
+    loop {
+        let mut more = HashSet::new();
+        for statement in &statements {
+            if let StatementBody::Expr(e) = &statement.body {
+                if matches!(defined_name(e), Some(n) if used.contains(n)) {
+                    more.extend(words(&statement.fmt(0)));
+                }
+            }
+        }
+        let found: HashSet<String> = statements
+            .iter()
+            .filter_map(trigger_function)
+            .filter(|n| more.contains(*n) && !used.contains(*n))
+            .map(|n| n.to_string())
+            .collect();
+        if found.is_empty() {
+            break;
+        }
+        used.extend(found);
+    }
+
+    Ok((
+        statements
+            .into_iter()
+            .filter(|s| {
+                is_definition(s) && !matches!(trigger_function(s), Some(n) if !used.contains(n))
+            })
+            .collect(),
+        call,
+    ))
+}
@@ -0,0 +1,217 @@
+// small rules that remove triggers that don't do anything, looking at the triggers of
+// each function that run at the same time (the ones with the same group)
+// to add a rule, write a function like the ones below and put it in RULES
+use crate::compiler_types::FunctionId;
+use crate::levelstring::{GdObj, ObjParam};
+
+const MOVE: f64 = 901.0;
+const ALPHA: f64 = 1007.0;
+const TOGGLE: f64 = 1049.0;
+
+// the trigger, and the other triggers that run at the same time before and after it
+type Rule = fn(&GdObj, &[&GdObj], &[&GdObj]) -> bool;
+
+// (why the trigger is removed, rule)
+pub const RULES: &[(&str, Rule)] = &[
+    ("it moves by 0", move_by_zero),
+    (
+        "it sets the opacity the group already has",
+        alpha_to_current,
+    ),
+    (
+        "the group is toggled again before anything uses it",
+        toggle_overwritten,
+    ),
+];
+
+fn number(obj: &GdObj, key: u16) -> f64 {
+    match obj.params.get(&key) {
+        Some(ObjParam::Number(n)) => *n,
+        _ => 0.0,
+    }
+}
+
+fn flag(obj: &GdObj, key: u16) -> bool {
+    matches!(obj.params.get(&key), Some(ObjParam::Bool(true)))
+}
+
+fn is(obj: &GdObj, id: f64) -> bool {
+    number(obj, 1) == id
+}
+
+fn target(obj: &GdObj) -> Option<&ObjParam> {
+    obj.params.get(&51)
+}
+
+fn move_by_zero(obj: &GdObj, _: &[&GdObj], _: &[&GdObj]) -> bool {
+    // the player and target modes move it even with no x and y
+    is(obj, MOVE)
+        && number(obj, 28) == 0.0
+        && number(obj, 29) == 0.0
+        && !flag(obj, 58)
+        && !flag(obj, 59)
+        && !flag(obj, 100)
+}
+
+// an instant alpha trigger after another one that set the group to the same opacity
+fn alpha_to_current(obj: &GdObj, before: &[&GdObj], _: &[&GdObj]) -> bool {
+    if !is(obj, ALPHA) || number(obj, 10) != 0.0 {
+        return false;
+    }
+    match before
+        .iter()
+        .rev()
+        .find(|o| is(o, ALPHA) && target(o) == target(obj))
+    {
+        Some(last) => number(last, 10) == 0.0 && number(last, 35) == number(obj, 35),
+        None => false,
+    }
+}
+
+// a toggle trigger that is undone by the next toggle of the same group, when nothing
+// in between spawns or otherwise uses the group
+fn toggle_overwritten(obj: &GdObj, _: &[&GdObj], after: &[&GdObj]) -> bool {
+    if !is(obj, TOGGLE) {
+        return false;
+    }
+    for o in after {
+        if target(o) == target(obj) {
+            return is(o, TOGGLE);
+        }
+    }
+    false
+}
+
+// removes the triggers the rules find, and tells why (by unique id)
+pub fn peephole(func_ids: &mut [FunctionId]) -> Vec<(usize, String)> {
+    let mut remarks = Vec::new();
+    for f in func_ids.iter_mut() {
+        let mut removed = vec![false; f.obj_list.len()];
+        for i in 0..f.obj_list.len() {
+            let obj = &f.obj_list[i].0;
+            let group = obj.params.get(&57);
+            let same_time = |j: &usize| !removed[*j] && f.obj_list[*j].0.params.get(&57) == group;
+            let before: Vec<&GdObj> = (0..i).filter(same_time).map(|j| &f.obj_list[j].0).collect();
+            let after: Vec<&GdObj> = (i + 1..f.obj_list.len())
+                .filter(same_time)
+                .map(|j| &f.obj_list[j].0)
+                .collect();
+            if let Some((why, _)) = RULES.iter().find(|(_, rule)| rule(obj, &before, &after)) {
+                remarks.push((obj.unique_id, format!("removed: {}", why)));
+                removed[i] = true;
+            }
+        }
+        let mut i = 0;
+        f.obj_list.retain(|_| {
+            i += 1;
+            !removed[i - 1]
+        });
+    }
+    remarks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::ObjectMode;
+    use crate::builtin::{Group, Id};
+
+    fn group(n: u16) -> ObjParam {
+        ObjParam::Group(Group {
+            id: Id::Specific(n),
+        })
+    }
+
+    fn trigger(unique_id: usize, params: &[(u16, ObjParam)]) -> GdObj {
+        let mut all = params.to_vec();
+        all.push((57, group(1)));
+        GdObj::for_test(unique_id, ObjectMode::Trigger, &all)
+    }
+
+    fn kept(objects: Vec<GdObj>) -> Vec<usize> {
+        let mut func_ids = vec![FunctionId {
+            parent: None,
+            width: None,
+            obj_list: objects.into_iter().map(|o| (o, 0)).collect(),
+        }];
+        peephole(&mut func_ids);
+        func_ids[0]
+            .obj_list
+            .iter()
+            .map(|(o, _)| o.unique_id)
+            .collect()
+    }
+
+    #[test]
+    fn move_by_zero_is_removed() {
+        let objects = vec![
+            trigger(0, &[(1, ObjParam::Number(MOVE)), (51, group(2))]),
+            trigger(
+                1,
+                &[
+                    (1, ObjParam::Number(MOVE)),
+                    (51, group(2)),
+                    (58, ObjParam::Bool(true)),
+                ],
+            ),
+            trigger(
+                2,
+                &[
+                    (1, ObjParam::Number(MOVE)),
+                    (51, group(2)),
+                    (28, ObjParam::Number(10.0)),
+                ],
+            ),
+        ];
+        assert_eq!(kept(objects), vec![1, 2]);
+    }
+
+    #[test]
+    fn alpha_to_current_value_is_removed() {
+        let alpha = |unique_id, opacity, duration| {
+            trigger(
+                unique_id,
+                &[
+                    (1, ObjParam::Number(ALPHA)),
+                    (51, group(2)),
+                    (35, ObjParam::Number(opacity)),
+                    (10, ObjParam::Number(duration)),
+                ],
+            )
+        };
+        assert_eq!(
+            kept(vec![
+                alpha(0, 0.5, 0.0),
+                alpha(1, 0.5, 0.0),
+                alpha(2, 1.0, 0.0)
+            ]),
+            vec![0, 2]
+        );
+        // the first one is still fading when the second one runs
+        assert_eq!(
+            kept(vec![alpha(0, 0.5, 1.0), alpha(1, 0.5, 0.0)]),
+            vec![0, 1]
+        );
+    }
+
+    #[test]
+    fn toggle_before_toggle_is_removed() {
+        let toggle = |unique_id, on| {
+            trigger(
+                unique_id,
+                &[
+                    (1, ObjParam::Number(TOGGLE)),
+                    (51, group(2)),
+                    (56, ObjParam::Bool(on)),
+                ],
+            )
+        };
+        let spawn = trigger(3, &[(1, ObjParam::Number(1268.0)), (51, group(2))]);
+        assert_eq!(kept(vec![toggle(0, true), toggle(1, false)]), vec![1]);
+        // the spawn trigger needs the group to be on
+        assert_eq!(
+            kept(vec![toggle(0, true), spawn, toggle(1, false)]),
+            vec![0, 3, 1]
+        );
+    }
+}
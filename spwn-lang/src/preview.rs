@@ -0,0 +1,301 @@
+// renders a simple top-down schematic of the objects a script adds (spwn preview)
+use crate::ast::ObjectMode;
+use crate::builtin::*;
+use crate::levelstring::{GdObj, ObjParam};
+
+use std::io::Write;
+
+// the image is scaled down if the level is bigger than this
+const MAX_SIZE: f64 = 4096.0;
+const MARGIN: f64 = 60.0;
+const BLOCK: f64 = 30.0;
+
+const BACKGROUND: [u8; 3] = [40, 44, 52];
+const GRID: [u8; 3] = [50, 55, 64];
+
+struct Shape {
+    x: f64,
+    y: f64,
+    size: f64,
+    color: [u8; 3],
+    trigger: Option<&'static str>,
+    description: String,
+}
+
+fn number(obj: &GdObj, key: u16) -> Option<f64> {
+    match obj.params.get(&key) {
+        Some(ObjParam::Number(n)) => Some(*n),
+        _ => None,
+    }
+}
+
+// (color, label) of the icon for a trigger
+fn trigger_icon(obj_id: u16) -> ([u8; 3], &'static str) {
+    match obj_id {
+        901 => ([97, 175, 239], "M"),
+        1268 => ([152, 195, 121], "S"),
+        1049 => ([224, 108, 117], "T"),
+        1006 => ([229, 192, 123], "P"),
+        1007 => ([86, 182, 194], "A"),
+        899 => ([198, 120, 221], "C"),
+        1346 => ([209, 154, 102], "R"),
+        1347 => ([190, 80, 220], "F"),
+        1616 => ([160, 50, 50], "X"),
+        1611 | 1811 | 1817 => ([180, 140, 90], "#"),
+        1595 | 1815 => ([120, 120, 220], "E"),
+        _ => ([220, 220, 220], "?"),
+    }
+}
+
+// color channels are drawn with a color picked from their id
+fn channel_color(channel: u16) -> [u8; 3] {
+    let hue = (channel as f64 * 47.0) % 360.0;
+    let c = 0.6;
+    let x = c * (1.0 - ((hue / 60.0) % 2.0 - 1.0).abs());
+    let (r, g, b) = match (hue / 60.0) as u8 {
+        0 => (c, x, 0.0),
+        1 => (x, c, 0.0),
+        2 => (0.0, c, x),
+        3 => (0.0, x, c),
+        4 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    };
+    [
+        ((r + 0.3) * 255.0) as u8,
+        ((g + 0.3) * 255.0) as u8,
+        ((b + 0.3) * 255.0) as u8,
+    ]
+}
+
+fn shapes(objects: &[GdObj]) -> Vec<Shape> {
+    objects
+        .iter()
+        .map(|obj| {
+            let obj_id = number(obj, 1).unwrap_or(0.0) as u16;
+            let (color, trigger) = match obj.mode {
+                ObjectMode::Trigger => {
+                    let (color, label) = trigger_icon(obj_id);
+                    (color, Some(label))
+                }
+                ObjectMode::Object => (
+                    match obj.params.get(&21) {
+                        Some(ObjParam::Color(Color {
+                            id: Id::Specific(c),
+                        })) => channel_color(*c),
+                        _ => [200, 200, 200],
+                    },
+                    None,
+                ),
+            };
+
+            let mut params: Vec<_> = obj.params.iter().collect();
+            params.sort_by_key(|(key, _)| **key);
+            let description = params
+                .iter()
+                .map(|(key, param)| format!("{}: {}", key, param))
+                .collect::<Vec<_>>()
+                .join(", ")
+                .replace('&', "&amp;")
+                .replace('<', "&lt;")
+                .replace('>', "&gt;");
+
+            Shape {
+                x: number(obj, 2).unwrap_or(0.0),
+                y: number(obj, 3).unwrap_or(0.0),
+                size: BLOCK * number(obj, 32).unwrap_or(1.0),
+                color,
+                trigger,
+                description,
+            }
+        })
+        .collect()
+}
+
+// area the shapes take up, and the scale that makes it fit in MAX_SIZE
+struct View {
+    min_x: f64,
+    max_y: f64,
+    width: f64,
+    height: f64,
+    scale: f64,
+}
+
+impl View {
+    fn new(shapes: &[Shape]) -> Self {
+        let mut min_x = 0.0f64;
+        let mut max_x = BLOCK;
+        let mut min_y = 0.0f64;
+        let mut max_y = BLOCK;
+        for s in shapes {
+            min_x = min_x.min(s.x - s.size / 2.0);
+            max_x = max_x.max(s.x + s.size / 2.0);
+            min_y = min_y.min(s.y - s.size / 2.0);
+            max_y = max_y.max(s.y + s.size / 2.0);
+        }
+        let width = max_x - min_x + MARGIN * 2.0;
+        let height = max_y - min_y + MARGIN * 2.0;
+        View {
+            min_x: min_x - MARGIN,
+            max_y: max_y + MARGIN,
+            width,
+            height,
+            scale: (MAX_SIZE / width).min(MAX_SIZE / height).min(1.0),
+        }
+    }
+
+    // level coordinates to image coordinates (y goes down in images)
+    fn point(&self, x: f64, y: f64) -> (f64, f64) {
+        ((x - self.min_x) * self.scale, (self.max_y - y) * self.scale)
+    }
+}
+
+pub fn render_svg(objects: &[GdObj]) -> String {
+    let shapes = shapes(objects);
+    let view = View::new(&shapes);
+    let hex = |c: [u8; 3]| format!("#{:02x}{:02x}{:02x}", c[0], c[1], c[2]);
+
+    let mut out = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" viewBox=\"0 0 {0} {1}\">\n\
+         <rect width=\"100%\" height=\"100%\" fill=\"{2}\"/>\n",
+        (view.width * view.scale).ceil(),
+        (view.height * view.scale).ceil(),
+        hex(BACKGROUND)
+    );
+
+    for s in &shapes {
+        let (x, y) = view.point(s.x, s.y);
+        let size = s.size * view.scale;
+        match s.trigger {
+            Some(label) => {
+                out += &format!(
+                    "<g><title>{}</title><circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"{:.1}\" fill=\"{}\"/>\
+                     <text x=\"{:.1}\" y=\"{:.1}\" font-size=\"{:.1}\" text-anchor=\"middle\" \
+                     dominant-baseline=\"central\" font-family=\"sans-serif\">{}</text></g>\n",
+                    s.description,
+                    x,
+                    y,
+                    size * 0.4,
+                    hex(s.color),
+                    x,
+                    y,
+                    size * 0.5,
+                    label
+                );
+            }
+            None => {
+                out += &format!(
+                    "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" fill=\"{}\" \
+                     stroke=\"#000\" stroke-opacity=\"0.3\"><title>{}</title></rect>\n",
+                    x - size / 2.0,
+                    y - size / 2.0,
+                    size,
+                    size,
+                    hex(s.color),
+                    s.description
+                );
+            }
+        }
+    }
+
+    out += "</svg>\n";
+    out
+}
+
+pub fn render_png(objects: &[GdObj]) -> Vec<u8> {
+    let shapes = shapes(objects);
+    let view = View::new(&shapes);
+
+    let width = (view.width * view.scale).ceil().max(1.0) as usize;
+    let height = (view.height * view.scale).ceil().max(1.0) as usize;
+    let mut pixels = vec![0u8; width * height * 3];
+
+    let mut fill = |x0: f64, y0: f64, x1: f64, y1: f64, color: [u8; 3], circle: bool| {
+        let (cx, cy) = ((x0 + x1) / 2.0, (y0 + y1) / 2.0);
+        let r = (x1 - x0) / 2.0;
+        for py in (y0.max(0.0) as usize)..(y1.ceil().max(0.0) as usize).min(height) {
+            for px in (x0.max(0.0) as usize)..(x1.ceil().max(0.0) as usize).min(width) {
+                if circle {
+                    let (dx, dy) = (px as f64 + 0.5 - cx, py as f64 + 0.5 - cy);
+                    if dx * dx + dy * dy > r * r {
+                        continue;
+                    }
+                }
+                let i = (py * width + px) * 3;
+                pixels[i..(i + 3)].copy_from_slice(&color);
+            }
+        }
+    };
+
+    fill(0.0, 0.0, width as f64, height as f64, BACKGROUND, false);
+
+    // block grid, if the blocks are big enough to see it
+    if BLOCK * view.scale >= 6.0 {
+        let step = BLOCK * view.scale;
+        let offset_x = (-view.min_x * view.scale).rem_euclid(step);
+        let offset_y = (view.max_y * view.scale).rem_euclid(step);
+        let mut x = offset_x;
+        while x < width as f64 {
+            fill(x, 0.0, x + 1.0, height as f64, GRID, false);
+            x += step;
+        }
+        let mut y = offset_y;
+        while y < height as f64 {
+            fill(0.0, y, width as f64, y + 1.0, GRID, false);
+            y += step;
+        }
+    }
+
+    for s in &shapes {
+        let (x, y) = view.point(s.x, s.y);
+        // at least a pixel, so tiny objects don't disappear
+        let half = (s.size * view.scale / 2.0).max(0.5);
+        match s.trigger {
+            Some(_) => fill(
+                x - half * 0.8,
+                y - half * 0.8,
+                x + half * 0.8,
+                y + half * 0.8,
+                s.color,
+                true,
+            ),
+            None => fill(x - half, y - half, x + half, y + half, s.color, false),
+        }
+    }
+
+    encode_png(width, height, &pixels)
+}
+
+// 8 bit RGB png
+fn encode_png(width: usize, height: usize, pixels: &[u8]) -> Vec<u8> {
+    use libflate::zlib;
+
+    fn chunk(out: &mut Vec<u8>, kind: &[u8], data: &[u8]) {
+        let mut hasher = crc32fast::Hasher::new();
+        hasher.update(kind);
+        hasher.update(data);
+        out.extend(&(data.len() as u32).to_be_bytes());
+        out.extend(kind);
+        out.extend(data);
+        out.extend(&hasher.finalize().to_be_bytes());
+    }
+
+    let mut raw = Vec::with_capacity((width * 3 + 1) * height);
+    for row in pixels.chunks(width * 3) {
+        raw.push(0); // no filter
+        raw.extend(row);
+    }
+    let mut encoder = zlib::Encoder::new(Vec::new()).unwrap();
+    encoder.write_all(&raw).unwrap();
+    let compressed = encoder.finish().into_result().unwrap();
+
+    let mut header = Vec::new();
+    header.extend(&(width as u32).to_be_bytes());
+    header.extend(&(height as u32).to_be_bytes());
+    header.extend(&[8, 2, 0, 0, 0]); // bit depth, color type (RGB), compression, filter, interlace
+
+    let mut out = b"\x89PNG\r\n\x1a\n".to_vec();
+    chunk(&mut out, b"IHDR", &header);
+    chunk(&mut out, b"IDAT", &compressed);
+    chunk(&mut out, b"IEND", &[]);
+    out
+}
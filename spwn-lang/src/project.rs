@@ -0,0 +1,333 @@
+// the [project] section of spwn.toml, so a project is built with just `spwn build`:
+//
+// [project]
+// entry = "src/main.spwn"
+// level_name = "my level"
+// include_paths = ["../shared"]
+// optimize = true
+// release = false
+// docs = true
+//
+// [dependencies]
+// physics = "libs/physics"
+// gd_utils = { git = "https://example.com/gd_utils.git", version = "^1.2" }
+// tweens = { version = "0.3" }
+//
+// paths are relative to the folder with spwn.toml. a dependency is a library folder that
+// `import physics` finds before anything else: either a folder in the project, or a
+// package that spwn install fetches into libraries/ (see packages.rs). a package without
+// a git url comes from the registry in [project]. docs = true is like spwn build --docs.
+// the build flags go over all of these
+use crate::manifest;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Project {
+    // the folder with spwn.toml
+    pub root: PathBuf,
+    pub entry: Option<PathBuf>,
+    pub level_name: Option<String>,
+    pub include_paths: Vec<PathBuf>,
+    pub optimize: Option<bool>,
+    pub release: Option<bool>,
+    pub docs: Option<bool>,
+    // a git host with a repository for each package, like https://example.com/spwn
+    pub registry: Option<String>,
+    pub dependencies: HashMap<String, Dependency>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Dependency {
+    // a library folder
+    Path(PathBuf),
+    // fetched from a git repository by spwn install (the registry's if git is None), at
+    // the newest tag that fits the version
+    Package {
+        git: Option<String>,
+        version: String,
+    },
+}
+
+// the file spwn install writes next to spwn.toml, with the packages it fetched
+pub const LOCK_FILE: &str = "spwn.lock";
+// where they are fetched to
+pub const PACKAGE_FOLDER: &str = "libraries";
+
+fn boolean(key: &str, value: &str) -> Result<bool, String> {
+    match value {
+        "true" => Ok(true),
+        "false" => Ok(false),
+        _ => Err(format!(
+            "expected true or false for `{}`, found `{}`",
+            key, value
+        )),
+    }
+}
+
+// { key = "value", other = "value" }
+fn table(value: &str) -> Result<HashMap<String, String>, String> {
+    if !(value.starts_with('{') && value.ends_with('}')) {
+        return Err(format!(
+            "expected a {{ key = value }} table, found `{}`",
+            value
+        ));
+    }
+    let mut out = HashMap::new();
+    for pair in manifest::split(&value[1..value.len() - 1], ',') {
+        if pair.trim().is_empty() {
+            continue;
+        }
+        match manifest::find(pair, '=') {
+            Some(pos) => out.insert(
+                manifest::unquote(&pair[..pos]),
+                manifest::unquote(&pair[(pos + 1)..]),
+            ),
+            None => return Err(format!("expected `key = value`, found `{}`", pair.trim())),
+        };
+    }
+    Ok(out)
+}
+
+fn dependency(value: &str, root: &Path) -> Result<Dependency, String> {
+    if !value.starts_with('{') {
+        return Ok(Dependency::Path(root.join(manifest::unquote(value))));
+    }
+    let mut table = table(value)?;
+    if let Some(path) = table.remove("path") {
+        return Ok(Dependency::Path(root.join(path)));
+    }
+    let dependency = Dependency::Package {
+        git: table.remove("git"),
+        version: table.remove("version").unwrap_or_else(|| "*".to_string()),
+    };
+    match table.keys().next() {
+        Some(key) => Err(format!("unknown option `{}` for a dependency", key)),
+        None => Ok(dependency),
+    }
+}
+
+// ["a", "b"] (or just "a")
+fn list(key: &str, value: &str) -> Result<Vec<String>, String> {
+    let inner = if value.starts_with('[') && value.ends_with(']') {
+        &value[1..value.len() - 1]
+    } else if value.starts_with('"') {
+        value
+    } else {
+        return Err(format!("expected a list of paths for `{}`", key));
+    };
+    Ok(manifest::split(inner, ',')
+        .into_iter()
+        .map(manifest::unquote)
+        .filter(|s| !s.is_empty())
+        .collect())
+}
+
+impl Project {
+    // looks for a spwn.toml file with a [project] section, starting in the given folder
+    // and going up through its parents
+    pub fn find(start: &Path) -> Result<Option<Self>, String> {
+        for dir in start.ancestors() {
+            let manifest = dir.join("spwn.toml");
+            if manifest.is_file() {
+                let content = std::fs::read_to_string(&manifest)
+                    .map_err(|e| format!("Could not read {}: {}", manifest.to_string_lossy(), e))?;
+                let dir = if dir.as_os_str().is_empty() {
+                    Path::new(".")
+                } else {
+                    dir
+                };
+                return Self::parse(&content, dir)
+                    .map_err(|e| format!("Error in {}: {}", manifest.to_string_lossy(), e));
+            }
+        }
+        Ok(None)
+    }
+
+    // None if there is no [project] or [dependencies] section
+    pub fn parse(content: &str, root: &Path) -> Result<Option<Self>, String> {
+        let mut project = Project {
+            root: root.to_path_buf(),
+            entry: None,
+            level_name: None,
+            include_paths: Vec::new(),
+            optimize: None,
+            release: None,
+            docs: None,
+            registry: None,
+            dependencies: HashMap::new(),
+        };
+        let is_ours = |section: &str| section == "project" || section == "dependencies";
+        if !manifest::sections(content).iter().any(|s| is_ours(s)) {
+            return Ok(None);
+        }
+
+        for entry in manifest::read(content, is_ours)? {
+            let (key, value) = (entry.key.as_str(), entry.value.as_str());
+            let text = entry.text();
+            let error = |e: String| entry.error(e);
+            if entry.section == "dependencies" {
+                project
+                    .dependencies
+                    .insert(key.to_string(), dependency(value, root).map_err(error)?);
+                continue;
+            }
+            match key {
+                "entry" => project.entry = Some(root.join(&text)),
+                "level_name" => project.level_name = Some(text.clone()),
+                "include_paths" => {
+                    project.include_paths = list(key, value)
+                        .map_err(error)?
+                        .iter()
+                        .map(|p| root.join(p))
+                        .collect()
+                }
+                "optimize" => project.optimize = Some(boolean(key, &text).map_err(error)?),
+                "release" => project.release = Some(boolean(key, &text).map_err(error)?),
+                "docs" => project.docs = Some(boolean(key, &text).map_err(error)?),
+                "registry" => project.registry = Some(text.trim_end_matches('/').to_string()),
+                _ => return Err(error(format!("unknown option `{}`", key))),
+            }
+        }
+        Ok(Some(project))
+    }
+
+    // the folder each dependency is imported from, with the packages (and the packages
+    // they depend on) from spwn.lock
+    pub fn dependency_folders(&self) -> Result<HashMap<String, PathBuf>, String> {
+        let locked = read_lock(&self.root)?;
+        let mut folders = HashMap::new();
+        for (name, dependency) in &self.dependencies {
+            if let Dependency::Path(path) = dependency {
+                folders.insert(name.clone(), path.clone());
+            } else if !locked.contains_key(name) {
+                return Err(format!(
+                    "The package {} isn't installed (run spwn install)",
+                    name
+                ));
+            }
+        }
+        for name in locked.keys() {
+            let folder = self.root.join(PACKAGE_FOLDER).join(name);
+            if !folder.is_dir() {
+                return Err(format!(
+                    "The package {} is in {} but not in {} (run spwn install)",
+                    name, LOCK_FILE, PACKAGE_FOLDER
+                ));
+            }
+            folders.entry(name.clone()).or_insert(folder);
+        }
+        Ok(folders)
+    }
+}
+
+// the packages in spwn.lock: name -> (git url, version)
+pub fn read_lock(root: &Path) -> Result<HashMap<String, (String, String)>, String> {
+    let path = root.join(LOCK_FILE);
+    let content = match std::fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(_) => return Ok(HashMap::new()),
+    };
+    let mut packages = HashMap::new();
+    let error = |e: String| format!("Error in {}: {}", path.to_string_lossy(), e);
+    for entry in manifest::read(&content, |s| s == "packages").map_err(error)? {
+        let error = |e: String| error(entry.error(e));
+        let (name, value) = (entry.key.as_str(), entry.value.as_str());
+        let mut table = table(value).map_err(error)?;
+        match (table.remove("git"), table.remove("version")) {
+            (Some(git), Some(version)) => packages.insert(name.to_string(), (git, version)),
+            _ => return Err(error("expected a git url and a version".to_string())),
+        };
+    }
+    Ok(packages)
+}
+
+pub fn write_lock(root: &Path, packages: &[(String, String, String)]) -> Result<(), String> {
+    let mut content = String::from(
+        "# the packages spwn install fetched (made by spwn, don't edit it)\n[packages]\n",
+    );
+    for (name, git, version) in packages {
+        content += &format!(
+            "{} = {{ git = \"{}\", version = \"{}\" }}\n",
+            name, git, version
+        );
+    }
+    let path = root.join(LOCK_FILE);
+    std::fs::write(&path, content)
+        .map_err(|e| format!("Could not write {}: {}", path.to_string_lossy(), e))
+}
+
+// spwn init: a spwn.toml and a script to start from, in the given folder
+pub fn init(dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let manifest = dir.join("spwn.toml");
+    let entry = dir.join("main.spwn");
+    if manifest.exists() {
+        return Err(format!("{} already exists", manifest.to_string_lossy()));
+    }
+    std::fs::create_dir_all(dir)
+        .map_err(|e| format!("Could not make {}: {}", dir.to_string_lossy(), e))?;
+    // the level is named after the folder
+    let name = dir
+        .canonicalize()
+        .ok()
+        .and_then(|d| d.file_name().map(|n| n.to_string_lossy().to_string()))
+        .unwrap_or_else(|| "my level".to_string());
+
+    let mut made = Vec::new();
+    let mut write = |path: &PathBuf, content: String| {
+        std::fs::write(path, content)
+            .map_err(|e| format!("Could not write {}: {}", path.to_string_lossy(), e))?;
+        made.push(path.clone());
+        Ok::<(), String>(())
+    };
+    write(
+        &manifest,
+        format!(
+            "[project]\nentry = \"main.spwn\"\nlevel_name = \"{}\"\ninclude_paths = []\noptimize = true\nrelease = false\n\n[dependencies]\n# name = \"path/to/library\"\n# name = {{ git = \"https://...\", version = \"1.0\" }}\n",
+            name.replace('"', "")
+        ),
+    )?;
+    // an entry file that is already there is kept
+    if !entry.exists() {
+        write(&entry, "$.print(\"Hello world!\")\n".to_string())?;
+    }
+    Ok(made)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_project_section_is_read() {
+        let root = Path::new("game");
+        let project = Project::parse(
+            "[triggers]\nx = 10\n\n[project]\nentry = \"src/main.spwn\"\nlevel_name = \"my level\" # shown in gd\ninclude_paths = [\"../shared\", \"vendor\"]\noptimize = false\n\n[dependencies]\nphysics = \"libs/physics\"\nutils = { git = \"https://example.com/utils.git\", version = \"^1.2\" }\n",
+            root,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(project.entry, Some(root.join("src/main.spwn")));
+        assert_eq!(project.level_name.as_deref(), Some("my level"));
+        assert_eq!(
+            project.include_paths,
+            vec![root.join("../shared"), root.join("vendor")]
+        );
+        assert_eq!(project.optimize, Some(false));
+        assert_eq!(project.release, None);
+        assert_eq!(
+            project.dependencies["physics"],
+            Dependency::Path(root.join("libs/physics"))
+        );
+        assert_eq!(
+            project.dependencies["utils"],
+            Dependency::Package {
+                git: Some("https://example.com/utils.git".to_string()),
+                version: "^1.2".to_string()
+            }
+        );
+
+        assert_eq!(Project::parse("[triggers]\nx = 10\n", root), Ok(None));
+        assert!(Project::parse("[project]\noptimize = yes\n", root).is_err());
+    }
+}
@@ -0,0 +1,230 @@
+// how the savefile (CCLocalLevels.dat) is encrypted. the game does it one way on windows
+// and android and another way on mac, and some private servers (GDPS) change the keys or
+// leave out the compression. a server's format is given as a profile in spwn.toml:
+//
+// [savefile]
+// profile = "my_gdps" # the one used when there's no --profile
+//
+// [savefile.my_gdps]
+// encryption = "xor"  # or "aes"
+// key = 11            # the xor key, or the aes key as 64 hex digits
+// compression = "gzip" # or "none"
+// path = "C:/Users/me/AppData/Local/MyGDPS/CCLocalLevels.dat"
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+// the key the game uses on mac
+const MAC_KEY: &[u8] = &[
+    0x69, 0x70, 0x75, 0x39, 0x54, 0x55, 0x76, 0x35, 0x34, 0x79, 0x76, 0x5D, 0x69, 0x73, 0x46, 0x4D,
+    0x68, 0x35, 0x40, 0x3B, 0x74, 0x2E, 0x35, 0x77, 0x33, 0x34, 0x45, 0x32, 0x52, 0x79, 0x40, 0x7B,
+];
+const XOR_KEY: u8 = 11;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Encryption {
+    // every byte xored with the key, after turning it into (url safe) base64
+    Xor(u8),
+    // aes-256 in ecb mode
+    Aes(Vec<u8>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SaveFormat {
+    pub encryption: Encryption,
+    // whether the xml is gzipped before it's put in base64 (only for xor)
+    pub compressed: bool,
+    // where the profile's savefile is (--save-file goes over it)
+    pub save_file: Option<PathBuf>,
+}
+
+impl Default for SaveFormat {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SaveFormat {
+    // the game's format on this platform
+    pub fn new() -> Self {
+        SaveFormat {
+            encryption: if cfg!(target_os = "macos") {
+                Encryption::Aes(MAC_KEY.to_vec())
+            } else {
+                Encryption::Xor(XOR_KEY)
+            },
+            compressed: true,
+            save_file: None,
+        }
+    }
+
+    // looks for a spwn.toml file, starting in the given folder and going up through its
+    // parents, and reads the profile from it (or the one it chooses, if there's no profile)
+    pub fn find(start: &Path, profile: Option<&str>) -> Result<Self, String> {
+        for dir in start.ancestors() {
+            let manifest = dir.join("spwn.toml");
+            if manifest.is_file() {
+                let mut format = match std::fs::read_to_string(&manifest) {
+                    Ok(content) => Self::parse(&content, profile)
+                        .map_err(|e| format!("Error in {}: {}", manifest.to_string_lossy(), e))?,
+                    Err(e) => {
+                        return Err(format!(
+                            "Could not read {}: {}",
+                            manifest.to_string_lossy(),
+                            e
+                        ))
+                    }
+                };
+                // relative to the folder with spwn.toml
+                format.save_file = format.save_file.map(|path| dir.join(path));
+                return Ok(format);
+            }
+        }
+        match profile {
+            Some(name) => Err(format!(
+                "There is no spwn.toml with a [savefile.{}] section",
+                name
+            )),
+            None => Ok(Self::new()),
+        }
+    }
+
+    pub fn parse(content: &str, profile: Option<&str>) -> Result<Self, String> {
+        // profile -> (line, key, value), with "" for [savefile] itself
+        let mut sections: HashMap<String, Vec<(usize, String, String)>> = HashMap::new();
+        let profile_name = |section: &str| match section.strip_prefix("savefile.") {
+            Some(p) => Some(crate::manifest::unquote(p)),
+            None if section == "savefile" => Some(String::new()),
+            None => None,
+        };
+        // a profile with nothing in it is still there
+        for section in crate::manifest::sections(content) {
+            if let Some(name) = profile_name(&section) {
+                sections.entry(name).or_default();
+            }
+        }
+        for entry in crate::manifest::read(content, |s| profile_name(s).is_some())? {
+            let section = profile_name(&entry.section).unwrap();
+            let value = entry.text();
+            sections
+                .entry(section)
+                .or_default()
+                .push((entry.line, entry.key, value));
+        }
+
+        let mut chosen = profile.map(|p| p.to_string());
+        for (line, key, value) in sections.get("").into_iter().flatten() {
+            match key.as_str() {
+                "profile" => {
+                    if chosen.is_none() {
+                        chosen = Some(value.clone())
+                    }
+                }
+                _ => return Err(format!("line {}: unknown option `{}`", line, key)),
+            }
+        }
+        let name = match chosen {
+            Some(name) => name,
+            None => return Ok(Self::new()),
+        };
+        let options = match sections.get(&name) {
+            Some(options) if !name.is_empty() => options,
+            _ => return Err(format!("there is no [savefile.{}] section", name)),
+        };
+
+        let mut format = Self::new();
+        let mut encryption = None;
+        let mut key = None;
+        for (line, k, value) in options {
+            let error = |e: String| format!("line {}: {}", line, e);
+            match k.as_str() {
+                "encryption" => match value.as_str() {
+                    "xor" | "aes" => encryption = Some(value.as_str()),
+                    _ => {
+                        return Err(error(format!(
+                            "expected \"xor\" or \"aes\" for `encryption`, found `{}`",
+                            value
+                        )))
+                    }
+                },
+                "key" => key = Some((*line, value.as_str())),
+                "compression" => {
+                    format.compressed = match value.as_str() {
+                        "gzip" => true,
+                        "none" => false,
+                        _ => {
+                            return Err(error(format!(
+                                "expected \"gzip\" or \"none\" for `compression`, found `{}`",
+                                value
+                            )))
+                        }
+                    }
+                }
+                "path" => format.save_file = Some(PathBuf::from(value)),
+                _ => return Err(error(format!("unknown option `{}`", k))),
+            }
+        }
+
+        format.encryption = match (encryption, &format.encryption) {
+            (Some("xor"), _) | (None, Encryption::Xor(_)) => Encryption::Xor(XOR_KEY),
+            _ => Encryption::Aes(MAC_KEY.to_vec()),
+        };
+        if let Some((line, key)) = key {
+            format.encryption = match format.encryption {
+                Encryption::Xor(_) => Encryption::Xor(key.parse().map_err(|_| {
+                    format!(
+                        "line {}: expected a number from 0 to 255 for the xor key, found `{}`",
+                        line, key
+                    )
+                })?),
+                Encryption::Aes(_) => Encryption::Aes(parse_hex(key).ok_or_else(|| {
+                    format!(
+                        "line {}: expected 64 hex digits for the aes key, found `{}`",
+                        line, key
+                    )
+                })?),
+            };
+        }
+        Ok(format)
+    }
+}
+
+// a 32 byte key
+fn parse_hex(text: &str) -> Option<Vec<u8>> {
+    if text.len() != 64 || !text.is_ascii() {
+        return None;
+    }
+    (0..32)
+        .map(|i| u8::from_str_radix(&text[(i * 2)..(i * 2 + 2)], 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn profiles_are_read() {
+        let manifest = "[triggers]\nx = 10\n\n[savefile]\nprofile = \"gdps\"\n\n[savefile.gdps]\nkey = 42\ncompression = \"none\"\npath = \"gdps/CCLocalLevels.dat\"\n\n[savefile.mac]\nencryption = \"aes\"\nkey = \"000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f\"\n";
+
+        let format = SaveFormat::parse(manifest, None).unwrap();
+        if !cfg!(target_os = "macos") {
+            assert_eq!(format.encryption, Encryption::Xor(42));
+        }
+        assert!(!format.compressed);
+        assert_eq!(
+            format.save_file,
+            Some(PathBuf::from("gdps/CCLocalLevels.dat"))
+        );
+
+        let format = SaveFormat::parse(manifest, Some("mac")).unwrap();
+        assert_eq!(format.encryption, Encryption::Aes((0..32).collect()));
+        assert!(format.compressed);
+
+        assert!(SaveFormat::parse(manifest, Some("other")).is_err());
+        assert_eq!(
+            SaveFormat::parse("[triggers]\nx = 1\n", None).unwrap(),
+            SaveFormat::new()
+        );
+        assert!(SaveFormat::parse("[savefile.a]\nkey = 300\n", Some("a")).is_err());
+    }
+}
@@ -0,0 +1,144 @@
+// where to put the objects for the shape builtins ($.line, $.circle and so on), done
+// natively since art can need thousands of objects
+// every position comes with the direction the shape goes there (in degrees,
+// counterclockwise from the right like the game's x and y)
+
+pub type Point = [f64; 2];
+
+fn along(a: Point, b: Point, t: f64) -> Point {
+    [a[0] + (b[0] - a[0]) * t, a[1] + (b[1] - a[1]) * t]
+}
+
+fn direction(a: Point, b: Point) -> f64 {
+    (b[1] - a[1]).atan2(b[0] - a[0]).to_degrees()
+}
+
+fn distance(a: Point, b: Point) -> f64 {
+    ((b[0] - a[0]).powi(2) + (b[1] - a[1]).powi(2)).sqrt()
+}
+
+// how many gaps of about `spacing` fit in a length
+fn steps(length: f64, spacing: f64) -> usize {
+    ((length / spacing).round() as usize).max(1)
+}
+
+pub fn line(a: Point, b: Point, spacing: f64) -> Vec<(Point, f64)> {
+    let n = steps(distance(a, b), spacing);
+    let dir = direction(a, b);
+    (0..=n)
+        .map(|i| (along(a, b, i as f64 / n as f64), dir))
+        .collect()
+}
+
+// angles in degrees, the arc goes from start to end (so counterclockwise if end is bigger)
+pub fn arc(center: Point, radius: f64, start: f64, end: f64, spacing: f64) -> Vec<(Point, f64)> {
+    let full = (end - start).abs() >= 360.0;
+    let n = steps(radius * (end - start).to_radians().abs(), spacing);
+    // a full circle doesn't get a second object where it ends
+    let last = if full { n - 1 } else { n };
+    let turn = if end >= start { 90.0 } else { -90.0 };
+    (0..=last)
+        .map(|i| {
+            let angle = start + (end - start) * i as f64 / n as f64;
+            let rad = angle.to_radians();
+            (
+                [
+                    center[0] + radius * rad.cos(),
+                    center[1] + radius * rad.sin(),
+                ],
+                angle + turn,
+            )
+        })
+        .collect()
+}
+
+// a bezier curve through the control points (2 is a line, 3 is quadratic, 4 is cubic
+// and so on), with the objects spaced evenly along it
+pub fn bezier(points: &[Point], spacing: f64) -> Vec<(Point, f64)> {
+    fn at(points: &[Point], t: f64) -> Point {
+        let mut p = points.to_vec();
+        while p.len() > 1 {
+            p = p.windows(2).map(|w| along(w[0], w[1], t)).collect();
+        }
+        p[0]
+    }
+
+    // the curve as a lot of short lines, to measure it
+    let rough: f64 = points.windows(2).map(|w| distance(w[0], w[1])).sum();
+    let samples = ((rough / spacing) as usize * 16).max(64);
+    let curve: Vec<Point> = (0..=samples)
+        .map(|i| at(points, i as f64 / samples as f64))
+        .collect();
+    let mut lengths = vec![0.0];
+    for w in curve.windows(2) {
+        lengths.push(lengths[lengths.len() - 1] + distance(w[0], w[1]));
+    }
+
+    let total = lengths[samples];
+    let n = steps(total, spacing);
+    let mut out = Vec::new();
+    let mut j = 0;
+    for i in 0..=n {
+        let goal = total * i as f64 / n as f64;
+        while j + 1 < samples && lengths[j + 1] < goal {
+            j += 1;
+        }
+        let part = lengths[j + 1] - lengths[j];
+        let t = if part > 0.0 {
+            ((goal - lengths[j]) / part).min(1.0)
+        } else {
+            0.0
+        };
+        out.push((
+            along(curve[j], curve[j + 1], t),
+            direction(curve[j], curve[j + 1]),
+        ));
+    }
+    out
+}
+
+fn inside(polygon: &[Point], p: Point) -> bool {
+    let mut inside = false;
+    let mut j = polygon.len() - 1;
+    for i in 0..polygon.len() {
+        let (a, b) = (polygon[i], polygon[j]);
+        if (a[1] > p[1]) != (b[1] > p[1])
+            && p[0] < (b[0] - a[0]) * (p[1] - a[1]) / (b[1] - a[1]) + a[0]
+        {
+            inside = !inside;
+        }
+        j = i;
+    }
+    inside
+}
+
+// a grid of positions inside the polygon (the direction is always 0)
+pub fn polygon_fill(polygon: &[Point], spacing: f64) -> Vec<(Point, f64)> {
+    let min_x = polygon.iter().map(|p| p[0]).fold(f64::INFINITY, f64::min);
+    let max_x = polygon
+        .iter()
+        .map(|p| p[0])
+        .fold(f64::NEG_INFINITY, f64::max);
+    let min_y = polygon.iter().map(|p| p[1]).fold(f64::INFINITY, f64::min);
+    let max_y = polygon
+        .iter()
+        .map(|p| p[1])
+        .fold(f64::NEG_INFINITY, f64::max);
+
+    // the grid is centered in the polygon's bounds
+    let columns = ((max_x - min_x) / spacing).floor() as usize;
+    let rows = ((max_y - min_y) / spacing).floor() as usize;
+    let x0 = (min_x + max_x - columns as f64 * spacing) / 2.0;
+    let y0 = (min_y + max_y - rows as f64 * spacing) / 2.0;
+
+    let mut out = Vec::new();
+    for row in 0..=rows {
+        for column in 0..=columns {
+            let p = [x0 + column as f64 * spacing, y0 + row as f64 * spacing];
+            if inside(polygon, p) {
+                out.push((p, 0.0));
+            }
+        }
+    }
+    out
+}
@@ -0,0 +1,311 @@
+// spawn triggers in the same group with the same delay start their groups in the same
+// frame, and the game doesn't promise which of those groups runs first. that's fine until
+// a trigger in one of them changes something a trigger in another one uses (like an item
+// that one adds to and the other counts, or a group one toggles off and the other spawns).
+// --check-spawn-order lists those pairs, and --spawn-order fixes the order to the one in
+// the script, either with a short extra delay on the later spawn trigger (delay) or by
+// making the group with the spawn triggers run them in order (ordered)
+use crate::builtin::{Group, Id};
+use crate::levelstring::{GdObj, ObjParam};
+
+use std::collections::{BTreeMap, HashMap};
+
+const SPAWN: f64 = 1268.0;
+const TOGGLE: f64 = 1049.0;
+const ALPHA: f64 = 1007.0;
+const PICKUP: f64 = 1817.0;
+const COUNT: f64 = 1611.0;
+const INSTANT_COUNT: f64 = 1811.0;
+
+// triggers that start the group they target
+const ACTIVATORS: [f64; 6] = [1268.0, 1611.0, 1811.0, 1595.0, 1815.0, 1812.0];
+
+// the spawn trigger's "spawn ordered" option
+const SPAWN_ORDERED: u16 = 441;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Fix {
+    // the later spawn trigger waits a little longer
+    Delay,
+    // the spawn triggers that start the group run its triggers in order
+    Ordered,
+}
+
+pub fn parse_fix(name: &str) -> Result<Fix, String> {
+    match name {
+        "delay" => Ok(Fix::Delay),
+        "ordered" => Ok(Fix::Ordered),
+        _ => Err(format!(
+            "Expected delay or ordered after --spawn-order, found {}",
+            name
+        )),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Access {
+    Read,
+    // adding to an item, which doesn't depend on the order
+    Add,
+    // setting something to a value
+    Set(String),
+}
+
+fn number(obj: &GdObj, key: u16) -> Option<f64> {
+    match obj.params.get(&key) {
+        Some(ObjParam::Number(n)) => Some(*n),
+        _ => None,
+    }
+}
+
+fn group(obj: &GdObj, key: u16) -> Option<Group> {
+    match obj.params.get(&key) {
+        Some(ObjParam::Group(g)) if g.id != Id::Specific(0) => Some(*g),
+        _ => None,
+    }
+}
+
+// what a trigger uses or changes: (what, how)
+fn accesses(obj: &GdObj) -> Vec<(String, Access)> {
+    let id = match number(obj, 1) {
+        Some(id) => id,
+        None => return Vec::new(),
+    };
+    let mut out = Vec::new();
+    if let Some(ObjParam::Item(item)) = obj.params.get(&80) {
+        let item = match item.id {
+            Id::Specific(n) => format!("item {}i", n),
+            Id::Arbitrary(n) => format!("item {}?i", n),
+        };
+        if id == PICKUP {
+            out.push((item, Access::Add));
+        } else if id == COUNT || id == INSTANT_COUNT {
+            out.push((item, Access::Read));
+        }
+    }
+    if let Some(target) = group(obj, 51) {
+        if id == TOGGLE {
+            let on = matches!(obj.params.get(&56), Some(ObjParam::Bool(true)));
+            out.push((format!("group {:?}", target), Access::Set(on.to_string())));
+        } else if id == ALPHA {
+            let opacity = number(obj, 35).unwrap_or(1.0);
+            out.push((
+                format!("opacity of {:?}", target),
+                Access::Set(opacity.to_string()),
+            ));
+        } else if ACTIVATORS.contains(&id) {
+            out.push((format!("group {:?}", target), Access::Read));
+        }
+    }
+    out
+}
+
+fn conflicts(a: &[(String, Access)], b: &[(String, Access)]) -> Option<String> {
+    for (what_a, access_a) in a {
+        for (what_b, access_b) in b {
+            let independent = match (access_a, access_b) {
+                (Access::Read, Access::Read) | (Access::Add, Access::Add) => true,
+                (Access::Set(x), Access::Set(y)) => x == y,
+                _ => false,
+            };
+            if what_a == what_b && !independent {
+                return Some(what_a.clone());
+            }
+        }
+    }
+    None
+}
+
+// spawn triggers that start their groups in the same frame, in the order they're in the
+// level: (group they're in, delay in frames) -> indices
+fn same_frame_spawns(objects: &[GdObj], fps: f64) -> BTreeMap<(Group, i64), Vec<usize>> {
+    let mut out = BTreeMap::<_, Vec<usize>>::new();
+    for (i, obj) in objects.iter().enumerate() {
+        if number(obj, 1) != Some(SPAWN) || group(obj, 51).is_none() {
+            continue;
+        }
+        // spawn triggers that aren't spawned run when the player gets to them
+        let parent = match group(obj, 57) {
+            Some(g) => g,
+            None => continue,
+        };
+        let frames = (number(obj, 63).unwrap_or(0.0) * fps).round() as i64;
+        out.entry((parent, frames)).or_default().push(i);
+    }
+    out
+}
+
+// (index of the earlier spawn trigger, index of the later one, what they both use)
+fn find_pairs(objects: &[GdObj], fps: f64) -> Vec<(usize, usize, String)> {
+    // what the triggers in each group use
+    let mut in_group = HashMap::<Group, Vec<(String, Access)>>::new();
+    for obj in objects {
+        if let Some(g) = group(obj, 57) {
+            in_group.entry(g).or_default().extend(accesses(obj));
+        }
+    }
+    let used = |spawn: usize| {
+        group(&objects[spawn], 51)
+            .and_then(|g| in_group.get(&g))
+            .map(|v| &v[..])
+            .unwrap_or(&[])
+    };
+
+    // groups that are only started by spawn triggers with "spawn ordered" on
+    let mut ordered = HashMap::<Group, bool>::new();
+    for obj in objects {
+        if let (Some(SPAWN), Some(target)) = (number(obj, 1), group(obj, 51)) {
+            let on = obj.params.get(&SPAWN_ORDERED) == Some(&ObjParam::Bool(true));
+            *ordered.entry(target).or_insert(true) &= on;
+        }
+    }
+
+    let mut out = Vec::new();
+    for spawns in same_frame_spawns(objects, fps).values() {
+        let parent = group(&objects[spawns[0]], 57).unwrap();
+        if ordered.get(&parent) == Some(&true) {
+            continue;
+        }
+        for (n, &later) in spawns.iter().enumerate() {
+            for &earlier in &spawns[..n] {
+                if group(&objects[earlier], 51) == group(&objects[later], 51) {
+                    continue;
+                }
+                if let Some(what) = conflicts(used(earlier), used(later)) {
+                    out.push((earlier, later, what));
+                }
+            }
+        }
+    }
+    out
+}
+
+// warnings for the spawn triggers whose group can run before or after another one that
+// starts in the same frame: (unique id, message)
+pub fn check_order(objects: &[GdObj], fps: f64) -> Vec<(usize, String)> {
+    find_pairs(objects, fps)
+        .into_iter()
+        .map(|(earlier, later, what)| {
+            (
+                objects[later].unique_id,
+                format!(
+                    "starts {:?} in the same frame as {:?} is started, and both use the {}, so which one goes first isn't fixed",
+                    group(&objects[later], 51).unwrap(),
+                    group(&objects[earlier], 51).unwrap(),
+                    what
+                ),
+            )
+        })
+        .collect()
+}
+
+// makes the pairs from check_order run in the order they're in the script. returns how
+// many triggers were changed
+pub fn fix_order(objects: &mut [GdObj], fps: f64, fix: Fix) -> usize {
+    let pairs = find_pairs(objects, fps);
+    let mut changed = 0;
+
+    let mut delayed = Vec::new();
+    let mut ordered = Vec::new();
+    for &(earlier, later, _) in &pairs {
+        let parent = group(&objects[later], 57);
+        let starters: Vec<usize> = objects
+            .iter()
+            .enumerate()
+            .filter(|(_, obj)| number(obj, 1) == Some(SPAWN) && group(obj, 51) == parent)
+            .map(|(i, _)| i)
+            .collect();
+        // the group has to be started by a spawn trigger to be run in order
+        if fix == Fix::Ordered && !starters.is_empty() {
+            ordered.extend(starters);
+        } else {
+            delayed.push((earlier, later));
+        }
+    }
+
+    for i in ordered {
+        if objects[i].params.get(&SPAWN_ORDERED) != Some(&ObjParam::Bool(true)) {
+            objects[i]
+                .params
+                .insert(SPAWN_ORDERED, ObjParam::Bool(true));
+            changed += 1;
+        }
+    }
+
+    // each spawn trigger waits a bit longer than the latest one it has to go after
+    delayed.sort_unstable_by_key(|&(_, later)| later);
+    let step = crate::timing::min_delay(fps);
+    let mut extra = HashMap::<usize, f64>::new();
+    for (earlier, later) in delayed {
+        let after = extra.get(&earlier).copied().unwrap_or(0.0) + step;
+        let e = extra.entry(later).or_insert(0.0);
+        if after > *e {
+            *e = after;
+        }
+    }
+    for (i, e) in extra {
+        let delay = number(&objects[i], 63).unwrap_or(0.0);
+        objects[i].params.insert(63, ObjParam::Number(delay + e));
+        changed += 1;
+    }
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::ObjectMode;
+    use crate::builtin::Item;
+
+    fn trigger(unique_id: usize, id: f64, in_group: u16, params: Vec<(u16, ObjParam)>) -> GdObj {
+        let mut all = vec![
+            (1, ObjParam::Number(id)),
+            (57, ObjParam::Group(Group::new(in_group))),
+        ];
+        all.extend(params);
+        GdObj::for_test(unique_id, ObjectMode::Trigger, &all)
+    }
+
+    fn level() -> Vec<GdObj> {
+        vec![
+            // group 1 starts 2 and 3 in the same frame
+            trigger(0, SPAWN, 1, vec![(51, ObjParam::Group(Group::new(2)))]),
+            trigger(1, SPAWN, 1, vec![(51, ObjParam::Group(Group::new(3)))]),
+            // 2 adds to item 5, and 3 checks it
+            trigger(2, PICKUP, 2, vec![(80, ObjParam::Item(Item::new(5)))]),
+            trigger(
+                3,
+                INSTANT_COUNT,
+                3,
+                vec![(80, ObjParam::Item(Item::new(5)))],
+            ),
+            // something starts group 1
+            trigger(4, SPAWN, 9, vec![(51, ObjParam::Group(Group::new(1)))]),
+        ]
+    }
+
+    #[test]
+    fn order_dependent_spawns_are_found_and_fixed() {
+        let mut objects = level();
+        let found = check_order(&objects, 60.0);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].0, 1);
+
+        assert_eq!(fix_order(&mut objects, 60.0, Fix::Delay), 1);
+        assert_eq!(number(&objects[1], 63), Some(0.05));
+        assert!(check_order(&objects, 60.0).is_empty());
+
+        let mut objects = level();
+        assert_eq!(fix_order(&mut objects, 60.0, Fix::Ordered), 1);
+        assert_eq!(
+            objects[4].params.get(&SPAWN_ORDERED),
+            Some(&ObjParam::Bool(true))
+        );
+        assert!(check_order(&objects, 60.0).is_empty());
+
+        // two pickups on the same item don't depend on the order
+        let mut objects = level();
+        objects[3] = trigger(3, PICKUP, 3, vec![(80, ObjParam::Item(Item::new(5)))]);
+        assert!(check_order(&objects, 60.0).is_empty());
+    }
+}
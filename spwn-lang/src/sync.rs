@@ -0,0 +1,136 @@
+// spwn sync: for levels where some objects are edited in the game and others in spwn.
+// the objects on one editor layer are written to a .spwn file (as $.add(obj{...}) calls),
+// and are given to spwn in the level, so the script that imports the file puts them back
+// (with the changes made in the file) every time it's built.
+// after editing them in the game again, running spwn sync again gets the changes
+use crate::ownership::{is_spwn_object, properties, signature};
+
+const EDITOR_LAYER_1: &str = "20";
+
+fn layer(obj: &str) -> u16 {
+    properties(obj)
+        .iter()
+        .find(|(key, _)| *key == EDITOR_LAYER_1)
+        .and_then(|(_, value)| value.parse().ok())
+        .unwrap_or(0)
+}
+
+// the objects in the level (not the level settings before them) that are on the layer
+fn on_layer(ls: &str, editor_layer: u16) -> impl Iterator<Item = &str> {
+    ls.split(';')
+        .skip(1)
+        .filter(move |obj| !obj.is_empty() && layer(obj) == editor_layer)
+}
+
+fn str_literal(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn value(key: &str, value: &str) -> Option<String> {
+    let ids = |suffix: &str| -> Vec<String> {
+        value
+            .split('.')
+            .filter(|id| !id.is_empty() && *id != signature())
+            .map(|id| format!("{}{}", id, suffix))
+            .collect()
+    };
+    match key {
+        // the group spwn gives its objects is added again when they're built
+        "57" => {
+            let groups = ids("g");
+            if groups.is_empty() {
+                None
+            } else {
+                Some(format!("[{}]", groups.join(", ")))
+            }
+        }
+        // main and secondary color
+        "21" | "22" => Some(format!("{}c", value)),
+        _ => Some(if value.parse::<f64>().is_ok() {
+            value.to_string()
+        } else {
+            str_literal(value)
+        }),
+    }
+}
+
+// the .spwn file with the objects on the layer
+pub fn to_spwn(ls: &str, editor_layer: u16) -> (String, usize) {
+    let mut out = format!(
+        "// the objects on editor layer {0}, from spwn sync --layer {0}\n\
+         // import this file to add them, and run spwn sync again after changing them in the game\n\n",
+        editor_layer
+    );
+    let mut count = 0;
+    for obj in on_layer(ls, editor_layer) {
+        let props: Vec<String> = properties(obj)
+            .iter()
+            .filter_map(|(key, v)| Some(format!("{}: {}", key, value(key, v)?)))
+            .collect();
+        out += &format!("$.add(obj{{{}}})\n", props.join(", "));
+        count += 1;
+    }
+    (out, count)
+}
+
+// puts the hand-placed objects on the layer in spwn's group, so they're replaced by the
+// ones from the file when the script is built
+pub fn give_to_spwn(ls: &str, editor_layer: u16) -> String {
+    let mut parts = ls.split(';');
+    let mut out = parts.next().unwrap_or("").to_string() + ";";
+    for obj in parts.filter(|obj| !obj.is_empty()) {
+        if layer(obj) == editor_layer && !is_spwn_object(obj) {
+            let mut props = properties(obj);
+            let groups;
+            match props.iter_mut().find(|(key, _)| *key == "57") {
+                Some((_, g)) => {
+                    groups = format!("{}.{}", g, signature());
+                    *g = &groups;
+                }
+                None => {
+                    groups = signature();
+                    props.push(("57", &groups));
+                }
+            }
+            let props: Vec<String> = props.iter().map(|(k, v)| format!("{},{}", k, v)).collect();
+            out += &props.join(",");
+        } else {
+            out += obj;
+        }
+        out += ";";
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LEVEL: &str =
+        "kS38,1;1,1,2,15,3,15,20,5,57,2.3;1,914,2,45,3,15,20,5,31,aGk=;1,1,2,75,3,15;";
+
+    #[test]
+    fn layer_objects_are_written_as_spwn() {
+        let (spwn, count) = to_spwn(LEVEL, 5);
+        assert_eq!(count, 2);
+        assert!(spwn.contains("$.add(obj{1: 1, 2: 15, 3: 15, 20: 5, 57: [2g, 3g]})\n"));
+        assert!(spwn.contains("$.add(obj{1: 914, 2: 45, 3: 15, 20: 5, 31: \"aGk=\"})\n"));
+        assert_eq!(to_spwn(LEVEL, 0).1, 1);
+    }
+
+    #[test]
+    fn layer_objects_are_given_to_spwn() {
+        let level = give_to_spwn(LEVEL, 5);
+        assert_eq!(
+            level,
+            "kS38,1;1,1,2,15,3,15,20,5,57,2.3.1001;1,914,2,45,3,15,20,5,31,aGk=,57,1001;1,1,2,75,3,15;"
+        );
+        // building removes them, and adds the ones from the file
+        assert_eq!(
+            crate::ownership::without_spwn_objects(&level),
+            "kS38,1;1,1,2,75,3,15;"
+        );
+        // the group is taken out again when they're written to the file
+        assert_eq!(to_spwn(&level, 5).0, to_spwn(LEVEL, 5).0);
+    }
+}
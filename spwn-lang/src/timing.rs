@@ -0,0 +1,191 @@
+// spawn delays only take effect on whole frames when the level is played,
+// so 0.1s at 60 fps (6 frames) is exact, but 0.11s (6.6 frames) isn't
+use crate::builtin::{Group, Id};
+use crate::levelstring::{GdObj, ObjParam};
+
+use std::collections::{HashMap, HashSet};
+
+pub const DEFAULT_FPS: f64 = 60.0;
+
+// the shortest delay used for ordering triggers (and for wait() without a time),
+// 3 frames, which is 0.05s at 60 fps
+pub fn min_delay(fps: f64) -> f64 {
+    3.0 / fps
+}
+
+// how far from a whole frame a delay can be before it counts as inexact
+const TOLERANCE: f64 = 1e-6;
+
+// triggers that start the group they target
+const ACTIVATORS: [f64; 6] = [1268.0, 1611.0, 1811.0, 1595.0, 1815.0, 1812.0];
+
+fn number(obj: &GdObj, key: u16) -> Option<f64> {
+    match obj.params.get(&key) {
+        Some(ObjParam::Number(n)) => Some(*n),
+        _ => None,
+    }
+}
+
+fn group(obj: &GdObj, key: u16) -> Group {
+    match obj.params.get(&key) {
+        Some(ObjParam::Group(g)) => *g,
+        _ => Group {
+            id: Id::Specific(0),
+        },
+    }
+}
+
+fn spawn_delay(obj: &GdObj) -> Option<f64> {
+    if number(obj, 1) == Some(1268.0) {
+        number(obj, 63)
+    } else {
+        None
+    }
+}
+
+fn frames(delay: f64, fps: f64) -> f64 {
+    delay * fps
+}
+
+fn is_exact(delay: f64, fps: f64) -> bool {
+    let frames = frames(delay, fps);
+    (frames - frames.round()).abs() < TOLERANCE
+}
+
+// warnings for the spawn delays that can't be done exactly: (unique id, message)
+pub fn check_delays(objects: &[GdObj], fps: f64) -> Vec<(usize, String)> {
+    objects
+        .iter()
+        .filter_map(|obj| {
+            let delay = spawn_delay(obj)?;
+            if is_exact(delay, fps) {
+                None
+            } else {
+                Some((
+                    obj.unique_id,
+                    format!(
+                        "spawn delay of {}s is {:.2} frames at {} fps, so it can't be done exactly",
+                        delay,
+                        frames(delay, fps),
+                        fps
+                    ),
+                ))
+            }
+        })
+        .collect()
+}
+
+// rounds every spawn delay to a whole number of frames. the rounding error is carried
+// along chains of spawn triggers (groups that are only started by one spawn trigger),
+// so a sequence of waits ends on the frame closest to the total time that was asked for.
+// returns how many delays were changed
+pub fn round_delays(objects: &mut [GdObj], fps: f64) -> usize {
+    let mut activated_by = HashMap::<Group, Vec<usize>>::new();
+    for (i, obj) in objects.iter().enumerate() {
+        if let Some(id) = number(obj, 1) {
+            if ACTIVATORS.contains(&id) {
+                activated_by.entry(group(obj, 51)).or_default().push(i);
+            }
+        }
+    }
+
+    // how far ahead of the requested time (in seconds) a group is started
+    fn error_in(
+        g: Group,
+        objects: &[GdObj],
+        activated_by: &HashMap<Group, Vec<usize>>,
+        fps: f64,
+        known: &mut HashMap<Group, f64>,
+        visiting: &mut HashSet<Group>,
+    ) -> f64 {
+        if let Some(e) = known.get(&g) {
+            return *e;
+        }
+        let error = match activated_by.get(&g).map(|v| &v[..]) {
+            // loops don't carry the error around
+            Some([i]) if visiting.insert(g) => {
+                let obj = &objects[*i];
+                let e = match spawn_delay(obj) {
+                    Some(delay) => {
+                        let before =
+                            error_in(group(obj, 57), objects, activated_by, fps, known, visiting);
+                        before + rounded(delay, before, fps) - delay
+                    }
+                    None => 0.0,
+                };
+                visiting.remove(&g);
+                e
+            }
+            _ => 0.0,
+        };
+        known.insert(g, error);
+        error
+    }
+
+    fn rounded(delay: f64, error: f64, fps: f64) -> f64 {
+        ((delay - error) * fps).round().max(0.0) / fps
+    }
+
+    let mut known = HashMap::new();
+    let mut new_delays = Vec::new();
+    for (i, obj) in objects.iter().enumerate() {
+        if let Some(delay) = spawn_delay(obj) {
+            let error = error_in(
+                group(obj, 57),
+                objects,
+                &activated_by,
+                fps,
+                &mut known,
+                &mut HashSet::new(),
+            );
+            let new_delay = rounded(delay, error, fps);
+            if (new_delay - delay).abs() > TOLERANCE {
+                new_delays.push((i, new_delay));
+            }
+        }
+    }
+
+    for (i, delay) in &new_delays {
+        objects[*i].params.insert(63, ObjParam::Number(*delay));
+    }
+    new_delays.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::ObjectMode;
+
+    fn spawn(unique_id: usize, from: u16, to: u16, delay: f64) -> GdObj {
+        GdObj::for_test(
+            unique_id,
+            ObjectMode::Trigger,
+            &[
+                (1, ObjParam::Number(1268.0)),
+                (57, ObjParam::Group(Group::new(from))),
+                (51, ObjParam::Group(Group::new(to))),
+                (63, ObjParam::Number(delay)),
+            ],
+        )
+    }
+
+    #[test]
+    fn rounding_error_carries_over() {
+        // three waits of 6.6 frames each
+        let mut objects = vec![
+            spawn(0, 0, 1, 0.11),
+            spawn(1, 1, 2, 0.11),
+            spawn(2, 2, 3, 0.11),
+        ];
+        assert_eq!(check_delays(&objects, 60.0).len(), 3);
+        assert_eq!(round_delays(&mut objects, 60.0), 3);
+
+        let frames: Vec<f64> = objects
+            .iter()
+            .map(|obj| (number(obj, 63).unwrap() * 60.0).round())
+            .collect();
+        // 19.8 frames in total, so the sequence ends after 20
+        assert_eq!(frames, vec![7.0, 6.0, 7.0]);
+        assert!(check_delays(&objects, 60.0).is_empty());
+    }
+}
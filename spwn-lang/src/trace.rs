@@ -0,0 +1,256 @@
+//tracing of the statements compiled during a macro call, used by `spwn expand`
+use crate::ast;
+use crate::ast::ObjectMode;
+use crate::builtin::*;
+use crate::compiler::{compile_main, RuntimeError};
+use crate::compiler_info::CompilerInfo;
+use crate::context::Context;
+use crate::fmt::SpwnFmt;
+use crate::globals::Globals;
+use crate::levelstring::{GdObj, ObjParam};
+use crate::parser::{FileRange, ParseNotes};
+
+use std::path::PathBuf;
+
+// the source the --call statements are in, so that their positions aren't taken
+// for positions in the script
+pub const CALL_SOURCE: &str = "<call>";
+
+pub struct TraceLine {
+    pub depth: usize,
+    pub text: String,
+    // where the statement came from (file name, position)
+    pub span: Option<(String, FileRange)>,
+}
+
+pub struct Trace {
+    pub lines: Vec<TraceLine>,
+    depth: usize,
+    // the statements before this one (the script itself) are not traced
+    // (by address, so the trace can be sent between threads)
+    start: usize,
+    active: bool,
+    // objects up to these are already in the trace
+    reported_order: usize,
+    reported_objects: usize,
+}
+
+impl Trace {
+    pub fn new(start: &ast::Statement) -> Self {
+        Trace {
+            lines: Vec::new(),
+            depth: 0,
+            start: start as *const ast::Statement as usize,
+            active: false,
+            reported_order: 0,
+            reported_objects: 0,
+        }
+    }
+
+    fn note(&mut self, text: String) {
+        self.lines.push(TraceLine {
+            depth: self.depth + 1,
+            text,
+            span: None,
+        })
+    }
+}
+
+// compiles the script, and then the call with tracing on
+pub fn expand_call(
+    mut statements: Vec<ast::Statement>,
+    path: PathBuf,
+    included_paths: Vec<PathBuf>,
+    notes: ParseNotes,
+    call: Vec<ast::Statement>,
+    aliases: Option<crate::aliases::Aliases>,
+) -> Result<Vec<TraceLine>, Box<RuntimeError>> {
+    if call.is_empty() {
+        return Err(Box::new(RuntimeError::RuntimeError {
+            message: "Nothing to expand".to_string(),
+            info: CompilerInfo {
+                depth: 0,
+                path: vec!["expand".to_string()],
+                pos: ((0, 0), (0, 0)),
+                current_file: PathBuf::from(CALL_SOURCE),
+                current_module: String::new(),
+                includes: included_paths,
+                origin: (PathBuf::from(CALL_SOURCE), ((0, 0), (0, 0))),
+                budget: None,
+            },
+        }));
+    }
+    // the call is compiled in the same scope as the script, so that
+    // the values defined in the script are still around
+    let start = statements.len();
+    statements.extend(call);
+
+    let mut globals = Globals::new(path);
+    globals.aliases = aliases;
+    globals.trace = Some(Trace::new(&statements[start]));
+    compile_main(statements, included_paths, notes, &mut globals)?;
+
+    Ok(globals.trace.unwrap().lines)
+}
+
+// the statements from the first one of the call on are in the call's source
+pub fn enter_call(statement: &ast::Statement, info: &mut CompilerInfo, globals: &Globals) {
+    if let Some(trace) = &globals.trace {
+        if !trace.active && statement as *const ast::Statement as usize == trace.start {
+            info.current_file = PathBuf::from(CALL_SOURCE);
+            info.origin = (info.current_file.clone(), statement.pos);
+        }
+    }
+}
+
+pub fn begin_statement(statement: &ast::Statement, info: &CompilerInfo, globals: &mut Globals) {
+    if let Some(trace) = &mut globals.trace {
+        if !trace.active {
+            if statement as *const ast::Statement as usize != trace.start {
+                return;
+            }
+            trace.active = true;
+            trace.reported_order = globals.trigger_order;
+            trace.reported_objects = globals.objects.len();
+        }
+        let text = match &statement.body {
+            ast::StatementBody::If(if_stmt) => format!("if {}", if_stmt.condition.fmt(0)),
+            ast::StatementBody::For(f) => format!("for {} in {}", f.symbol, f.array.fmt(0)),
+            ast::StatementBody::Try(_) => "try".to_string(),
+            ast::StatementBody::Defer(_) => "defer".to_string(),
+            body => {
+                let full = body.fmt(0);
+                let mut lines = full.lines();
+                let first = lines.next().unwrap_or("").to_string();
+                if lines.next().is_some() {
+                    first + " ..."
+                } else {
+                    first
+                }
+            }
+        };
+        let file = info
+            .current_file
+            .file_name()
+            .map(|f| f.to_string_lossy().to_string())
+            .unwrap_or_default();
+        trace.lines.push(TraceLine {
+            depth: trace.depth,
+            text: if statement.arrow {
+                format!("-> {}", text)
+            } else {
+                text
+            },
+            span: Some((file, statement.pos)),
+        });
+        trace.depth += 1;
+    }
+}
+
+pub fn end_statement(
+    statement: &ast::Statement,
+    contexts: &[Context],
+    info: &CompilerInfo,
+    globals: &mut Globals,
+) {
+    crate::history::record_statement(statement, contexts, info, globals);
+
+    let mut trace = match globals.trace.take() {
+        Some(t) if t.active => t,
+        t => {
+            globals.trace = t;
+            return;
+        }
+    };
+    trace.depth -= 1;
+
+    // show the value a variable ended up with
+    if let ast::StatementBody::Expr(expr) = &statement.body {
+        if expr.operators.first() == Some(&ast::Operator::Assign) && expr.values[0].path.is_empty()
+        {
+            if let ast::ValueBody::Symbol(name) = &expr.values[0].value.body {
+                let mut values = Vec::<String>::new();
+                for context in contexts {
+                    if let Some(val) = context.variables.get(name) {
+                        let val_str = globals.stored_values[*val].to_short_str(globals);
+                        if !values.contains(&val_str) {
+                            values.push(val_str);
+                        }
+                    }
+                }
+                for val_str in values {
+                    trace.note(format!("// {} = {}", name, val_str));
+                }
+            }
+        }
+    }
+
+    // objects that were added since the last statement
+    let mut new_objects = Vec::<(usize, &GdObj)>::new();
+    for func in &globals.func_ids {
+        for (obj, order) in &func.obj_list {
+            if *order > trace.reported_order {
+                new_objects.push((*order, obj));
+            }
+        }
+    }
+    new_objects.sort_by_key(|(order, _)| *order);
+
+    let mut notes: Vec<String> = new_objects.iter().map(|(_, obj)| obj_str(obj)).collect();
+    notes.extend(
+        globals.objects[trace.reported_objects..]
+            .iter()
+            .map(obj_str),
+    );
+
+    for note in notes {
+        trace.note(note);
+    }
+    trace.reported_order = globals.trigger_order;
+    trace.reported_objects = globals.objects.len();
+
+    globals.trace = Some(trace);
+}
+
+fn id_str(id: &Id, class: &str) -> String {
+    match id {
+        Id::Specific(n) => format!("{}{}", n, class),
+        Id::Arbitrary(n) => format!("?{}{}", n, class),
+    }
+}
+
+fn param_str(param: &ObjParam) -> String {
+    match param {
+        ObjParam::Group(g) => id_str(&g.id, "g"),
+        ObjParam::Color(c) => id_str(&c.id, "c"),
+        ObjParam::Block(b) => id_str(&b.id, "b"),
+        ObjParam::Item(i) => id_str(&i.id, "i"),
+        ObjParam::Bool(b) => b.to_string(),
+        ObjParam::Text(t) => format!("\"{}\"", t),
+        ObjParam::GroupList(list) => format!(
+            "[{}]",
+            list.iter()
+                .map(|g| id_str(&g.id, "g"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        a => a.to_string(),
+    }
+}
+
+pub fn obj_str(obj: &GdObj) -> String {
+    let mut params: Vec<_> = obj.params.iter().collect();
+    params.sort_by_key(|(key, _)| **key);
+    format!(
+        "{} {{ {} }}",
+        match obj.mode {
+            ObjectMode::Object => "obj",
+            ObjectMode::Trigger => "trigger",
+        },
+        params
+            .iter()
+            .map(|(key, param)| format!("{}: {}", key, param_str(param)))
+            .collect::<Vec<_>>()
+            .join(", ")
+    )
+}
@@ -0,0 +1,100 @@
+// where the triggers are put in the level, set in a [triggers] section of spwn.toml or
+// with the build flags (so they can go somewhere away from the part of the level
+// that is played)
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Stacking {
+    // each function is a column of triggers, and the functions go to the right
+    Horizontal,
+    // each function is a row of triggers, and the functions go up
+    Vertical,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TriggerArea {
+    // the bottom left corner, in blocks
+    pub x: u32,
+    pub y: u32,
+    // how far (in blocks) the functions go before starting again next to the
+    // ones before, 0 for no limit
+    pub max_width: u32,
+    pub stacking: Stacking,
+}
+
+impl Default for TriggerArea {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TriggerArea {
+    pub fn new() -> Self {
+        TriggerArea {
+            x: 0,
+            y: 41,
+            max_width: 0,
+            stacking: Stacking::Horizontal,
+        }
+    }
+
+    // looks for a spwn.toml file with a [triggers] section, starting in the given folder
+    // and going up through its parents
+    pub fn find(start: &Path) -> Result<Self, String> {
+        for dir in start.ancestors() {
+            let manifest = dir.join("spwn.toml");
+            if manifest.is_file() {
+                return match std::fs::read_to_string(&manifest) {
+                    Ok(content) => Self::parse(&content)
+                        .map_err(|e| format!("Error in {}: {}", manifest.to_string_lossy(), e)),
+                    Err(e) => Err(format!(
+                        "Could not read {}: {}",
+                        manifest.to_string_lossy(),
+                        e
+                    )),
+                };
+            }
+        }
+        Ok(Self::new())
+    }
+
+    pub fn parse(content: &str) -> Result<Self, String> {
+        let mut area = Self::new();
+        for entry in crate::manifest::read(content, |s| s == "triggers")? {
+            area.set(&entry.key, &entry.text())
+                .map_err(|e| entry.error(e))?;
+        }
+        Ok(area)
+    }
+
+    // sets one option (the build flags use this too)
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let number = || {
+            value.parse::<u32>().map_err(|_| {
+                format!(
+                    "expected a whole number of blocks for `{}`, found `{}`",
+                    key, value
+                )
+            })
+        };
+        match key {
+            "x" => self.x = number()?,
+            "y" => self.y = number()?,
+            "max_width" => self.max_width = number()?,
+            "stacking" => {
+                self.stacking = match value {
+                    "horizontal" => Stacking::Horizontal,
+                    "vertical" => Stacking::Vertical,
+                    _ => {
+                        return Err(format!(
+                            "expected \"horizontal\" or \"vertical\" for `stacking`, found `{}`",
+                            value
+                        ))
+                    }
+                }
+            }
+            _ => return Err(format!("unknown option `{}`", key)),
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,259 @@
+// terminal dashboard for `spwn build --tui`
+use crate::compiler_types::FunctionId;
+use crate::trace::obj_str;
+
+use std::collections::HashSet;
+use std::io::{BufRead, IsTerminal, Write};
+use std::time::{Duration, Instant};
+
+// same as the limit used when the ids are given out
+pub const ID_LIMIT: usize = 999;
+// the editor doesn't let you place more objects than this
+pub const OBJECT_LIMIT: usize = 80_000;
+
+const BAR_WIDTH: usize = 30;
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const DIM: &str = "\x1b[2m";
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const YELLOW: &str = "\x1b[33m";
+const CYAN: &str = "\x1b[36m";
+
+// the escape code, or nothing when the output can't show colors (see color_choice)
+fn paint(color: bool, code: &'static str) -> &'static str {
+    if color {
+        code
+    } else {
+        ""
+    }
+}
+
+fn colors_on() -> bool {
+    crate::color_choice(&std::io::stdout()) == termcolor::ColorChoice::Always
+}
+
+enum PhaseState {
+    Running(Instant),
+    Done(Duration),
+}
+
+pub struct Dashboard {
+    script: String,
+    phases: Vec<(String, PhaseState)>,
+    warnings: Vec<String>,
+    objects: Option<usize>,
+    used_ids: Option<[usize; 4]>,
+    color: bool,
+}
+
+impl Dashboard {
+    pub fn new(script: &str) -> Self {
+        Dashboard {
+            script: script.to_string(),
+            phases: Vec::new(),
+            warnings: Vec::new(),
+            objects: None,
+            used_ids: None,
+            color: colors_on(),
+        }
+    }
+
+    fn stop_running(&mut self) {
+        if let Some((_, state)) = self.phases.last_mut() {
+            if let PhaseState::Running(start) = state {
+                *state = PhaseState::Done(start.elapsed());
+            }
+        }
+    }
+
+    pub fn start_phase(&mut self, name: &str) {
+        self.stop_running();
+        self.phases
+            .push((name.to_string(), PhaseState::Running(Instant::now())));
+        self.draw();
+    }
+
+    pub fn finish(&mut self) {
+        self.stop_running();
+        self.draw();
+    }
+
+    pub fn add_warnings(&mut self, warnings: &[String]) {
+        self.warnings.extend(warnings.iter().cloned());
+        self.draw();
+    }
+
+    pub fn set_objects(&mut self, objects: usize) {
+        self.objects = Some(objects);
+        self.draw();
+    }
+
+    pub fn set_used_ids(&mut self, used_ids: [usize; 4]) {
+        self.used_ids = Some(used_ids);
+        self.draw();
+    }
+
+    pub fn draw(&self) {
+        let c = |code| paint(self.color, code);
+        // without colors the screen isn't cleared either, each draw comes after the last
+        let mut out = String::from(if self.color { "\x1b[2J\x1b[H" } else { "\n" });
+        out += &format!("{}SPWN build{} {}\n\n", c(BOLD), c(RESET), self.script);
+
+        out += &format!("{}Phases{}\n", c(BOLD), c(RESET));
+        for (name, state) in &self.phases {
+            out += &match state {
+                PhaseState::Running(start) => format!(
+                    "  {}…{} {} {}({:.1}s){}\n",
+                    c(CYAN),
+                    c(RESET),
+                    name,
+                    c(DIM),
+                    start.elapsed().as_secs_f64(),
+                    c(RESET)
+                ),
+                PhaseState::Done(time) => format!(
+                    "  {}✓{} {} {}({} ms){}\n",
+                    c(GREEN),
+                    c(RESET),
+                    name,
+                    c(DIM),
+                    time.as_millis(),
+                    c(RESET)
+                ),
+            };
+        }
+
+        out += &format!("\n{}Usage{}\n", c(BOLD), c(RESET));
+        if let Some(objects) = self.objects {
+            out += &gauge("objects", objects, OBJECT_LIMIT, self.color);
+        }
+        if let Some(used_ids) = self.used_ids {
+            for (i, name) in ["groups", "colors", "block IDs", "item IDs"]
+                .iter()
+                .enumerate()
+            {
+                out += &gauge(name, used_ids[i], ID_LIMIT, self.color);
+            }
+        }
+
+        out += &format!(
+            "\n{}Warnings ({}){}\n",
+            c(BOLD),
+            self.warnings.len(),
+            c(RESET)
+        );
+        for warning in &self.warnings {
+            out += &format!("  {}!{} {}\n", c(YELLOW), c(RESET), warning);
+        }
+
+        let mut stdout = std::io::stdout();
+        stdout.write_all(out.as_bytes()).unwrap();
+        stdout.flush().unwrap();
+    }
+}
+
+fn gauge(name: &str, value: usize, limit: usize, color: bool) -> String {
+    let c = |code| paint(color, code);
+    let filled = (value * BAR_WIDTH / limit).min(BAR_WIDTH);
+    let bar = if value > limit {
+        c(RED)
+    } else if value * 10 > limit * 9 {
+        c(YELLOW)
+    } else {
+        c(GREEN)
+    };
+    format!(
+        "  {:<10} {}{}{}{}{} {}/{}\n",
+        name,
+        bar,
+        "█".repeat(filled),
+        c(DIM),
+        "░".repeat(BAR_WIDTH - filled),
+        c(RESET),
+        value,
+        limit
+    )
+}
+
+// lets the user look through the functions and the triggers in them,
+// until they quit (only when there's someone to type things)
+pub fn browse(func_ids: &[FunctionId]) {
+    if !std::io::stdin().is_terminal() {
+        return;
+    }
+
+    let color = colors_on();
+    let c = |code| paint(color, code);
+    let mut expanded = HashSet::new();
+    expanded.insert(0);
+    let stdin = std::io::stdin();
+
+    loop {
+        let mut out = format!("\n{}Functions{}\n", c(BOLD), c(RESET));
+        for (i, func) in func_ids.iter().enumerate() {
+            if func.parent.is_none() {
+                tree(func_ids, i, 1, &expanded, color, &mut out);
+            }
+        }
+        out += "\nType a function number to expand or collapse it, or q to quit: ";
+        print!("{}", out);
+        std::io::stdout().flush().unwrap();
+
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line).unwrap_or(0) == 0 {
+            break;
+        }
+        match line.trim() {
+            "q" | "quit" => break,
+            a => match a.parse::<usize>() {
+                Ok(i) if i < func_ids.len() => {
+                    if !expanded.remove(&i) {
+                        expanded.insert(i);
+                    }
+                }
+                _ => println!("{}No function called {}{}", c(RED), a, c(RESET)),
+            },
+        }
+    }
+}
+
+fn tree(
+    func_ids: &[FunctionId],
+    index: usize,
+    depth: usize,
+    expanded: &HashSet<usize>,
+    color: bool,
+    out: &mut String,
+) {
+    let c = |code| paint(color, code);
+    let indent = "  ".repeat(depth);
+    let func = &func_ids[index];
+    let open = expanded.contains(&index);
+    *out += &format!(
+        "{}{} {}[{}]{} function {} {}({} triggers){}\n",
+        indent,
+        if open { "▾" } else { "▸" },
+        c(CYAN),
+        index,
+        c(RESET),
+        index,
+        c(DIM),
+        func.obj_list.len(),
+        c(RESET)
+    );
+    if !open {
+        return;
+    }
+    let mut triggers: Vec<_> = func.obj_list.iter().collect();
+    triggers.sort_by_key(|(_, order)| *order);
+    for (obj, _) in triggers {
+        *out += &format!("{}    {}\n", indent, obj_str(obj));
+    }
+    for (i, child) in func_ids.iter().enumerate() {
+        if child.parent == Some(index) {
+            tree(func_ids, i, depth + 1, expanded, color, out);
+        }
+    }
+}
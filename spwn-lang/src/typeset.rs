@@ -0,0 +1,117 @@
+// lays out text as one text object per letter for $.typeset, so dialogue and
+// menus can be aligned and wrapped without placing every letter by hand
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+pub struct Settings {
+    pub scale: f64,
+    pub align: Align,
+    // lines are wrapped between words to fit, 0 for no wrapping
+    pub max_width: f64,
+    pub line_height: f64,
+    // extra space between letters
+    pub kerning: f64,
+}
+
+pub struct Layout {
+    // each letter and where its center goes
+    pub letters: Vec<(char, f64, f64)>,
+    pub left: f64,
+    pub top: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+// about how wide each letter of the game's text font is at scale 1 (a block is 30)
+fn letter_width(c: char) -> f64 {
+    match c {
+        ' ' => 10.0,
+        'i' | 'l' | 'j' | '!' | '.' | ',' | '\'' | ':' | ';' | '|' => 8.0,
+        'f' | 't' | 'r' | 'I' | '(' | ')' | '[' | ']' | '"' | '-' => 12.0,
+        'm' | 'w' => 24.0,
+        'M' | 'W' | '@' => 26.0,
+        'A'..='Z' => 20.0,
+        '0'..='9' => 18.0,
+        _ => 16.0,
+    }
+}
+
+fn text_width(text: &str, settings: &Settings) -> f64 {
+    let count = text.chars().count();
+    if count == 0 {
+        return 0.0;
+    }
+    text.chars()
+        .map(|c| letter_width(c) * settings.scale)
+        .sum::<f64>()
+        + settings.kerning * (count - 1) as f64
+}
+
+// the lines the text is split into, wrapping between words
+fn lines(text: &str, settings: &Settings) -> Vec<String> {
+    let mut out = Vec::new();
+    for paragraph in text.split('\n') {
+        let mut line = String::new();
+        for word in paragraph.split(' ') {
+            let longer = if line.is_empty() {
+                word.to_string()
+            } else {
+                format!("{} {}", line, word)
+            };
+            if settings.max_width > 0.0
+                && !line.is_empty()
+                && text_width(&longer, settings) > settings.max_width
+            {
+                out.push(line);
+                line = word.to_string();
+            } else {
+                line = longer;
+            }
+        }
+        out.push(line);
+    }
+    out
+}
+
+// x is the left edge, center or right edge depending on the alignment,
+// and y is the center of the first line
+pub fn layout(text: &str, x: f64, y: f64, settings: &Settings) -> Layout {
+    let lines = lines(text, settings);
+    let widths: Vec<f64> = lines.iter().map(|l| text_width(l, settings)).collect();
+    let width = widths.iter().cloned().fold(0.0, f64::max);
+
+    let mut letters = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        let mut left = match settings.align {
+            Align::Left => x,
+            Align::Center => x - widths[i] / 2.0,
+            Align::Right => x - widths[i],
+        };
+        let line_y = y - i as f64 * settings.line_height;
+        for c in line.chars() {
+            let w = letter_width(c) * settings.scale;
+            if c != ' ' {
+                letters.push((c, left + w / 2.0, line_y));
+            }
+            left += w + settings.kerning;
+        }
+    }
+
+    let left = match settings.align {
+        Align::Left => x,
+        Align::Center => x - width / 2.0,
+        Align::Right => x - width,
+    };
+    Layout {
+        letters,
+        left,
+        top: y + settings.line_height / 2.0,
+        width,
+        height: lines.len() as f64 * settings.line_height,
+    }
+}
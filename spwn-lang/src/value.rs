@@ -1,12 +1,13 @@
 use crate::ast;
 use crate::{compiler_types::*, context::*, globals::Globals, levelstring::*, value_storage::*};
 use crate::compiler_info::CompilerInfo;
-use crate::compiler::import_module;
+use crate::compiler::{catch_context, catch_thrown, import_module};
 use crate::builtin::*;
 //use std::boxed::Box;
 
 use std::collections::HashMap;
 use std::path::PathBuf;
+use crate::parser::FileRange;
 use smallvec::smallvec;
 
 use crate::compiler::RuntimeError;
@@ -46,6 +47,7 @@ pub struct Macro {
     )>,
     pub def_context: Context,
     pub def_file: PathBuf,
+    pub def_pos: FileRange,
     pub body: Vec<ast::Statement>,
     pub tag: ast::Attribute,
 }
@@ -61,41 +63,87 @@ pub enum Pattern {
     Either(Box<Pattern>, Box<Pattern>),
 }
 
+// == for values that don't implement _equal_ themselves
+// arrays, dictionaries (and custom types) and objects are equal if everything in them is,
+// and macros are only equal to the same macro (defined in the same place, with the same
+// variables around it)
 pub fn value_equality(val1: StoredValue, val2: StoredValue, globals: &Globals) -> bool {
     match (&globals.stored_values[val1], &globals.stored_values[val2]) {
         (Value::Array(a1), Value::Array(a2)) => {
-            if a1.len() != a2.len() {
-                return false;
-            }
-
-            for i in 0..a1.len() {
-                if !value_equality(a1[i], a2[i], globals) {
-                    return false;
-                }
-            }
-            true
+            a1.len() == a2.len()
+                && a1
+                    .iter()
+                    .zip(a2)
+                    .all(|(v1, v2)| value_equality(*v1, *v2, globals))
         }
         (Value::Dict(d1), Value::Dict(d2)) => {
-            if d1.len() != d2.len() {
-                return false;
-            }
+            d1.len() == d2.len()
+                && d1.iter().all(|(key, v1)| match d2.get(key) {
+                    Some(v2) => value_equality(*v1, *v2, globals),
+                    None => false,
+                })
+        }
+        // the order of the properties doesn't matter
+        (Value::Obj(o1, mode1), Value::Obj(o2, mode2)) => {
+            mode1 == mode2 && o1.len() == o2.len() && o1.iter().all(|param| o2.contains(param))
+        }
+        (Value::Macro(m1), Value::Macro(m2)) => {
+            m1.def_file == m2.def_file
+                && m1.def_pos == m2.def_pos
+                && m1.def_context.start_group == m2.def_context.start_group
+                && m1.def_context.variables == m2.def_context.variables
+        }
+        (a, b) => a == b,
+    }
+}
 
-            for key in d1.keys() {
-                if let Some(val1) = d2.get(key) {
-                    if let Some(val2) = d1.get(key) {
-                        if !value_equality(*val1, *val2, globals) {
-                            return false;
-                        }
-                    } else {
-                        unreachable!()
-                    }
-                } else {
-                    return false;
-                }
+// for $.hash, the same for values that are equal with value_equality
+pub fn value_hash(val: StoredValue, globals: &Globals) -> u64 {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::Hasher;
+
+    let mut hasher = DefaultHasher::new();
+    hash_value(val, globals, &mut hasher);
+    hasher.finish()
+}
+
+fn hash_value(val: StoredValue, globals: &Globals, state: &mut impl std::hash::Hasher) {
+    use std::hash::Hash;
+
+    let value = &globals.stored_values[val];
+    value.to_num(globals).hash(state);
+    match value {
+        Value::Array(a) => {
+            a.len().hash(state);
+            for v in a {
+                hash_value(*v, globals, state);
             }
-            true
         }
-        (a, b) => a == b,
+        Value::Dict(d) => {
+            let mut keys: Vec<_> = d.keys().collect();
+            keys.sort();
+            for key in keys {
+                key.hash(state);
+                hash_value(d[key], globals, state);
+            }
+        }
+        Value::Obj(o, _) => {
+            let mut params: Vec<String> = o.iter().map(|p| format!("{:?}", p)).collect();
+            params.sort();
+            params.hash(state);
+        }
+        Value::Macro(m) => {
+            m.def_file.hash(state);
+            format!("{:?}", m.def_pos).hash(state);
+            format!("{:?}", m.def_context.start_group).hash(state);
+            let mut variables: Vec<_> = m.def_context.variables.iter().collect();
+            variables.sort();
+            variables.hash(state);
+        }
+        // 0 and -0 are equal
+        Value::Number(n) => (if *n == 0.0 { 0.0 } else { *n }).to_bits().hash(state),
+        Value::Str(s) => s.hash(state),
+        other => format!("{:?}", other).hash(state),
     }
 }
 
@@ -339,6 +387,176 @@ impl Value {
     }
 }
 
+// how much of a value $.print shows (--print-depth and --print-elements)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PrintLimits {
+    // how many dictionaries and arrays deep it goes
+    pub depth: usize,
+    // how many elements of each dictionary and array are shown
+    pub elements: usize,
+    // puts the elements on their own lines when they don't fit on one
+    pub indent: bool,
+}
+
+impl Default for PrintLimits {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PrintLimits {
+    pub fn new() -> Self {
+        PrintLimits {
+            depth: 8,
+            elements: 50,
+            indent: true,
+        }
+    }
+
+    // for values in error messages, which should stay short and on one line
+    pub fn short() -> Self {
+        PrintLimits {
+            depth: 2,
+            elements: MAX_DICT_EL_DISPLAY,
+            indent: false,
+        }
+    }
+}
+
+// lines longer than this are split up when indenting
+const PRETTY_LINE_WIDTH: usize = 80;
+
+impl Value {
+    // like to_str, but with the limits, and a dictionary or array that contains itself
+    // is shown as <self-reference> instead of going on forever
+    pub fn to_pretty_str(&self, globals: &Globals, limits: PrintLimits) -> String {
+        self.pretty(globals, limits, 0, &mut Vec::new())
+    }
+
+    // to show a value in an error message
+    pub fn to_short_str(&self, globals: &Globals) -> String {
+        self.to_pretty_str(globals, PrintLimits::short())
+    }
+
+    fn pretty(
+        &self,
+        globals: &Globals,
+        limits: PrintLimits,
+        depth: usize,
+        seen: &mut Vec<StoredValue>,
+    ) -> String {
+        let (open, close, prefix, elements): (_, _, _, Vec<(Option<&String>, StoredValue)>) =
+            match self {
+                Value::Dict(d) => {
+                    let prefix = match d.get(TYPE_MEMBER_NAME) {
+                        Some(t) => globals.stored_values[*t].to_str(globals) + "::",
+                        None => String::new(),
+                    };
+                    let mut elements: Vec<_> = d
+                        .iter()
+                        .filter(|(k, _)| *k != TYPE_MEMBER_NAME)
+                        .map(|(k, v)| (Some(k), *v))
+                        .collect();
+                    elements.sort_by(|a, b| a.0.cmp(&b.0));
+                    ("{", "}", prefix, elements)
+                }
+                Value::Array(a) => ("[", "]", String::new(), a.iter().map(|v| (None, *v)).collect()),
+                _ => return self.to_str(globals),
+            };
+
+        if elements.is_empty() {
+            return format!("{}{}{}", prefix, open, close);
+        }
+        if depth >= limits.depth {
+            let count = match elements.len() {
+                1 => "1 element".to_string(),
+                n => format!("{} elements", n),
+            };
+            return format!("{}{}... ({}){}", prefix, open, count, close);
+        }
+
+        let mut parts = Vec::new();
+        for (key, id) in elements.iter().take(limits.elements) {
+            let val = if seen.contains(id) {
+                "<self-reference>".to_string()
+            } else {
+                seen.push(*id);
+                let val = match &globals.stored_values[*id] {
+                    Value::Str(s) => format!("{:?}", s),
+                    v => v.pretty(globals, limits, depth + 1, seen),
+                };
+                seen.pop();
+                val
+            };
+            parts.push(match key {
+                Some(k) => format!("{}: {}", k, val),
+                None => val,
+            });
+        }
+        if elements.len() > limits.elements {
+            parts.push(format!("... ({} more)", elements.len() - limits.elements));
+        }
+
+        let one_line = format!("{}{}{}{}", prefix, open, parts.join(", "), close);
+        if !limits.indent || (one_line.len() <= PRETTY_LINE_WIDTH && !one_line.contains('\n')) {
+            return one_line;
+        }
+        let mut out = format!("{}{}\n", prefix, open);
+        for part in parts {
+            out += &format!("    {},\n", part.replace('\n', "\n    "));
+        }
+        out + close
+    }
+}
+
+// what `as` can convert each type to (besides the type itself and @string, which
+// everything can be converted to), by type id
+// custom types can only be converted to @dictionary (leaving out the type), unless they
+// implement _as_ for other types
+pub fn conversions(from: TypeId) -> &'static [TypeId] {
+    match from {
+        // @number -> @group, @color, @block, @item, @bool
+        4 => &[0, 1, 2, 3, 5],
+        // @group, @color, @block, @item -> @number (only for IDs that aren't arbitrary)
+        0 | 1 | 2 | 3 => &[4],
+        // @bool -> @number
+        5 => &[4],
+        // @trigger_function -> @group (its start group)
+        6 => &[0],
+        // @string -> @number, @array (of characters)
+        9 => &[4, 10],
+        // @array -> @pattern (each element as a pattern)
+        10 => &[18],
+        // @type_indicator -> @pattern
+        14 => &[18],
+        // @range -> @array
+        17 => &[10],
+        // custom types -> @dictionary
+        t if t > 20 => &[7],
+        _ => &[],
+    }
+}
+
+fn conversion_error(from: TypeId, to: TypeId, info: &CompilerInfo, globals: &Globals) -> RuntimeError {
+    let name = |t: TypeId| format!("@{}", find_key_for_value(&globals.type_ids, t).unwrap());
+    let mut targets: Vec<String> = conversions(from).iter().map(|t| name(*t)).collect();
+    targets.push(name(9));
+    let last = targets.pop().unwrap();
+    let mut can = if targets.is_empty() {
+        last
+    } else {
+        format!("{} or {}", targets.join(", "), last)
+    };
+    if from > 20 {
+        can += &format!(", or to other types if {} implements _as_ for them", name(from));
+    }
+    let message = format!("{} can't be converted to {} (it can be converted to {})", name(from), name(to), can);
+    RuntimeError::RuntimeError {
+        message,
+        info: info.clone(),
+    }
+}
+
 pub fn convert_type(
     val: &Value,
     typ: TypeId,
@@ -346,8 +564,9 @@ pub fn convert_type(
     globals: &mut Globals,
     context: &Context,
 ) -> Result<Value, RuntimeError> {
+    let from = val.to_num(globals);
 
-    if val.to_num(globals) == typ {
+    if from == typ {
         return Ok(val.clone())
     }
 
@@ -355,241 +574,328 @@ pub fn convert_type(
         return Ok(Value::Str(val.to_str(globals)));
     }
 
-    Ok(match val {
-        Value::Number(n) => match typ {
-            0 => Value::Group(Group::new(*n as u16)),
-            1 => Value::Color(Color::new(*n as u16)),
-            2 => Value::Block(Block::new(*n as u16)),
-            3 => Value::Item(Item::new(*n as u16)),
-            4 => Value::Number(*n),
-            5 => Value::Bool(*n != 0.0),
+    if !conversions(from).contains(&typ) {
+        return Err(conversion_error(from, typ, info, globals));
+    }
 
-            _ => {
-                return Err(RuntimeError::RuntimeError {
-                    message: format!(
-                        "Number can't be converted to '{}'!",
-                        find_key_for_value(&globals.type_ids, typ).unwrap()
-                    ),
-                    info: info.clone(),
-                })
-            }
+    let unknown_id = |what: &str| RuntimeError::RuntimeError {
+        message: format!("This {} isn't known at this time, and can therefore not be converted to a number!", what),
+        info: info.clone(),
+    };
+
+    Ok(match (val, typ) {
+        (Value::Number(n), 0) => Value::Group(Group::new(*n as u16)),
+        (Value::Number(n), 1) => Value::Color(Color::new(*n as u16)),
+        (Value::Number(n), 2) => Value::Block(Block::new(*n as u16)),
+        (Value::Number(n), 3) => Value::Item(Item::new(*n as u16)),
+        (Value::Number(n), 5) => Value::Bool(*n != 0.0),
+
+        (Value::Group(g), 4) => match g.id {
+            Id::Specific(n) => Value::Number(n as f64),
+            _ => return Err(unknown_id("group")),
         },
-
-        Value::Group(g) => match typ {
-            
-            4 => Value::Number(match g.id {
-                Id::Specific(n) => n as f64,
-                _ => return Err(RuntimeError::RuntimeError {
-                    message: "This group isn\'t known at this time, and can therefore not be converted to a number!".to_string(),
-                    info: info.clone(),
-                })
-            }),
-            _ => {
-                return Err(RuntimeError::RuntimeError {
-                    message: format!(
-                        "Group can't be converted to '{}'!",
-                        find_key_for_value(&globals.type_ids, typ).unwrap()
-                    ),
-                    info: info.clone(),
-                })
-            }
+        (Value::Color(c), 4) => match c.id {
+            Id::Specific(n) => Value::Number(n as f64),
+            _ => return Err(unknown_id("color")),
         },
-
-        Value::Color(c) => match typ {
-            
-            4 => Value::Number(match c.id {
-                Id::Specific(n) => n as f64,
-                _ => return Err(RuntimeError::RuntimeError {
-                    message: "This color isn\'t known at this time, and can therefore not be converted to a number!".to_string(),
-                    info: info.clone(),
-                })
-            }),
-            _ => {
-                return Err(RuntimeError::RuntimeError {
-                    message: format!(
-                        "Color can't be converted to '{}'!",
-                        find_key_for_value(&globals.type_ids, typ).unwrap()
-                    ),
-                    info: info.clone(),
-                })
-            }
+        (Value::Block(b), 4) => match b.id {
+            Id::Specific(n) => Value::Number(n as f64),
+            _ => return Err(unknown_id("block ID")),
         },
-
-        Value::Block(b) => match typ {
-            
-            4 => Value::Number(match b.id {
-                Id::Specific(n) => n as f64,
-                _ => return Err(RuntimeError::RuntimeError {
-                    message: "This block ID isn\'t known at this time, and can therefore not be converted to a number!".to_string(),
-                    info: info.clone(),
-                })
-            }),
-            _ => {
-                return Err(RuntimeError::RuntimeError {
-                    message: format!(
-                        "Block ID can't be converted to '{}'!",
-                        find_key_for_value(&globals.type_ids, typ).unwrap()
-                    ),
-                    info: info.clone(),
-                })
-            }
+        (Value::Item(i), 4) => match i.id {
+            Id::Specific(n) => Value::Number(n as f64),
+            _ => return Err(unknown_id("item ID")),
         },
 
-        Value::Item(i) => match typ {
-            
-            4 => Value::Number(match i.id {
-                Id::Specific(n) => n as f64,
-                _ => return Err(RuntimeError::RuntimeError {
-                    message: "This item ID isn\'t known at this time, and can therefore not be converted to a number!".to_string(),
-                    info: info.clone(),
-                })
-            }),
-            _ => {
-                return Err(RuntimeError::RuntimeError {
-                    message: format!(
-                        "Item ID can't be converted to '{}'!",
-                        find_key_for_value(&globals.type_ids, typ).unwrap()
-                    ),
-                    info: info.clone(),
-                })
-            }
-        },
+        (Value::Bool(b), 4) => Value::Number(if *b { 1.0 } else { 0.0 }),
 
-        Value::Bool(b) => match typ {
-            
-            4 => Value::Number(if *b { 1.0 } else { 0.0 }),
-            _ => {
-                return Err(RuntimeError::RuntimeError {
-                    message: format!(
-                        "Boolean can't be converted to '{}'!",
-                        find_key_for_value(&globals.type_ids, typ).unwrap()
-                    ),
-                    info: info.clone(),
-                })
-            }
+        (Value::TriggerFunc(f), 0) => Value::Group(f.start_group),
+
+        (Value::Range(start, end, step), 10) => {
+            Value::Array(if start < end { 
+                (*start..*end).step_by(*step).map(|x| 
+                    store_value(Value::Number(x as f64), 1, globals, &context)).collect::<Vec<StoredValue>>() 
+            } else { 
+                (*end..*start).step_by(*step).rev().map(|x| 
+                    store_value(Value::Number(x as f64), 1, globals, &context)).collect::<Vec<StoredValue>>()
+            })
         },
 
-        Value::TriggerFunc(f) => match typ {
-            
-            0 => Value::Group(f.start_group),
-            _ => {
-                return Err(RuntimeError::RuntimeError {
-                    message: format!(
-                        "Trigger function can't be converted to '{}'!",
-                        find_key_for_value(&globals.type_ids, typ).unwrap()
-                    ),
-                    info: info.clone(),
-                })
+        (Value::Str(s), 4) => {
+            let out: std::result::Result<f64, _> = s.parse();
+            match out {
+                Ok(n) => Value::Number(n),
+                _ => {
+                    return Err(RuntimeError::RuntimeError {
+                        message: format!("Cannot convert '{}' to @number", s),
+                        info: info.clone()
+                    })
+                }
             }
         },
+        (Value::Str(s), 10) => {
+            Value::Array(s.chars().map(|x| store_value(Value::Str(x.to_string()), 1, globals, &context)).collect::<Vec<StoredValue>>())
+        },
 
-        Value::Range(start, end, step) => match typ {
-            10 => {
-                Value::Array(if start < end { 
-                    (*start..*end).step_by(*step).map(|x| 
-                        store_value(Value::Number(x as f64), 1, globals, &context)).collect::<Vec<StoredValue>>() 
-                } else { 
-                    (*end..*start).step_by(*step).rev().map(|x| 
-                        store_value(Value::Number(x as f64), 1, globals, &context)).collect::<Vec<StoredValue>>()
-                })
-            },
-            _ => {
-                return Err(RuntimeError::RuntimeError {
-                    message: format!(
-                        "Range can't be converted to '{}'!",
-                        find_key_for_value(&globals.type_ids, typ).unwrap()
-                    ),
-                    info: info.clone(),
+        (Value::Array(arr), 18) => {
+            // pattern
+            let mut new_vec = Vec::new();
+            for el in arr {
+                new_vec.push(match globals.stored_values[*el].clone() {
+                    Value::Pattern(p) => p,
+                    a => if let Value::Pattern(p) = convert_type(&a, 18, info, globals, context)? {
+                        p
+                    } else {
+                        unreachable!()
+                    },
                 })
             }
-        },
+            Value::Pattern(Pattern::Array(new_vec))
+        }
 
-        Value::Str(s) => match typ {
-            4 => {
-                let out: std::result::Result<f64, _> = s.parse();
-                match out {
-                    Ok(n) => Value::Number(n),
-                    _ => {
-                        return Err(RuntimeError::RuntimeError {
-                            message: format!("Cannot convert '{}' to @number", s),
-                            info: info.clone()
-                        })
+        (Value::TypeIndicator(t), 18) => Value::Pattern(Pattern::Type(*t)),
+
+        (Value::Dict(d), 7) => {
+            let mut d = d.clone();
+            d.remove(TYPE_MEMBER_NAME);
+            Value::Dict(d)
+        }
+
+        _ => unreachable!("conversion in the table isn't implemented"),
+    })
+
+}
+
+//copied from https://stackoverflow.com/questions/59401720/how-do-i-find-the-key-for-a-value-in-a-hashmap
+// compares the value of a switch with the value of a case, giving back
+// (whether it matched, context) for every context the comparison outputs
+fn switch_case_matches(
+    val: StoredValue,
+    case_val: StoredValue,
+    context: &Context,
+    globals: &mut Globals,
+    info: &CompilerInfo,
+) -> Result<Vec<(bool, Context)>, RuntimeError> {
+    let (start, end, step) = match (&globals.stored_values[case_val], &globals.stored_values[val]) {
+        (Value::Range(start, end, step), Value::Number(n)) => {
+            // known at compile time, no need for any triggers
+            let n = *n;
+            let inside = range_values(*start, *end, *step).any(|x| x as f64 == n);
+            return Ok(vec![(inside, context.clone())]);
+        }
+        // switching on a range compares the ranges themselves
+        (Value::Range(start, end, step), other) if !matches!(other, Value::Range(..)) => (*start, *end, *step),
+        _ => return compare_values(val, case_val, "_equal_", context, globals, info),
+    };
+
+    if step == 1 && start < end {
+        // start <= val < end, which is just two instant count checks for counters
+        let start_val = store_const_value(Value::Number(start as f64), 1, globals, context);
+        let end_val = store_const_value(Value::Number(end as f64), 1, globals, context);
+        let mut out = Vec::new();
+        for (b, c) in compare_values(val, start_val, "_more_or_equal_", context, globals, info)? {
+            if b {
+                out.extend(compare_values(val, end_val, "_less_than_", &c, globals, info)?);
+            } else {
+                out.push((false, c));
+            }
+        }
+        Ok(out)
+    } else {
+        // ranges with steps are checked one number at a time
+        let mut out = Vec::new();
+        let mut left = vec![context.clone()];
+        for x in range_values(start, end, step) {
+            if left.is_empty() {
+                break;
+            }
+            let x_val = store_const_value(Value::Number(x as f64), 1, globals, context);
+            let mut not_matched = Vec::new();
+            for c in left {
+                for (b, c) in compare_values(val, x_val, "_equal_", &c, globals, info)? {
+                    if b {
+                        out.push((true, c));
+                    } else {
+                        not_matched.push(c);
                     }
                 }
-            },
-            10 => {
-                Value::Array(s.chars().map(|x| store_value(Value::Str(x.to_string()), 1, globals, &context)).collect::<Vec<StoredValue>>())
-            },
-            _ => {
-                return Err(RuntimeError::RuntimeError {
-                    message: format!(
-                        "String can't be converted to '{}'!",
-                        find_key_for_value(&globals.type_ids, typ).unwrap()
-                    ),
-                    info: info.clone(),
-                })
             }
-        },
+            left = not_matched;
+        }
+        out.extend(left.into_iter().map(|c| (false, c)));
+        Ok(out)
+    }
+}
 
-        Value::Array(arr) => match typ {
-            18 => {
-                // pattern
-                let mut new_vec = Vec::new();
-                for el in arr {
-                    new_vec.push(match globals.stored_values[*el].clone() {
-                        Value::Pattern(p) => p,
-                        a => if let Value::Pattern(p) = convert_type(&a, 18, info, globals, context)? {
-                            p
-                        } else {
-                            unreachable!()
-                        },
-                    })
+// switching a counter on at least this many constant numbers or ranges uses a binary search
+const DISPATCH_TREE_MIN_VALUES: usize = 4;
+
+// number literals and ranges of them (like `5`, `-1` or `0..2..10`)
+fn is_constant_case(e: &ast::Expression) -> bool {
+    e.operators.iter().all(|op| *op == ast::Operator::Range)
+        && e.values.iter().all(|v| {
+            v.path.is_empty()
+                && matches!(v.operator, None | Some(ast::UnaryOperator::Minus))
+                && matches!(v.value.body, ast::ValueBody::Number(_))
+        })
+}
+
+// [start, end, case]
+type Segments = Vec<(i32, i32, usize)>;
+
+// the numbers each case of a counter switch matches, as sorted non-overlapping
+// [start, end) segments with the index of the case they go to
+// (none if the switch can't use a binary search)
+fn dispatch_segments(
+    val: StoredValue,
+    cases: &[ast::Case],
+    context: &Context,
+    globals: &mut Globals,
+    info: &CompilerInfo,
+) -> Result<Option<Segments>, RuntimeError> {
+    if globals.get_type_str(val) != "counter" {
+        return Ok(None);
+    }
+    let mut intervals = Vec::new();
+    let mut value_count = 0;
+    for (i, case) in cases.iter().enumerate() {
+        let values = match &case.typ {
+            ast::CaseType::Value(values) if values.iter().all(is_constant_case) => values,
+            ast::CaseType::Default => continue,
+            _ => return Ok(None),
+        };
+        for v in values {
+            value_count += 1;
+            let (evaled, _) = v.eval(context, globals, info.clone(), true)?;
+            match evaled.first().map(|(v, _)| globals.stored_values[*v].clone()) {
+                Some(Value::Number(n)) if n.fract() == 0.0 => {
+                    intervals.push((n as i32, n as i32 + 1, i))
+                }
+                Some(Value::Range(start, end, 1)) if start < end => intervals.push((start, end, i)),
+                Some(Value::Range(start, end, step)) => {
+                    intervals.extend(range_values(start, end, step).map(|x| (x, x + 1, i)))
                 }
-                Value::Pattern(Pattern::Array(new_vec))
+                _ => return Ok(None),
             }
+        }
+    }
+    if value_count < DISPATCH_TREE_MIN_VALUES {
+        return Ok(None);
+    }
 
-            _ => {
-                return Err(RuntimeError::RuntimeError {
-                    message: format!(
-                        "Array can't be converted to '{}'!",
-                        find_key_for_value(&globals.type_ids, typ).unwrap()
-                    ),
-                    info: info.clone(),
-                })
+    // cut everything up at every start and end, and give each piece to the first case that has it
+    let mut bounds: Vec<i32> = intervals.iter().flat_map(|(s, e, _)| vec![*s, *e]).collect();
+    bounds.sort_unstable();
+    bounds.dedup();
+    let mut segments: Segments = Vec::new();
+    for piece in bounds.windows(2) {
+        let (start, end) = (piece[0], piece[1]);
+        if let Some((_, _, case)) = intervals.iter().find(|(s, e, _)| *s <= start && end <= *e) {
+            match segments.last_mut() {
+                Some(last) if last.1 == start && last.2 == *case => last.1 = end,
+                _ => segments.push((start, end, *case)),
             }
+        }
+    }
+    Ok(Some(segments))
+}
 
+// compares the counter with the middle segment's start, and goes on in the half it's in.
+// bounds is what the counter is already known to be in: [low, high)
+fn dispatch_tree(
+    val: StoredValue,
+    segments: &[(i32, i32, usize)],
+    bounds: (Option<i32>, Option<i32>),
+    context: &Context,
+    globals: &mut Globals,
+    info: &CompilerInfo,
+) -> Result<Vec<(Option<usize>, Context)>, RuntimeError> {
+    let mut out = Vec::new();
+    if let [(start, end, case)] = segments {
+        // check the ends of the segment that aren't known yet
+        let mut inside = vec![context.clone()];
+        if !matches!(bounds.0, Some(low) if low >= *start) {
+            let start_val = store_const_value(Value::Number(*start as f64), 1, globals, context);
+            let mut next = Vec::new();
+            for c in inside {
+                for (b, c) in compare_values(val, start_val, "_more_or_equal_", &c, globals, info)? {
+                    if b {
+                        next.push(c)
+                    } else {
+                        out.push((None, c))
+                    }
+                }
+            }
+            inside = next;
         }
-        Value::TypeIndicator(t) =>  match typ {
-            18 => {
-                
-                Value::Pattern(Pattern::Type(*t))
+        if !matches!(bounds.1, Some(high) if high <= *end) {
+            let end_val = store_const_value(Value::Number(*end as f64), 1, globals, context);
+            let mut next = Vec::new();
+            for c in inside {
+                for (b, c) in compare_values(val, end_val, "_less_than_", &c, globals, info)? {
+                    if b {
+                        next.push(c)
+                    } else {
+                        out.push((None, c))
+                    }
+                }
             }
+            inside = next;
+        }
+        out.extend(inside.into_iter().map(|c| (Some(*case), c)));
+        return Ok(out);
+    }
+    if segments.is_empty() {
+        return Ok(vec![(None, context.clone())]);
+    }
+
+    let mid = segments.len() / 2;
+    let pivot = segments[mid].0;
+    let pivot_val = store_const_value(Value::Number(pivot as f64), 1, globals, context);
+    for (below, c) in compare_values(val, pivot_val, "_less_than_", context, globals, info)? {
+        out.extend(if below {
+            dispatch_tree(val, &segments[..mid], (bounds.0, Some(pivot)), &c, globals, info)?
+        } else {
+            dispatch_tree(val, &segments[mid..], (Some(pivot), bounds.1), &c, globals, info)?
+        });
+    }
+    Ok(out)
+}
+
+// the numbers a range goes through, like in a for loop
+fn range_values(start: i32, end: i32, step: usize) -> Box<dyn Iterator<Item = i32>> {
+    if start < end {
+        Box::new((start..end).step_by(step))
+    } else {
+        Box::new((end..start).step_by(step).rev())
+    }
+}
 
+fn compare_values(
+    val1: StoredValue,
+    val2: StoredValue,
+    operator: &str,
+    context: &Context,
+    globals: &mut Globals,
+    info: &CompilerInfo,
+) -> Result<Vec<(bool, Context)>, RuntimeError> {
+    let mut out = Vec::new();
+    for (r, c) in handle_operator(val1, val2, operator, context, globals, info)? {
+        match globals.stored_values[r] {
+            Value::Bool(b) => out.push((b, c)),
+            // if the operator for that type doesn't output a boolean, it can't be
+            // used in a switch statement
             _ => {
                 return Err(RuntimeError::RuntimeError {
-                    message: format!(
-                        "Type-Indicator can't be converted to '{}'!",
-                        find_key_for_value(&globals.type_ids, typ).unwrap()
-                    ),
+                    message: format!("{} operator returned non-boolean value", operator),
                     info: info.clone(),
                 })
             }
         }
-
-        _ => {
-            return Err(RuntimeError::RuntimeError {
-                message: format!(
-                    "'{}' can't be converted to '{}'!",
-                     find_key_for_value(&globals.type_ids, val.to_num(globals)).unwrap(), find_key_for_value(&globals.type_ids, typ).unwrap(),
-                ),
-                info: info.clone(),
-            })
-        }
-    })
-
+    }
+    Ok(out)
 }
 
-//copied from https://stackoverflow.com/questions/59401720/how-do-i-find-the-key-for-a-value-in-a-hashmap
 pub fn find_key_for_value(map: &HashMap<String, (u16, PathBuf, (usize, usize))>, value: u16) -> Option<&String> {
     map.iter()
         .find_map(|(key, val)| if val.0 == value { Some(key) } else { None })
@@ -658,6 +964,7 @@ pub fn macro_to_value(
                         body: m.body.statements.clone(),
                         def_context: defaults.1.clone(),
                         def_file: info.current_file.clone(),
+                        def_pos: info.pos,
                         tag: m.properties.clone(),
                     })),
                     1,
@@ -855,7 +1162,7 @@ impl ast::Variable {
                         }
                         a => {
                             return Err(RuntimeError::RuntimeError {
-                                message: format!("Expected boolean condition in ternary statement, found {}", a.to_str(globals)),
+                                message: format!("Expected boolean condition in ternary statement, found {}", a.to_short_str(globals)),
                                 info,
 
                             })
@@ -864,6 +1171,30 @@ impl ast::Variable {
                 }
             }
 
+            ast::ValueBody::Try(t) => {
+                let tried = catch_thrown(globals, |globals| {
+                    t.try_expr.eval(&context, globals, info.clone(), constant)
+                })?;
+                let (evaled, returns) = match tried {
+                    Ok(evaled) => evaled,
+                    Err(message) => {
+                        let catch_context = catch_context(&context, &t.catch_symbol, message, globals);
+                        t.catch_expr.eval(&catch_context, globals, info.clone(), constant)?
+                    }
+                };
+                inner_returns.extend(returns);
+                // the error variable is only in the catch block
+                start_val.extend(evaled.into_iter().map(|(val, c)| {
+                    (
+                        val,
+                        Context {
+                            variables: context.variables.clone(),
+                            ..c
+                        },
+                    )
+                }));
+            }
+
             ast::ValueBody::Switch(expr, cases) => {
                 // ok so in spwn you have to always assume every expression will split the context, that is,
                 // output multiple values in multiple contexts. This is called context splitting. A list of 
@@ -874,6 +1205,24 @@ impl ast::Variable {
 
                 // now we loop through every value the first expression outputted
                 for (val1, context) in evaled {
+                    // counters matched against a lot of constant cases get a binary search instead
+                    if let Some(segments) = dispatch_segments(val1, cases, &context, globals, &info)? {
+                        for (case, c) in dispatch_tree(val1, &segments, (None, None), &context, globals, &info)? {
+                            let body = match case {
+                                Some(i) => &cases[i].body,
+                                None => match cases.last() {
+                                    Some(ast::Case { typ: ast::CaseType::Default, body }) => body,
+                                    // no else case, nothing comes out
+                                    _ => continue,
+                                },
+                            };
+                            let (evaled, returns) = body.eval(&c, globals, info.clone(), constant)?;
+                            inner_returns.extend(returns);
+                            start_val.extend(evaled);
+                        }
+                        continue;
+                    }
+
                     //lets store the current contexts we are working with in a vector, starting with only the context
                     // outputted from the first expression
                     let mut contexts = vec![context.clone()];
@@ -886,64 +1235,51 @@ impl ast::Variable {
                         }
 
                         match &case.typ {
-                            ast::CaseType::Value(v) => {
+                            ast::CaseType::Value(values) => {
                                 // in this type of case we want to check if the original expression is
-                                // equal to some value. for this, we use the == operator
+                                // equal to one of the values. for this, we use the == operator
+                                // (ranges are checked with >= and <, so they work for counters too)
+                                let mut matched = Vec::new();
 
-                                // lets first evaluate the value we will compare to
-                                // remember, we have to evaluate it in all the contexts we are working with
-                                let mut all_values = Vec::new();
-                                for c in &contexts {
-                                    let (evaled, returns) = v.eval(c, globals, info.clone(), constant)?;
-                                    inner_returns.extend(returns);
-                                    all_values.extend(evaled);
-                                }
+                                for v in values {
+                                    if contexts.is_empty() {
+                                        break
+                                    }
+                                    // lets first evaluate the value we will compare to
+                                    // remember, we have to evaluate it in all the contexts we are working with
+                                    let mut all_values = Vec::new();
+                                    for c in &contexts {
+                                        let (evaled, returns) = v.eval(c, globals, info.clone(), constant)?;
+                                        inner_returns.extend(returns);
+                                        all_values.extend(evaled);
+                                    }
 
-                                // lets clear the contexts list for now, as we will refill it
-                                // with new contexts from the next few evaluations
-                                contexts.clear();
-                                
-                                // looping through all the values of the expression we just evaled
-                                for (val2, c) in all_values {
-
-                                    // lets compare the two values with the == operator
-                                    // since this is an expression in itself, we also have to assume
-                                    // this will output multiple values
-                                    let results = handle_operator(
-                                        val1, 
-                                        val2, 
-                                        "_equal_", 
-                                        &c, 
-                                        globals, 
-                                        &info
-                                    )?;
+                                    // lets clear the contexts list for now, as we will refill it
+                                    // with the contexts that didn't match
+                                    contexts.clear();
 
-                                    // lets loop through all those result values
-                                    for (r, c) in results {
-                                        if let Value::Bool(b) = globals.stored_values[r] {
+                                    for (val2, c) in all_values {
+                                        // since this is an expression in itself, we also have to assume
+                                        // the comparison will output multiple contexts
+                                        for (b, c) in switch_case_matches(val1, val2, &c, globals, &info)? {
                                             if b {
-                                                // if the two values match, we output this value to the output "start val"
                                                 // we can't break here, because the two values might only match in this one context,
                                                 // and there may be more contexts left to check
-                                                let (evaled, returns) = case.body.eval(&c, globals, info.clone(), constant)?;
-                                                inner_returns.extend(returns);
-                                                start_val.extend(evaled);
+                                                matched.push(c)
                                             } else {
-                                                // if they dont match, we keep going through the cases in this context
+                                                // if they dont match, we keep going through the values in this context
                                                 contexts.push(c)
                                             }
-                                        } else {
-                                            // if the == operator for that type doesn't output a boolean, it can't be
-                                            // used in a switch statement
-                                            return Err(RuntimeError::RuntimeError {
-                                                message: "== operator returned non-boolean value".to_string(),
-                                                info,
-                                                
-                                            });
                                         }
                                     }
                                 }
 
+                                // the contexts that matched one of the values output the body
+                                for c in matched {
+                                    let (evaled, returns) = case.body.eval(&c, globals, info.clone(), constant)?;
+                                    inner_returns.extend(returns);
+                                    start_val.extend(evaled);
+                                }
                             }
                             ast::CaseType::Pattern(p) => {
                                 // this is pretty much the same as the one before, except that we use .matches_pat
@@ -1070,7 +1406,7 @@ impl ast::Variable {
                                 return Err(RuntimeError::RuntimeError {
                                     message: format!(
                                         "expected either @number or @object_key as object key, found: {}",
-                                        a.to_str(globals)
+                                        a.to_short_str(globals)
                                     ),
                                     info,
                                 })
@@ -1087,7 +1423,7 @@ impl ast::Variable {
                                         return Err(RuntimeError::RuntimeError {
                                             message: format!(
                                                 "key required value to match {}, found {}",
-                                                pat.to_str(globals), val.to_str(globals)
+                                                pat.to_str(globals), val.to_short_str(globals)
                                             ),
                                             info,
                                         })
@@ -1096,7 +1432,7 @@ impl ast::Variable {
                                 let err = Err(RuntimeError::RuntimeError {
                                     message: format!(
                                         "{} is not a valid object value",
-                                        val.to_str(globals)
+                                        val.to_short_str(globals)
                                     ),
                                     info: info.clone(),
                                 });
@@ -1167,6 +1503,12 @@ impl ast::Variable {
             }
             //ast::ValueLiteral::Resolved(r) => out.push((r.clone(), context)),
             ast::ValueBody::Null => start_val.push((1, context.clone())),
+            ast::ValueBody::ErrorExpr(_) => {
+                return Err(RuntimeError::RuntimeError {
+                    message: "Cannot compile an expression with syntax errors".to_string(),
+                    info,
+                })
+            }
         };
 
         let mut path_iter = self.path.iter();
@@ -1246,7 +1588,7 @@ impl ast::Variable {
                                     return Err(RuntimeError::RuntimeError {
                                         message: format!(
                                             "Expected type indicator, found: {}",
-                                            a.to_str(globals)
+                                            a.to_short_str(globals)
                                         ),
                                         info,
                                     })
@@ -1401,7 +1743,7 @@ impl ast::Variable {
                                                             Value::Dict(map)
                                                         }
                                                     };
-                                                    let stored = store_const_value(out_val, globals.stored_values.map.get(&prev_v).unwrap().lifetime, globals, &index.1);
+                                                    let stored = store_const_value(out_val, globals.stored_values.get(prev_v).unwrap().lifetime, globals, &index.1);
                                                     new_out.push((stored, index.1, prev_v));
                                                     break;
                                                 }
@@ -1479,7 +1821,7 @@ impl ast::Variable {
                                 return Err(RuntimeError::RuntimeError {
                                     message: format!(
                                         "Cannot index this type: {}",
-                                        a.to_str(globals)
+                                        a.to_short_str(globals)
                                     ),
                                     info,
                                 })
@@ -1492,7 +1834,7 @@ impl ast::Variable {
 
                 ast::Path::Increment => {
                     for (prev_v,prev_c, _) in &mut with_parent {
-                        let is_mutable = globals.stored_values.map[&prev_v].mutable;
+                        let is_mutable = globals.stored_values.get(*prev_v).unwrap().mutable;
                         match &mut globals.stored_values[*prev_v] {
                             Value::Number(n) => {
                                 *n += 1.0;
@@ -1510,7 +1852,7 @@ impl ast::Variable {
 
                 ast::Path::Decrement => {
                     for (prev_v,prev_c, _) in &mut with_parent {
-                        let is_mutable = globals.stored_values.map[&prev_v].mutable;
+                        let is_mutable = globals.stored_values.get(*prev_v).unwrap().mutable;
                         match &mut globals.stored_values[*prev_v] {
                             Value::Number(n) => {
                                 *n -= 1.0;                          
@@ -1552,7 +1894,7 @@ impl ast::Variable {
                                 return Err(RuntimeError::RuntimeError {
                                 message: format!(
                                     "Attempted to construct on a value that is not a type indicator: {}",
-                                    a.to_str(globals)
+                                    a.to_short_str(globals)
                                 ),
                                 info,
                             });
@@ -1618,6 +1960,7 @@ impl ast::Variable {
                                 let mut all_values = Returns::new();
 
                                 for (args, context) in evaled_args {
+                                    let first = args.first().copied();
                                     let evaled = built_in_function(
                                         &name,
                                         args,
@@ -1625,8 +1968,11 @@ impl ast::Variable {
                                         globals,
                                         &context,
                                     )?;
-                                    all_values
-                                        .push((store_value(evaled, 1, globals, &context), context))
+                                    let stored = store_value(evaled, 1, globals, &context);
+                                    if let (Some(first), "freeze") = (first, name.as_str()) {
+                                        globals.copy_frozen(first, stored);
+                                    }
+                                    all_values.push((stored, context))
                                 }
 
                                 with_parent =
@@ -1636,7 +1982,7 @@ impl ast::Variable {
                                 return Err(RuntimeError::RuntimeError {
                                     message: format!(
                                         "Cannot call ( ... ) on '{}'",
-                                        a.to_str(globals)
+                                        a.to_short_str(globals)
                                     ),
                                     info,
                                 })
@@ -1933,7 +2279,7 @@ impl ast::Variable {
         
 
         for p in &self.path {
-            (*globals.stored_values.map.get_mut(&value).unwrap()).lifetime = globals.get_lifetime(current_ptr);
+            globals.stored_values.get_mut(value).unwrap().lifetime = globals.get_lifetime(current_ptr);
             if !defined {
                 return Err(RuntimeError::RuntimeError {
                     message: format!("Cannot run {} on an undefined value", p.fmt(0)),
@@ -1947,10 +2293,10 @@ impl ast::Variable {
                     match val.member(m.clone(), &context, globals) {
                         Some(s) => current_ptr = s,
                         None => {
-                            let stored = globals.stored_values.map.get_mut(&current_ptr).unwrap();
+                            let stored = globals.stored_values.get_mut(current_ptr).unwrap();
                             if !stored.mutable {
                                 return Err(RuntimeError::RuntimeError {
-                                    message: "Cannot edit members of a constant value".to_string(),
+                                    message: format!("Cannot edit members of a constant value{}", globals.frozen_note(current_ptr)),
                                     info: info.clone(),
                                 });
                             }
@@ -1971,20 +2317,25 @@ impl ast::Variable {
                 ast::Path::Index(i) => {
                     let (evaled, _) = i.eval(&context, globals, info.clone(), true)?;
                     let first_context_eval = evaled[0].0;
+                    if evaled.len() > 1 && matches!(globals.stored_values[current_ptr], Value::Dict(_)) {
+                        globals.warnings.push(format!(
+                            "{}:{}:{}: context splitting inside of an index definition. Use $.dict_add for better results",
+                            crate::compiler::display_path(&info.current_file),
+                            info.pos.0 .0,
+                            info.pos.0 .1 + 1
+                        ));
+                    }
                     match &globals.stored_values[current_ptr] {
                         Value::Dict(d)  => {
-                            if evaled.len() > 1 {
-                                println!("Warning: context splitting inside of an index definition. Use $.dict_add for better results");
-                            }
                             if let Value::Str(st) = globals.stored_values[first_context_eval].clone() {
 
                                 match d.get(&st) {
                                     Some(_) => current_ptr = first_context_eval,
                                     None => {
-                                        let stored = globals.stored_values.map.get_mut(&current_ptr).unwrap();
+                                        let stored = globals.stored_values.get_mut(current_ptr).unwrap();
                                         if !stored.mutable {
                                             return Err(RuntimeError::RuntimeError {
-                                                message: "Cannot edit members of a constant value".to_string(),
+                                                message: format!("Cannot edit members of a constant value{}", globals.frozen_note(current_ptr)),
                                                 info: info.clone(),
                                             });
                                         }
@@ -2036,7 +2387,7 @@ impl ast::Variable {
                             return Err(RuntimeError::RuntimeError {
                                 message: format!(
                                     "Expected a type-indicator to define an implementation on, found {}",
-                                    a.to_str(globals)
+                                    a.to_short_str(globals)
                                 ),
                                 info: info.clone(),
                             });
@@ -6,13 +6,21 @@ use crate::globals::Globals;
 use crate::value::*;
 
 use std::collections::HashMap;
+use std::sync::Arc;
 
 use crate::compiler::{BUILTIN_STORAGE, NULL_STORAGE};
 
 pub type StoredValue = usize; //index to stored value in globals.stored_values
 
+// cloning the storage is cheap: the values in its shared base are shared by every copy
+// (and only copied into one when it changes them), so each context can get its own
+// storage to be evaluated on its own thread
+#[derive(Clone)]
 pub struct ValStorage {
-    pub map: HashMap<usize, StoredValData>, //val, fn context, mutable, lifetime
+    // the shared values, which are never deleted
+    base: Arc<HashMap<usize, StoredValData>>,
+    // the values made since then, and the shared ones that were changed
+    map: HashMap<usize, StoredValData>, //val, fn context, mutable, lifetime
 }
 
 #[derive(Debug, Clone)]
@@ -35,8 +43,7 @@ impl std::ops::Index<usize> for ValStorage {
 
     fn index(&self, i: usize) -> &Self::Output {
         &self
-            .map
-            .get(&i)
+            .get(i)
             .unwrap_or_else(|| panic!("index {} not found", i))
             .val
     }
@@ -44,14 +51,21 @@ impl std::ops::Index<usize> for ValStorage {
 
 impl std::ops::IndexMut<usize> for ValStorage {
     fn index_mut(&mut self, i: usize) -> &mut Self::Output {
-        &mut self.map.get_mut(&i).unwrap().val
+        &mut self.get_mut(i).unwrap().val
     }
 }
 
 use std::collections::HashSet;
+impl Default for ValStorage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl ValStorage {
     pub fn new() -> Self {
         ValStorage {
+            base: Arc::new(HashMap::new()),
             map: vec![
                 (
                     BUILTIN_STORAGE,
@@ -78,9 +92,53 @@ impl ValStorage {
         }
     }
 
+    pub fn get(&self, index: usize) -> Option<&StoredValData> {
+        self.map.get(&index).or_else(|| self.base.get(&index))
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut StoredValData> {
+        if !self.map.contains_key(&index) {
+            let data = self.base.get(&index)?.clone();
+            self.map.insert(index, data);
+        }
+        self.map.get_mut(&index)
+    }
+
+    pub fn insert(&mut self, index: usize, data: StoredValData) {
+        self.map.insert(index, data);
+    }
+
+    // shared values can't be removed
+    pub fn remove(&mut self, index: usize) {
+        self.map.remove(&index);
+    }
+
+    pub fn len(&self) -> usize {
+        self.base.len()
+            + self
+                .map
+                .keys()
+                .filter(|i| !self.base.contains_key(i))
+                .count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // makes every value so far part of the shared base. they live as long as the
+    // storage does after this (like the values imports export)
+    pub fn share(&mut self) {
+        let base = Arc::make_mut(&mut self.base);
+        for (index, mut data) in self.map.drain() {
+            data.lifetime = 9999;
+            base.insert(index, data);
+        }
+    }
+
     pub fn set_mutability(&mut self, index: usize, mutable: bool) {
         if !mutable || !matches!(self[index], Value::Macro(_)) {
-            (*self.map.get_mut(&index).unwrap()).mutable = mutable;
+            self.get_mut(index).unwrap().mutable = mutable;
         }
 
         match self[index].clone() {
@@ -100,7 +158,7 @@ impl ValStorage {
     }
 
     pub fn get_lifetime(&self, index: usize) -> u16 {
-        self.map.get(&index).unwrap().lifetime
+        self.get(index).unwrap().lifetime
     }
 
     pub fn increment_lifetimes(&mut self) {
@@ -140,8 +198,7 @@ impl ValStorage {
             return;
         }
         let val = &mut (*self
-            .map
-            .get_mut(&index)
+            .get_mut(index)
             .expect(&(index.to_string() + " index not found")))
         .lifetime;
 
@@ -188,7 +245,7 @@ pub fn store_value(
     let index = globals.val_id;
     let mutable = !matches!(val, Value::Macro(_));
 
-    (*globals).stored_values.map.insert(
+    (*globals).stored_values.insert(
         index,
         StoredValData {
             val,
@@ -252,6 +309,17 @@ pub fn clone_and_get_value(
     old_val
 }
 
+// values are copied (with everything inside them) when they are:
+// - assigned to a variable (`a = b`, `let a = b`, `a = b` for an existing variable)
+// - passed to a macro as an argument (the copy can't be changed)
+// - added to an array with push, or put in an array or dictionary literal
+// - the variable of a for loop (changing it doesn't change the array or dictionary)
+// - extracted from a dictionary
+// `self` in a macro is the only reference: changing it changes the value the macro was
+// called on (like `arr.push(1)`), and so does changing a value through an index or member
+// (`arr[0].a = 1`)
+// $.deep_copy makes a copy of a value explicitly (which isn't frozen, even if the
+// value was frozen with $.freeze)
 pub fn clone_value(
     index: usize,
     lifetime: u16,
@@ -267,7 +335,7 @@ pub fn clone_value(
     //profit
     let new_index = globals.val_id;
 
-    (*globals).stored_values.map.insert(
+    (*globals).stored_values.insert(
         new_index,
         StoredValData {
             val: old_val,
@@ -277,6 +345,7 @@ pub fn clone_value(
         },
     );
     (*globals).val_id += 1;
+    globals.copy_frozen(index, new_index);
     new_index
 }
 
@@ -288,7 +357,7 @@ pub fn store_const_value(
 ) -> StoredValue {
     let index = globals.val_id;
 
-    (*globals).stored_values.map.insert(
+    (*globals).stored_values.insert(
         index,
         StoredValData {
             val,
@@ -310,7 +379,7 @@ pub fn store_val_m(
 ) -> StoredValue {
     let index = globals.val_id;
 
-    (*globals).stored_values.map.insert(
+    (*globals).stored_values.insert(
         index,
         StoredValData {
             val,
@@ -322,3 +391,44 @@ pub fn store_val_m(
     (*globals).val_id += 1;
     index
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data(val: Value) -> StoredValData {
+        StoredValData {
+            val,
+            fn_context: Group::new(0),
+            mutable: true,
+            lifetime: 1,
+        }
+    }
+
+    #[test]
+    fn copies_share_values_until_they_change_them() {
+        let mut storage = ValStorage::new();
+        storage.insert(2, data(Value::Number(1.0)));
+        storage.share();
+        storage.insert(3, data(Value::Number(2.0)));
+        assert_eq!(storage.len(), 4);
+
+        let mut copy = storage.clone();
+        assert!(Arc::ptr_eq(&storage.base, &copy.base));
+        // evaluated on another thread
+        let copy = std::thread::spawn(move || {
+            copy[2] = Value::Number(10.0);
+            copy.decrement_lifetimes();
+            copy.clean_up();
+            copy
+        })
+        .join()
+        .unwrap();
+        assert_eq!(copy[2], Value::Number(10.0));
+        assert_eq!(storage[2], Value::Number(1.0));
+        assert!(Arc::ptr_eq(&storage.base, &copy.base));
+        // shared values stay, the others are deleted when their lifetime is over
+        assert!(copy.get(1).is_some());
+        assert!(copy.get(3).is_none());
+    }
+}
@@ -0,0 +1,51 @@
+// the entry point for the browser playground (the wasm feature). from javascript:
+//
+// const result = compile_source(code, { optimize: true, release: false })
+// if (result.ok) show(result.level_string) else show(result.errors)
+//
+// the libraries that come with spwn are built in, and imports of other files don't work
+// since there are no files. the compiler needs a bigger stack than the default, so it's
+// linked with -C link-args=-zstack-size=67108864 (64 MB). the result is a plain object:
+//  - ok: if it was built
+//  - level_string, object_count and used_ids ([groups, colors, block ids, item ids])
+//  - output: what the script printed
+//  - warnings (strings) and lints, or errors (both like --error-format=json)
+use crate::api::Spwn;
+use crate::documentation::json_str;
+
+use wasm_bindgen::prelude::*;
+
+fn option(options: &JsValue, name: &str) -> Option<bool> {
+    js_sys::Reflect::get(options, &JsValue::from_str(name))
+        .ok()
+        .and_then(|v| v.as_bool())
+}
+
+fn json_list(items: impl Iterator<Item = String>) -> String {
+    format!("[{}]", items.collect::<Vec<_>>().join(","))
+}
+
+#[wasm_bindgen]
+pub fn compile_source(source: &str, options: JsValue) -> JsValue {
+    let spwn = Spwn::builder()
+        .optimize(option(&options, "optimize").unwrap_or(true))
+        .release(option(&options, "release").unwrap_or(false));
+
+    let json = match spwn.compile_source(source) {
+        Ok(level) => format!(
+            "{{\"ok\":true,\"level_string\":{},\"object_count\":{},\"used_ids\":{},\"output\":{},\"warnings\":{},\"lints\":{}}}",
+            json_str(&level.level_string),
+            level.objects.len(),
+            json_list(level.used_ids.iter().map(|n| n.to_string())),
+            json_list(level.output.iter().map(|s| json_str(s))),
+            json_list(level.warnings.iter().map(|s| json_str(s))),
+            json_list(level.lints.iter().map(|d| d.to_json())),
+        ),
+        Err(err) => format!(
+            "{{\"ok\":false,\"output\":{},\"errors\":{}}}",
+            json_list(err.output.iter().map(|s| json_str(s))),
+            json_list(err.errors.iter().map(|d| d.to_json())),
+        ),
+    };
+    js_sys::JSON::parse(&json).unwrap_or(JsValue::NULL)
+}
@@ -0,0 +1,145 @@
+// spwn build --watch: builds the script, then builds it again every time it or a file it
+// imports changes. each build runs spwn again without --watch (so a failed build doesn't
+// stop watching), and that build tells the watcher which files it read through a file
+// named in SPWN_WATCH_LIST
+use crate::flags::VALUE_FLAGS;
+use crate::globals::Globals;
+use crate::interrupt;
+use crate::print_with_color;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, SystemTime};
+use termcolor::Color;
+
+const LIST_VAR: &str = "SPWN_WATCH_LIST";
+// how often the files are checked
+const POLL: Duration = Duration::from_millis(300);
+// editors often save in a few steps, so the build waits a little after a change
+const SETTLE: Duration = Duration::from_millis(100);
+
+// in the build the watcher started: writes the script and the files it imported
+pub fn report_files(globals: &Globals) {
+    let list = match std::env::var_os(LIST_VAR) {
+        Some(list) => PathBuf::from(list),
+        None => return,
+    };
+    let mut files: Vec<PathBuf> = globals.imported_files.iter().cloned().collect();
+    files.push(std::fs::canonicalize(&globals.path).unwrap_or_else(|_| globals.path.clone()));
+    files.sort();
+    files.dedup();
+    let content: Vec<String> = files
+        .iter()
+        .map(|f| f.to_string_lossy().to_string())
+        .collect();
+    let _ = std::fs::write(list, content.join("\n"));
+}
+
+fn modified(files: &[PathBuf]) -> Vec<Option<SystemTime>> {
+    files
+        .iter()
+        .map(|f| std::fs::metadata(f).and_then(|m| m.modified()).ok())
+        .collect()
+}
+
+// the list is removed when watching stops
+struct ListFile(PathBuf);
+
+impl Drop for ListFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+// args are the ones spwn was started with (after the program name), without --watch.
+// runs until ctrl-c is pressed
+pub fn run(args: Vec<String>, script: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let exe = std::env::current_exe()?;
+    let list =
+        ListFile(std::env::temp_dir().join(format!("spwn-watch-{}.txt", std::process::id())));
+    let list = &list.0;
+    let mut files = vec![script.to_path_buf()];
+    // the build that's running gets the ctrl-c too
+    interrupt::catch_ctrl_c(true);
+    loop {
+        let _ = std::fs::remove_file(list);
+        Command::new(&exe)
+            .args(&args)
+            .env(LIST_VAR, list)
+            .status()?;
+        if interrupt::interrupted() {
+            return Ok(());
+        }
+
+        // a build that stopped before it got to the imports keeps the files from before
+        if let Ok(content) = std::fs::read_to_string(list) {
+            files = content.lines().map(PathBuf::from).collect();
+        }
+        print_with_color(
+            &format!(
+                "\nWatching {} files for changes (ctrl+c to stop)",
+                files.len()
+            ),
+            Color::Cyan,
+        );
+        let before = modified(&files);
+        while modified(&files) == before {
+            if interrupt::interrupted() {
+                return Ok(());
+            }
+            std::thread::sleep(POLL);
+        }
+        std::thread::sleep(SETTLE);
+        print_with_color("\nA file changed, building again ...\n", Color::Cyan);
+    }
+}
+
+// the arguments for the builds, without --watch (the values of other flags, like a level
+// called --watch, and the defines after -- are left alone)
+pub fn build_args(args: &[String]) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--watch" => continue,
+            "--" => {
+                out.push(arg.clone());
+                out.extend(args.by_ref().cloned());
+            }
+            flag if VALUE_FLAGS.contains(&flag) => {
+                out.push(arg.clone());
+                out.extend(args.next().cloned());
+            }
+            _ => out.push(arg.clone()),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn watch_is_left_out_of_the_builds() {
+        let args: Vec<String> = ["build", "main.spwn", "--watch", "-c", "--", "--watch"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert_eq!(
+            build_args(&args),
+            vec!["build", "main.spwn", "-c", "--", "--watch"]
+        );
+    }
+
+    #[test]
+    fn flag_values_called_watch_are_kept() {
+        let args: Vec<String> = ["build", "main.spwn", "-n", "--watch", "--watch", "-c"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert_eq!(
+            build_args(&args),
+            vec!["build", "main.spwn", "-n", "--watch", "-c"]
+        );
+    }
+}